@@ -0,0 +1,134 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::delegates::Permission;
+use crate::error::GigError;
+use crate::moderation::not_banned;
+use crate::orgs;
+use crate::rate_limit;
+use crate::trending;
+use crate::visibility;
+use crate::{get_memory, Memory, GIG_ANALYTICS_MEMORY_ID, GIG_STORAGE};
+
+/// Views and bids a gig has drawn so far.
+#[derive(candid::CandidType, Clone, Default, Serialize, Deserialize)]
+struct Activity {
+    views: u64,
+    unique_viewers: u64,
+    bids: u64,
+    first_bid_at: Option<u64>,
+}
+
+impl Storable for Activity {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Activity {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// How a gig has performed since it was posted.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct GigAnalytics {
+    pub gig_id: u64,
+    pub views: u64,                           // Every recorded view, repeats included.
+    pub unique_viewers: u64,
+    pub bids: u64,                            // Bids placed, including later rejected ones.
+    pub first_bid_at: Option<u64>,
+    pub time_to_first_bid_secs: Option<u64>,  // From posting; `None` until the first bid.
+}
+
+thread_local! {
+    /// Activity counters keyed by gig ID. Gigs without views or bids have no entry.
+    static ACTIVITY: RefCell<StableBTreeMap<u64, Activity, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(GIG_ANALYTICS_MEMORY_ID)));
+}
+
+/// Apply `change` to a gig's activity counters.
+fn update(gig_id: u64, change: impl FnOnce(&mut Activity)) {
+    ACTIVITY.with(|activity| {
+        let mut activity = activity.borrow_mut();
+        let mut entry = activity.get(&gig_id).unwrap_or_default();
+        change(&mut entry);
+        activity.insert(gig_id, entry);
+    });
+}
+
+/// Number of views and bids a gig has drawn, for gig summaries.
+pub fn counts(gig_id: u64) -> (u64, u64) {
+    ACTIVITY
+        .with(|activity| activity.borrow().get(&gig_id))
+        .map_or((0, 0), |entry| (entry.views, entry.bids))
+}
+
+/// Count a bid placed on a gig at `now`.
+pub fn record_bid(gig_id: u64, now: u64) {
+    update(gig_id, |entry| {
+        entry.bids += 1;
+        entry.first_bid_at.get_or_insert(now);
+    });
+    trending::record_bid(gig_id);
+}
+
+/// Drop the counters of a gig that was removed.
+pub fn forget_gig(gig_id: u64) {
+    ACTIVITY.with(|activity| activity.borrow_mut().remove(&gig_id));
+}
+
+/// Record that the caller viewed a gig. Every view is counted, but only a principal's
+/// first view adds to the gig's trending score. Views are rate limited per principal.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn record_view(gig_id: u64) -> Result<(), GigError> {
+    let viewer = caller();
+    if viewer == Principal::anonymous() {
+        return Err(GigError::unauthorized("Anonymous principals cannot record views"));
+    }
+    let viewable = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .is_some_and(|gig| visibility::can_view(&gig));
+    if !viewable {
+        return Err(GigError::not_found("Gig"));
+    }
+    rate_limit::check(viewer, rate_limit::Action::RecordView)?;
+    let first = trending::record_view(gig_id, viewer);
+    update(gig_id, |entry| {
+        entry.views += 1;
+        if first {
+            entry.unique_viewers += 1;
+        }
+    });
+    Ok(())
+}
+
+/// Retrieve the views, bids and time to first bid of a gig. Employer, their organization's
+/// managers or delegates allowed to post only.
+#[ic_cdk::query]
+pub fn get_gig_analytics(id: u64) -> Result<GigAnalytics, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer, their organization or a delegate can see how it performs.
+    if !orgs::acts_for_employer(&gig, caller(), Permission::Post) {
+        return Err(GigError::unauthorized("Only the employer can view this gig's analytics"));
+    }
+    let entry = ACTIVITY.with(|activity| activity.borrow().get(&id)).unwrap_or_default();
+    Ok(GigAnalytics {
+        gig_id: id,
+        views: entry.views,
+        unique_viewers: entry.unique_viewers,
+        bids: entry.bids,
+        first_bid_at: entry.first_bid_at,
+        time_to_first_bid_secs: entry
+            .first_bid_at
+            .map(|at| at.saturating_sub(gig.created_at) / 1_000_000_000),
+    })
+}
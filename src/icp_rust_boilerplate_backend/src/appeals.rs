@@ -0,0 +1,221 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::arbitration;
+use crate::disputes::{self, Dispute, Ruling};
+use crate::error::GigError;
+use crate::log;
+use crate::moderation::not_banned;
+use crate::teams;
+use crate::{get_memory, GigStatus, Memory, APPEALS_MEMORY_ID, GIG_STORAGE};
+
+/// Arbitrators seated on an appeal panel.
+const PANEL_SIZE: usize = 3;
+/// Time the panel has to reach a majority: seven days.
+const VOTING_WINDOW_NANOS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+/// Longest appeal reason, in bytes.
+const MAX_REASON_LEN: usize = 1000;
+
+/// A panel member's vote on an appeal.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct PanelVote {
+    pub arbitrator: Principal,
+    pub ruling: Ruling,
+    pub voted_at: u64,
+}
+
+/// The losing party's challenge of a dispute ruling, heard by a panel of arbitrators.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Appeal {
+    pub gig_id: u64,
+    pub appellant: Principal,
+    pub reason: String,
+    pub original_ruling: Ruling,
+    pub panel: Vec<Principal>,      // Drawn from the staked pool, without the first arbitrator.
+    pub votes: Vec<PanelVote>,
+    pub ruling: Option<Ruling>,     // The panel's majority ruling, which is binding.
+    pub filed_at: u64,
+    pub voting_due_at: u64,         // The original ruling stands if no majority by then.
+    pub decided_at: Option<u64>,
+}
+
+impl Storable for Appeal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Appeal {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// The latest appeal of each gig, keyed by gig ID.
+    static APPEALS: RefCell<StableBTreeMap<u64, Appeal, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(APPEALS_MEMORY_ID)));
+}
+
+/// The appeal filed against a dispute, ignoring appeals of earlier disputes of the gig.
+fn appeal_of(dispute: &Dispute) -> Option<Appeal> {
+    APPEALS
+        .with(|appeals| appeals.borrow().get(&dispute.gig_id))
+        .filter(|appeal| appeal.filed_at >= dispute.raised_at)
+}
+
+/// Appeal an arbitrator's ruling to a panel of three other arbitrators. Only the losing
+/// party can appeal, once, within three days of the ruling.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn appeal_dispute(gig_id: u64, reason: String) -> Result<Appeal, GigError> {
+    let appellant = caller();
+    if reason.trim().is_empty() || reason.len() > MAX_REASON_LEN {
+        return Err(GigError::validation("reason", "Must be between 1 and 1000 bytes"));
+    }
+    let dispute = disputes::get_dispute(gig_id).ok_or_else(|| GigError::not_found("Dispute"))?;
+    let (Some(ruling), Some(appeal_due_at)) = (dispute.ruling.clone(), dispute.appeal_due_at)
+    else {
+        return Err(GigError::validation("gig_id", "There is no appealable ruling"));
+    };
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    if gig.status != GigStatus::Disputed {
+        return Err(GigError::invalid_state("Disputed", gig.status));
+    }
+    // Ensure only the party the ruling went against, or either party of a split, can appeal.
+    let losers = match ruling {
+        Ruling::Worker => vec![gig.employer],
+        Ruling::Employer => teams::workers(&gig),
+        Ruling::Split { .. } => [vec![gig.employer], teams::workers(&gig)].concat(),
+    };
+    if !losers.contains(&appellant) {
+        return Err(GigError::unauthorized("Only the losing party can appeal"));
+    }
+    if time() > appeal_due_at {
+        return Err(GigError::validation("gig_id", "The appeal window has closed"));
+    }
+    if appeal_of(&dispute).is_some() {
+        return Err(GigError::AlreadyExists { resource: "Appeal".to_string() });
+    }
+    let exclude: Vec<Principal> = dispute.resolved_by.into_iter().collect();
+    let candidates = arbitration::pool(gig_id, &exclude);
+    if candidates.len() < PANEL_SIZE {
+        return Err(GigError::validation("gig_id", "Not enough staked arbitrators for a panel"));
+    }
+
+    // Record the appeal before drawing the panel so the ruling is not enforced meanwhile.
+    let now = time();
+    let mut appeal = Appeal {
+        gig_id,
+        appellant,
+        reason,
+        original_ruling: ruling,
+        panel: Vec::new(),
+        votes: Vec::new(),
+        ruling: None,
+        filed_at: now,
+        voting_due_at: now + VOTING_WINDOW_NANOS,
+        decided_at: None,
+    };
+    APPEALS.with(|appeals| appeals.borrow_mut().insert(gig_id, appeal.clone()));
+    appeal.panel = arbitration::draw(candidates, PANEL_SIZE).await;
+    APPEALS.with(|appeals| {
+        let mut appeals = appeals.borrow_mut();
+        if let Some(mut stored) = appeals.get(&gig_id) {
+            stored.panel = appeal.panel.clone();
+            appeals.insert(gig_id, stored);
+        }
+    });
+    Ok(appeal)
+}
+
+/// Vote on an appeal. Once a majority of the panel agrees its ruling is final and the
+/// escrow moves; a failed transfer is retried by the timer. Panel members only.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn vote_appeal(gig_id: u64, ruling: Ruling) -> Result<Appeal, GigError> {
+    let arbitrator = caller();
+    disputes::validate_ruling(&ruling)?;
+    let appeal = APPEALS.with(|appeals| {
+        let mut appeals = appeals.borrow_mut();
+        let mut appeal = appeals.get(&gig_id).ok_or_else(|| GigError::not_found("Appeal"))?;
+        // Ensure only the seated panel can vote on the appeal.
+        if !appeal.panel.contains(&arbitrator) {
+            return Err(GigError::unauthorized("Only the appeal panel can vote"));
+        }
+        if appeal.ruling.is_some() {
+            return Err(GigError::invalid_state("Pending", "Decided"));
+        }
+        if appeal.votes.iter().any(|vote| vote.arbitrator == arbitrator) {
+            return Err(GigError::AlreadyExists { resource: "Vote".to_string() });
+        }
+        let now = time();
+        if now > appeal.voting_due_at {
+            return Err(GigError::validation("gig_id", "The voting window has closed"));
+        }
+        appeal.votes.push(PanelVote { arbitrator, ruling: ruling.clone(), voted_at: now });
+        let agreeing = appeal.votes.iter().filter(|vote| vote.ruling == ruling).count();
+        if agreeing > PANEL_SIZE / 2 {
+            appeal.ruling = Some(ruling);
+            appeal.decided_at = Some(now);
+        }
+        appeals.insert(gig_id, appeal.clone());
+        Ok(appeal)
+    })?;
+    if let Some(ruling) = appeal.ruling.clone() {
+        if let Err(error) = disputes::enforce(gig_id, ruling).await {
+            log::warn(&format!("Cannot enforce the appeal ruling on gig {}: {:?}", gig_id, error));
+        }
+    }
+    Ok(appeal)
+}
+
+/// Retrieve the latest appeal of a gig's dispute.
+#[ic_cdk::query]
+pub fn get_appeal(gig_id: u64) -> Option<Appeal> {
+    APPEALS.with(|appeals| appeals.borrow().get(&gig_id))
+}
+
+/// Whether `arbitrator` sits on an undecided appeal panel and has not voted yet.
+pub fn has_open_vote(arbitrator: Principal) -> bool {
+    APPEALS.with(|appeals| {
+        appeals.borrow().iter().any(|(_, appeal)| {
+            appeal.ruling.is_none()
+                && appeal.panel.contains(&arbitrator)
+                && !appeal.votes.iter().any(|vote| vote.arbitrator == arbitrator)
+        })
+    })
+}
+
+/// Enforce every ruling that has become binding: unappealed rulings once the appeal
+/// window closes, panel rulings, and original rulings whose panel missed its deadline.
+pub fn enforce_binding_rulings() {
+    let now = time();
+    for dispute in disputes::provisional_rulings() {
+        let Some(original) = dispute.ruling.clone() else {
+            continue;
+        };
+        let binding = match appeal_of(&dispute) {
+            Some(appeal) => match appeal.ruling {
+                Some(ruling) => Some(ruling),
+                None => (now > appeal.voting_due_at).then_some(original),
+            },
+            None => dispute.appeal_due_at.filter(|due| now > *due).map(|_| original),
+        };
+        if let Some(ruling) = binding {
+            // A failed transfer leaves the gig disputed, so the next sweep retries it.
+            ic_cdk::spawn(async move {
+                if let Err(error) = disputes::enforce(dispute.gig_id, ruling).await {
+                    let gig_id = dispute.gig_id;
+                    log::warn(&format!("Cannot enforce the ruling on gig {}: {:?}", gig_id, error));
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::appeals;
+use crate::disputes;
+use crate::error::GigError;
+use crate::ledger;
+use crate::moderation::not_banned;
+use crate::teams;
+use crate::treasury;
+use crate::{get_memory, Memory, StorablePrincipal, ARBITRATOR_STAKES_MEMORY_ID, GIG_STORAGE};
+
+/// Smallest stake that places an arbitrator in the selection pool: one ICP.
+const MIN_STAKE: u64 = 100_000_000;
+/// Time a selected arbitrator has to rule: seven days.
+const RULING_WINDOW_NANOS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+/// Share of the stake taken from an arbitrator who misses a ruling deadline.
+const SLASH_PERCENT: u64 = 10;
+
+/// ICP an arbitrator has locked in the canister to join the selection pool.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct ArbitratorStake {
+    pub arbitrator: Principal,
+    pub amount: u64,            // Stake still held, in e8s.
+    pub slashed_amount: u64,    // Stake taken for missed rulings so far.
+    pub missed_rulings: u32,
+    pub staked_at: u64,
+    pub updated_at: u64,
+}
+
+impl Storable for ArbitratorStake {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for ArbitratorStake {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Arbitrator stakes keyed by arbitrator.
+    static STAKES: RefCell<StableBTreeMap<StorablePrincipal, ArbitratorStake, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(ARBITRATOR_STAKES_MEMORY_ID)));
+}
+
+/// Lock ICP in the canister to join the arbitrator selection pool, or top up a stake.
+/// The caller must have approved the canister as a spender beforehand. Registered
+/// arbitrators only.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn stake_arbitrator(amount: u64) -> Result<ArbitratorStake, GigError> {
+    let arbitrator = caller();
+    if !disputes::is_arbitrator(arbitrator) {
+        return Err(GigError::unauthorized("Only a registered arbitrator can stake"));
+    }
+    if amount <= ledger::ICP_TRANSFER_FEE {
+        return Err(GigError::validation("amount", "Must exceed the ledger fee"));
+    }
+    ledger::transfer_from(ledger::icp_ledger(), arbitrator, amount).await?;
+
+    let now = time();
+    let key = StorablePrincipal(arbitrator);
+    let stake = STAKES.with(|stakes| {
+        let mut stakes = stakes.borrow_mut();
+        let mut stake = stakes.get(&key).unwrap_or(ArbitratorStake {
+            arbitrator,
+            amount: 0,
+            slashed_amount: 0,
+            missed_rulings: 0,
+            staked_at: now,
+            updated_at: now,
+        });
+        stake.amount += amount;
+        stake.updated_at = now;
+        stakes.insert(key, stake.clone());
+        stake
+    });
+    Ok(stake)
+}
+
+/// Withdraw the caller's whole stake, leaving the selection pool. Arbitrators with an
+/// unresolved case cannot withdraw until they rule.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn unstake_arbitrator() -> Result<u64, GigError> {
+    let arbitrator = caller();
+    if disputes::has_open_case(arbitrator) || appeals::has_open_vote(arbitrator) {
+        return Err(GigError::validation("arbitrator", "Rule on your open disputes first"));
+    }
+    // Remove the stake before awaiting so concurrent calls cannot withdraw twice.
+    let key = StorablePrincipal(arbitrator);
+    let stake = STAKES
+        .with(|stakes| stakes.borrow_mut().remove(&key))
+        .ok_or_else(|| GigError::not_found("Stake"))?;
+    if stake.amount > ledger::ICP_TRANSFER_FEE {
+        let amount = stake.amount - ledger::ICP_TRANSFER_FEE;
+        if let Err(error) = ledger::transfer(ledger::icp_ledger(), arbitrator, amount).await {
+            STAKES.with(|stakes| stakes.borrow_mut().insert(key, stake));
+            return Err(error);
+        }
+    }
+    Ok(stake.amount)
+}
+
+/// Retrieve an arbitrator's stake.
+#[ic_cdk::query]
+pub fn get_arbitrator_stake(arbitrator: Principal) -> Option<ArbitratorStake> {
+    STAKES.with(|stakes| stakes.borrow().get(&StorablePrincipal(arbitrator)))
+}
+
+/// Arbitrators eligible to rule on a gig: registered, staked at least `MIN_STAKE`, not a
+/// party to the gig and not in `exclude`.
+pub fn pool(gig_id: u64, exclude: &[Principal]) -> Vec<Principal> {
+    let parties: Vec<Principal> = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .map(|gig| [vec![gig.employer], teams::workers(&gig)].concat())
+        .unwrap_or_default();
+    STAKES.with(|stakes| {
+        stakes
+            .borrow()
+            .iter()
+            .filter(|(_, stake)| stake.amount >= MIN_STAKE)
+            .map(|(key, _)| key.0)
+            .filter(|arbitrator| disputes::is_arbitrator(*arbitrator))
+            .filter(|arbitrator| !parties.contains(arbitrator))
+            .filter(|arbitrator| !exclude.contains(arbitrator))
+            .collect()
+    })
+}
+
+/// Pick an arbitrator for a new dispute from the staked pool using the management
+/// canister's randomness. Without eligible arbitrators any registered arbitrator can rule.
+pub async fn select_arbitrator(gig_id: u64) {
+    reselect(gig_id, Vec::new()).await;
+}
+
+/// Pick an arbitrator for a dispute from the staked pool, skipping `exclude`.
+async fn reselect(gig_id: u64, exclude: Vec<Principal>) {
+    let candidates = pool(gig_id, &exclude);
+    if candidates.is_empty() {
+        disputes::assign_arbitrator(gig_id, None, None);
+        return;
+    }
+    // A failed randomness call falls back to the first candidate rather than leaving the
+    // dispute without an arbitrator.
+    let seed = match raw_rand().await {
+        Ok((bytes,)) => bytes.iter().take(8).fold(0u64, |acc, byte| (acc << 8) | *byte as u64),
+        Err(_) => 0,
+    };
+    let arbitrator = candidates[(seed % candidates.len() as u64) as usize];
+    disputes::assign_arbitrator(gig_id, Some(arbitrator), Some(time() + RULING_WINDOW_NANOS));
+}
+
+/// Draw `count` arbitrators at random from `candidates` using the management canister's
+/// randomness, for example to seat an appeal panel.
+pub async fn draw(mut candidates: Vec<Principal>, count: usize) -> Vec<Principal> {
+    let bytes = raw_rand().await.map(|(bytes,)| bytes).unwrap_or_default();
+    let count = count.min(candidates.len());
+    for i in 0..count {
+        let roll = bytes.get(i).copied().unwrap_or(0) as usize;
+        let j = i + roll % (candidates.len() - i);
+        candidates.swap(i, j);
+    }
+    candidates.truncate(count);
+    candidates
+}
+
+/// Slash the stake of every selected arbitrator who let a ruling deadline pass, moving the
+/// slashed ICP to the treasury, and hand their disputes to another arbitrator.
+pub fn slash_missed_rulings() {
+    for dispute in disputes::overdue_rulings(time()) {
+        let Some(arbitrator) = dispute.arbitrator else {
+            continue;
+        };
+        let key = StorablePrincipal(arbitrator);
+        STAKES.with(|stakes| {
+            let mut stakes = stakes.borrow_mut();
+            if let Some(mut stake) = stakes.get(&key) {
+                let slashed = stake.amount * SLASH_PERCENT / 100;
+                stake.amount -= slashed;
+                stake.slashed_amount += slashed;
+                stake.missed_rulings += 1;
+                stake.updated_at = time();
+                stakes.insert(key, stake);
+                treasury::accrue(ledger::icp_ledger(), ledger::ICP_TRANSFER_FEE, slashed);
+            }
+        });
+        // Clear the deadline first so the next sweep does not slash again while the
+        // replacement is being drawn.
+        disputes::assign_arbitrator(dispute.gig_id, None, None);
+        ic_cdk::spawn(reselect(dispute.gig_id, vec![arbitrator]));
+    }
+}
@@ -0,0 +1,254 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use sha2::{Digest, Sha256};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::id;
+use crate::moderation::{self, not_banned};
+use crate::{
+    get_memory, IdCell, Memory, ATTACHMENTS_MEMORY_ID, ATTACHMENT_CHUNKS_MEMORY_ID,
+    ATTACHMENT_ID_COUNTER_MEMORY_ID,
+};
+
+/// Largest chunk accepted by a single upload call.
+const MAX_CHUNK_SIZE: usize = 256 * 1024;
+/// Largest file that can be assembled from chunks.
+const MAX_ATTACHMENT_SIZE: u64 = 8 * 1024 * 1024;
+/// Most chunks a file can be split into.
+const MAX_CHUNKS: u32 = (MAX_ATTACHMENT_SIZE / MAX_CHUNK_SIZE as u64) as u32;
+/// Most attachments a gig brief or a submission can reference.
+const MAX_ATTACHMENTS_PER_GIG: usize = 5;
+
+/// Metadata of an uploaded file. The content is stored separately in chunks.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: u64,
+    pub owner: Principal,           // Uploader; the only principal that can attach the file.
+    pub name: String,               // File name, set on finalisation.
+    pub content_type: String,       // MIME type, set on finalisation.
+    pub size: u64,                  // Total bytes across all chunks.
+    pub chunk_count: u32,
+    pub created_at: u64,
+    pub finalized_at: Option<u64>,  // `None` while chunks are still being uploaded.
+}
+
+impl Storable for Attachment {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Attachment {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Raw bytes of one chunk of a file.
+struct Chunk(Vec<u8>);
+
+impl Storable for Chunk {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Chunk(bytes.into_owned())
+    }
+}
+
+impl BoundedStorable for Chunk {
+    const MAX_SIZE: u32 = MAX_CHUNK_SIZE as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Counter to generate unique IDs for attachments.
+    static ATTACHMENT_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(get_memory(ATTACHMENT_ID_COUNTER_MEMORY_ID), 0)
+            .expect("Cannot create an attachment counter")
+    );
+
+    /// Attachment metadata keyed by attachment ID.
+    static ATTACHMENTS: RefCell<StableBTreeMap<u64, Attachment, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(ATTACHMENTS_MEMORY_ID)));
+
+    /// File content keyed by `(attachment_id, chunk_index)` so a file's chunks are contiguous.
+    static CHUNKS: RefCell<StableBTreeMap<(u64, u32), Chunk, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(ATTACHMENT_CHUNKS_MEMORY_ID)));
+}
+
+/// Ensure the caller may reference every attachment in `ids`: each must be finalised,
+/// uploaded by the caller and listed once.
+pub fn ensure_attachable(ids: &[u64]) -> Result<(), GigError> {
+    if ids.len() > MAX_ATTACHMENTS_PER_GIG {
+        return Err(GigError::validation("attachments", "At most 5 attachments are allowed"));
+    }
+    for (position, id) in ids.iter().enumerate() {
+        if ids[..position].contains(id) {
+            return Err(GigError::validation("attachments", "Attachments must be distinct"));
+        }
+        let attachment = get_attachment(*id).ok_or_else(|| GigError::not_found("Attachment"))?;
+        if attachment.owner != caller() {
+            return Err(GigError::unauthorized("Only the uploader can attach this file"));
+        }
+        if attachment.finalized_at.is_none() {
+            return Err(GigError::validation("attachments", "Attachment is not finalized"));
+        }
+    }
+    Ok(())
+}
+
+/// SHA-256 of a finalised attachment's content, or `None` if it is not finalised.
+pub fn content_hash(attachment_id: u64) -> Option<[u8; 32]> {
+    let attachment = get_attachment(attachment_id)?;
+    attachment.finalized_at?;
+    let mut hasher = Sha256::new();
+    CHUNKS.with(|chunks| {
+        for (_, chunk) in chunks
+            .borrow()
+            .range((attachment_id, 0)..(attachment_id, attachment.chunk_count))
+        {
+            hasher.update(&chunk.0);
+        }
+    });
+    Some(hasher.finalize().into())
+}
+
+/// Upload one chunk of a file. Pass `None` as `attachment_id` to start a new file; the ID
+/// to use for the remaining chunks is returned. Re-uploading an index replaces the chunk.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn upload_attachment_chunk(
+    attachment_id: Option<u64>,
+    index: u32,
+    data: Vec<u8>,
+) -> Result<u64, GigError> {
+    let owner = caller();
+    if owner == Principal::anonymous() {
+        return Err(GigError::unauthorized("Anonymous principals cannot upload files"));
+    }
+    moderation::ensure_not_banned(owner)?;
+    if data.is_empty() || data.len() > MAX_CHUNK_SIZE {
+        return Err(GigError::validation("data", "Chunks must be between 1 byte and 256 KiB"));
+    }
+    if index >= MAX_CHUNKS {
+        return Err(GigError::validation("index", "Files are limited to 32 chunks"));
+    }
+
+    let mut attachment = match attachment_id {
+        Some(id) => {
+            let attachment = get_attachment(id).ok_or_else(|| GigError::not_found("Attachment"))?;
+            // Ensure only the uploader can add to the file, and only until it is finalised.
+            if attachment.owner != owner {
+                return Err(GigError::unauthorized("Only the uploader can add chunks"));
+            }
+            if attachment.finalized_at.is_some() {
+                return Err(GigError::validation("attachment_id", "Attachment is finalized"));
+            }
+            attachment
+        }
+        None => {
+            let id = id::next(&ATTACHMENT_ID_COUNTER, "Attachment")?;
+            Attachment {
+                id,
+                owner,
+                name: String::new(),
+                content_type: String::new(),
+                size: 0,
+                chunk_count: 0,
+                created_at: time(),
+                finalized_at: None,
+            }
+        }
+    };
+
+    let replaced = CHUNKS
+        .with(|chunks| chunks.borrow().get(&(attachment.id, index)))
+        .map_or(0, |chunk| chunk.0.len() as u64);
+    let size = attachment.size - replaced + data.len() as u64;
+    if size > MAX_ATTACHMENT_SIZE {
+        return Err(GigError::validation("data", "Files are limited to 8 MiB"));
+    }
+    attachment.size = size;
+    attachment.chunk_count = attachment.chunk_count.max(index + 1);
+
+    CHUNKS.with(|chunks| chunks.borrow_mut().insert((attachment.id, index), Chunk(data)));
+    ATTACHMENTS.with(|attachments| {
+        attachments.borrow_mut().insert(attachment.id, attachment.clone())
+    });
+    Ok(attachment.id)
+}
+
+/// Seal an uploaded file so it can be downloaded and attached to gigs. Every chunk up to
+/// the highest uploaded index must be present. Uploader only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn finalize_attachment(
+    attachment_id: u64,
+    name: String,
+    content_type: String,
+) -> Result<Attachment, GigError> {
+    let mut attachment =
+        get_attachment(attachment_id).ok_or_else(|| GigError::not_found("Attachment"))?;
+    // Ensure only the uploader can finalise the file.
+    if attachment.owner != caller() {
+        return Err(GigError::unauthorized("Only the uploader can finalize this attachment"));
+    }
+    if attachment.finalized_at.is_some() {
+        return Err(GigError::validation("attachment_id", "Attachment is already finalized"));
+    }
+    let name = name.trim().to_string();
+    if name.is_empty() || name.len() > 255 {
+        return Err(GigError::validation("name", "Name must be between 1 and 255 bytes"));
+    }
+    let content_type = content_type.trim().to_lowercase();
+    if content_type.is_empty() || content_type.len() > 100 {
+        return Err(GigError::validation(
+            "content_type",
+            "Content type must be between 1 and 100 bytes",
+        ));
+    }
+    // Ensure no chunk was skipped.
+    let uploaded = CHUNKS.with(|chunks| {
+        chunks
+            .borrow()
+            .range((attachment_id, 0)..(attachment_id, attachment.chunk_count))
+            .count()
+    });
+    if uploaded != attachment.chunk_count as usize {
+        return Err(GigError::validation("attachment_id", "Some chunks are missing"));
+    }
+
+    attachment.name = name;
+    attachment.content_type = content_type;
+    attachment.finalized_at = Some(time());
+    ATTACHMENTS.with(|attachments| {
+        attachments.borrow_mut().insert(attachment_id, attachment.clone())
+    });
+    Ok(attachment)
+}
+
+/// Retrieve the metadata of an attachment by ID.
+#[ic_cdk::query]
+pub fn get_attachment(attachment_id: u64) -> Option<Attachment> {
+    ATTACHMENTS.with(|attachments| attachments.borrow().get(&attachment_id))
+}
+
+/// Download one chunk of a finalised attachment.
+#[ic_cdk::query]
+pub fn get_attachment_chunk(attachment_id: u64, index: u32) -> Result<Vec<u8>, GigError> {
+    let attachment =
+        get_attachment(attachment_id).ok_or_else(|| GigError::not_found("Attachment"))?;
+    if attachment.finalized_at.is_none() {
+        return Err(GigError::validation("attachment_id", "Attachment is not finalized"));
+    }
+    CHUNKS
+        .with(|chunks| chunks.borrow().get(&(attachment_id, index)))
+        .map(|chunk| chunk.0)
+        .ok_or_else(|| GigError::not_found("Chunk"))
+}
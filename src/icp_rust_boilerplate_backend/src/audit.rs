@@ -0,0 +1,191 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{StableBTreeMap, StableLog, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::{
+    get_memory, Gig, GigStatus, Memory, AUDIT_INDEX_MEMORY_ID, AUDIT_LOG_DATA_MEMORY_ID,
+    AUDIT_LOG_INDEX_MEMORY_ID, MAX_PAGE_SIZE,
+};
+
+/// Kind of change recorded in the audit log.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum AuditAction {
+    Created,
+    Updated,
+    Deleted,
+    EscrowFrozen,       // A dispute froze the gig's escrow.
+    EscrowUnfrozen,     // The dispute's ruling lifted the freeze.
+    CertificateSkipped, // The certificate counter ran out, so the completion minted none.
+    WebhookSkipped,     // The delivery counter ran out, so webhooks missed this change.
+}
+
+/// A single recorded change to a gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,                           // Position in the log.
+    pub gig_id: u64,
+    pub actor: Principal,                   // Caller whose message made the change.
+    pub timestamp: u64,
+    pub action: AuditAction,
+    pub previous_status: Option<GigStatus>, // `None` on creation.
+    pub status: Option<GigStatus>,          // `None` on deletion.
+    pub changed_fields: Vec<String>,        // Names of the gig fields that changed.
+}
+
+/// A page of audit entries along with the total number of entries.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    pub total: u64,
+}
+
+impl Storable for AuditEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+thread_local! {
+    /// Append-only log of gig changes.
+    static AUDIT_LOG: RefCell<StableLog<AuditEntry, Memory, Memory>> = RefCell::new(
+        StableLog::init(
+            get_memory(AUDIT_LOG_INDEX_MEMORY_ID),
+            get_memory(AUDIT_LOG_DATA_MEMORY_ID),
+        )
+        .expect("Cannot create the audit log")
+    );
+
+    /// Log positions keyed by `(gig_id, seq)` so a gig's history is contiguous.
+    static AUDIT_INDEX: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(AUDIT_INDEX_MEMORY_ID)));
+}
+
+/// Names of the fields that differ between two versions of a gig.
+pub fn changed_fields(previous: &Gig, current: &Gig) -> Vec<String> {
+    let mut changed = Vec::new();
+    let mut check = |name: &str, differs: bool| {
+        if differs {
+            changed.push(name.to_string());
+        }
+    };
+    check("title", previous.title != current.title);
+    check("description", previous.description != current.description);
+    check("deadline", previous.deadline != current.deadline);
+    check("assigned_to", previous.assigned_to != current.assigned_to);
+    check("status", previous.status != current.status);
+    check("submission_note", previous.submission_note != current.submission_note);
+    check("milestones", previous.milestones != current.milestones);
+    check("payment", previous.payment != current.payment);
+    check("category", previous.category != current.category);
+    check("tags", previous.tags != current.tags);
+    check("budget", previous.budget != current.budget);
+    check("archived_from", previous.archived_from != current.archived_from);
+    check("auto_approve_days", previous.auto_approve_days != current.auto_approve_days);
+    check("attachments", previous.attachments != current.attachments);
+    check(
+        "submission_attachments",
+        previous.submission_attachments != current.submission_attachments,
+    );
+    check("proposed_at", previous.proposed_at != current.proposed_at);
+    check("visibility", previous.visibility != current.visibility);
+    check("required_skills", previous.required_skills != current.required_skills);
+    check("gig_type", previous.gig_type != current.gig_type);
+    check("delivery_terms", previous.delivery_terms != current.delivery_terms);
+    check("depends_on", previous.depends_on != current.depends_on);
+    check("org_id", previous.org_id != current.org_id);
+    check("dispute_split", previous.dispute_split != current.dispute_split);
+    check("reminder_hours", previous.reminder_hours != current.reminder_hours);
+    check("usd_budget", previous.usd_budget != current.usd_budget);
+    check("purge_at", previous.purge_at != current.purge_at);
+    check("approvals", previous.approvals != current.approvals);
+    check(
+        "auto_approve_after_secs",
+        previous.auto_approve_after_secs != current.auto_approve_after_secs,
+    );
+    changed
+}
+
+/// Append the change from `previous` to `current` to the log. Writes that change nothing,
+/// such as rewrites during migrations, are not recorded.
+pub fn record(previous: Option<&Gig>, current: Option<&Gig>) {
+    let (gig_id, action, changed) = match (previous, current) {
+        (None, Some(gig)) => (gig.id, AuditAction::Created, Vec::new()),
+        (Some(old), Some(new)) => (new.id, AuditAction::Updated, changed_fields(old, new)),
+        (Some(gig), None) => (gig.id, AuditAction::Deleted, Vec::new()),
+        (None, None) => return,
+    };
+    if action == AuditAction::Updated && changed.is_empty() {
+        return;
+    }
+    append(
+        gig_id,
+        action,
+        previous.map(|gig| gig.status.clone()),
+        current.map(|gig| gig.status.clone()),
+        changed,
+    );
+}
+
+/// Record something that happened to a gig without changing it, such as a dispute freezing
+/// its escrow or a completion that could not mint its certificate.
+pub fn record_note(gig: &Gig, action: AuditAction) {
+    append(gig.id, action, Some(gig.status.clone()), Some(gig.status.clone()), Vec::new());
+}
+
+/// Append an entry made by the caller to the log and index it under its gig.
+fn append(
+    gig_id: u64,
+    action: AuditAction,
+    previous_status: Option<GigStatus>,
+    status: Option<GigStatus>,
+    changed_fields: Vec<String>,
+) {
+    AUDIT_LOG.with(|log| {
+        let log = log.borrow();
+        let entry = AuditEntry {
+            seq: log.len(),
+            gig_id,
+            actor: caller(),
+            timestamp: time(),
+            action,
+            previous_status,
+            status,
+            changed_fields,
+        };
+        let seq = log.append(&entry).expect("Cannot append to the audit log");
+        AUDIT_INDEX.with(|index| index.borrow_mut().insert((gig_id, seq), ()));
+    });
+}
+
+/// Retrieve every recorded change to a gig, oldest first.
+#[ic_cdk::query]
+pub fn get_gig_history(gig_id: u64) -> Vec<AuditEntry> {
+    let seqs: Vec<u64> = AUDIT_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u64::MAX))
+            .map(|((_, seq), _)| seq)
+            .collect()
+    });
+    AUDIT_LOG.with(|log| {
+        let log = log.borrow();
+        seqs.into_iter().filter_map(|seq| log.get(seq)).collect()
+    })
+}
+
+/// Retrieve a page of the audit log, oldest first. `limit` is capped at `MAX_PAGE_SIZE`.
+#[ic_cdk::query]
+pub fn get_audit_log(offset: u64, limit: u64) -> AuditPage {
+    AUDIT_LOG.with(|log| {
+        let log = log.borrow();
+        let end = offset.saturating_add(limit.min(MAX_PAGE_SIZE)).min(log.len());
+        let entries = (offset..end).filter_map(|seq| log.get(seq)).collect();
+        AuditPage { entries, total: log.len() }
+    })
+}
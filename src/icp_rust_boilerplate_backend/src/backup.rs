@@ -0,0 +1,199 @@
+use ic_cdk::api::stable::{stable64_grow, stable64_read, stable64_size, stable64_write};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::Cell;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::error::GigError;
+use crate::id;
+use crate::{ensure_controller, get_memory, Memory, FIRST_USED_MEMORY_ID, GIG_STORAGE};
+
+/// Bytes of stable memory in one backup chunk: 1 MiB, well within the reply size limit.
+const CHUNK_SIZE: u64 = 1024 * 1024;
+/// Size of a stable memory page.
+const WASM_PAGE_BYTES: u64 = 64 * 1024;
+
+/// One slice of the canister's stable memory, which holds its entire state.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct BackupChunk {
+    pub chunk_index: u64,
+    pub chunk_count: u64,
+    pub data: Vec<u8>,
+    pub chunk_hash: Vec<u8>,    // SHA-256 of `data`.
+}
+
+/// How far a restore has got.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct RestoreProgress {
+    pub chunks_received: u64,
+    pub chunk_count: u64,
+    pub state_hash: Option<Vec<u8>>,    // Set once every chunk is in; compare with the source.
+}
+
+/// A restore under way: the chunks written so far and the running state hash.
+struct Restore {
+    chunk_count: u64,
+    chunks_received: u64,
+    hasher: Sha256,
+    state_hash: Option<Vec<u8>>,
+}
+
+thread_local! {
+    /// The restore under way, if any. Kept on the heap because the stable memory is being
+    /// overwritten underneath the canister's own structures.
+    static RESTORE: RefCell<Option<Restore>> = const { RefCell::new(None) };
+
+    /// When the canister first took an update call other than a restore, 0 until then.
+    static FIRST_USED_AT: RefCell<Cell<u64, Memory>> = RefCell::new(
+        Cell::init(get_memory(FIRST_USED_MEMORY_ID), 0)
+            .expect("Cannot initialize the first-use time")
+    );
+}
+
+/// Number of chunks the current stable memory splits into.
+fn chunk_count() -> u64 {
+    (stable64_size() * WASM_PAGE_BYTES).div_ceil(CHUNK_SIZE)
+}
+
+/// Read one chunk of stable memory.
+fn read_chunk(chunk_index: u64) -> Vec<u8> {
+    let total_bytes = stable64_size() * WASM_PAGE_BYTES;
+    let offset = chunk_index * CHUNK_SIZE;
+    let mut data = vec![0; CHUNK_SIZE.min(total_bytes - offset) as usize];
+    stable64_read(offset, &mut data);
+    data
+}
+
+/// Whether a restore has started. The stored state is then not what the heap structures
+/// expect, so nothing may touch stable memory until the canister is upgraded.
+pub fn is_restoring() -> bool {
+    RESTORE.with(|restore| restore.borrow().is_some())
+}
+
+/// Reject the call while a restore is under way.
+pub fn ensure_not_restoring() -> Result<(), String> {
+    if is_restoring() {
+        return Err("A backup is being restored; upgrade the canister to load it".to_string());
+    }
+    Ok(())
+}
+
+/// Remember that the canister has taken an update call, so it can no longer be restored
+/// into. Any state of its own may have been written from then on. Also run as a migration,
+/// for canisters upgraded from before first use was recorded.
+pub fn mark_in_use() {
+    FIRST_USED_AT.with(|cell| {
+        if *cell.borrow().get() == 0 {
+            cell.borrow_mut().set(time()).expect("Cannot record the first-use time");
+        }
+    });
+}
+
+/// Whether the canister has never taken an update call and holds no gigs.
+fn is_untouched() -> bool {
+    FIRST_USED_AT.with(|cell| *cell.borrow().get() == 0)
+        && GIG_STORAGE.with(|storage| storage.borrow().is_empty())
+        && id::peek_gig_id() == 0
+}
+
+/// Guard run before `import_backup`. Unlike `not_banned` it reads nothing from stable
+/// memory, which no longer holds the canister's own structures once a restore started.
+pub fn controller_only() -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&caller()) {
+        return Err("Only a canister controller can perform this action".to_string());
+    }
+    Ok(())
+}
+
+/// Retrieve one chunk of the canister's stable memory for a backup. Stream every chunk
+/// from 0 to `chunk_count - 1`, while the canister takes no writes, so the chunks form
+/// one consistent snapshot. Controller only.
+#[ic_cdk::query]
+pub fn export_backup(chunk_index: u64) -> Result<BackupChunk, GigError> {
+    ensure_controller()?;
+    let chunk_count = chunk_count();
+    if chunk_index >= chunk_count {
+        return Err(GigError::validation("chunk_index", "Must be below the chunk count"));
+    }
+    let data = read_chunk(chunk_index);
+    let chunk_hash = Sha256::digest(&data).to_vec();
+    Ok(BackupChunk { chunk_index, chunk_count, data, chunk_hash })
+}
+
+/// Retrieve the hash of the whole state: SHA-256 over the hashes of every backup chunk in
+/// order. A restore reports the same hash once every chunk is in. Reads all of stable
+/// memory, so it fits the query instruction limit only for moderately sized canisters.
+/// Controller only.
+#[ic_cdk::query]
+pub fn get_state_hash() -> Result<Vec<u8>, GigError> {
+    ensure_controller()?;
+    let mut hasher = Sha256::new();
+    for chunk_index in 0..chunk_count() {
+        hasher.update(Sha256::digest(read_chunk(chunk_index)));
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Write one backup chunk into this canister's stable memory, in order from chunk 0.
+/// Only a freshly installed canister that has taken no other update call can be restored
+/// into. Once the first chunk is written the background timers stop and every other
+/// update call is rejected; when the reported state hash matches the source, upgrade the
+/// canister with the same module to load the restored state. Controller only.
+#[ic_cdk::update(guard = "controller_only")]
+pub fn import_backup(chunk: BackupChunk) -> Result<RestoreProgress, GigError> {
+    ensure_controller()?;
+    if Sha256::digest(&chunk.data).as_slice() != chunk.chunk_hash.as_slice() {
+        return Err(GigError::validation("chunk_hash", "Does not match the chunk data"));
+    }
+    if chunk.data.len() as u64 > CHUNK_SIZE
+        || (chunk.chunk_index + 1 < chunk.chunk_count && chunk.data.len() as u64 != CHUNK_SIZE)
+    {
+        return Err(GigError::validation("data", "Only the last chunk may be short"));
+    }
+    if !is_restoring() {
+        if chunk.chunk_index != 0 {
+            return Err(GigError::validation("chunk_index", "A restore starts at chunk 0"));
+        }
+        if !is_untouched() {
+            return Err(GigError::invalid_state_text("A freshly installed canister", "In use"));
+        }
+        let restore = Restore {
+            chunk_count: chunk.chunk_count,
+            chunks_received: 0,
+            hasher: Sha256::new(),
+            state_hash: None,
+        };
+        RESTORE.with(|cell| *cell.borrow_mut() = Some(restore));
+    }
+    RESTORE.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let restore = cell.as_mut().expect("A restore is under way");
+        if chunk.chunk_count != restore.chunk_count
+            || chunk.chunk_index != restore.chunks_received
+        {
+            return Err(GigError::validation(
+                "chunk_index",
+                &format!("Expected chunk {} of {}", restore.chunks_received, restore.chunk_count),
+            ));
+        }
+        let offset = chunk.chunk_index * CHUNK_SIZE;
+        let end = offset + chunk.data.len() as u64;
+        let pages = stable64_size();
+        if end > pages * WASM_PAGE_BYTES {
+            stable64_grow(end.div_ceil(WASM_PAGE_BYTES) - pages)
+                .map_err(|error| GigError::validation("data", &format!("{:?}", error)))?;
+        }
+        stable64_write(offset, &chunk.data);
+        restore.hasher.update(&chunk.chunk_hash);
+        restore.chunks_received += 1;
+        if restore.chunks_received == restore.chunk_count {
+            restore.state_hash = Some(restore.hasher.clone().finalize().to_vec());
+        }
+        Ok(RestoreProgress {
+            chunks_received: restore.chunks_received,
+            chunk_count: restore.chunk_count,
+            state_hash: restore.state_hash.clone(),
+        })
+    })
+}
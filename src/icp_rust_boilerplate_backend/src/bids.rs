@@ -0,0 +1,366 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::analytics;
+use crate::contracts;
+use crate::delegates::Permission;
+use crate::dependencies;
+use crate::error::GigError;
+use crate::id;
+use crate::invitations;
+use crate::moderation::{self, not_banned};
+use crate::orgs;
+use crate::profiles;
+use crate::rate_limit;
+use crate::roles;
+use crate::state_machine;
+use crate::visibility::Visibility;
+use crate::{
+    do_insert_gig, get_memory, Gig, GigStatus, IdCell, Memory, BIDS_MEMORY_ID,
+    BID_ID_COUNTER_MEMORY_ID, BID_OFFERS_MEMORY_ID, GIG_STORAGE,
+};
+
+/// Most counter-offers a negotiation can go through.
+const MAX_OFFER_ROUNDS: u32 = 10;
+/// Longest terms accepted on a counter-offer, in bytes.
+const MAX_TERMS_LEN: usize = 500;
+/// Longest proposal accepted on a bid, in bytes.
+const MAX_PROPOSAL_LEN: usize = 1000;
+
+/// Counter-offers keyed by `((gig_id, bid_id), round)`.
+type OfferMap = StableBTreeMap<((u64, u64), u32), Offer, Memory>;
+
+/// A worker's application to a gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Bid {
+    pub id: u64,
+    pub gig_id: u64,
+    pub worker: Principal,
+    pub proposal: String,   // Pitch from the worker.
+    pub amount: u64,        // Price the worker asks for the gig.
+    pub status: BidStatus,
+    pub created_at: u64,
+    pub agreed_amount: Option<u64>,   // Price settled on when the bid was accepted.
+    pub agreed_terms: Option<String>, // Terms of the accepted counter-offer, if any.
+}
+
+/// One step of the negotiation on a bid: new terms from the employer or the worker.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Offer {
+    pub gig_id: u64,
+    pub bid_id: u64,
+    pub round: u32,         // 1 for the first counter-offer on the bid.
+    pub from: Principal,
+    pub amount: u64,
+    pub terms: String,
+    pub made_at: u64,
+}
+
+/// Enum representing possible statuses of a bid.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum BidStatus {
+    Pending,    // Waiting for the employer's decision.
+    Accepted,   // The worker was assigned the gig.
+    Rejected,   // Another bid was accepted.
+}
+
+impl Storable for Bid {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Bid {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for Offer {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Offer {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Counter to generate unique IDs for bids.
+    static BID_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(get_memory(BID_ID_COUNTER_MEMORY_ID), 0)
+            .expect("Cannot create a bid counter")
+    );
+
+    /// Bids keyed by `(gig_id, bid_id)` so a gig's bids are contiguous.
+    static BID_STORAGE: RefCell<StableBTreeMap<(u64, u64), Bid, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(BIDS_MEMORY_ID)));
+
+    /// Counter-offers on bids, keyed so each negotiation is contiguous.
+    static OFFERS: RefCell<OfferMap> =
+        RefCell::new(StableBTreeMap::init(get_memory(BID_OFFERS_MEMORY_ID)));
+}
+
+/// Ensure a price asked or offered for a gig is positive and within its budget, if it has
+/// one.
+fn ensure_amount(gig: &Gig, amount: u64) -> Result<(), GigError> {
+    if amount == 0 {
+        return Err(GigError::validation("amount", "Amount must be greater than zero"));
+    }
+    if gig.budget.as_ref().is_some_and(|budget| amount > budget.amount) {
+        return Err(GigError::validation("amount", "Amount cannot exceed the gig's budget"));
+    }
+    Ok(())
+}
+
+/// Apply to an open gig with a proposal and asking price, which cannot exceed the gig's
+/// budget if it has one.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn apply_to_gig(gig_id: u64, proposal: String, amount: u64) -> Result<Bid, GigError> {
+    let worker = caller();
+    if worker == Principal::anonymous() {
+        return Err(GigError::unauthorized("Anonymous principals cannot apply to gigs"));
+    }
+    moderation::ensure_not_banned(worker)?;
+    rate_limit::check(worker, rate_limit::Action::ApplyToGig)?;
+    // Only registered workers can bid.
+    if !profiles::is_registered(worker) {
+        return Err(GigError::unauthorized("Register a profile before applying to gigs"));
+    }
+    if proposal.trim().is_empty() {
+        return Err(GigError::validation("proposal", "Proposal cannot be empty"));
+    }
+    if proposal.len() > MAX_PROPOSAL_LEN {
+        return Err(GigError::validation("proposal", "Proposal must be at most 1000 bytes"));
+    }
+
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure the gig is still accepting applications.
+    if gig.status != GigStatus::Open {
+        return Err(GigError::invalid_state("Open", gig.status));
+    }
+    ensure_amount(&gig, amount)?;
+    // Invite-only gigs take bids from invited workers alone.
+    if gig.visibility() == Visibility::InviteOnly && !invitations::is_invited(gig_id, worker) {
+        return Err(GigError::unauthorized("This gig only takes bids from invited workers"));
+    }
+    // Employers cannot bid on their own gigs.
+    if gig.employer == worker {
+        return Err(GigError::unauthorized("Employers cannot apply to their own gigs"));
+    }
+    // One bid per worker per gig.
+    if list_bids(gig_id).iter().any(|bid| bid.worker == worker) {
+        return Err(GigError::AlreadyExists { resource: "Bid".to_string() });
+    }
+
+    let id = id::next(&BID_ID_COUNTER, "Bid")?;
+
+    let bid = Bid {
+        id,
+        gig_id,
+        worker,
+        proposal,
+        amount,
+        status: BidStatus::Pending,
+        created_at: time(),
+        agreed_amount: None,
+        agreed_terms: None,
+    };
+    BID_STORAGE.with(|storage| storage.borrow_mut().insert((gig_id, id), bid.clone()));
+    analytics::record_bid(gig_id, bid.created_at);
+    Ok(bid)
+}
+
+/// Retrieve all bids placed on a gig.
+#[ic_cdk::query]
+pub fn list_bids(gig_id: u64) -> Vec<Bid> {
+    BID_STORAGE.with(|storage| {
+        storage
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u64::MAX))
+            .map(|(_, bid)| bid)
+            .collect()
+    })
+}
+
+/// Look up a gig and one of its bids, ensuring both are still open to negotiation.
+fn open_bid(gig_id: u64, bid_id: u64) -> Result<(Gig, Bid), GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure the gig is open before assignment.
+    if gig.status != GigStatus::Open {
+        return Err(GigError::invalid_state("Open", gig.status));
+    }
+    let bid = BID_STORAGE
+        .with(|storage| storage.borrow().get(&(gig_id, bid_id)))
+        .ok_or_else(|| GigError::not_found("Bid"))?;
+    if bid.status != BidStatus::Pending {
+        return Err(GigError::invalid_state("Pending", bid.status));
+    }
+    Ok((gig, bid))
+}
+
+/// Latest counter-offer on a bid, if the parties have started negotiating.
+fn latest_offer(gig_id: u64, bid_id: u64) -> Option<Offer> {
+    OFFERS.with(|offers| {
+        offers
+            .borrow()
+            .range(((gig_id, bid_id), 0)..=((gig_id, bid_id), u32::MAX))
+            .map(|(_, offer)| offer)
+            .last()
+    })
+}
+
+/// Answer a bid with new terms, whose amount must be positive and within the gig's budget.
+/// The employer's side, which includes their organization and delegates allowed to assign,
+/// and the worker take turns: the employer's side counters the bid or the worker's last
+/// counter-offer, and the worker re-counters the employer's. Negotiations end after ten
+/// counter-offers.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn counter_offer(
+    gig_id: u64,
+    bid_id: u64,
+    amount: u64,
+    terms: String,
+) -> Result<Offer, GigError> {
+    let from = caller();
+    moderation::ensure_not_banned(from)?;
+    if terms.len() > MAX_TERMS_LEN {
+        return Err(GigError::validation("terms", "Must be at most 500 bytes"));
+    }
+    let (gig, bid) = open_bid(gig_id, bid_id)?;
+    // Ensure only the employer, their organization, a delegate or the bidder can negotiate.
+    let from_worker = from == bid.worker;
+    if !from_worker && !orgs::acts_for_employer(&gig, from, Permission::Assign) {
+        return Err(GigError::unauthorized("Only the employer or bidder can negotiate"));
+    }
+    ensure_amount(&gig, amount)?;
+    let latest = latest_offer(gig_id, bid_id);
+    // The bid itself is the worker's opening offer.
+    let last_from_worker = latest.as_ref().is_none_or(|offer| offer.from == bid.worker);
+    if last_from_worker == from_worker {
+        return Err(GigError::validation("bid_id", "Waiting for the other party to respond"));
+    }
+    let round = latest.map_or(1, |offer| offer.round + 1);
+    if round > MAX_OFFER_ROUNDS {
+        return Err(GigError::validation("bid_id", "No counter-offers left on this bid"));
+    }
+
+    let offer = Offer { gig_id, bid_id, round, from, amount, terms, made_at: time() };
+    OFFERS.with(|offers| offers.borrow_mut().insert(((gig_id, bid_id), round), offer.clone()));
+    Ok(offer)
+}
+
+/// Accept the latest counter-offer on a bid, assigning the gig to the bidder on those
+/// terms. Only the side the offer was made to can accept it: the bidder, or the employer,
+/// their organization or a delegate allowed to assign.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn accept_offer(gig_id: u64, bid_id: u64) -> Result<Gig, GigError> {
+    let (gig, bid) = open_bid(gig_id, bid_id)?;
+    let offer = latest_offer(gig_id, bid_id).ok_or_else(|| GigError::not_found("Offer"))?;
+    let accepter = caller();
+    // Ensure only the side the offer was made to can accept it.
+    let allowed = if offer.from == bid.worker {
+        orgs::acts_for_employer(&gig, accepter, Permission::Assign)
+    } else {
+        accepter == bid.worker
+    };
+    if !allowed {
+        return Err(GigError::unauthorized("Only the party the offer was made to can accept it"));
+    }
+    moderation::ensure_not_banned(accepter)?;
+    assign_bid(gig, bid, offer.amount, Some(offer.terms))
+}
+
+/// Retrieve the counter-offers made on a bid, oldest first. Negotiations are private to
+/// the employer, the bidder and moderators.
+#[ic_cdk::query]
+pub fn get_negotiation(gig_id: u64, bid_id: u64) -> Result<Vec<Offer>, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    let bid = BID_STORAGE
+        .with(|storage| storage.borrow().get(&(gig_id, bid_id)))
+        .ok_or_else(|| GigError::not_found("Bid"))?;
+    if caller() != gig.employer && caller() != bid.worker {
+        roles::ensure_moderator()?;
+    }
+    Ok(OFFERS.with(|offers| {
+        offers
+            .borrow()
+            .range(((gig_id, bid_id), 0)..=((gig_id, bid_id), u32::MAX))
+            .map(|(_, offer)| offer)
+            .collect()
+    }))
+}
+
+/// Accept a bid, assigning the gig to its worker and rejecting the other bids. Applying
+/// counts as consent, so the gig moves straight to `Assigned`. If the worker has made a
+/// counter-offer, the bid is accepted on its terms.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn accept_bid(gig_id: u64, bid_id: u64) -> Result<Gig, GigError> {
+    let (gig, bid) = open_bid(gig_id, bid_id)?;
+    // Ensure only the employer, their organization or a delegate can accept bids.
+    if !orgs::acts_for_employer(&gig, caller(), Permission::Assign) {
+        return Err(GigError::unauthorized("Only the employer can accept bids on this gig"));
+    }
+    let (amount, terms) = match latest_offer(gig_id, bid_id) {
+        Some(offer) if offer.from == gig.employer => {
+            return Err(GigError::validation(
+                "bid_id",
+                "Waiting for the worker to answer your counter-offer",
+            ));
+        }
+        Some(offer) => (offer.amount, Some(offer.terms)),
+        None => (bid.amount, None),
+    };
+    assign_bid(gig, bid, amount, terms)
+}
+
+/// Assign the gig to a bid's worker on the agreed terms and settle every bid on the gig in
+/// the same call.
+fn assign_bid(
+    mut gig: Gig,
+    bid: Bid,
+    amount: u64,
+    terms: Option<String>,
+) -> Result<Gig, GigError> {
+    state_machine::ensure_transition(&gig.status, &GigStatus::Assigned)?;
+    GIG_STORAGE.with(|storage| dependencies::ensure_met(&storage.borrow(), &gig))?;
+    let bids = list_bids(gig.id);
+    BID_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        for mut other in bids {
+            if other.id == bid.id {
+                other.status = BidStatus::Accepted;
+                other.agreed_amount = Some(amount);
+                other.agreed_terms = terms.clone();
+            } else {
+                other.status = BidStatus::Rejected;
+            }
+            storage.insert((gig.id, other.id), other);
+        }
+    });
+
+    gig.assigned_to = Some(bid.worker);
+    state_machine::transition(&mut gig, GigStatus::Assigned)?;
+    gig.updated_at = Some(time());
+    do_insert_gig(&mut gig);
+    contracts::draft(&gig, &bid, amount, terms);
+    Ok(gig)
+}
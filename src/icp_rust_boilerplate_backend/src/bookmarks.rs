@@ -0,0 +1,89 @@
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+
+use crate::error::GigError;
+use crate::events::{self, EventPage};
+use crate::moderation::not_banned;
+use crate::visibility;
+use crate::{
+    get_memory, GigSummary, Memory, StorablePrincipal, BOOKMARKS_MEMORY_ID, GIG_STORAGE,
+};
+
+/// Most gigs a principal can bookmark.
+const MAX_BOOKMARKS: usize = 200;
+/// Most feed events looked at per `get_watch_events` call.
+const WATCH_SCAN_LIMIT: u64 = 1000;
+
+thread_local! {
+    /// Bookmark times keyed by `(principal, gig_id)`.
+    static BOOKMARKS: RefCell<StableBTreeMap<(StorablePrincipal, u64), u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(BOOKMARKS_MEMORY_ID)));
+}
+
+/// IDs of the gigs the caller has bookmarked, in ID order.
+fn bookmarked_ids() -> Vec<u64> {
+    let key = StorablePrincipal(caller());
+    BOOKMARKS.with(|bookmarks| {
+        bookmarks
+            .borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    })
+}
+
+/// Bookmark a gig to keep an eye on it.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn bookmark_gig(id: u64) -> Result<(), GigError> {
+    let viewable = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&id))
+        .is_some_and(|gig| visibility::can_view(&gig));
+    if !viewable {
+        return Err(GigError::not_found("Gig"));
+    }
+    let key = (StorablePrincipal(caller()), id);
+    if BOOKMARKS.with(|bookmarks| bookmarks.borrow().contains_key(&key)) {
+        return Err(GigError::AlreadyExists { resource: "Bookmark".to_string() });
+    }
+    if bookmarked_ids().len() >= MAX_BOOKMARKS {
+        return Err(GigError::validation("id", "At most 200 gigs can be bookmarked"));
+    }
+    BOOKMARKS.with(|bookmarks| bookmarks.borrow_mut().insert(key, time()));
+    Ok(())
+}
+
+/// Remove a gig from the caller's bookmarks.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn unbookmark_gig(id: u64) -> Result<(), GigError> {
+    BOOKMARKS
+        .with(|bookmarks| bookmarks.borrow_mut().remove(&(StorablePrincipal(caller()), id)))
+        .map(|_| ())
+        .ok_or_else(|| GigError::not_found("Bookmark"))
+}
+
+/// Retrieve the gigs the caller has bookmarked that they can still view, in ID order.
+#[ic_cdk::query]
+pub fn get_bookmarks() -> Vec<GigSummary> {
+    let ids = bookmarked_ids();
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        ids.into_iter()
+            .filter_map(|id| storage.get(&id))
+            .filter(visibility::can_view)
+            .map(|gig| GigSummary::from(&gig))
+            .collect()
+    })
+}
+
+/// Retrieve up to `limit` status changes of the caller's bookmarked gigs from the event
+/// feed, starting at `since_seq`. Each call looks at a bounded stretch of the feed, so a
+/// page can be empty while `next_seq` still advances.
+#[ic_cdk::query]
+pub fn get_watch_events(since_seq: u64, limit: u64) -> EventPage {
+    let ids = bookmarked_ids();
+    events::page_matching(since_seq, limit, WATCH_SCAN_LIMIT, |event| {
+        events::is_status_change(&event.kind) && ids.binary_search(&event.gig_id).is_ok()
+    })
+}
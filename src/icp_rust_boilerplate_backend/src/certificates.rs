@@ -0,0 +1,231 @@
+use candid::{CandidType, Decode, Encode, Nat, Principal};
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::audit::{self, AuditAction};
+use crate::id;
+use crate::ledger::Account;
+use crate::state_machine;
+use crate::teams;
+use crate::{
+    get_memory, Gig, IdCell, Memory, StorablePrincipal, CERTIFICATES_MEMORY_ID,
+    CERTIFICATE_GIG_INDEX_MEMORY_ID, CERTIFICATE_ID_COUNTER_MEMORY_ID,
+    CERTIFICATE_OWNER_INDEX_MEMORY_ID, MAX_PAGE_SIZE,
+};
+
+/// Name of the certificate collection.
+const COLLECTION_NAME: &str = "GigSphere Certificates of Completion";
+/// Symbol of the certificate collection.
+const COLLECTION_SYMBOL: &str = "GIGCERT";
+
+/// A non-transferable ICRC-7 token certifying that a worker completed a gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Certificate {
+    pub token_id: u64,
+    pub gig_id: u64,
+    pub owner: Principal,       // The worker, or one member of the team that did the work.
+    pub title: String,          // Gig title at completion.
+    pub employer: Principal,
+    pub completed_at: u64,
+}
+
+/// ICRC-3 style metadata value, limited to the kinds certificates use.
+#[derive(CandidType, Clone, Serialize, Deserialize)]
+pub enum Value {
+    Nat(Nat),
+    Text(String),
+}
+
+impl Storable for Certificate {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Certificate {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    static CERTIFICATE_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(get_memory(CERTIFICATE_ID_COUNTER_MEMORY_ID), 0)
+            .expect("Cannot create a certificate counter")
+    );
+
+    /// Certificates keyed by token ID.
+    static CERTIFICATES: RefCell<StableBTreeMap<u64, Certificate, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(CERTIFICATES_MEMORY_ID)));
+
+    /// Token IDs keyed by `(owner, token_id)`.
+    static OWNER_INDEX: RefCell<StableBTreeMap<(StorablePrincipal, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(CERTIFICATE_OWNER_INDEX_MEMORY_ID)));
+
+    /// Token IDs keyed by `(gig_id, owner)`, so a gig certifies each worker once.
+    static GIG_INDEX: RefCell<StableBTreeMap<(u64, StorablePrincipal), u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(CERTIFICATE_GIG_INDEX_MEMORY_ID)));
+}
+
+/// Mint certificates to whoever completed a gig that was just approved. Restoring an
+/// archived gig and split dispute rulings mint nothing.
+pub fn issue(previous: Option<&Gig>, gig: &Gig) {
+    if !state_machine::is_completion(previous, gig) {
+        return;
+    }
+    for worker in teams::workers(gig) {
+        mint(gig, worker);
+    }
+}
+
+/// Mint a certificate of `gig` to `owner` unless they already hold one. An exhausted
+/// counter skips the mint and notes it in the audit log rather than failing the approval.
+fn mint(gig: &Gig, owner: Principal) {
+    let key = (gig.id, StorablePrincipal(owner));
+    if GIG_INDEX.with(|index| index.borrow().contains_key(&key)) {
+        return;
+    }
+    let Ok(token_id) = id::next(&CERTIFICATE_ID_COUNTER, "Certificate") else {
+        audit::record_note(gig, AuditAction::CertificateSkipped);
+        return;
+    };
+    let certificate = Certificate {
+        token_id,
+        gig_id: gig.id,
+        owner,
+        title: gig.title.clone(),
+        employer: gig.employer,
+        completed_at: time(),
+    };
+    CERTIFICATES.with(|certificates| certificates.borrow_mut().insert(token_id, certificate));
+    OWNER_INDEX.with(|index| index.borrow_mut().insert((StorablePrincipal(owner), token_id), ()));
+    GIG_INDEX.with(|index| index.borrow_mut().insert(key, token_id));
+}
+
+/// Token IDs held by `owner`, in ID order.
+fn tokens_held_by(owner: Principal) -> Vec<u64> {
+    let key = StorablePrincipal(owner);
+    OWNER_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    })
+}
+
+/// Certificates are minted to plain principals, so subaccounts hold none.
+fn owner_of_account(account: &Account) -> Option<Principal> {
+    account.subaccount.is_none().then_some(account.owner)
+}
+
+/// A token ID as `u64`, or `None` if it is out of range.
+fn token_id(id: &Nat) -> Option<u64> {
+    u64::try_from(id.0.clone()).ok()
+}
+
+/// Retrieve the certificates held by a worker, in minting order.
+#[ic_cdk::query]
+pub fn get_certificates(owner: Principal) -> Vec<Certificate> {
+    let ids = tokens_held_by(owner);
+    CERTIFICATES.with(|certificates| {
+        let certificates = certificates.borrow();
+        ids.iter().filter_map(|id| certificates.get(id)).collect()
+    })
+}
+
+/// ICRC-7: name of the collection.
+#[ic_cdk::query]
+pub fn icrc7_name() -> String {
+    COLLECTION_NAME.to_string()
+}
+
+/// ICRC-7: symbol of the collection.
+#[ic_cdk::query]
+pub fn icrc7_symbol() -> String {
+    COLLECTION_SYMBOL.to_string()
+}
+
+/// ICRC-7: number of certificates minted.
+#[ic_cdk::query]
+pub fn icrc7_total_supply() -> Nat {
+    Nat::from(CERTIFICATES.with(|certificates| certificates.borrow().len()))
+}
+
+/// ICRC-7: collection-level metadata.
+#[ic_cdk::query]
+pub fn icrc7_collection_metadata() -> Vec<(String, Value)> {
+    vec![
+        ("icrc7:name".to_string(), Value::Text(icrc7_name())),
+        ("icrc7:symbol".to_string(), Value::Text(icrc7_symbol())),
+        ("icrc7:total_supply".to_string(), Value::Nat(icrc7_total_supply())),
+    ]
+}
+
+/// ICRC-7: metadata of each requested token, `None` for unknown tokens.
+#[ic_cdk::query]
+pub fn icrc7_token_metadata(token_ids: Vec<Nat>) -> Vec<Option<Vec<(String, Value)>>> {
+    CERTIFICATES.with(|certificates| {
+        let certificates = certificates.borrow();
+        token_ids
+            .iter()
+            .map(|id| {
+                let certificate = token_id(id).and_then(|id| certificates.get(&id))?;
+                Some(vec![
+                    ("gig_id".to_string(), Value::Nat(Nat::from(certificate.gig_id))),
+                    ("title".to_string(), Value::Text(certificate.title)),
+                    ("employer".to_string(), Value::Text(certificate.employer.to_text())),
+                    ("completed_at".to_string(), Value::Nat(Nat::from(certificate.completed_at))),
+                ])
+            })
+            .collect()
+    })
+}
+
+/// ICRC-7: owner of each requested token, `None` for unknown tokens.
+#[ic_cdk::query]
+pub fn icrc7_owner_of(token_ids: Vec<Nat>) -> Vec<Option<Account>> {
+    CERTIFICATES.with(|certificates| {
+        let certificates = certificates.borrow();
+        token_ids
+            .iter()
+            .map(|id| {
+                let certificate = token_id(id).and_then(|id| certificates.get(&id))?;
+                Some(Account::from(certificate.owner))
+            })
+            .collect()
+    })
+}
+
+/// ICRC-7: number of certificates held by each account.
+#[ic_cdk::query]
+pub fn icrc7_balance_of(accounts: Vec<Account>) -> Vec<Nat> {
+    accounts
+        .iter()
+        .map(|account| {
+            let held = owner_of_account(account).map_or(0, |owner| tokens_held_by(owner).len());
+            Nat::from(held)
+        })
+        .collect()
+}
+
+/// ICRC-7: token IDs held by an account after `prev`, at most `take` (capped at
+/// `MAX_PAGE_SIZE`).
+#[ic_cdk::query]
+pub fn icrc7_tokens_of(account: Account, prev: Option<Nat>, take: Option<Nat>) -> Vec<Nat> {
+    let Some(owner) = owner_of_account(&account) else {
+        return Vec::new();
+    };
+    let after = prev.as_ref().and_then(token_id);
+    let take = take.as_ref().and_then(token_id).unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    tokens_held_by(owner)
+        .into_iter()
+        .filter(|id| after.is_none_or(|after| *id > after))
+        .take(take as usize)
+        .map(Nat::from)
+        .collect()
+}
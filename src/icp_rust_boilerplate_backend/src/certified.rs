@@ -0,0 +1,116 @@
+use candid::Encode;
+use ic_certified_map::{labeled, labeled_hash, AsHashTree, Hash, HashTree, RbTree};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::visibility;
+use crate::{Gig, GIG_STORAGE};
+
+/// Label the gig tree is certified under.
+const GIGS_LABEL: &[u8] = b"gigs";
+
+/// A gig together with the proof that it is part of the certified state.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct CertifiedGig {
+    pub gig: Option<Gig>,
+    pub certificate: Option<Vec<u8>>,   // System certificate over the canister's certified data.
+    pub witness: Vec<u8>,               // CBOR hash tree proving the gig's hash, or its absence.
+}
+
+/// All gigs together with the proof that they are the certified state.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct CertifiedGigs {
+    pub gigs: Vec<Gig>,
+    pub certificate: Option<Vec<u8>>,
+    pub witness: Vec<u8>,               // CBOR hash tree of every gig hash, unlisted gigs included.
+}
+
+thread_local! {
+    /// SHA-256 of every candid-encoded gig keyed by big-endian gig ID. Lives on the heap and
+    /// is rebuilt from stable memory after upgrades.
+    static TREE: RefCell<RbTree<Vec<u8>, Hash>> = const { RefCell::new(RbTree::new()) };
+}
+
+/// Hash committed to for a gig.
+fn gig_hash(gig: &Gig) -> Hash {
+    Sha256::digest(Encode!(gig).unwrap()).into()
+}
+
+/// Publish the current root hash as the canister's certified data.
+fn certify(tree: &RbTree<Vec<u8>, Hash>) {
+    ic_cdk::api::set_certified_data(&labeled_hash(GIGS_LABEL, &tree.root_hash()));
+}
+
+/// Serialize a witness the way agents expect: self-described CBOR.
+fn encode_witness(tree: HashTree) -> Vec<u8> {
+    let mut serializer = serde_cbor::ser::Serializer::new(Vec::new());
+    serializer.self_describe().unwrap();
+    labeled(GIGS_LABEL, tree).serialize(&mut serializer).unwrap();
+    serializer.into_inner()
+}
+
+/// Move a gig's leaf from its `previous` to its `current` version and re-certify.
+/// Pass `None` for `previous` on insert and for `current` on removal.
+pub fn update(previous: Option<&Gig>, current: Option<&Gig>) {
+    TREE.with(|tree| {
+        let mut tree = tree.borrow_mut();
+        match (previous, current) {
+            (_, Some(gig)) => tree.insert(gig.id.to_be_bytes().to_vec(), gig_hash(gig)),
+            (Some(gig), None) => tree.delete(&gig.id.to_be_bytes()),
+            (None, None) => return,
+        }
+        certify(&tree);
+    });
+}
+
+/// Rebuild the tree from stable memory, which is needed after every upgrade.
+pub fn rebuild() {
+    TREE.with(|tree| {
+        let mut tree = tree.borrow_mut();
+        *tree = GIG_STORAGE.with(|storage| {
+            storage
+                .borrow()
+                .iter()
+                .map(|(id, gig)| (id.to_be_bytes().to_vec(), gig_hash(&gig)))
+                .collect()
+        });
+        certify(&tree);
+    });
+}
+
+/// Retrieve a gig with a certificate clients can verify without trusting the replica.
+/// Gigs the caller may not view are returned as `None`, still with a witness.
+#[ic_cdk::query]
+pub fn get_gig_certified(id: u64) -> CertifiedGig {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&id))
+        .filter(visibility::can_view);
+    let witness = TREE.with(|tree| encode_witness(tree.borrow().witness(&id.to_be_bytes())));
+    CertifiedGig {
+        gig,
+        certificate: ic_cdk::api::data_certificate(),
+        witness,
+    }
+}
+
+/// Retrieve all gigs on the public board, with a certificate over every stored gig.
+#[ic_cdk::query]
+pub fn get_all_gigs_certified() -> CertifiedGigs {
+    let gigs = GIG_STORAGE.with(|storage| {
+        storage
+            .borrow()
+            .iter()
+            .filter(|(id, _)| visibility::is_listed(*id))
+            .map(|(_, gig)| gig)
+            .collect()
+    });
+    let witness = TREE.with(|tree| {
+        encode_witness(tree.borrow().value_range(&0u64.to_be_bytes(), &u64::MAX.to_be_bytes()))
+    });
+    CertifiedGigs {
+        gigs,
+        certificate: ic_cdk::api::data_certificate(),
+        witness,
+    }
+}
@@ -0,0 +1,310 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::attachments;
+use crate::delegates::{self, Permission};
+use crate::error::GigError;
+use crate::ledger;
+use crate::moderation::{self, not_banned};
+use crate::roles;
+use crate::skills;
+use crate::timesheets::GigType;
+use crate::validation;
+use crate::{
+    get_memory, insert_gig, Gig, GigPayload, GigStatus, Memory, Payment, CHANGES_MEMORY_ID,
+    GIG_STORAGE, MAX_REQUIRED_SKILLS,
+};
+
+/// Longest note accepted on a change proposal, in bytes.
+const MAX_NOTE_LEN: usize = 500;
+
+/// The scope of a gig the assigned worker agreed to, which changes only with their consent.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct ScopeChange {
+    pub title: String,
+    pub description: String,
+    pub deadline: u64,
+    pub budget: u64,
+    pub attachments: Option<Vec<u64>>,
+    pub required_skills: Option<Vec<String>>,
+}
+
+impl ScopeChange {
+    /// The current scope of a gig.
+    fn of(gig: &Gig) -> Self {
+        ScopeChange {
+            title: gig.title.clone(),
+            description: gig.description.clone(),
+            deadline: gig.deadline,
+            budget: gig.budget.as_ref().map_or(0, |budget| budget.amount),
+            attachments: gig.attachments.clone(),
+            required_skills: gig.required_skills.clone(),
+        }
+    }
+}
+
+/// An employer's proposal to change the scope of an assigned gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct ChangeProposal {
+    pub gig_id: u64,
+    pub number: u32,                // 1 for the first proposal on the gig.
+    pub worker: Principal,          // The worker whose consent is needed.
+    pub scope: ScopeChange,         // The scope the gig would have once accepted.
+    pub note: String,
+    pub base_version: u64,          // Version of the gig the proposal was made against.
+    pub status: ChangeStatus,
+    pub proposed_at: u64,
+    pub responded_at: Option<u64>,
+}
+
+/// Enum representing the outcome of a change proposal.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum ChangeStatus {
+    Pending,    // Waiting for the worker's answer.
+    Accepted,   // The change was applied to the gig.
+    Rejected,   // The worker declined the change.
+    Withdrawn,  // The employer took the proposal back.
+}
+
+impl Storable for ChangeProposal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for ChangeProposal {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Change proposals keyed by `(gig_id, number)` so a gig's history is contiguous.
+    static CHANGES: RefCell<StableBTreeMap<(u64, u32), ChangeProposal, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(CHANGES_MEMORY_ID)));
+}
+
+/// Whether a worker has agreed to the gig's scope, so it can no longer be edited directly.
+fn is_scope_locked(gig: &Gig) -> bool {
+    matches!(gig.status, GigStatus::Assigned | GigStatus::Submitted | GigStatus::Disputed)
+}
+
+/// Ensure an `update_gig` payload leaves the agreed scope of a gig alone. Once a worker is
+/// assigned, the scope only changes through an accepted proposal or extension request.
+pub fn ensure_scope_kept(
+    gig: &Gig,
+    payload: &GigPayload,
+    ledger: Principal,
+) -> Result<(), GigError> {
+    if !is_scope_locked(gig) {
+        return Ok(());
+    }
+    let required_skills = payload
+        .required_skills
+        .clone()
+        .map(|skills| skills::normalize_skills(skills, MAX_REQUIRED_SKILLS))
+        .transpose()?;
+    let scope = ScopeChange {
+        title: payload.title.clone(),
+        description: payload.description.clone(),
+        deadline: payload.deadline,
+        budget: payload.budget,
+        attachments: payload.attachments.clone(),
+        required_skills,
+    };
+    let same_token = gig.budget.as_ref().map(|budget| budget.ledger) == Some(ledger);
+    if scope != ScopeChange::of(gig) || !same_token {
+        return Err(GigError::validation(
+            "payload",
+            "The assigned worker must agree to scope changes; use propose_change",
+        ));
+    }
+    Ok(())
+}
+
+/// Latest change proposal on a gig.
+fn latest(gig_id: u64) -> Option<ChangeProposal> {
+    CHANGES.with(|changes| {
+        changes
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u32::MAX))
+            .map(|(_, proposal)| proposal)
+            .last()
+    })
+}
+
+/// Check a proposed scope against the gig limits, normalising its skills.
+fn validate_scope(gig: &Gig, scope: &mut ScopeChange) -> Result<(), GigError> {
+    validation::validate_gig_payload(&GigPayload {
+        title: scope.title.clone(),
+        description: scope.description.clone(),
+        budget: scope.budget,
+        ..Default::default()
+    })?;
+    if scope.deadline != gig.deadline {
+        validation::validate_deadline(scope.deadline)?;
+    }
+    // Hourly budgets are derived from the rate and hour cap the worker agreed to.
+    let budget = gig.budget.as_ref().map_or(0, |budget| budget.amount);
+    if gig.gig_type() != GigType::Fixed && scope.budget != budget {
+        return Err(GigError::validation("budget", "Hourly budgets follow the rate and hour cap"));
+    }
+    if let Some(ids) = &scope.attachments {
+        attachments::ensure_attachable(ids)?;
+    }
+    scope.required_skills = scope
+        .required_skills
+        .take()
+        .map(|skills| skills::normalize_skills(skills, MAX_REQUIRED_SKILLS))
+        .transpose()?;
+    Ok(())
+}
+
+/// Propose a new scope for an assigned gig. The change applies once the assigned worker
+/// accepts it. `expected_version` must match the gig's current version. Employer or their
+/// delegates only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn propose_change(
+    gig_id: u64,
+    mut scope: ScopeChange,
+    note: String,
+    expected_version: u64,
+) -> Result<ChangeProposal, GigError> {
+    moderation::ensure_not_banned(caller())?;
+    if note.len() > MAX_NOTE_LEN {
+        return Err(GigError::validation("note", "Must be at most 500 bytes"));
+    }
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer or a delegate can propose changes.
+    if !delegates::may_act(&gig, caller(), Permission::Post) {
+        return Err(GigError::unauthorized("Only the employer can propose changes"));
+    }
+    if gig.version() != expected_version {
+        return Err(GigError::Conflict { expected: expected_version, actual: gig.version() });
+    }
+    // Submitted work is judged against the scope it was delivered under.
+    if gig.status != GigStatus::Assigned {
+        return Err(GigError::invalid_state("Assigned", gig.status));
+    }
+    let worker = gig.assigned_to.ok_or_else(|| GigError::not_found("Assigned worker"))?;
+    validate_scope(&gig, &mut scope)?;
+    if scope == ScopeChange::of(&gig) {
+        return Err(GigError::validation("scope", "Must differ from the current scope"));
+    }
+    let previous = latest(gig_id);
+    if previous.as_ref().is_some_and(|proposal| proposal.status == ChangeStatus::Pending) {
+        return Err(GigError::AlreadyExists { resource: "Change proposal".to_string() });
+    }
+
+    let proposal = ChangeProposal {
+        gig_id,
+        number: previous.map_or(1, |proposal| proposal.number + 1),
+        worker,
+        scope,
+        note,
+        base_version: gig.version(),
+        status: ChangeStatus::Pending,
+        proposed_at: time(),
+        responded_at: None,
+    };
+    CHANGES.with(|changes| {
+        changes.borrow_mut().insert((gig_id, proposal.number), proposal.clone())
+    });
+    Ok(proposal)
+}
+
+/// Accept or reject the pending change proposal on a gig. Accepting applies the new scope,
+/// provided the gig has not changed since the proposal was made. Assigned worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn respond_change(gig_id: u64, accept: bool) -> Result<Gig, GigError> {
+    let mut proposal = latest(gig_id)
+        .filter(|proposal| proposal.status == ChangeStatus::Pending)
+        .ok_or_else(|| GigError::not_found("Pending change proposal"))?;
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        // Ensure only the worker the proposal was made to can answer.
+        if proposal.worker != caller() || gig.assigned_to != Some(caller()) {
+            return Err(GigError::unauthorized("Only the assigned worker can answer this proposal"));
+        }
+        if gig.status != GigStatus::Assigned {
+            return Err(GigError::invalid_state("Assigned", gig.status));
+        }
+        if accept {
+            if gig.version() != proposal.base_version {
+                return Err(GigError::Conflict {
+                    expected: proposal.base_version,
+                    actual: gig.version(),
+                });
+            }
+            // Time may have run past the proposed deadline while the proposal was pending.
+            if proposal.scope.deadline != gig.deadline {
+                validation::validate_deadline(proposal.scope.deadline)?;
+            }
+            let scope = proposal.scope.clone();
+            let token = gig.budget.as_ref().map_or_else(ledger::icp_ledger, |budget| budget.ledger);
+            gig.title = scope.title;
+            gig.description = scope.description;
+            gig.deadline = scope.deadline;
+            gig.budget = Some(Payment { ledger: token, amount: scope.budget });
+            gig.attachments = scope.attachments;
+            gig.required_skills = scope.required_skills;
+            gig.updated_at = Some(time());
+            insert_gig(&mut storage, &mut gig);
+        }
+        proposal.status = if accept { ChangeStatus::Accepted } else { ChangeStatus::Rejected };
+        proposal.responded_at = Some(time());
+        CHANGES.with(|changes| {
+            changes.borrow_mut().insert((gig_id, proposal.number), proposal)
+        });
+        Ok(gig)
+    })
+}
+
+/// Withdraw the pending change proposal on a gig. Employer or their delegates only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn withdraw_change(gig_id: u64) -> Result<ChangeProposal, GigError> {
+    let mut proposal = latest(gig_id)
+        .filter(|proposal| proposal.status == ChangeStatus::Pending)
+        .ok_or_else(|| GigError::not_found("Pending change proposal"))?;
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer or a delegate can withdraw a proposal.
+    if !delegates::may_act(&gig, caller(), Permission::Post) {
+        return Err(GigError::unauthorized("Only the employer can withdraw this proposal"));
+    }
+    proposal.status = ChangeStatus::Withdrawn;
+    proposal.responded_at = Some(time());
+    CHANGES.with(|changes| {
+        changes.borrow_mut().insert((gig_id, proposal.number), proposal.clone())
+    });
+    Ok(proposal)
+}
+
+/// Retrieve every change proposal made on a gig, oldest first. Proposals are private to the
+/// employer, the worker and moderators.
+#[ic_cdk::query]
+pub fn get_change_proposals(gig_id: u64) -> Result<Vec<ChangeProposal>, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    if caller() != gig.employer && gig.assigned_to != Some(caller()) {
+        roles::ensure_moderator()?;
+    }
+    Ok(CHANGES.with(|changes| {
+        changes
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u32::MAX))
+            .map(|(_, proposal)| proposal)
+            .collect()
+    }))
+}
@@ -0,0 +1,195 @@
+use crate::error::GigError;
+use crate::{Gig, GigStatus};
+
+/// Nanoseconds in a second.
+const SECOND_NANOS: u64 = 1_000_000_000;
+/// Longest bonus window a gig can set: ninety days.
+const MAX_BONUS_WINDOW_SECS: u64 = 90 * 24 * 60 * 60;
+/// Longest grace period for late delivery a gig can set: thirty days.
+const MAX_GRACE_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// An amount added to or taken off a payout.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum Adjustment {
+    Percent(u8), // Share of the base payout, 1 to 100.
+    Fixed(u64),  // Flat amount in the token's base units.
+}
+
+impl Adjustment {
+    /// Size of the adjustment on a payout of `base`.
+    fn amount(&self, base: u64) -> u64 {
+        match self {
+            Adjustment::Percent(percent) => (base as u128 * *percent as u128 / 100) as u64,
+            Adjustment::Fixed(amount) => *amount,
+        }
+    }
+}
+
+/// Bonus and penalty clauses applied to the payout based on when the work was submitted.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub struct DeliveryTerms {
+    pub early_bonus: Option<Adjustment>,
+    pub bonus_window_secs: u64,         // How long before the deadline work earns the bonus.
+    pub late_penalty: Option<Adjustment>,
+    pub grace_secs: u64,                // How long after the deadline late work is accepted.
+}
+
+/// Check delivery terms against the clause limits.
+pub fn validate(terms: &DeliveryTerms) -> Result<(), GigError> {
+    let adjustments = [("early_bonus", &terms.early_bonus), ("late_penalty", &terms.late_penalty)];
+    for (field, adjustment) in adjustments {
+        match adjustment {
+            Some(Adjustment::Percent(percent)) if !(1..=100).contains(percent) => {
+                return Err(GigError::validation(field, "Percentages must be 1 to 100"));
+            }
+            Some(Adjustment::Fixed(0)) => {
+                return Err(GigError::validation(field, "Fixed amounts must be positive"));
+            }
+            _ => {}
+        }
+    }
+    if terms.early_bonus.is_some()
+        && (terms.bonus_window_secs == 0 || terms.bonus_window_secs > MAX_BONUS_WINDOW_SECS)
+    {
+        return Err(GigError::validation("bonus_window_secs", "Must be 1 second to 90 days"));
+    }
+    if terms.late_penalty.is_some() && (terms.grace_secs == 0 || terms.grace_secs > MAX_GRACE_SECS)
+    {
+        return Err(GigError::validation("grace_secs", "Must be 1 second to 30 days"));
+    }
+    Ok(())
+}
+
+/// Ensure a gig's delivery terms can be replaced: the worker agreed to them on
+/// assignment, so they are fixed once the gig leaves `Open`.
+pub fn ensure_change(gig: &Gig, terms: &Option<DeliveryTerms>) -> Result<(), GigError> {
+    if gig.delivery_terms != *terms && gig.status != GigStatus::Open {
+        return Err(GigError::validation("delivery_terms", "Can only change while the gig is open"));
+    }
+    Ok(())
+}
+
+/// When an unsubmitted gig expires: at its deadline, or after the grace period if late
+/// work is accepted with a penalty.
+pub fn expires_at(gig: &Gig) -> u64 {
+    let grace = match &gig.delivery_terms {
+        Some(terms) if terms.late_penalty.is_some() && gig.status == GigStatus::Assigned => {
+            terms.grace_secs.saturating_mul(SECOND_NANOS)
+        }
+        _ => 0,
+    };
+    gig.deadline.saturating_add(grace)
+}
+
+/// Apply the gig's bonus or penalty to a payout of `base`, judged on the final submission.
+pub fn adjust(gig: &Gig, base: u64) -> u64 {
+    let (Some(terms), Some(submitted_at)) = (&gig.delivery_terms, gig.submitted_at) else {
+        return base;
+    };
+    if submitted_at > gig.deadline {
+        if let Some(penalty) = &terms.late_penalty {
+            return base.saturating_sub(penalty.amount(base));
+        }
+    } else if gig.deadline - submitted_at >= terms.bonus_window_secs.saturating_mul(SECOND_NANOS) {
+        if let Some(bonus) = &terms.early_bonus {
+            return base.saturating_add(bonus.amount(base));
+        }
+    }
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deadline of every test gig, in nanoseconds.
+    const DEADLINE: u64 = 100 * 24 * 60 * 60 * SECOND_NANOS;
+    /// One day in nanoseconds.
+    const DAY_NANOS: u64 = 24 * 60 * 60 * SECOND_NANOS;
+
+    /// An assigned gig with `terms`, submitted at `submitted_at`.
+    fn gig(terms: Option<DeliveryTerms>, submitted_at: Option<u64>) -> Gig {
+        Gig {
+            deadline: DEADLINE,
+            status: GigStatus::Assigned,
+            submitted_at,
+            delivery_terms: terms,
+            ..Gig::for_test(0)
+        }
+    }
+
+    fn terms(early_bonus: Option<Adjustment>, late_penalty: Option<Adjustment>) -> DeliveryTerms {
+        DeliveryTerms {
+            early_bonus,
+            bonus_window_secs: 24 * 60 * 60,
+            late_penalty,
+            grace_secs: 24 * 60 * 60,
+        }
+    }
+
+    #[test]
+    fn gigs_without_terms_or_submission_pay_the_base() {
+        assert_eq!(adjust(&gig(None, Some(0)), 1_000), 1_000);
+        let both = terms(Some(Adjustment::Percent(10)), Some(Adjustment::Percent(10)));
+        assert_eq!(adjust(&gig(Some(both), None), 1_000), 1_000);
+    }
+
+    #[test]
+    fn early_work_earns_the_bonus() {
+        let terms = terms(Some(Adjustment::Percent(10)), None);
+        let early = gig(Some(terms.clone()), Some(DEADLINE - DAY_NANOS));
+        assert_eq!(adjust(&early, 1_000), 1_100);
+        // Inside the bonus window is on time but not early.
+        let on_time = gig(Some(terms), Some(DEADLINE - DAY_NANOS + 1));
+        assert_eq!(adjust(&on_time, 1_000), 1_000);
+    }
+
+    #[test]
+    fn late_work_takes_the_penalty() {
+        let terms = terms(None, Some(Adjustment::Fixed(300)));
+        assert_eq!(adjust(&gig(Some(terms.clone()), Some(DEADLINE + 1)), 1_000), 700);
+        // Work submitted exactly at the deadline is not late.
+        assert_eq!(adjust(&gig(Some(terms), Some(DEADLINE)), 1_000), 1_000);
+    }
+
+    #[test]
+    fn zero_base_stays_zero_under_percentages() {
+        let both = terms(Some(Adjustment::Percent(50)), Some(Adjustment::Percent(50)));
+        assert_eq!(adjust(&gig(Some(both.clone()), Some(0)), 0), 0);
+        assert_eq!(adjust(&gig(Some(both), Some(DEADLINE + 1)), 0), 0);
+    }
+
+    #[test]
+    fn percentages_round_down() {
+        let bonus = terms(Some(Adjustment::Percent(1)), None);
+        assert_eq!(adjust(&gig(Some(bonus), Some(0)), 199), 200);
+        let penalty = terms(None, Some(Adjustment::Percent(33)));
+        assert_eq!(adjust(&gig(Some(penalty), Some(DEADLINE + 1)), 10), 7);
+    }
+
+    #[test]
+    fn penalties_never_go_below_zero() {
+        let fixed = terms(None, Some(Adjustment::Fixed(5_000)));
+        assert_eq!(adjust(&gig(Some(fixed), Some(DEADLINE + 1)), 1_000), 0);
+        let whole = terms(None, Some(Adjustment::Percent(100)));
+        assert_eq!(adjust(&gig(Some(whole), Some(DEADLINE + 1)), 1_000), 0);
+    }
+
+    #[test]
+    fn bonuses_saturate_instead_of_overflowing() {
+        let fixed = terms(Some(Adjustment::Fixed(u64::MAX)), None);
+        assert_eq!(adjust(&gig(Some(fixed), Some(0)), 1), u64::MAX);
+        let percent = terms(Some(Adjustment::Percent(100)), None);
+        assert_eq!(adjust(&gig(Some(percent), Some(0)), u64::MAX), u64::MAX);
+        // The percentage itself is computed without overflowing on the largest base.
+        let half = terms(None, Some(Adjustment::Percent(50)));
+        assert_eq!(adjust(&gig(Some(half), Some(DEADLINE + 1)), u64::MAX), u64::MAX - u64::MAX / 2);
+    }
+
+    #[test]
+    fn bonus_windows_longer_than_time_itself_are_never_met() {
+        let mut terms = terms(Some(Adjustment::Percent(10)), None);
+        terms.bonus_window_secs = u64::MAX;
+        assert_eq!(adjust(&gig(Some(terms), Some(0)), 1_000), 1_000);
+    }
+}
@@ -0,0 +1,344 @@
+use candid::{Decode, Encode, Principal};
+use ic_stable_structures::{Cell, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::moderation::not_banned;
+use crate::{ensure_controller, get_memory, Memory, CONFIG_MEMORY_ID, MAX_AUTO_APPROVE_DAYS};
+
+/// Longest title any configuration may allow, so gigs stay within their stable size bound.
+const TITLE_LEN_CEILING: u32 = 200;
+/// Longest description any configuration may allow, for the same reason.
+const DESCRIPTION_LEN_CEILING: u32 = 1000;
+/// Highest platform fee a controller can set: 20%.
+const MAX_FEE_BPS: u16 = 2_000;
+/// Time a deleted gig can be restored by default: seven days.
+const DEFAULT_DELETION_GRACE_SECS: u64 = 7 * 24 * 60 * 60;
+/// Bounds on the deletion grace period: one hour to ninety days.
+const DELETION_GRACE_RANGE_SECS: std::ops::RangeInclusive<u64> = 60 * 60..=90 * 24 * 60 * 60;
+/// Views a principal can record by default: 120 an hour.
+const DEFAULT_VIEW_LIMIT: RateLimit = RateLimit { burst: 120, refill_secs: 30 };
+/// Bonus a referrer earns by default when a referee completes their first gig: 0.1 ICP.
+const DEFAULT_REFERRAL_BONUS: u64 = 10_000_000;
+/// Largest referral bonus a controller can set: 10 ICP.
+const MAX_REFERRAL_BONUS: u64 = 1_000_000_000;
+/// ICP a referee must be paid for a gig by default before it earns the referral bonus: 1 ICP.
+const DEFAULT_REFERRAL_MIN_PAYOUT: u64 = 100_000_000;
+/// Signatures high-value gigs need by default.
+const DEFAULT_MULTISIG_APPROVALS: u32 = 2;
+/// Bounds on the signatures a controller can require of high-value gigs.
+const MULTISIG_APPROVALS_RANGE: std::ops::RangeInclusive<u32> = 2..=10;
+
+/// Burst size of a rate-limited action and the seconds it takes to earn back one call.
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+pub struct RateLimit {
+    pub burst: u64,
+    pub refill_secs: u64,
+}
+
+/// Canister settings controllers can change without redeploying code.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub max_title_len: u32,             // In bytes; at most 200.
+    pub max_description_len: u32,       // In bytes; at most 1000.
+    pub min_budget: u64,                // In the token's base units; at least 1.
+    pub max_budget: u64,
+    pub unverified_max_budget: Option<u64>, // Larger budgets need a verified employer.
+    pub fee_bps: u16,                   // Platform fee on worker payouts; at most 2000.
+    pub max_open_gigs: Option<u32>,     // Open gigs per employer; `None` is no limit.
+    pub verified_max_open_gigs: Option<u32>, // The same for verified employers.
+    pub auto_approve_days: u32,         // Default auto-approval window; 1 to 90 days.
+    pub post_gig_limit: RateLimit,
+    pub apply_limit: RateLimit,
+    pub message_limit: RateLimit,
+    pub low_cycles_threshold: Option<u64>,  // Admins are warned below this; `None` is 2T cycles.
+    pub top_up_canister: Option<Principal>, // Called as `top_up(canister_id)` when cycles are low.
+    pub deletion_grace_secs: Option<u64>,   // Undo window of deleted gigs; `None` is 7 days.
+    pub view_limit: Option<RateLimit>,      // Recorded views; `None` is 120 an hour.
+    pub referral_bonus: Option<u64>,        // ICP e8s per referee's first gig; `None` is 0.1 ICP.
+    pub referral_min_payout: Option<u64>,   // ICP e8s that gig must pay; `None` is 1 ICP.
+    pub multisig_threshold: Option<u64>,    // Budgets above need several approvers; `None` is off.
+    pub multisig_approvals: Option<u32>,    // Signatures those gigs need; `None` is 2.
+}
+
+/// Changes to apply to the configuration; unset fields keep their current value.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+pub struct ConfigPatch {
+    pub max_title_len: Option<u32>,
+    pub max_description_len: Option<u32>,
+    pub min_budget: Option<u64>,
+    pub max_budget: Option<u64>,
+    pub unverified_max_budget: Option<Option<u64>>,
+    pub fee_bps: Option<u16>,
+    pub max_open_gigs: Option<Option<u32>>,
+    pub verified_max_open_gigs: Option<Option<u32>>,
+    pub auto_approve_days: Option<u32>,
+    pub post_gig_limit: Option<RateLimit>,
+    pub apply_limit: Option<RateLimit>,
+    pub message_limit: Option<RateLimit>,
+    pub low_cycles_threshold: Option<u64>,
+    pub top_up_canister: Option<Option<Principal>>,
+    pub deletion_grace_secs: Option<u64>,
+    pub view_limit: Option<RateLimit>,
+    pub referral_bonus: Option<u64>,
+    pub referral_min_payout: Option<u64>,
+    pub multisig_threshold: Option<Option<u64>>,
+    pub multisig_approvals: Option<u32>,
+}
+
+/// Layout stored before the configuration grew beyond the validation limits.
+#[derive(candid::CandidType, Deserialize)]
+struct ConfigV0 {
+    max_title_len: u32,
+    max_description_len: u32,
+    min_budget: u64,
+    max_budget: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_title_len: 100,
+            max_description_len: DESCRIPTION_LEN_CEILING,
+            min_budget: 1,
+            max_budget: u64::MAX,
+            unverified_max_budget: None,
+            fee_bps: 0,
+            max_open_gigs: Some(20),
+            verified_max_open_gigs: Some(100),
+            auto_approve_days: 14,
+            post_gig_limit: RateLimit { burst: 10, refill_secs: 6 * 60 },  // 10 gigs an hour.
+            apply_limit: RateLimit { burst: 20, refill_secs: 3 * 60 },     // 20 bids an hour.
+            message_limit: RateLimit { burst: 30, refill_secs: 10 },       // 6 messages a minute.
+            low_cycles_threshold: None,
+            top_up_canister: None,
+            deletion_grace_secs: None,
+            view_limit: None,
+            referral_bonus: None,
+            referral_min_payout: None,
+            multisig_threshold: None,
+            multisig_approvals: None,
+        }
+    }
+}
+
+impl From<ConfigV0> for Config {
+    fn from(config: ConfigV0) -> Self {
+        Config {
+            max_title_len: config.max_title_len,
+            max_description_len: config.max_description_len,
+            min_budget: config.min_budget,
+            max_budget: config.max_budget,
+            ..Config::default()
+        }
+    }
+}
+
+impl Storable for Config {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self)
+            .unwrap_or_else(|_| Decode!(bytes.as_ref(), ConfigV0).unwrap().into())
+    }
+}
+
+thread_local! {
+    /// Current canister settings.
+    static CONFIG: RefCell<Cell<Config, Memory>> = RefCell::new(
+        Cell::init(get_memory(CONFIG_MEMORY_ID), Config::default())
+            .expect("Cannot create the config")
+    );
+}
+
+/// Current canister settings.
+pub fn get() -> Config {
+    CONFIG.with(|cell| cell.borrow().get().clone())
+}
+
+/// Seconds a deleted gig waits as `PendingDeletion` before it is purged.
+pub fn deletion_grace_secs() -> u64 {
+    get().deletion_grace_secs.unwrap_or(DEFAULT_DELETION_GRACE_SECS)
+}
+
+/// Rate limit on recorded views.
+pub fn view_limit() -> RateLimit {
+    get().view_limit.unwrap_or(DEFAULT_VIEW_LIMIT)
+}
+
+/// ICP e8s a referrer earns when a referee completes their first gig.
+pub fn referral_bonus() -> u64 {
+    get().referral_bonus.unwrap_or(DEFAULT_REFERRAL_BONUS)
+}
+
+/// ICP e8s of escrow a referee must be paid for a gig before it earns the referral bonus.
+pub fn referral_min_payout() -> u64 {
+    get().referral_min_payout.unwrap_or(DEFAULT_REFERRAL_MIN_PAYOUT)
+}
+
+/// Budget, in the token's base units, above which escrow releases need several approvers.
+pub fn multisig_threshold() -> Option<u64> {
+    get().multisig_threshold
+}
+
+/// Signatures each escrow release of a high-value gig needs.
+pub fn multisig_approvals() -> u32 {
+    get().multisig_approvals.unwrap_or(DEFAULT_MULTISIG_APPROVALS)
+}
+
+/// Replace the stored settings without checking them; used by migrations.
+pub fn set(config: Config) {
+    CONFIG
+        .with(|cell| cell.borrow_mut().set(config))
+        .expect("Cannot store the config");
+}
+
+/// Check a rate limit can be enforced.
+fn validate_rate_limit(field: &str, limit: &RateLimit) -> Result<(), GigError> {
+    if limit.burst == 0 || limit.refill_secs == 0 {
+        return Err(GigError::validation(field, "Burst and refill must both be at least 1"));
+    }
+    Ok(())
+}
+
+/// Check every setting is within range.
+fn validate(config: &Config) -> Result<(), GigError> {
+    if config.max_title_len == 0 || config.max_title_len > TITLE_LEN_CEILING {
+        return Err(GigError::validation("max_title_len", "Must be between 1 and 200"));
+    }
+    if config.max_description_len > DESCRIPTION_LEN_CEILING {
+        return Err(GigError::validation("max_description_len", "Must be at most 1000"));
+    }
+    if config.min_budget == 0 || config.min_budget > config.max_budget {
+        return Err(GigError::validation(
+            "min_budget",
+            "Must be at least 1 and at most max_budget",
+        ));
+    }
+    if config.fee_bps > MAX_FEE_BPS {
+        return Err(GigError::validation("fee_bps", "Fee cannot exceed 2000 basis points"));
+    }
+    if config.max_open_gigs == Some(0) {
+        return Err(GigError::validation("max_open_gigs", "Must be at least 1"));
+    }
+    if let (Some(max), Some(verified_max)) = (config.max_open_gigs, config.verified_max_open_gigs)
+    {
+        if verified_max < max {
+            return Err(GigError::validation(
+                "verified_max_open_gigs",
+                "Must be at least max_open_gigs",
+            ));
+        }
+    }
+    if config.max_open_gigs.is_none() && config.verified_max_open_gigs.is_some() {
+        return Err(GigError::validation(
+            "verified_max_open_gigs",
+            "Must be unset while max_open_gigs is unset",
+        ));
+    }
+    if !(1..=MAX_AUTO_APPROVE_DAYS).contains(&config.auto_approve_days) {
+        return Err(GigError::validation("auto_approve_days", "Must be between 1 and 90 days"));
+    }
+    if config.deletion_grace_secs.is_some_and(|secs| !DELETION_GRACE_RANGE_SECS.contains(&secs))
+    {
+        return Err(GigError::validation(
+            "deletion_grace_secs",
+            "Must be between one hour and 90 days",
+        ));
+    }
+    if config.referral_bonus.is_some_and(|bonus| bonus > MAX_REFERRAL_BONUS) {
+        return Err(GigError::validation("referral_bonus", "Must be at most 10 ICP"));
+    }
+    if config
+        .multisig_approvals
+        .is_some_and(|approvals| !MULTISIG_APPROVALS_RANGE.contains(&approvals))
+    {
+        return Err(GigError::validation("multisig_approvals", "Must be between 2 and 10"));
+    }
+    validate_rate_limit("post_gig_limit", &config.post_gig_limit)?;
+    validate_rate_limit("apply_limit", &config.apply_limit)?;
+    validate_rate_limit("message_limit", &config.message_limit)?;
+    if let Some(limit) = &config.view_limit {
+        validate_rate_limit("view_limit", limit)?;
+    }
+    Ok(())
+}
+
+/// Retrieve the current canister settings.
+#[ic_cdk::query]
+pub fn get_config() -> Config {
+    get()
+}
+
+/// Change some canister settings, keeping the rest, and return the result. The patch is
+/// applied only if every resulting setting is valid. Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn update_config(patch: ConfigPatch) -> Result<Config, GigError> {
+    ensure_controller()?;
+    let mut config = get();
+    if let Some(max_title_len) = patch.max_title_len {
+        config.max_title_len = max_title_len;
+    }
+    if let Some(max_description_len) = patch.max_description_len {
+        config.max_description_len = max_description_len;
+    }
+    if let Some(min_budget) = patch.min_budget {
+        config.min_budget = min_budget;
+    }
+    if let Some(max_budget) = patch.max_budget {
+        config.max_budget = max_budget;
+    }
+    if let Some(unverified_max_budget) = patch.unverified_max_budget {
+        config.unverified_max_budget = unverified_max_budget;
+    }
+    if let Some(fee_bps) = patch.fee_bps {
+        config.fee_bps = fee_bps;
+    }
+    if let Some(max_open_gigs) = patch.max_open_gigs {
+        config.max_open_gigs = max_open_gigs;
+    }
+    if let Some(verified_max_open_gigs) = patch.verified_max_open_gigs {
+        config.verified_max_open_gigs = verified_max_open_gigs;
+    }
+    if let Some(auto_approve_days) = patch.auto_approve_days {
+        config.auto_approve_days = auto_approve_days;
+    }
+    if let Some(limit) = patch.post_gig_limit {
+        config.post_gig_limit = limit;
+    }
+    if let Some(limit) = patch.apply_limit {
+        config.apply_limit = limit;
+    }
+    if let Some(limit) = patch.message_limit {
+        config.message_limit = limit;
+    }
+    if let Some(threshold) = patch.low_cycles_threshold {
+        config.low_cycles_threshold = Some(threshold);
+    }
+    if let Some(top_up_canister) = patch.top_up_canister {
+        config.top_up_canister = top_up_canister;
+    }
+    if let Some(secs) = patch.deletion_grace_secs {
+        config.deletion_grace_secs = Some(secs);
+    }
+    if let Some(limit) = patch.view_limit {
+        config.view_limit = Some(limit);
+    }
+    if let Some(bonus) = patch.referral_bonus {
+        config.referral_bonus = Some(bonus);
+    }
+    if let Some(min_payout) = patch.referral_min_payout {
+        config.referral_min_payout = Some(min_payout);
+    }
+    if let Some(threshold) = patch.multisig_threshold {
+        config.multisig_threshold = threshold;
+    }
+    if let Some(approvals) = patch.multisig_approvals {
+        config.multisig_approvals = Some(approvals);
+    }
+    validate(&config)?;
+    set(config.clone());
+    Ok(config)
+}
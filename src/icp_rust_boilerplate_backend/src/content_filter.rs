@@ -0,0 +1,176 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::moderation::not_banned;
+use crate::roles;
+use crate::search;
+use crate::tags::{self, Tag};
+use crate::{get_memory, Memory, BANNED_TERMS_MEMORY_ID, FLAGGED_GIGS_MEMORY_ID};
+
+/// Most banned terms a single gig records when flagged.
+const MAX_FLAGGED_TERMS: usize = 10;
+
+/// What happens to a gig whose title or description contains a banned term.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum FilterAction {
+    Reject, // The post or update is refused.
+    Flag,   // The gig is published and queued for moderator review.
+}
+
+/// A word on the content denylist.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct BannedTerm {
+    pub term: String,
+    pub action: FilterAction,
+    pub added_by: Principal,
+    pub added_at: u64,
+}
+
+/// A gig published with flagged terms, awaiting moderator review.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct FlaggedGig {
+    pub gig_id: u64,
+    pub terms: Vec<String>,     // The flagged terms found, at most 10.
+    pub flagged_at: u64,
+}
+
+impl Storable for BannedTerm {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for BannedTerm {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for FlaggedGig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for FlaggedGig {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Denylisted terms keyed by term.
+    static BANNED_TERMS: RefCell<StableBTreeMap<Tag, BannedTerm, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(BANNED_TERMS_MEMORY_ID)));
+
+    /// Flagged gigs keyed by gig ID.
+    static FLAGGED_GIGS: RefCell<StableBTreeMap<u64, FlaggedGig, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(FLAGGED_GIGS_MEMORY_ID)));
+}
+
+/// Check a gig's title and description against the denylist. Returns the flagged terms
+/// found, or an error if any term is rejected. Terms match whole words, ignoring case.
+pub fn screen(title: &str, description: &str) -> Result<Vec<String>, GigError> {
+    let mut flagged = Vec::new();
+    BANNED_TERMS.with(|terms| {
+        let terms = terms.borrow();
+        if terms.is_empty() {
+            return Ok(());
+        }
+        for (field, text) in [("title", title), ("description", description)] {
+            for word in search::terms(text) {
+                let Some(banned) = terms.get(&Tag(word)) else {
+                    continue;
+                };
+                match banned.action {
+                    FilterAction::Reject => {
+                        return Err(GigError::validation(field, "Contains a banned term"));
+                    }
+                    FilterAction::Flag => {
+                        if !flagged.contains(&banned.term) && flagged.len() < MAX_FLAGGED_TERMS {
+                            flagged.push(banned.term);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    })?;
+    Ok(flagged)
+}
+
+/// Queue a stored gig for review if `screen` flagged terms in it, or drop it from the
+/// queue once its text is clean.
+pub fn record(gig_id: u64, terms: Vec<String>) {
+    FLAGGED_GIGS.with(|flagged| {
+        let mut flagged = flagged.borrow_mut();
+        if terms.is_empty() {
+            flagged.remove(&gig_id);
+        } else {
+            flagged.insert(gig_id, FlaggedGig { gig_id, terms, flagged_at: time() });
+        }
+    });
+}
+
+/// Drop the review entry of a deleted gig.
+pub fn forget_gig(gig_id: u64) {
+    FLAGGED_GIGS.with(|flagged| flagged.borrow_mut().remove(&gig_id));
+}
+
+/// Add a word to the denylist, or change the action of one already on it. Moderator only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn add_banned_term(term: String, action: FilterAction) -> Result<BannedTerm, GigError> {
+    roles::ensure_moderator()?;
+    let term = tags::normalize(&term);
+    // Gig text is matched word by word, so only a single search term can ever match.
+    if search::terms(&term).into_iter().ne([term.clone()]) {
+        return Err(GigError::validation("term", "Must be one word of 2 to 32 letters or digits"));
+    }
+    let banned = BannedTerm { term: term.clone(), action, added_by: caller(), added_at: time() };
+    BANNED_TERMS.with(|terms| terms.borrow_mut().insert(Tag(term), banned.clone()));
+    Ok(banned)
+}
+
+/// Remove a word from the denylist. Moderator only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn remove_banned_term(term: String) -> Result<(), GigError> {
+    roles::ensure_moderator()?;
+    BANNED_TERMS
+        .with(|terms| terms.borrow_mut().remove(&Tag(tags::normalize(&term))))
+        .map(|_| ())
+        .ok_or_else(|| GigError::not_found("Banned term"))
+}
+
+/// Retrieve the denylist in alphabetical order. Moderator only.
+#[ic_cdk::query]
+pub fn list_banned_terms() -> Result<Vec<BannedTerm>, GigError> {
+    roles::ensure_moderator()?;
+    Ok(BANNED_TERMS.with(|terms| terms.borrow().iter().map(|(_, term)| term).collect()))
+}
+
+/// Retrieve the gigs awaiting review for flagged terms, in gig ID order. Moderator only.
+#[ic_cdk::query]
+pub fn list_flagged_gigs() -> Result<Vec<FlaggedGig>, GigError> {
+    roles::ensure_moderator()?;
+    Ok(FLAGGED_GIGS.with(|flagged| flagged.borrow().iter().map(|(_, gig)| gig).collect()))
+}
+
+/// Clear a gig from the review queue after checking it. Moderator only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn dismiss_flag(gig_id: u64) -> Result<(), GigError> {
+    roles::ensure_moderator()?;
+    FLAGGED_GIGS
+        .with(|flagged| flagged.borrow_mut().remove(&gig_id))
+        .map(|_| ())
+        .ok_or_else(|| GigError::not_found("Flagged gig"))
+}
@@ -0,0 +1,495 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use sha2::{Digest, Sha256};
+use std::{borrow::Cow, cell::RefCell, collections::BTreeSet};
+
+use crate::bids::Bid;
+use crate::config;
+use crate::delegates::{self, Permission};
+use crate::error::GigError;
+use crate::escrow;
+use crate::ledger;
+use crate::moderation::not_banned;
+use crate::multisig;
+use crate::rates;
+use crate::revisions;
+use crate::roles;
+use crate::tokens::{self, Payment};
+use crate::validation;
+use crate::{
+    do_insert_gig, get_memory, Gig, GigStatus, Memory, CONTRACTS_MEMORY_ID,
+    CONTRACT_AMENDMENTS_MEMORY_ID, GIG_STORAGE,
+};
+
+/// Longest note or counter-offer terms accepted on an amendment, in bytes.
+const MAX_TEXT_LEN: usize = 500;
+/// Most revisions an amendment can allow.
+const MAX_REVISION_LIMIT: u32 = 10;
+
+/// Amendments keyed by `((gig_id, bid_id), version)`, so each contract's chain is contiguous.
+type AmendmentMap = StableBTreeMap<((u64, u64), u32), Amendment, Memory>;
+
+/// Terms a worker and an employer agreed on when a bid was accepted. The terms never
+/// change once drafted; each party signs them by calling `sign_contract`.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Contract {
+    pub gig_id: u64,
+    pub bid_id: u64,
+    pub employer: Principal,
+    pub worker: Principal,
+    pub title: String,
+    pub scope: String,                  // Gig description when the bid was accepted.
+    pub terms: Option<String>,          // Terms of the accepted counter-offer, if any.
+    pub amount: u64,                    // Agreed price, in the token's base units.
+    pub ledger: Principal,
+    pub deadline: u64,
+    pub max_revisions: u32,             // Revisions the employer can request.
+    pub terms_hash: Vec<u8>,            // SHA-256 of the terms above; signers echo it.
+    pub created_at: u64,
+    pub employer_signed_at: Option<u64>,
+    pub worker_signed_at: Option<u64>,
+}
+
+impl Contract {
+    /// Whether both parties have signed.
+    pub fn is_signed(&self) -> bool {
+        self.employer_signed_at.is_some() && self.worker_signed_at.is_some()
+    }
+
+    /// The terms as originally signed.
+    fn original_terms(&self) -> ContractTerms {
+        ContractTerms {
+            scope: self.scope.clone(),
+            terms: self.terms.clone(),
+            amount: self.amount,
+            deadline: self.deadline,
+            max_revisions: self.max_revisions,
+        }
+    }
+}
+
+/// The terms of a contract an amendment can change.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct ContractTerms {
+    pub scope: String,
+    pub terms: Option<String>,
+    pub amount: u64,
+    pub deadline: u64,
+    pub max_revisions: u32,
+}
+
+/// A change to a signed contract, proposed by one party and confirmed by the other.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Amendment {
+    pub gig_id: u64,
+    pub bid_id: u64,
+    pub version: u32,                   // 1 for the first amendment of the contract.
+    pub proposed_by: Principal,
+    pub terms: ContractTerms,           // The full terms once the amendment is accepted.
+    pub note: String,
+    pub previous_hash: Vec<u8>,         // Hash of the version this amendment replaces.
+    pub terms_hash: Vec<u8>,            // SHA-256 over `previous_hash` and `terms`.
+    pub status: AmendmentStatus,
+    pub proposed_at: u64,
+    pub responded_at: Option<u64>,
+}
+
+/// Enum representing the outcome of an amendment.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum AmendmentStatus {
+    Pending,    // Waiting for the other party's answer.
+    Accepted,   // Both parties agreed; the terms are in force.
+    Rejected,   // The other party declined.
+    Withdrawn,  // The proposer took the amendment back.
+}
+
+impl Storable for Amendment {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Amendment {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for Contract {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Contract {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Contracts keyed by `(gig_id, bid_id)`, so a gig that was reassigned keeps the
+    /// contracts of its earlier workers.
+    static CONTRACTS: RefCell<StableBTreeMap<(u64, u64), Contract, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(CONTRACTS_MEMORY_ID)));
+
+    /// Amendment chains of signed contracts.
+    static AMENDMENTS: RefCell<AmendmentMap> =
+        RefCell::new(StableBTreeMap::init(get_memory(CONTRACT_AMENDMENTS_MEMORY_ID)));
+
+    /// Gigs whose escrow deposit is being awaited.
+    static FUNDING: RefCell<BTreeSet<u64>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// SHA-256 over every term of a contract, in a fixed encoding.
+fn hash_terms(contract: &Contract) -> Vec<u8> {
+    let encoded = Encode!(
+        &contract.gig_id,
+        &contract.bid_id,
+        &contract.employer,
+        &contract.worker,
+        &contract.title,
+        &contract.scope,
+        &contract.terms,
+        &contract.amount,
+        &contract.ledger,
+        &contract.deadline,
+        &contract.max_revisions
+    )
+    .unwrap();
+    Sha256::digest(encoded).to_vec()
+}
+
+/// Draft the contract for a bid that was just accepted on `gig`.
+pub fn draft(gig: &Gig, bid: &Bid, amount: u64, terms: Option<String>) {
+    let mut contract = Contract {
+        gig_id: gig.id,
+        bid_id: bid.id,
+        employer: gig.employer,
+        worker: bid.worker,
+        title: gig.title.clone(),
+        scope: gig.description.clone(),
+        terms,
+        amount,
+        ledger: gig.budget.as_ref().map_or_else(ledger::icp_ledger, |budget| budget.ledger),
+        deadline: gig.deadline,
+        max_revisions: revisions::MAX_REVISIONS,
+        terms_hash: Vec::new(),
+        created_at: time(),
+        employer_signed_at: None,
+        worker_signed_at: None,
+    };
+    contract.terms_hash = hash_terms(&contract);
+    CONTRACTS.with(|contracts| contracts.borrow_mut().insert((gig.id, bid.id), contract));
+}
+
+/// The contract of a gig's current assignment, if it was assigned through a bid.
+pub fn current(gig_id: u64) -> Option<Contract> {
+    CONTRACTS.with(|contracts| {
+        contracts
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u64::MAX))
+            .map(|(_, contract)| contract)
+            .max_by_key(|contract| contract.created_at)
+    })
+}
+
+/// Sign the contract of a gig's current assignment. `terms_hash` must be the hash of the
+/// terms the caller reviewed, so a signature never covers terms the signer has not seen.
+/// Employer or worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn sign_contract(gig_id: u64, terms_hash: Vec<u8>) -> Result<Contract, GigError> {
+    let mut contract = current(gig_id).ok_or_else(|| GigError::not_found("Contract"))?;
+    if terms_hash != contract.terms_hash {
+        return Err(GigError::validation("terms_hash", "Does not match the contract terms"));
+    }
+    let caller = caller();
+    // Ensure only the parties to the contract can sign it.
+    let signed_at = if caller == contract.employer {
+        &mut contract.employer_signed_at
+    } else if caller == contract.worker {
+        &mut contract.worker_signed_at
+    } else {
+        return Err(GigError::unauthorized("Only the employer or worker can sign"));
+    };
+    if signed_at.is_some() {
+        return Err(GigError::AlreadyExists { resource: "Signature".to_string() });
+    }
+    *signed_at = Some(time());
+    CONTRACTS.with(|contracts| {
+        contracts.borrow_mut().insert((gig_id, contract.bid_id), contract.clone())
+    });
+    Ok(contract)
+}
+
+/// Fund the escrow of an assigned gig with the contract amount once both parties have
+/// signed. The employer must have approved the canister as a spender beforehand. Gigs
+/// funded when they were posted already hold their escrow. Employer or their delegates only.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn fund_gig(gig_id: u64) -> Result<Gig, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer or a delegate can fund the gig.
+    if !delegates::may_act(&gig, caller(), Permission::Assign) {
+        return Err(GigError::unauthorized("Only the employer can fund this gig"));
+    }
+    if gig.status != GigStatus::Assigned {
+        return Err(GigError::invalid_state("Assigned", gig.status));
+    }
+    if gig.payment.is_some() {
+        return Err(GigError::AlreadyExists { resource: "Escrow".to_string() });
+    }
+    let contract = current(gig_id).ok_or_else(|| GigError::not_found("Contract"))?;
+    if !contract.is_signed() {
+        return Err(GigError::validation("gig_id", "Both parties must sign the contract first"));
+    }
+    let amount = effective_terms(&contract).0.amount;
+    multisig::ensure_approvers(&gig, Some(amount))?;
+    let token = tokens::accepted(contract.ledger)?;
+    // Mark the gig before awaiting so a concurrent call cannot deposit twice.
+    if !FUNDING.with(|funding| funding.borrow_mut().insert(gig_id)) {
+        return Err(GigError::invalid_state("No deposit in progress", "In progress"));
+    }
+    let usd_rate = rates::current(contract.ledger).await.ok();
+    let deposit = escrow::deposit(&token, gig.employer, amount).await;
+    FUNDING.with(|funding| funding.borrow_mut().remove(&gig_id));
+    let block = deposit?;
+
+    escrow::record(gig_id, gig.employer, &token, amount, block, usd_rate);
+    let mut gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // The gig may have been cancelled while the deposit was awaited; give the funds back.
+    if gig.status != GigStatus::Assigned {
+        let status = gig.status.clone();
+        escrow::refund_or_queue(gig_id).await;
+        return Err(GigError::invalid_state("Assigned", status));
+    }
+    gig.payment = Some(Payment { ledger: contract.ledger, amount });
+    gig.updated_at = Some(time());
+    do_insert_gig(&mut gig);
+    Ok(gig)
+}
+
+/// Amendments of a contract, oldest first.
+fn amendments_of(contract: &Contract) -> Vec<Amendment> {
+    let key = (contract.gig_id, contract.bid_id);
+    AMENDMENTS.with(|amendments| {
+        amendments
+            .borrow()
+            .range((key, 0)..=(key, u32::MAX))
+            .map(|(_, amendment)| amendment)
+            .collect()
+    })
+}
+
+/// The terms of a contract in force, with the hash of the version they come from: the
+/// latest accepted amendment, or the original terms.
+fn effective_terms(contract: &Contract) -> (ContractTerms, Vec<u8>) {
+    amendments_of(contract)
+        .into_iter()
+        .rev()
+        .find(|amendment| amendment.status == AmendmentStatus::Accepted)
+        .map(|amendment| (amendment.terms, amendment.terms_hash))
+        .unwrap_or_else(|| (contract.original_terms(), contract.terms_hash.clone()))
+}
+
+/// Revisions the employer can request on a gig: whatever its contract allows, or
+/// `revisions::MAX_REVISIONS` for gigs assigned without one.
+pub fn max_revisions(gig_id: u64) -> u32 {
+    current(gig_id).map_or(revisions::MAX_REVISIONS, |contract| {
+        effective_terms(&contract).0.max_revisions
+    })
+}
+
+/// Check proposed terms against the gig they would govern.
+fn validate_terms(
+    gig: &Gig,
+    current: &ContractTerms,
+    terms: &ContractTerms,
+) -> Result<(), GigError> {
+    if terms == current {
+        return Err(GigError::validation("terms", "Must differ from the terms in force"));
+    }
+    let max_scope_len = config::get().max_description_len as usize;
+    if terms.scope.trim().is_empty() || terms.scope.len() > max_scope_len {
+        return Err(GigError::validation(
+            "scope",
+            &format!("Must be between 1 and {} bytes", max_scope_len),
+        ));
+    }
+    if terms.terms.as_ref().is_some_and(|text| text.len() > MAX_TEXT_LEN) {
+        return Err(GigError::validation("terms", "Must be at most 500 bytes"));
+    }
+    if terms.amount == 0 {
+        return Err(GigError::validation("amount", "Must be at least 1"));
+    }
+    // The escrow holds the amount it was funded with.
+    if gig.payment.is_some() && terms.amount != current.amount {
+        return Err(GigError::validation("amount", "Cannot change once the escrow is funded"));
+    }
+    if terms.deadline != current.deadline {
+        validation::validate_deadline(terms.deadline)?;
+    }
+    if terms.max_revisions > MAX_REVISION_LIMIT {
+        return Err(GigError::validation("max_revisions", "Must be at most 10"));
+    }
+    Ok(())
+}
+
+/// Propose new terms for a gig's signed contract. They take effect once the other party
+/// accepts with `respond_amendment`. Employer or worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn propose_amendment(
+    gig_id: u64,
+    terms: ContractTerms,
+    note: String,
+) -> Result<Amendment, GigError> {
+    if note.len() > MAX_TEXT_LEN {
+        return Err(GigError::validation("note", "Must be at most 500 bytes"));
+    }
+    let contract = current(gig_id).ok_or_else(|| GigError::not_found("Contract"))?;
+    let proposer = caller();
+    // Ensure only the parties to the contract can amend it.
+    if proposer != contract.employer && proposer != contract.worker {
+        return Err(GigError::unauthorized("Only the employer or worker can amend"));
+    }
+    if !contract.is_signed() {
+        return Err(GigError::validation("gig_id", "Both parties must sign the contract first"));
+    }
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Submitted work is judged against the terms it was delivered under.
+    if gig.status != GigStatus::Assigned || gig.assigned_to != Some(contract.worker) {
+        return Err(GigError::invalid_state("Assigned", gig.status));
+    }
+    let chain = amendments_of(&contract);
+    if chain.last().is_some_and(|amendment| amendment.status == AmendmentStatus::Pending) {
+        return Err(GigError::AlreadyExists { resource: "Amendment".to_string() });
+    }
+    let (in_force, previous_hash) = effective_terms(&contract);
+    validate_terms(&gig, &in_force, &terms)?;
+
+    let terms_hash = Sha256::digest(Encode!(&previous_hash, &terms).unwrap()).to_vec();
+    let amendment = Amendment {
+        gig_id,
+        bid_id: contract.bid_id,
+        version: chain.len() as u32 + 1,
+        proposed_by: proposer,
+        terms,
+        note,
+        previous_hash,
+        terms_hash,
+        status: AmendmentStatus::Pending,
+        proposed_at: time(),
+        responded_at: None,
+    };
+    let key = ((gig_id, contract.bid_id), amendment.version);
+    AMENDMENTS.with(|amendments| amendments.borrow_mut().insert(key, amendment.clone()));
+    Ok(amendment)
+}
+
+/// The pending amendment of a gig's contract.
+fn pending(gig_id: u64) -> Result<(Contract, Amendment), GigError> {
+    let contract = current(gig_id).ok_or_else(|| GigError::not_found("Contract"))?;
+    amendments_of(&contract)
+        .pop()
+        .filter(|amendment| amendment.status == AmendmentStatus::Pending)
+        .map(|amendment| (contract, amendment))
+        .ok_or_else(|| GigError::not_found("Pending amendment"))
+}
+
+/// Accept or reject the pending amendment of a gig's contract. Accepting puts its terms in
+/// force and moves the gig's deadline to the amended one. Other party only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn respond_amendment(gig_id: u64, accept: bool) -> Result<Amendment, GigError> {
+    let (contract, mut amendment) = pending(gig_id)?;
+    let responder = caller();
+    // Ensure only the party the amendment was proposed to can answer.
+    let other = if amendment.proposed_by == contract.employer {
+        contract.worker
+    } else {
+        contract.employer
+    };
+    if responder != other {
+        return Err(GigError::unauthorized("Only the other party can answer this amendment"));
+    }
+    if accept {
+        let mut gig = GIG_STORAGE
+            .with(|storage| storage.borrow().get(&gig_id))
+            .ok_or_else(|| GigError::not_found("Gig"))?;
+        if gig.status != GigStatus::Assigned {
+            return Err(GigError::invalid_state("Assigned", gig.status));
+        }
+        // Time may have run past the proposed deadline, or the escrow been funded, while
+        // the amendment was pending.
+        let (in_force, _) = effective_terms(&contract);
+        validate_terms(&gig, &in_force, &amendment.terms)?;
+        if gig.deadline != amendment.terms.deadline {
+            gig.deadline = amendment.terms.deadline;
+            gig.updated_at = Some(time());
+            do_insert_gig(&mut gig);
+        }
+    }
+    amendment.status = if accept { AmendmentStatus::Accepted } else { AmendmentStatus::Rejected };
+    amendment.responded_at = Some(time());
+    let key = ((gig_id, contract.bid_id), amendment.version);
+    AMENDMENTS.with(|amendments| amendments.borrow_mut().insert(key, amendment.clone()));
+    Ok(amendment)
+}
+
+/// Withdraw the pending amendment of a gig's contract. Proposer only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn withdraw_amendment(gig_id: u64) -> Result<Amendment, GigError> {
+    let (contract, mut amendment) = pending(gig_id)?;
+    // Ensure only the proposer can withdraw the amendment.
+    if amendment.proposed_by != caller() {
+        return Err(GigError::unauthorized("Only the proposer can withdraw this amendment"));
+    }
+    amendment.status = AmendmentStatus::Withdrawn;
+    amendment.responded_at = Some(time());
+    let key = ((gig_id, contract.bid_id), amendment.version);
+    AMENDMENTS.with(|amendments| amendments.borrow_mut().insert(key, amendment.clone()));
+    Ok(amendment)
+}
+
+/// Retrieve the amendment chain of a gig's current contract, oldest first. Amendments are
+/// private to the parties and moderators.
+#[ic_cdk::query]
+pub fn get_amendments(gig_id: u64) -> Result<Vec<Amendment>, GigError> {
+    let contract = current(gig_id).ok_or_else(|| GigError::not_found("Contract"))?;
+    let caller = caller();
+    if caller != contract.employer && caller != contract.worker {
+        roles::ensure_moderator()?;
+    }
+    Ok(amendments_of(&contract))
+}
+
+/// Retrieve every contract drafted for a gig, oldest first. Contracts are private to
+/// their parties and moderators.
+#[ic_cdk::query]
+pub fn get_contracts(gig_id: u64) -> Result<Vec<Contract>, GigError> {
+    let contracts: Vec<Contract> = CONTRACTS.with(|contracts| {
+        contracts
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u64::MAX))
+            .map(|(_, contract)| contract)
+            .collect()
+    });
+    let caller = caller();
+    if !contracts.iter().any(|contract| caller == contract.employer || caller == contract.worker)
+    {
+        roles::ensure_moderator()?;
+    }
+    Ok(contracts)
+}
@@ -0,0 +1,47 @@
+use ic_cdk::api::time;
+use std::cell::RefCell;
+
+use crate::config;
+use crate::events::{self, EventKind};
+use crate::log;
+use crate::roles;
+
+/// Balance below which admins are warned when no threshold is configured: two trillion
+/// cycles.
+pub const DEFAULT_LOW_CYCLES_THRESHOLD: u64 = 2_000_000_000_000;
+/// Shortest time between two low-cycles warnings: one day.
+const ALERT_INTERVAL_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+thread_local! {
+    /// Time of the latest low-cycles warning. Kept on the heap, so an upgrade can at most
+    /// repeat one warning early.
+    static LAST_ALERT: RefCell<Option<u64>> = const { RefCell::new(None) };
+}
+
+/// Warn admins through the event feed when the cycles balance drops below the configured
+/// threshold, at most once a day, and ask the configured top-up canister for cycles.
+pub fn check_balance() {
+    let config = config::get();
+    let threshold = config.low_cycles_threshold.unwrap_or(DEFAULT_LOW_CYCLES_THRESHOLD);
+    let balance = u64::try_from(ic_cdk::api::canister_balance128()).unwrap_or(u64::MAX);
+    if balance >= threshold {
+        LAST_ALERT.with(|last| *last.borrow_mut() = None);
+        return;
+    }
+    let now = time();
+    let recently_alerted =
+        LAST_ALERT.with(|last| last.borrow().is_some_and(|at| now - at < ALERT_INTERVAL_NANOS));
+    if recently_alerted {
+        return;
+    }
+    LAST_ALERT.with(|last| *last.borrow_mut() = Some(now));
+    log::warn(&format!("Cycles balance {} is below the threshold of {}", balance, threshold));
+    let kind = EventKind::LowCycles { balance, threshold };
+    events::push(events::NO_GIG, kind, &roles::admins());
+    // The top-up canister is told which canister to fund; its reply is not awaited.
+    if let Some(top_up) = config.top_up_canister {
+        if let Err(code) = ic_cdk::notify(top_up, "top_up", (ic_cdk::id(),)) {
+            log::error(&format!("Cannot ask {} for a cycles top-up: {:?}", top_up, code));
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use ic_cdk::api::time;
+use ic_stable_structures::Cell;
+use std::cell::RefCell;
+use std::time::Duration;
+
+use crate::appeals;
+use crate::arbitration;
+use crate::backup;
+use crate::clauses;
+use crate::cycles;
+use crate::disputes;
+use crate::error::GigError;
+use crate::escrow;
+use crate::idempotency;
+use crate::indexes;
+use crate::log;
+use crate::market;
+use crate::multisig;
+use crate::moderation::not_banned;
+use crate::rate_limit;
+use crate::reminders;
+use crate::sharding;
+use crate::state_machine;
+use crate::teams;
+use crate::templates;
+use crate::trending;
+use crate::webhooks;
+use crate::{
+    approve_submission, ensure_controller, get_memory, insert_gig, purge_due_deletions, Gig,
+    GigStatus, Memory, ACCEPTANCE_WINDOW_MEMORY_ID, GIG_STORAGE,
+};
+
+/// How often gigs are checked for missed deadlines.
+const DEADLINE_SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Nanoseconds in a day.
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Time a proposed worker has to accept by default: three days.
+const DEFAULT_ACCEPTANCE_WINDOW_SECS: u64 = 3 * 24 * 60 * 60;
+/// Bounds on the acceptance window a controller can set: one hour to thirty days.
+const ACCEPTANCE_WINDOW_RANGE_SECS: std::ops::RangeInclusive<u64> = 60 * 60..=30 * 24 * 60 * 60;
+
+thread_local! {
+    /// Seconds a proposed worker has to accept before the gig reopens.
+    static ACCEPTANCE_WINDOW_SECS: RefCell<Cell<u64, Memory>> = RefCell::new(
+        Cell::init(get_memory(ACCEPTANCE_WINDOW_MEMORY_ID), DEFAULT_ACCEPTANCE_WINDOW_SECS)
+            .expect("Cannot create the acceptance window")
+    );
+}
+
+/// Start the periodic deadline sweep, which also sends deadline reminders, posts recurring
+/// gigs, retries failed refunds, settles disputes by default, enforces rulings once they
+/// are binding, slashes arbitrators who missed a ruling, delivers queued webhooks, purges
+/// deleted gigs past their grace period, decays trending scores, drops budgets that left
+/// the market rate window, prunes idle rate limit buckets and expired idempotency keys,
+/// spawns a new shard once the canister taking gigs is full and warns admins when cycles
+/// run low.
+/// Timers do not survive upgrades, so this runs from both `init` and `post_upgrade`.
+pub fn start_timer() {
+    ic_cdk_timers::set_timer_interval(DEADLINE_SWEEP_INTERVAL, || {
+        // A restore is overwriting stable memory underneath the sweep's structures.
+        if backup::is_restoring() {
+            log::debug("Skipped the sweep while a restore is in progress");
+            return;
+        }
+        expire_overdue_gigs();
+        reopen_unaccepted_proposals();
+        auto_approve_stale_submissions();
+        reminders::send_due();
+        templates::post_due_recurrences();
+        escrow::retry_refunds();
+        disputes::default_judgments();
+        appeals::enforce_binding_rulings();
+        arbitration::slash_missed_rulings();
+        webhooks::deliver_due();
+        purge_due_deletions();
+        trending::decay();
+        market::prune();
+        rate_limit::prune();
+        idempotency::prune();
+        sharding::check_capacity();
+        cycles::check_balance();
+    });
+}
+
+/// Move every open, pending or assigned gig whose deadline, and any grace period for late
+/// delivery, has passed to `Expired`, refunding the escrow of gigs that were never filled:
+/// open ones and those whose proposed worker had not accepted yet.
+pub fn expire_overdue_gigs() {
+    let now = time();
+    let candidates = indexes::ids_due_by(now);
+
+    let unfilled: Vec<u64> = GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut unfilled = Vec::new();
+        for id in candidates {
+            let Some(mut gig) = storage.get(&id) else {
+                continue;
+            };
+            if clauses::expires_at(&gig) >= now {
+                continue;
+            }
+            // Nobody worked on a gig that was never assigned, so its escrow goes back.
+            let worked_on = gig.status == GigStatus::Assigned;
+            if state_machine::transition(&mut gig, GigStatus::Expired).is_ok() {
+                gig.updated_at = Some(now);
+                insert_gig(&mut storage, &mut gig);
+                if !worked_on && escrow::is_locked(id) {
+                    unfilled.push(id);
+                }
+            }
+        }
+        unfilled
+    });
+    for id in unfilled {
+        ic_cdk::spawn(escrow::refund_or_queue(id));
+    }
+}
+
+/// Return every gig whose proposed worker has not accepted within the acceptance window
+/// to `Open`, disbanding proposed teams. Bids are untouched, so the worker's bid, if any,
+/// is still pending.
+pub fn reopen_unaccepted_proposals() {
+    let now = time();
+    let window = get_acceptance_window_secs().saturating_mul(1_000_000_000);
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        for id in indexes::ids_with_status(&GigStatus::PendingAcceptance) {
+            let Some(mut gig) = storage.get(&id) else {
+                continue;
+            };
+            // Proposals made before `proposed_at` existed are timed from their last update.
+            let Some(proposed_at) = gig.proposed_at.or(gig.updated_at) else {
+                continue;
+            };
+            if proposed_at.saturating_add(window) >= now {
+                continue;
+            }
+            if state_machine::transition(&mut gig, GigStatus::Open).is_ok() {
+                gig.assigned_to = None;
+                gig.updated_at = Some(now);
+                insert_gig(&mut storage, &mut gig);
+                teams::disband(id);
+            }
+        }
+    });
+}
+
+/// Set how long a proposed worker has to accept, between one hour and thirty days.
+/// Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn set_acceptance_window_secs(secs: u64) -> Result<(), GigError> {
+    ensure_controller()?;
+    if !ACCEPTANCE_WINDOW_RANGE_SECS.contains(&secs) {
+        return Err(GigError::validation("secs", "Must be between one hour and thirty days"));
+    }
+    ACCEPTANCE_WINDOW_SECS
+        .with(|window| window.borrow_mut().set(secs))
+        .expect("Cannot store the acceptance window");
+    Ok(())
+}
+
+/// Retrieve how long a proposed worker has to accept, in seconds.
+#[ic_cdk::query]
+pub fn get_acceptance_window_secs() -> u64 {
+    ACCEPTANCE_WINDOW_SECS.with(|window| *window.borrow().get())
+}
+
+/// Time a submission to `gig` waits for the employer before approving itself: the
+/// window in seconds if one is set, otherwise the window in days.
+fn auto_approve_window(gig: &Gig) -> Option<u64> {
+    match gig.auto_approve_after_secs {
+        Some(secs) => Some(secs.saturating_mul(1_000_000_000)),
+        None => gig.auto_approve_days.map(|days| days as u64 * DAY_NANOS),
+    }
+}
+
+/// Approve every submission the employer has left alone for longer than the gig's
+/// auto-approval window, releasing escrow to the worker. A revision request restarts the
+/// window with the next submission. Gigs whose releases need several signatures are left
+/// to their approvers.
+pub fn auto_approve_stale_submissions() {
+    let now = time();
+    let stale: Vec<u64> = GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        indexes::ids_with_status(&GigStatus::Submitted)
+            .into_iter()
+            .filter_map(|id| storage.get(&id))
+            // High-value gigs only move with their approvers' signatures.
+            .filter(|gig| !multisig::needs_signatures(gig))
+            .filter(|gig| match (gig.submitted_at, auto_approve_window(gig)) {
+                (Some(submitted_at), Some(window)) => submitted_at.saturating_add(window) < now,
+                _ => false,
+            })
+            .map(|gig| gig.id)
+            .collect()
+    });
+    for id in stale {
+        // A failed payout leaves the gig submitted, so the next sweep retries it.
+        ic_cdk::spawn(async move {
+            if let Err(error) = approve_submission(id).await {
+                log::warn(&format!("Cannot auto-approve gig {}: {:?}", id, error));
+            }
+        });
+    }
+}
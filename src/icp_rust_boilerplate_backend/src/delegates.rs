@@ -0,0 +1,203 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::moderation::{self, not_banned};
+use crate::{
+    get_memory, post_gig_as, Gig, GigPayload, Memory, StorablePrincipal,
+    DELEGATE_INDEX_MEMORY_ID, DELEGATIONS_MEMORY_ID,
+};
+
+/// Most delegates an employer can appoint.
+const MAX_DELEGATES: usize = 20;
+
+/// What a delegate may do on an employer's behalf.
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+pub enum Permission {
+    Post,       // Post gigs and edit, cancel, delete or restore them.
+    Assign,     // Invite workers, accept bids, assign and fund gigs.
+    Approve,    // Review hours, request revisions and approve work and milestones.
+}
+
+/// Permissions an employer granted another principal.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Delegation {
+    pub employer: Principal,
+    pub delegate: Principal,
+    pub permissions: Vec<Permission>,
+    pub granted_at: u64,
+}
+
+impl Storable for Delegation {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Delegation {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Delegations keyed by `(employer, delegate)`.
+    static DELEGATIONS:
+        RefCell<StableBTreeMap<(StorablePrincipal, StorablePrincipal), Delegation, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(DELEGATIONS_MEMORY_ID)));
+
+    /// Employers keyed by `(delegate, employer)`, for the employers a principal acts for.
+    static DELEGATE_INDEX:
+        RefCell<StableBTreeMap<(StorablePrincipal, StorablePrincipal), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(DELEGATE_INDEX_MEMORY_ID)));
+}
+
+/// Lowest principal, to start a range scan over every principal.
+fn min_principal() -> StorablePrincipal {
+    StorablePrincipal(Principal::from_slice(&[]))
+}
+
+/// Whether `delegate` holds `permission` from `employer`.
+pub fn is_delegate(employer: Principal, delegate: Principal, permission: Permission) -> bool {
+    DELEGATIONS
+        .with(|delegations| {
+            delegations
+                .borrow()
+                .get(&(StorablePrincipal(employer), StorablePrincipal(delegate)))
+        })
+        .is_some_and(|delegation| delegation.permissions.contains(&permission))
+}
+
+/// Whether `principal` holds any permission from `employer`.
+pub fn is_any_delegate(employer: Principal, principal: Principal) -> bool {
+    let key = (StorablePrincipal(employer), StorablePrincipal(principal));
+    DELEGATIONS.with(|delegations| delegations.borrow().contains_key(&key))
+}
+
+/// Delegates of `employer` holding `permission`, in principal order.
+pub fn holding(employer: Principal, permission: Permission) -> Vec<Principal> {
+    granted_by(employer)
+        .into_iter()
+        .filter(|delegation| delegation.permissions.contains(&permission))
+        .map(|delegation| delegation.delegate)
+        .collect()
+}
+
+/// Whether `principal` can do what `permission` covers on a gig: its employer, or a
+/// delegate of the employer holding the permission.
+pub fn may_act(gig: &Gig, principal: Principal, permission: Permission) -> bool {
+    gig.employer == principal || is_delegate(gig.employer, principal, permission)
+}
+
+/// Delegations granted by `employer`, in delegate order.
+fn granted_by(employer: Principal) -> Vec<Delegation> {
+    let key = StorablePrincipal(employer);
+    DELEGATIONS.with(|delegations| {
+        delegations
+            .borrow()
+            .range((key, min_principal())..)
+            .take_while(|((owner, _), _)| *owner == key)
+            .map(|(_, delegation)| delegation)
+            .collect()
+    })
+}
+
+/// Let `delegate` act for the caller with `permissions`, replacing any earlier grant.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn grant_delegate(
+    delegate: Principal,
+    permissions: Vec<Permission>,
+) -> Result<Delegation, GigError> {
+    let employer = caller();
+    if employer == Principal::anonymous() || delegate == Principal::anonymous() {
+        return Err(GigError::unauthorized("Anonymous principals cannot be delegates"));
+    }
+    if delegate == employer {
+        return Err(GigError::validation("delegate", "You already act for yourself"));
+    }
+    if moderation::is_banned(delegate) {
+        return Err(GigError::validation("delegate", "Delegate is banned"));
+    }
+    let mut unique: Vec<Permission> = Vec::with_capacity(permissions.len());
+    for permission in permissions {
+        if !unique.contains(&permission) {
+            unique.push(permission);
+        }
+    }
+    if unique.is_empty() {
+        return Err(GigError::validation("permissions", "Grant at least one permission"));
+    }
+    let key = (StorablePrincipal(employer), StorablePrincipal(delegate));
+    let exists = DELEGATIONS.with(|delegations| delegations.borrow().contains_key(&key));
+    if !exists && granted_by(employer).len() >= MAX_DELEGATES {
+        return Err(GigError::validation("delegate", "At most 20 delegates can be appointed"));
+    }
+
+    let delegation = Delegation { employer, delegate, permissions: unique, granted_at: time() };
+    DELEGATIONS.with(|delegations| delegations.borrow_mut().insert(key, delegation.clone()));
+    DELEGATE_INDEX.with(|index| {
+        index.borrow_mut().insert((StorablePrincipal(delegate), StorablePrincipal(employer)), ())
+    });
+    Ok(delegation)
+}
+
+/// Withdraw every permission the caller granted `delegate`.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn revoke_delegate(delegate: Principal) -> Result<(), GigError> {
+    let employer = caller();
+    DELEGATIONS
+        .with(|delegations| {
+            delegations
+                .borrow_mut()
+                .remove(&(StorablePrincipal(employer), StorablePrincipal(delegate)))
+        })
+        .ok_or_else(|| GigError::not_found("Delegate"))?;
+    DELEGATE_INDEX.with(|index| {
+        index.borrow_mut().remove(&(StorablePrincipal(delegate), StorablePrincipal(employer)))
+    });
+    Ok(())
+}
+
+/// Retrieve the delegates the caller appointed.
+#[ic_cdk::query]
+pub fn list_delegates() -> Vec<Delegation> {
+    granted_by(caller())
+}
+
+/// Retrieve the delegations under which the caller acts for other employers.
+#[ic_cdk::query]
+pub fn list_delegations() -> Vec<Delegation> {
+    let delegate = StorablePrincipal(caller());
+    let employers: Vec<StorablePrincipal> = DELEGATE_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((delegate, min_principal())..)
+            .take_while(|((key, _), _)| *key == delegate)
+            .map(|((_, employer), _)| employer)
+            .collect()
+    });
+    DELEGATIONS.with(|delegations| {
+        let delegations = delegations.borrow();
+        employers
+            .into_iter()
+            .filter_map(|employer| delegations.get(&(employer, delegate)))
+            .collect()
+    })
+}
+
+/// Post a gig on behalf of an employer who granted the caller `Permission::Post`. Any escrow
+/// is drawn from the employer, who must have approved the transfer.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn post_gig_for(employer: Principal, payload: GigPayload) -> Result<Gig, GigError> {
+    // Ensure only a delegate allowed to post can post for the employer.
+    if !is_delegate(employer, caller(), Permission::Post) {
+        return Err(GigError::unauthorized("You may not post gigs for this employer"));
+    }
+    post_gig_as(employer, payload).await
+}
@@ -0,0 +1,147 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::attachments;
+use crate::error::GigError;
+use crate::moderation::not_banned;
+use crate::{get_memory, Memory, DELIVERY_PROOFS_MEMORY_ID, GIG_STORAGE};
+
+/// Length of a SHA-256 digest, in bytes.
+const HASH_LEN: usize = 32;
+/// Longest URL a deliverable can be revealed at, in bytes.
+const MAX_URL_LEN: usize = 500;
+
+/// Where a committed deliverable was revealed.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub enum Artifact {
+    Attachment(u64), // A finalised attachment; the canister checks it against the hash.
+    Url(String),     // An external location; anyone can fetch it and compare the hash.
+}
+
+/// A worker's reveal of the deliverable behind a committed hash.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Reveal {
+    pub artifact: Artifact,
+    pub verified: bool,         // The canister hashed the artifact and it matched.
+    pub revealed_at: u64,
+}
+
+/// Tamper-evident record of what a worker delivered with a submission, and when.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct DeliveryProof {
+    pub gig_id: u64,
+    pub index: u32,             // Position among the gig's submissions, from 0.
+    pub worker: Principal,
+    pub hash: Vec<u8>,          // SHA-256 of the deliverable.
+    pub committed_at: u64,
+    pub reveal: Option<Reveal>,
+}
+
+impl Storable for DeliveryProof {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for DeliveryProof {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Delivery proofs keyed by `(gig_id, index)`, so a gig's proofs are in submission order.
+    static DELIVERY_PROOFS: RefCell<StableBTreeMap<(u64, u32), DeliveryProof, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(DELIVERY_PROOFS_MEMORY_ID)));
+}
+
+/// Check that a submitted deliverable hash is a SHA-256 digest.
+pub fn validate_hash(hash: &[u8]) -> Result<(), GigError> {
+    if hash.len() != HASH_LEN {
+        return Err(GigError::validation("deliverable_hash", "Must be a 32-byte SHA-256 digest"));
+    }
+    Ok(())
+}
+
+/// Latest delivery proof of a gig.
+fn latest(gig_id: u64) -> Option<DeliveryProof> {
+    DELIVERY_PROOFS.with(|proofs| {
+        proofs
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u32::MAX))
+            .last()
+            .map(|(_, proof)| proof)
+    })
+}
+
+/// Record the deliverable hash `worker` submitted with their work on a gig.
+pub fn commit(gig_id: u64, worker: Principal, hash: Vec<u8>) -> DeliveryProof {
+    let index = latest(gig_id).map_or(0, |proof| proof.index + 1);
+    let proof = DeliveryProof {
+        gig_id,
+        index,
+        worker,
+        hash,
+        committed_at: time(),
+        reveal: None,
+    };
+    DELIVERY_PROOFS.with(|proofs| proofs.borrow_mut().insert((gig_id, index), proof.clone()));
+    proof
+}
+
+/// Reveal the deliverable behind the latest hash committed to a gig. Attachments are
+/// hashed by the canister and must match; URLs are recorded for anyone to check. Each
+/// commitment is revealed once. Committing worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn reveal_deliverable(gig_id: u64, artifact: Artifact) -> Result<DeliveryProof, GigError> {
+    if !GIG_STORAGE.with(|storage| storage.borrow().contains_key(&gig_id)) {
+        return Err(GigError::not_found("Gig"));
+    }
+    let mut proof = latest(gig_id).ok_or_else(|| GigError::not_found("Delivery proof"))?;
+    // Ensure only the worker who committed the hash can reveal it.
+    if proof.worker != caller() {
+        return Err(GigError::unauthorized("Only the submitting worker can reveal"));
+    }
+    if proof.reveal.is_some() {
+        return Err(GigError::AlreadyExists { resource: "Reveal".to_string() });
+    }
+    let verified = match &artifact {
+        Artifact::Attachment(id) => {
+            attachments::ensure_attachable(&[*id])?;
+            let hash =
+                attachments::content_hash(*id).ok_or_else(|| GigError::not_found("Attachment"))?;
+            if hash.as_slice() != proof.hash.as_slice() {
+                return Err(GigError::validation("artifact", "Does not match the committed hash"));
+            }
+            true
+        }
+        Artifact::Url(url) => {
+            if url.trim().is_empty() || url.len() > MAX_URL_LEN {
+                return Err(GigError::validation("artifact", "URL must be 1 to 500 bytes"));
+            }
+            false
+        }
+    };
+    proof.reveal = Some(Reveal { artifact, verified, revealed_at: time() });
+    DELIVERY_PROOFS
+        .with(|proofs| proofs.borrow_mut().insert((gig_id, proof.index), proof.clone()));
+    Ok(proof)
+}
+
+/// Retrieve the delivery proofs of a gig, in submission order.
+#[ic_cdk::query]
+pub fn get_delivery_proofs(gig_id: u64) -> Vec<DeliveryProof> {
+    DELIVERY_PROOFS.with(|proofs| {
+        proofs
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u32::MAX))
+            .map(|(_, proof)| proof)
+            .collect()
+    })
+}
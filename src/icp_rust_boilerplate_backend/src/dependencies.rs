@@ -0,0 +1,94 @@
+use candid::Principal;
+use ic_stable_structures::StableBTreeMap;
+
+use crate::error::GigError;
+use crate::{Gig, GigStatus, Memory};
+
+/// Most prerequisites a gig can declare.
+const MAX_DEPENDENCIES: usize = 10;
+
+/// Whether a gig counts as done for the gigs that depend on it. Approved gigs stay done
+/// when the employer archives them.
+fn is_done(gig: &Gig) -> bool {
+    gig.status == GigStatus::Approved || gig.archived_from == Some(GigStatus::Approved)
+}
+
+/// Check the prerequisites declared by a gig of `employer` and return them deduplicated.
+/// Prerequisites must be other existing gigs of the same employer, and when an existing
+/// gig (`gig_id`) changes its prerequisites they must not lead back to it.
+pub fn validate(
+    storage: &StableBTreeMap<u64, Gig, Memory>,
+    gig_id: Option<u64>,
+    employer: Principal,
+    depends_on: Vec<u64>,
+) -> Result<Vec<u64>, GigError> {
+    let mut prerequisites: Vec<u64> = Vec::with_capacity(depends_on.len());
+    for id in depends_on {
+        if !prerequisites.contains(&id) {
+            prerequisites.push(id);
+        }
+    }
+    if prerequisites.len() > MAX_DEPENDENCIES {
+        return Err(GigError::validation("depends_on", "A gig can depend on at most 10 gigs"));
+    }
+    for id in &prerequisites {
+        if Some(*id) == gig_id {
+            return Err(GigError::validation("depends_on", "A gig cannot depend on itself"));
+        }
+        let prerequisite = storage
+            .get(id)
+            .ok_or_else(|| GigError::validation("depends_on", "Prerequisite gig not found"))?;
+        if prerequisite.employer != employer {
+            return Err(GigError::validation("depends_on", "Prerequisites must be your own gigs"));
+        }
+    }
+    // A new gig has no dependents yet, so only updates can close a cycle.
+    if let Some(gig_id) = gig_id {
+        if reaches(storage, &prerequisites, gig_id) {
+            return Err(GigError::validation("depends_on", "Dependencies cannot form a cycle"));
+        }
+    }
+    Ok(prerequisites)
+}
+
+/// Whether `target` is reachable from `start` by following declared prerequisites.
+fn reaches(storage: &StableBTreeMap<u64, Gig, Memory>, start: &[u64], target: u64) -> bool {
+    let mut pending = start.to_vec();
+    let mut visited: Vec<u64> = Vec::new();
+    while let Some(id) = pending.pop() {
+        if id == target {
+            return true;
+        }
+        if visited.contains(&id) {
+            continue;
+        }
+        visited.push(id);
+        if let Some(gig) = storage.get(&id) {
+            pending.extend(gig.depends_on.iter().flatten());
+        }
+    }
+    false
+}
+
+/// Ensure a gig's prerequisites can be replaced: they gate assignment, so they are fixed
+/// once the gig leaves `Open`.
+pub fn ensure_change(gig: &Gig, depends_on: &[u64]) -> Result<(), GigError> {
+    let current = gig.depends_on.as_deref().unwrap_or_default();
+    if current != depends_on && gig.status != GigStatus::Open {
+        return Err(GigError::validation("depends_on", "Can only change while the gig is open"));
+    }
+    Ok(())
+}
+
+/// Ensure every prerequisite of a gig has been approved, so it can be assigned.
+pub fn ensure_met(storage: &StableBTreeMap<u64, Gig, Memory>, gig: &Gig) -> Result<(), GigError> {
+    for id in gig.depends_on.iter().flatten() {
+        if !storage.get(id).is_some_and(|prerequisite| is_done(&prerequisite)) {
+            return Err(GigError::validation(
+                "depends_on",
+                &format!("Prerequisite gig {} has not been approved yet", id),
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,565 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::arbitration;
+use crate::attachments;
+use crate::audit::{self, AuditAction};
+use crate::error::GigError;
+use crate::log;
+use crate::escrow;
+use crate::moderation::not_banned;
+use crate::reputation;
+use crate::state_machine;
+use crate::teams;
+use crate::{
+    ensure_controller, get_memory, insert_gig, Gig, GigStatus, Memory, StorablePrincipal,
+    ARBITRATORS_MEMORY_ID, DISPUTES_MEMORY_ID, EVIDENCE_MEMORY_ID, GIG_STORAGE,
+};
+
+/// Maximum length of a dispute reason or evidence entry.
+const MAX_DISPUTE_TEXT_LEN: usize = 1000;
+/// Time the parties have to submit evidence after a dispute is raised: five days.
+const EVIDENCE_WINDOW_NANOS: u64 = 5 * 24 * 60 * 60 * 1_000_000_000;
+/// Time the losing party has to appeal an arbitrator's ruling: three days.
+const APPEAL_WINDOW_NANOS: u64 = 3 * 24 * 60 * 60 * 1_000_000_000;
+
+/// A dispute raised over an assigned gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Dispute {
+    pub gig_id: u64,
+    pub raised_by: Principal,
+    pub reason: String,
+    pub raised_at: u64,
+    pub ruling: Option<Ruling>,     // Set once an arbitrator resolves the dispute.
+    pub resolved_by: Option<Principal>,
+    pub resolved_at: Option<u64>,
+    pub arbitrator: Option<Principal>, // Staked arbitrator selected to rule, if any.
+    pub ruling_due_at: Option<u64>,    // When the selected arbitrator must have ruled by.
+    pub evidence_due_at: Option<u64>,  // Parties cannot add evidence after this.
+    pub appeal_due_at: Option<u64>,    // Set on appealable rulings; binding after this.
+}
+
+/// Outcome of a dispute.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum Ruling {
+    Worker,     // The work is accepted and escrow is released to the worker.
+    Employer,   // Escrow is refunded and the gig is reopened.
+    Split { worker_percent: u8 }, // Escrow is divided and the gig is closed as approved.
+}
+
+/// How a split ruling divided the escrow that was left when it was enforced.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct DisputeSplit {
+    pub worker_percent: u8,
+    pub worker_amount: u64,     // Paid to the worker before platform and ledger fees.
+    pub employer_amount: u64,   // Refunded to the employer before the ledger fee.
+}
+
+/// Ensure a ruling can be applied. Splits must leave each party a share; the whole escrow
+/// going to one party is a `Worker` or `Employer` ruling.
+pub fn validate_ruling(ruling: &Ruling) -> Result<(), GigError> {
+    match ruling {
+        Ruling::Split { worker_percent } if !(1..=99).contains(worker_percent) => {
+            Err(GigError::validation("worker_percent", "Must be between 1 and 99"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A piece of evidence submitted to a dispute.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Evidence {
+    pub gig_id: u64,
+    pub submitted_by: Principal,
+    pub content: String,
+    pub submitted_at: u64,
+    pub attachments: Option<Vec<u64>>, // Finalised attachments backing the entry.
+}
+
+/// A dispute together with the evidence submitted to it.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct DisputeRecord {
+    pub dispute: Dispute,
+    pub evidence: Vec<Evidence>,
+}
+
+impl Storable for Dispute {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Dispute {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for Evidence {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Evidence {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Principals allowed to resolve disputes.
+    static ARBITRATORS: RefCell<StableBTreeMap<StorablePrincipal, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(ARBITRATORS_MEMORY_ID)));
+
+    /// Disputes keyed by gig ID.
+    static DISPUTE_STORAGE: RefCell<StableBTreeMap<u64, Dispute, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(DISPUTES_MEMORY_ID)));
+
+    /// Evidence keyed by `(gig_id, sequence)` in submission order.
+    static EVIDENCE_STORAGE: RefCell<StableBTreeMap<(u64, u64), Evidence, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(EVIDENCE_MEMORY_ID)));
+}
+
+/// Whether the principal is a registered arbitrator.
+pub fn is_arbitrator(principal: Principal) -> bool {
+    ARBITRATORS.with(|arbitrators| arbitrators.borrow().contains_key(&StorablePrincipal(principal)))
+}
+
+/// Register an arbitrator. Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn add_arbitrator(arbitrator: Principal) -> Result<(), GigError> {
+    ensure_controller()?;
+    ARBITRATORS.with(|arbitrators| {
+        arbitrators.borrow_mut().insert(StorablePrincipal(arbitrator), ())
+    });
+    Ok(())
+}
+
+/// Remove an arbitrator. Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn remove_arbitrator(arbitrator: Principal) -> Result<(), GigError> {
+    ensure_controller()?;
+    ARBITRATORS
+        .with(|arbitrators| arbitrators.borrow_mut().remove(&StorablePrincipal(arbitrator)))
+        .map(|_| ())
+        .ok_or_else(|| GigError::not_found("Arbitrator"))
+}
+
+/// Retrieve all registered arbitrators.
+#[ic_cdk::query]
+pub fn list_arbitrators() -> Vec<Principal> {
+    ARBITRATORS.with(|arbitrators| arbitrators.borrow().iter().map(|(p, _)| p.0).collect())
+}
+
+/// Raise a dispute over an assigned or submitted gig, freezing any escrow until it is ruled
+/// on. Callable by the employer, the worker or a member of the team.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn raise_dispute(gig_id: u64, reason: String) -> Result<Dispute, GigError> {
+    let caller = caller();
+    if reason.trim().is_empty() || reason.len() > MAX_DISPUTE_TEXT_LEN {
+        return Err(GigError::validation("reason", "Reason must be between 1 and 1000 bytes"));
+    }
+    let dispute = GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        // Ensure only the parties of the gig can raise a dispute.
+        if gig.employer != caller && !teams::workers(&gig).contains(&caller) {
+            return Err(GigError::unauthorized(
+                "Only the employer or assigned workers can raise a dispute",
+            ));
+        }
+        // Disputes only make sense while work is in progress or awaiting approval.
+        state_machine::ensure_transition(&gig.status, &GigStatus::Disputed)?;
+        // Hold the funds so only the ruling can move them. A dispute cannot be raised while
+        // an approval is paying the escrow out.
+        let frozen = escrow::freeze(gig_id)?;
+
+        let dispute = Dispute {
+            gig_id,
+            raised_by: caller,
+            reason,
+            raised_at: time(),
+            ruling: None,
+            resolved_by: None,
+            resolved_at: None,
+            arbitrator: None,
+            ruling_due_at: None,
+            evidence_due_at: Some(time() + EVIDENCE_WINDOW_NANOS),
+            appeal_due_at: None,
+        };
+        DISPUTE_STORAGE.with(|disputes| disputes.borrow_mut().insert(gig_id, dispute.clone()));
+        // Evidence of an earlier dispute over the gig does not carry over to this one.
+        clear_evidence(gig_id);
+
+        state_machine::transition(&mut gig, GigStatus::Disputed)?;
+        gig.updated_at = Some(time());
+        insert_gig(&mut storage, &mut gig);
+        if frozen {
+            audit::record_note(&gig, AuditAction::EscrowFrozen);
+        }
+        Ok::<_, GigError>(dispute)
+    })?;
+    // Spawned futures run up to their first await straight away, and selection reads the
+    // gig map, so the map must no longer be borrowed here.
+    ic_cdk::spawn(arbitration::select_arbitrator(gig_id));
+    Ok(dispute)
+}
+
+/// Remove the evidence submitted to a gig's dispute.
+fn clear_evidence(gig_id: u64) {
+    EVIDENCE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let keys: Vec<(u64, u64)> =
+            storage.range((gig_id, 0)..=(gig_id, u64::MAX)).map(|(key, _)| key).collect();
+        for key in keys {
+            storage.remove(&key);
+        }
+    });
+}
+
+/// Attach evidence, optionally backed by uploaded files, to an open dispute. Callable by
+/// the parties until the evidence deadline and by arbitrators at any time.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn submit_evidence(
+    gig_id: u64,
+    content: String,
+    attachments: Option<Vec<u64>>,
+) -> Result<Evidence, GigError> {
+    let caller = caller();
+    if content.trim().is_empty() || content.len() > MAX_DISPUTE_TEXT_LEN {
+        return Err(GigError::validation("content", "Evidence must be between 1 and 1000 bytes"));
+    }
+    if let Some(ids) = &attachments {
+        attachments::ensure_attachable(ids)?;
+    }
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    let is_party = gig.employer == caller || teams::workers(&gig).contains(&caller);
+    if !is_party && !is_arbitrator(caller) {
+        return Err(GigError::unauthorized("Only the parties or an arbitrator can submit evidence"));
+    }
+    let dispute = get_dispute(gig_id).ok_or_else(|| GigError::not_found("Dispute"))?;
+    if dispute.ruling.is_some() {
+        return Err(GigError::invalid_state("Unresolved", "Resolved"));
+    }
+    let past_deadline = dispute.evidence_due_at.is_some_and(|due| due < time());
+    if past_deadline && !is_arbitrator(caller) {
+        return Err(GigError::validation("gig_id", "The evidence deadline has passed"));
+    }
+
+    let evidence = Evidence {
+        gig_id,
+        submitted_by: caller,
+        content,
+        submitted_at: time(),
+        attachments,
+    };
+    EVIDENCE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let seq = storage
+            .range((gig_id, 0)..=(gig_id, u64::MAX))
+            .last()
+            .map_or(0, |((_, seq), _)| seq + 1);
+        storage.insert((gig_id, seq), evidence.clone());
+    });
+    Ok(evidence)
+}
+
+/// Rule on a dispute in favour of either party. Only the selected arbitrator can rule on
+/// a dispute that has one; any arbitrator can rule when the staked pool was empty.
+///
+/// The ruling is provisional: the losing party has three days to appeal it, after which
+/// the timer enforces it. A `Worker` ruling approves the gig and releases escrow to the
+/// worker; an `Employer` ruling refunds escrow and reopens the gig; a `Split` ruling pays
+/// the worker their percentage of the remaining escrow, refunds the rest and closes the gig.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn resolve_dispute(gig_id: u64, ruling: Ruling) -> Result<Dispute, GigError> {
+    let arbitrator = caller();
+    if !is_arbitrator(arbitrator) {
+        return Err(GigError::unauthorized("Only an arbitrator can resolve disputes"));
+    }
+    validate_ruling(&ruling)?;
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    if gig.status != GigStatus::Disputed {
+        return Err(GigError::invalid_state("Disputed", gig.status));
+    }
+    DISPUTE_STORAGE.with(|disputes| {
+        let mut disputes = disputes.borrow_mut();
+        let mut dispute = disputes.get(&gig_id).ok_or_else(|| GigError::not_found("Dispute"))?;
+        if dispute.ruling.is_some() {
+            return Err(GigError::invalid_state("Unresolved", "Resolved"));
+        }
+        if dispute.arbitrator.is_some_and(|selected| selected != arbitrator) {
+            return Err(GigError::unauthorized("Another arbitrator was selected for this dispute"));
+        }
+        let now = time();
+        dispute.ruling = Some(ruling);
+        dispute.resolved_by = Some(arbitrator);
+        dispute.resolved_at = Some(now);
+        dispute.appeal_due_at = Some(now + APPEAL_WINDOW_NANOS);
+        disputes.insert(gig_id, dispute.clone());
+        Ok(dispute)
+    })
+}
+
+/// Move a disputed gig's escrow as `ruling` directs.
+async fn move_funds(gig_id: u64, ruling: &Ruling) -> Result<(), GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    if gig.status != GigStatus::Disputed {
+        return Err(GigError::invalid_state("Disputed", gig.status));
+    }
+    match ruling {
+        Ruling::Worker if teams::is_team_gig(gig_id) => {
+            let owed = escrow::get_escrow(gig_id).map_or(0, |escrow| escrow.remaining());
+            teams::release_ruled(gig_id, owed).await
+        }
+        Ruling::Worker => {
+            let worker = gig.assigned_to.ok_or_else(|| GigError::not_found("Assigned worker"))?;
+            escrow::release_ruled(gig_id, worker, None).await
+        }
+        Ruling::Employer => escrow::refund_ruled(gig_id).await,
+        Ruling::Split { worker_percent } => {
+            let split = record_split(gig_id, *worker_percent)?;
+            // The worker's share goes first, so on a retry whatever exceeds the employer's
+            // share is what the worker is still owed.
+            if let Some(escrow) = escrow::get_escrow(gig_id) {
+                let owed = escrow.remaining().saturating_sub(split.employer_amount);
+                if teams::is_team_gig(gig_id) {
+                    teams::release_ruled(gig_id, owed).await?;
+                } else if owed > 0 {
+                    let worker =
+                        gig.assigned_to.ok_or_else(|| GigError::not_found("Assigned worker"))?;
+                    escrow::release_ruled(gig_id, worker, Some(owed)).await?;
+                }
+            }
+            escrow::refund_ruled(gig_id).await
+        }
+    }
+}
+
+/// Record on the gig how a split divides its remaining escrow, keeping the split already
+/// recorded when a failed transfer is retried.
+fn record_split(gig_id: u64, worker_percent: u8) -> Result<DisputeSplit, GigError> {
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        if let Some(split) = gig.dispute_split.clone() {
+            return Ok(split);
+        }
+        let remaining = escrow::get_escrow(gig_id).map_or(0, |escrow| escrow.remaining());
+        let worker_amount = remaining * worker_percent as u64 / 100;
+        let split = DisputeSplit {
+            worker_percent,
+            worker_amount,
+            employer_amount: remaining - worker_amount,
+        };
+        gig.dispute_split = Some(split.clone());
+        gig.updated_at = Some(time());
+        insert_gig(&mut storage, &mut gig);
+        Ok(split)
+    })
+}
+
+/// Enforce the binding ruling on a gig whose dispute was ruled on and not overturned.
+pub async fn enforce(gig_id: u64, ruling: Ruling) -> Result<Gig, GigError> {
+    move_funds(gig_id, &ruling).await?;
+    close(gig_id, &ruling)
+}
+
+/// Apply a binding ruling to a disputed gig on behalf of `resolver`, moving the escrow
+/// accordingly. Callers are responsible for checking that `resolver` may rule.
+pub async fn settle(gig_id: u64, ruling: Ruling, resolver: Principal) -> Result<Gig, GigError> {
+    validate_ruling(&ruling)?;
+    // Move the funds before the ruling is recorded so a failed transfer can be retried.
+    move_funds(gig_id, &ruling).await?;
+
+    DISPUTE_STORAGE.with(|disputes| {
+        let mut disputes = disputes.borrow_mut();
+        if let Some(mut dispute) = disputes.get(&gig_id) {
+            dispute.ruling = Some(ruling.clone());
+            dispute.resolved_by = Some(resolver);
+            dispute.resolved_at = Some(time());
+            dispute.appeal_due_at = None;
+            disputes.insert(gig_id, dispute);
+        }
+    });
+    close(gig_id, &ruling)
+}
+
+/// Move a disputed gig, once its escrow has moved, to the status `ruling` leaves it in and
+/// lift the freeze on its escrow.
+fn close(gig_id: u64, ruling: &Ruling) -> Result<Gig, GigError> {
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        match ruling {
+            Ruling::Worker => {
+                state_machine::transition(&mut gig, GigStatus::Approved)?;
+                for worker in teams::workers(&gig) {
+                    reputation::record_completion(gig.employer, worker);
+                }
+            }
+            Ruling::Employer => {
+                state_machine::transition(&mut gig, GigStatus::Open)?;
+                gig.assigned_to = None;
+                teams::disband(gig_id);
+                // The escrow went back to the employer, so the reopened gig is unfunded.
+                gig.payment = None;
+            }
+            Ruling::Split { .. } => state_machine::transition(&mut gig, GigStatus::Approved)?,
+        }
+        gig.updated_at = Some(time());
+        insert_gig(&mut storage, &mut gig);
+        if escrow::unfreeze(gig_id) {
+            audit::record_note(&gig, AuditAction::EscrowUnfrozen);
+        }
+        Ok(gig)
+    })
+}
+
+/// Freeze the escrow of every disputed gig, for canisters upgraded from before disputes
+/// froze escrow.
+pub fn freeze_disputed_escrows() {
+    let disputed: Vec<Gig> = GIG_STORAGE.with(|storage| {
+        storage
+            .borrow()
+            .iter()
+            .filter(|(_, gig)| gig.status == GigStatus::Disputed)
+            .map(|(_, gig)| gig)
+            .collect()
+    });
+    for gig in disputed {
+        if escrow::freeze(gig.id).unwrap_or(false) {
+            audit::record_note(&gig, AuditAction::EscrowFrozen);
+        }
+    }
+}
+
+/// Rule for the party that raised a dispute when the other party submitted no evidence
+/// before the deadline. The canister itself is recorded as the resolver.
+pub fn default_judgments() {
+    let now = time();
+    let defaulted: Vec<(u64, Ruling)> = DISPUTE_STORAGE.with(|disputes| {
+        disputes
+            .borrow()
+            .iter()
+            .map(|(_, dispute)| dispute)
+            .filter(|dispute| dispute.ruling.is_none())
+            .filter(|dispute| dispute.evidence_due_at.is_some_and(|due| due < now))
+            .filter_map(|dispute| {
+                let gig = GIG_STORAGE.with(|storage| storage.borrow().get(&dispute.gig_id))?;
+                let (ruling, respondents) = if dispute.raised_by == gig.employer {
+                    (Ruling::Employer, teams::workers(&gig))
+                } else {
+                    (Ruling::Worker, vec![gig.employer])
+                };
+                if respondents.is_empty() {
+                    return None;
+                }
+                let responded = get_evidence(dispute.gig_id)
+                    .iter()
+                    .any(|evidence| respondents.contains(&evidence.submitted_by));
+                (!responded).then_some((dispute.gig_id, ruling))
+            })
+            .collect()
+    });
+    for (gig_id, ruling) in defaulted {
+        // A failed transfer leaves the dispute open, so the next sweep retries it.
+        ic_cdk::spawn(async move {
+            if let Err(error) = settle(gig_id, ruling, ic_cdk::id()).await {
+                log::warn(&format!("Cannot enter default judgment on gig {}: {:?}", gig_id, error));
+            }
+        });
+    }
+}
+
+/// Record the arbitrator selected to rule on an unresolved dispute and their deadline.
+pub fn assign_arbitrator(gig_id: u64, arbitrator: Option<Principal>, ruling_due_at: Option<u64>) {
+    DISPUTE_STORAGE.with(|disputes| {
+        let mut disputes = disputes.borrow_mut();
+        if let Some(mut dispute) = disputes.get(&gig_id).filter(|d| d.ruling.is_none()) {
+            dispute.arbitrator = arbitrator;
+            dispute.ruling_due_at = ruling_due_at;
+            disputes.insert(gig_id, dispute);
+        }
+    });
+}
+
+/// Unresolved disputes whose selected arbitrator let the ruling deadline pass.
+pub fn overdue_rulings(now: u64) -> Vec<Dispute> {
+    DISPUTE_STORAGE.with(|disputes| {
+        disputes
+            .borrow()
+            .iter()
+            .map(|(_, dispute)| dispute)
+            .filter(|dispute| dispute.ruling.is_none())
+            .filter(|dispute| dispute.ruling_due_at.is_some_and(|due| due < now))
+            .collect()
+    })
+}
+
+/// Ruled disputes whose gig is still disputed because the ruling has not been enforced.
+pub fn provisional_rulings() -> Vec<Dispute> {
+    DISPUTE_STORAGE.with(|disputes| {
+        disputes
+            .borrow()
+            .iter()
+            .map(|(_, dispute)| dispute)
+            .filter(|dispute| dispute.ruling.is_some() && dispute.appeal_due_at.is_some())
+            .filter(|dispute| {
+                GIG_STORAGE
+                    .with(|storage| storage.borrow().get(&dispute.gig_id))
+                    .is_some_and(|gig| gig.status == GigStatus::Disputed)
+            })
+            .collect()
+    })
+}
+
+/// Whether `arbitrator` was selected for a dispute that is still unresolved.
+pub fn has_open_case(arbitrator: Principal) -> bool {
+    DISPUTE_STORAGE.with(|disputes| {
+        disputes
+            .borrow()
+            .iter()
+            .any(|(_, dispute)| dispute.ruling.is_none() && dispute.arbitrator == Some(arbitrator))
+    })
+}
+
+/// Retrieve the dispute raised over a gig.
+#[ic_cdk::query]
+pub fn get_dispute(gig_id: u64) -> Option<Dispute> {
+    DISPUTE_STORAGE.with(|disputes| disputes.borrow().get(&gig_id))
+}
+
+/// Retrieve a gig's dispute with all of its evidence.
+#[ic_cdk::query]
+pub fn get_dispute_record(gig_id: u64) -> Option<DisputeRecord> {
+    get_dispute(gig_id).map(|dispute| DisputeRecord { dispute, evidence: get_evidence(gig_id) })
+}
+
+/// Retrieve the evidence submitted to a gig's dispute, in submission order.
+#[ic_cdk::query]
+pub fn get_evidence(gig_id: u64) -> Vec<Evidence> {
+    EVIDENCE_STORAGE.with(|storage| {
+        storage
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u64::MAX))
+            .map(|(_, evidence)| evidence)
+            .collect()
+    })
+}
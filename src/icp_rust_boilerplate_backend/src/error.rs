@@ -0,0 +1,57 @@
+use candid::Principal;
+use std::fmt::Debug;
+
+/// Errors returned by update calls.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum GigError {
+    NotFound { resource: String },                      // The referenced entity does not exist.
+    Unauthorized { reason: String },                    // The caller may not perform the action.
+    InvalidState { expected: String, actual: String },  // The entity's state forbids the action.
+    ValidationError { field: String, reason: String },  // An argument was rejected.
+    AlreadyExists { resource: String },                 // The entity would be a duplicate.
+    EscrowLocked,                                       // Locked escrow funds prevent the action.
+    LedgerError { message: String },                    // A call to a ledger canister failed.
+    Conflict { expected: u64, actual: u64 },            // The entity changed since it was read.
+    RateLimited { retry_after_secs: u64 },              // The caller sent too many requests.
+    ExchangeRateUnavailable { message: String },        // A token could not be priced in USD.
+    IdExhausted { resource: String },                   // No further ID can be handed out.
+    QuotaExceeded { resource: String, limit: u32 },     // The caller holds the most allowed.
+    Sharded { canister_id: Principal },                 // The call belongs on another canister.
+    ShardUnavailable { canister_id: Principal, message: String }, // A shard call failed.
+}
+
+impl GigError {
+    pub fn not_found(resource: &str) -> Self {
+        GigError::NotFound { resource: resource.to_string() }
+    }
+
+    pub fn unauthorized(reason: &str) -> Self {
+        GigError::Unauthorized { reason: reason.to_string() }
+    }
+
+    /// Build an `InvalidState` error from a description of the expected state and the
+    /// actual state, which is rendered with its `Debug` name.
+    pub fn invalid_state(expected: &str, actual: impl Debug) -> Self {
+        GigError::InvalidState {
+            expected: expected.to_string(),
+            actual: format!("{:?}", actual),
+        }
+    }
+
+    /// Build an `InvalidState` error from plain descriptions of the expected and actual
+    /// states, for states that have no `Debug` name of their own.
+    pub fn invalid_state_text(expected: &str, actual: &str) -> Self {
+        GigError::InvalidState { expected: expected.to_string(), actual: actual.to_string() }
+    }
+
+    pub fn validation(field: &str, reason: &str) -> Self {
+        GigError::ValidationError {
+            field: field.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn ledger(message: String) -> Self {
+        GigError::LedgerError { message }
+    }
+}
@@ -0,0 +1,407 @@
+use candid::{Decode, Encode, Nat, Principal};
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::ledger;
+use crate::log;
+use crate::rates::UsdRate;
+use crate::tokens::Token;
+use crate::treasury;
+use crate::{
+    ensure_controller, get_memory, GigStatus, Memory, ESCROW_MEMORY_ID, GIG_STORAGE,
+    REFUND_QUEUE_MEMORY_ID,
+};
+
+/// Wait before the first retry of a failed refund: ten minutes. Each further failure
+/// doubles it.
+const REFUND_RETRY_BASE_NANOS: u64 = 10 * 60 * 1_000_000_000;
+/// Longest wait between refund retries: one day.
+const REFUND_RETRY_MAX_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Funds locked in the canister on behalf of a gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Escrow {
+    pub gig_id: u64,
+    pub employer: Principal,
+    pub amount: u64,                    // Amount locked, in the token's base units.
+    pub status: EscrowStatus,
+    pub deposit_block: Nat,             // Ledger block of the employer's deposit.
+    pub release_block: Option<Nat>,     // Ledger block of the latest payout.
+    pub funded_at: u64,
+    pub released_at: Option<u64>,
+    pub released_amount: Option<u64>,   // Amount paid out so far, including ledger fees.
+    pub ledger: Option<Principal>,      // Ledger of the escrowed token; `None` is ICP.
+    pub fee: Option<u64>,               // Ledger fee at funding time; `None` is the ICP fee.
+    pub platform_fee: Option<u64>,      // Platform fees withheld from payouts so far.
+    pub usd_rate: Option<UsdRate>,      // USD price of the token when the escrow was funded.
+    pub frozen_at: Option<u64>,         // Set while a dispute holds the funds.
+}
+
+impl Escrow {
+    /// Amount still held by the canister.
+    pub fn remaining(&self) -> u64 {
+        self.amount - self.released_amount.unwrap_or(0)
+    }
+
+    /// Ledger holding the escrowed funds.
+    pub fn ledger(&self) -> Principal {
+        self.ledger.unwrap_or_else(ledger::icp_ledger)
+    }
+
+    /// Fee charged by the ledger on each payout.
+    pub fn fee(&self) -> u64 {
+        self.fee.unwrap_or(ledger::ICP_TRANSFER_FEE)
+    }
+}
+
+/// Enum representing the lifecycle of escrowed funds.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum EscrowStatus {
+    Locked,     // Funds are held by the canister.
+    Releasing,  // A payout is in flight.
+    Released,   // Funds have been paid out to the worker.
+    Refunded,   // Funds have been returned to the employer.
+}
+
+/// A refund whose ledger call failed, waiting to be retried.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct PendingRefund {
+    pub gig_id: u64,
+    pub attempts: u32,          // Failed attempts so far.
+    pub next_attempt_at: u64,
+    pub last_error: String,
+}
+
+impl Storable for Escrow {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Escrow {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for PendingRefund {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for PendingRefund {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Escrow records keyed by gig ID.
+    static ESCROW_STORAGE: RefCell<StableBTreeMap<u64, Escrow, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(ESCROW_MEMORY_ID)));
+
+    /// Failed refunds keyed by gig ID.
+    static REFUND_QUEUE: RefCell<StableBTreeMap<u64, PendingRefund, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(REFUND_QUEUE_MEMORY_ID)));
+}
+
+/// Pull `amount` of `token` from the employer into the canister. The employer must have
+/// approved the canister as a spender on the token's ledger beforehand.
+pub async fn deposit(token: &Token, employer: Principal, amount: u64) -> Result<Nat, GigError> {
+    // The payout has to cover the ledger fee, so tiny escrows are rejected up front.
+    if amount <= token.fee {
+        return Err(GigError::validation(
+            "escrow_amount",
+            &format!("Must exceed the ledger fee of {} {}", token.fee, token.symbol),
+        ));
+    }
+    ledger::transfer_from(token.ledger, employer, amount).await
+}
+
+/// Record a completed deposit against a gig, with the token's USD rate at funding time
+/// if one was available.
+pub fn record(
+    gig_id: u64,
+    employer: Principal,
+    token: &Token,
+    amount: u64,
+    deposit_block: Nat,
+    usd_rate: Option<UsdRate>,
+) {
+    let escrow = Escrow {
+        gig_id,
+        employer,
+        amount,
+        status: EscrowStatus::Locked,
+        deposit_block,
+        release_block: None,
+        funded_at: time(),
+        released_at: None,
+        released_amount: None,
+        ledger: Some(token.ledger),
+        fee: Some(token.fee),
+        platform_fee: None,
+        usd_rate,
+        frozen_at: None,
+    };
+    ESCROW_STORAGE.with(|storage| storage.borrow_mut().insert(gig_id, escrow));
+}
+
+/// Whether the gig has funds that have not been paid out yet.
+pub fn is_locked(gig_id: u64) -> bool {
+    ESCROW_STORAGE.with(|storage| {
+        storage
+            .borrow()
+            .get(&gig_id)
+            .is_some_and(|escrow| {
+                escrow.status == EscrowStatus::Locked || escrow.status == EscrowStatus::Releasing
+            })
+    })
+}
+
+/// Freeze or unfreeze a gig's escrow, returning whether anything changed. Only escrow
+/// that has not been paid out can be frozen.
+fn set_frozen(gig_id: u64, frozen: bool) -> bool {
+    ESCROW_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let Some(mut escrow) = storage.get(&gig_id) else {
+            return false;
+        };
+        let settled =
+            escrow.status == EscrowStatus::Released || escrow.status == EscrowStatus::Refunded;
+        if escrow.frozen_at.is_some() == frozen || (frozen && settled) {
+            return false;
+        }
+        escrow.frozen_at = frozen.then(time);
+        storage.insert(gig_id, escrow);
+        true
+    })
+}
+
+/// Freeze a gig's escrow while a dispute is open, so only a ruling can move it. Returns
+/// whether the escrow was frozen by this call. Fails while a payout is in flight, since the
+/// transfer would complete regardless of the freeze.
+pub fn freeze(gig_id: u64) -> Result<bool, GigError> {
+    if get_escrow(gig_id).is_some_and(|escrow| escrow.status == EscrowStatus::Releasing) {
+        return Err(GigError::invalid_state("Locked", EscrowStatus::Releasing));
+    }
+    Ok(set_frozen(gig_id, true))
+}
+
+/// Lift a dispute's freeze from a gig's escrow. Returns whether it was frozen.
+pub fn unfreeze(gig_id: u64) -> bool {
+    set_frozen(gig_id, false)
+}
+
+/// Pay the remaining escrowed funds of a gig out to the worker, minus the platform and
+/// ledger fees. Gigs without escrow are a no-op; frozen escrow cannot be released.
+pub async fn release(gig_id: u64, worker: Principal) -> Result<(), GigError> {
+    payout(gig_id, worker, None, EscrowStatus::Released, false).await
+}
+
+/// Pay part of the escrowed funds out to the worker, minus the platform and ledger fees,
+/// keeping the rest locked. Gigs without escrow are a no-op; frozen escrow cannot be
+/// released.
+pub async fn release_partial(gig_id: u64, worker: Principal, amount: u64) -> Result<(), GigError> {
+    payout(gig_id, worker, Some(amount), EscrowStatus::Released, false).await
+}
+
+/// Return the remaining escrowed funds of a gig to its employer, minus the ledger fee.
+/// Gigs without escrow are a no-op; frozen escrow cannot be refunded.
+pub async fn refund(gig_id: u64) -> Result<(), GigError> {
+    match get_escrow(gig_id) {
+        Some(escrow) => payout(gig_id, escrow.employer, None, EscrowStatus::Refunded, false).await,
+        None => Ok(()),
+    }
+}
+
+/// Pay `amount` (or everything still locked) of a disputed gig's escrow out to the worker
+/// as a ruling directs, even while it is frozen.
+pub async fn release_ruled(
+    gig_id: u64,
+    worker: Principal,
+    amount: Option<u64>,
+) -> Result<(), GigError> {
+    payout(gig_id, worker, amount, EscrowStatus::Released, true).await
+}
+
+/// Return what is left of a disputed gig's escrow to its employer as a ruling directs,
+/// even while it is frozen.
+pub async fn refund_ruled(gig_id: u64) -> Result<(), GigError> {
+    match get_escrow(gig_id) {
+        Some(escrow) => payout(gig_id, escrow.employer, None, EscrowStatus::Refunded, true).await,
+        None => Ok(()),
+    }
+}
+
+/// Refund a gig's escrow, queueing a retry with backoff if the ledger call fails rather
+/// than surfacing the error.
+pub async fn refund_or_queue(gig_id: u64) {
+    let result = refund(gig_id).await;
+    REFUND_QUEUE.with(|queue| {
+        let mut queue = queue.borrow_mut();
+        match result {
+            // Escrows settled some other way no longer need refunding either.
+            Err(error) if is_locked(gig_id) => {
+                let attempts = queue.get(&gig_id).map_or(0, |pending| pending.attempts) + 1;
+                let backoff = REFUND_RETRY_BASE_NANOS
+                    .saturating_mul(1 << (attempts - 1).min(16))
+                    .min(REFUND_RETRY_MAX_NANOS);
+                let pending = PendingRefund {
+                    gig_id,
+                    attempts,
+                    next_attempt_at: time().saturating_add(backoff),
+                    last_error: format!("{:?}", error),
+                };
+                log::warn(&format!(
+                    "Cannot refund gig {} (attempt {}): {:?}",
+                    gig_id, attempts, error
+                ));
+                queue.insert(gig_id, pending);
+            }
+            _ => {
+                queue.remove(&gig_id);
+            }
+        }
+    });
+}
+
+/// Retry every queued refund that is due while the escrow is still locked. Refunds of gigs
+/// that were reopened since with their escrow, for instance by pushing back an expired
+/// deadline, are dropped; leftovers of approved gigs and escrow of cancelled, expired,
+/// deleted or orphaned gigs keep being retried.
+pub fn retry_refunds() {
+    let now = time();
+    let due: Vec<u64> = REFUND_QUEUE.with(|queue| {
+        queue
+            .borrow()
+            .iter()
+            .filter(|(_, pending)| pending.next_attempt_at <= now)
+            .map(|(gig_id, _)| gig_id)
+            .collect()
+    });
+    for gig_id in due {
+        let reopened = GIG_STORAGE.with(|storage| {
+            storage.borrow().get(&gig_id).is_some_and(|gig| {
+                gig.payment.is_some()
+                    && matches!(
+                        gig.status,
+                        GigStatus::Open
+                            | GigStatus::PendingAcceptance
+                            | GigStatus::Assigned
+                            | GigStatus::Submitted
+                            | GigStatus::Disputed
+                    )
+            })
+        });
+        if reopened || !is_locked(gig_id) {
+            REFUND_QUEUE.with(|queue| queue.borrow_mut().remove(&gig_id));
+            continue;
+        }
+        ic_cdk::spawn(refund_or_queue(gig_id));
+    }
+}
+
+/// Retrieve the refunds waiting to be retried. Controller only.
+#[ic_cdk::query]
+pub fn get_pending_refunds() -> Result<Vec<PendingRefund>, GigError> {
+    ensure_controller()?;
+    Ok(REFUND_QUEUE.with(|queue| queue.borrow().iter().map(|(_, pending)| pending).collect()))
+}
+
+/// Transfer `amount` (or everything still locked) to `to`. Payouts to the worker withhold
+/// the platform fee for the treasury. Once nothing is left locked the escrow is settled
+/// with `settled`. Frozen escrow only moves for a `ruling`.
+async fn payout(
+    gig_id: u64,
+    to: Principal,
+    amount: Option<u64>,
+    settled: EscrowStatus,
+    ruling: bool,
+) -> Result<(), GigError> {
+    // Mark the escrow as releasing before awaiting so concurrent calls cannot pay twice.
+    let payout = ESCROW_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        match storage.get(&gig_id) {
+            Some(mut escrow) => {
+                if escrow.status != EscrowStatus::Locked {
+                    return Err(GigError::invalid_state("Locked", escrow.status));
+                }
+                if escrow.frozen_at.is_some() && !ruling {
+                    return Err(GigError::invalid_state_text("Not frozen", "Frozen by a dispute"));
+                }
+                let remaining = escrow.remaining();
+                let amount = amount.unwrap_or(remaining);
+                if amount > remaining {
+                    return Err(GigError::validation("amount", "Exceeds the remaining escrow"));
+                }
+                escrow.status = EscrowStatus::Releasing;
+                let platform_fee = match settled {
+                    EscrowStatus::Released => treasury::fee_for(amount),
+                    _ => 0,
+                };
+                let payout = (escrow.ledger(), escrow.fee(), amount, platform_fee);
+                storage.insert(gig_id, escrow);
+                Ok(Some(payout))
+            }
+            None => Ok(None),
+        }
+    })?;
+    let Some((ledger, fee, amount, platform_fee)) = payout else {
+        return Ok(());
+    };
+
+    // Amounts that cannot cover the ledger fee are settled without a transfer.
+    let net = amount - platform_fee;
+    let result = if net > fee {
+        ledger::transfer(ledger, to, net - fee).await.map(Some)
+    } else {
+        Ok(None)
+    };
+
+    ESCROW_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut escrow = storage.get(&gig_id).expect("Escrow disappeared during release");
+        match result {
+            Ok(block) => {
+                escrow.released_amount = Some(escrow.released_amount.unwrap_or(0) + amount);
+                if platform_fee > 0 {
+                    escrow.platform_fee = Some(escrow.platform_fee.unwrap_or(0) + platform_fee);
+                    treasury::accrue(ledger, fee, platform_fee);
+                }
+                escrow.status = if escrow.remaining() == 0 {
+                    settled
+                } else {
+                    EscrowStatus::Locked
+                };
+                if block.is_some() {
+                    escrow.release_block = block;
+                }
+                escrow.released_at = Some(time());
+                storage.insert(gig_id, escrow);
+                Ok(())
+            }
+            Err(e) => {
+                // Put the funds back under lock so the release can be retried.
+                escrow.status = EscrowStatus::Locked;
+                storage.insert(gig_id, escrow);
+                Err(e)
+            }
+        }
+    })
+}
+
+/// Retrieve the escrow record of a gig.
+#[ic_cdk::query]
+pub fn get_escrow(gig_id: u64) -> Option<Escrow> {
+    ESCROW_STORAGE.with(|storage| storage.borrow().get(&gig_id))
+}
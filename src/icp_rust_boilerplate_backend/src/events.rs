@@ -0,0 +1,210 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::audit::{self, AuditAction};
+use crate::id;
+use crate::notifications;
+use crate::teams;
+use crate::webhooks;
+use crate::{
+    get_memory, Gig, GigStatus, IdCell, Memory, EVENTS_MEMORY_ID, EVENT_SEQ_MEMORY_ID,
+    MAX_PAGE_SIZE,
+};
+
+/// Number of events kept before the oldest are dropped.
+const EVENT_CAPACITY: u64 = 10_000;
+/// Gig ID carried by platform events that concern no gig.
+pub const NO_GIG: u64 = u64::MAX;
+
+/// What happened to a gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum EventKind {
+    GigPosted { employer: Principal },
+    GigUpdated,                             // Details changed without a status change.
+    WorkerProposed { worker: Principal },   // Awaiting the worker's acceptance.
+    GigAssigned { worker: Principal },
+    WorkSubmitted,
+    RevisionRequested,                      // Submitted work was sent back to the worker.
+    GigApproved,
+    GigDisputed,
+    GigReopened,                            // Back to `Open` after a decline or a ruling.
+    GigExpired,
+    GigCancelled,
+    GigArchived,
+    GigRestored,
+    GigDeletionScheduled { purge_at: u64 }, // Undoable with `undo_delete` until `purge_at`.
+    GigDeleted,                             // Purged by an admin.
+    DeadlineReminder { hours_before: u32, employer: Principal, worker: Principal },
+    LowCycles { balance: u64, threshold: u64 }, // Sent to admins; the gig ID is `NO_GIG`.
+}
+
+/// A sequenced event in the feed.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub seq: u64,
+    pub gig_id: u64,
+    pub kind: EventKind,
+    pub timestamp: u64,
+}
+
+/// A batch of events and the cursor to continue from.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct EventPage {
+    pub events: Vec<Event>,
+    pub next_seq: u64,      // Pass as `since_seq` to fetch the following events.
+    pub oldest_seq: u64,    // Oldest event still buffered; anything older has been dropped.
+}
+
+impl Storable for Event {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Event {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Sequence number of the next event.
+    static EVENT_SEQ: RefCell<IdCell> = RefCell::new(
+        IdCell::init(get_memory(EVENT_SEQ_MEMORY_ID), 0)
+            .expect("Cannot create the event sequence counter")
+    );
+
+    /// Ring buffer of the most recent events keyed by sequence number.
+    static EVENTS: RefCell<StableBTreeMap<u64, Event, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(EVENTS_MEMORY_ID)));
+}
+
+/// Event describing the change from `previous` to `current`, if anything changed.
+fn describe(previous: Option<&Gig>, current: Option<&Gig>) -> Option<(u64, EventKind)> {
+    let (old, new) = match (previous, current) {
+        (None, Some(gig)) => {
+            return Some((gig.id, EventKind::GigPosted { employer: gig.employer }))
+        }
+        (Some(gig), None) => return Some((gig.id, EventKind::GigDeleted)),
+        (Some(old), Some(new)) => (old, new),
+        (None, None) => return None,
+    };
+    if old.status == new.status {
+        return (!audit::changed_fields(old, new).is_empty())
+            .then_some((new.id, EventKind::GigUpdated));
+    }
+    if matches!(old.status, GigStatus::Archived | GigStatus::PendingDeletion) {
+        return Some((new.id, EventKind::GigRestored));
+    }
+    if old.status == GigStatus::Submitted && new.status == GigStatus::Assigned {
+        return Some((new.id, EventKind::RevisionRequested));
+    }
+    let kind = match (&new.status, new.assigned_to) {
+        (GigStatus::PendingAcceptance, Some(worker)) => EventKind::WorkerProposed { worker },
+        (GigStatus::Assigned, Some(worker)) => EventKind::GigAssigned { worker },
+        (GigStatus::Submitted, _) => EventKind::WorkSubmitted,
+        (GigStatus::Approved, _) => EventKind::GigApproved,
+        (GigStatus::Disputed, _) => EventKind::GigDisputed,
+        (GigStatus::Open, _) => EventKind::GigReopened,
+        (GigStatus::Expired, _) => EventKind::GigExpired,
+        (GigStatus::Cancelled, _) => EventKind::GigCancelled,
+        (GigStatus::Archived, _) => EventKind::GigArchived,
+        (GigStatus::PendingDeletion, _) => {
+            EventKind::GigDeletionScheduled { purge_at: new.purge_at.unwrap_or_default() }
+        }
+        // Assignment states always carry a worker.
+        (GigStatus::PendingAcceptance | GigStatus::Assigned, None) => EventKind::GigUpdated,
+    };
+    Some((new.id, kind))
+}
+
+/// Principals a gig write affects: the employer and whoever was or is doing the work.
+fn parties(previous: Option<&Gig>, current: Option<&Gig>) -> Vec<Principal> {
+    let mut parties: Vec<Principal> = Vec::new();
+    for gig in [previous, current].into_iter().flatten() {
+        parties.push(gig.employer);
+        parties.extend(teams::workers(gig));
+    }
+    parties.sort();
+    parties.dedup();
+    parties
+}
+
+/// Append the event for a gig write to the feed, notify the parties and queue it for the
+/// employer's webhooks.
+pub fn emit(previous: Option<&Gig>, current: Option<&Gig>) {
+    if let Some((gig_id, kind)) = describe(previous, current) {
+        let event = push(gig_id, kind, &parties(previous, current));
+        if let Some(gig) = current.or(previous) {
+            if webhooks::enqueue(&event, gig.employer).is_err() {
+                audit::record_note(gig, AuditAction::WebhookSkipped);
+            }
+        }
+    }
+}
+
+/// Append an event about a gig to the feed, dropping the oldest event when full, and
+/// deliver it to the inboxes of `recipients`.
+pub fn push(gig_id: u64, kind: EventKind, recipients: &[Principal]) -> Event {
+    let seq = id::next(&EVENT_SEQ, "Event").expect("Cannot increment the event sequence counter");
+    EVENTS.with(|events| {
+        let mut events = events.borrow_mut();
+        let event = Event { seq, gig_id, kind, timestamp: time() };
+        notifications::deliver(&event, recipients);
+        events.insert(seq, event.clone());
+        if seq >= EVENT_CAPACITY {
+            events.remove(&(seq - EVENT_CAPACITY));
+        }
+        event
+    })
+}
+
+/// Whether an event moved a gig to a new status, rather than posting or editing it.
+pub fn is_status_change(kind: &EventKind) -> bool {
+    !matches!(
+        kind,
+        EventKind::GigPosted { .. }
+            | EventKind::GigUpdated
+            | EventKind::DeadlineReminder { .. }
+            | EventKind::LowCycles { .. }
+    )
+}
+
+/// Up to `limit` events accepted by `filter` starting at `since_seq`, oldest first,
+/// looking at no more than `scan` events. `next_seq` continues after the last event
+/// looked at, so sparse matches do not stall the cursor.
+pub fn page_matching(
+    since_seq: u64,
+    limit: u64,
+    scan: u64,
+    filter: impl Fn(&Event) -> bool,
+) -> EventPage {
+    EVENTS.with(|events| {
+        let events = events.borrow();
+        let mut page: Vec<Event> = Vec::new();
+        let mut next_seq = since_seq;
+        for (seq, event) in events.range(since_seq..).take(scan as usize) {
+            if page.len() as u64 >= limit.min(MAX_PAGE_SIZE) {
+                break;
+            }
+            next_seq = seq + 1;
+            if filter(&event) {
+                page.push(event);
+            }
+        }
+        let oldest_seq = events.iter().next().map_or(next_seq, |(seq, _)| seq);
+        EventPage { events: page, next_seq, oldest_seq }
+    })
+}
+
+/// Retrieve up to `limit` events starting at `since_seq`, oldest first. `limit` is capped
+/// at `MAX_PAGE_SIZE`.
+#[ic_cdk::query]
+pub fn get_events(since_seq: u64, limit: u64) -> EventPage {
+    page_matching(since_seq, limit, limit, |_| true)
+}
@@ -0,0 +1,169 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::delegates::{self, Permission};
+use crate::error::GigError;
+use crate::moderation::{self, not_banned};
+use crate::visibility;
+use crate::{
+    get_memory, insert_gig, Gig, GigStatus, Memory, EXTENSIONS_MEMORY_ID, GIG_STORAGE,
+};
+
+/// Longest reason accepted on an extension request, in bytes.
+const MAX_REASON_LEN: usize = 500;
+
+/// A worker's request to push back the deadline of an assigned gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct ExtensionRequest {
+    pub gig_id: u64,
+    pub number: u32,                // 1 for the first request on the gig.
+    pub worker: Principal,
+    pub previous_deadline: u64,
+    pub new_deadline: u64,
+    pub reason: String,
+    pub status: ExtensionStatus,
+    pub requested_at: u64,
+    pub responded_at: Option<u64>,
+}
+
+/// Enum representing the employer's answer to an extension request.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum ExtensionStatus {
+    Pending,    // Waiting for the employer's answer.
+    Accepted,   // The deadline was moved.
+    Declined,   // The deadline stands.
+}
+
+impl Storable for ExtensionRequest {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for ExtensionRequest {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Extension requests keyed by `(gig_id, number)` so a gig's history is contiguous.
+    static EXTENSIONS: RefCell<StableBTreeMap<(u64, u32), ExtensionRequest, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(EXTENSIONS_MEMORY_ID)));
+}
+
+/// Latest extension request on a gig.
+fn latest(gig_id: u64) -> Option<ExtensionRequest> {
+    EXTENSIONS.with(|extensions| {
+        extensions
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u32::MAX))
+            .map(|(_, request)| request)
+            .last()
+    })
+}
+
+/// Ask the employer to move the deadline of an assigned gig to `new_deadline`. Only one
+/// request can be pending at a time. Assigned worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn request_extension(
+    gig_id: u64,
+    new_deadline: u64,
+    reason: String,
+) -> Result<ExtensionRequest, GigError> {
+    let worker = caller();
+    moderation::ensure_not_banned(worker)?;
+    if reason.trim().is_empty() || reason.len() > MAX_REASON_LEN {
+        return Err(GigError::validation("reason", "Must be between 1 and 500 bytes"));
+    }
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the assigned worker can ask for more time.
+    if gig.assigned_to != Some(worker) {
+        return Err(GigError::unauthorized("Only the assigned worker can request an extension"));
+    }
+    if gig.status != GigStatus::Assigned {
+        return Err(GigError::invalid_state("Assigned", gig.status));
+    }
+    if new_deadline <= gig.deadline {
+        return Err(GigError::validation("new_deadline", "Must be after the current deadline"));
+    }
+    let previous = latest(gig_id);
+    if previous.as_ref().is_some_and(|request| request.status == ExtensionStatus::Pending) {
+        return Err(GigError::AlreadyExists { resource: "Extension request".to_string() });
+    }
+
+    let request = ExtensionRequest {
+        gig_id,
+        number: previous.map_or(1, |request| request.number + 1),
+        worker,
+        previous_deadline: gig.deadline,
+        new_deadline,
+        reason,
+        status: ExtensionStatus::Pending,
+        requested_at: time(),
+        responded_at: None,
+    };
+    EXTENSIONS.with(|extensions| {
+        extensions.borrow_mut().insert((gig_id, request.number), request.clone())
+    });
+    Ok(request)
+}
+
+/// Accept or decline the pending extension request on a gig. Accepting moves the deadline.
+/// Employer or their delegates only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn respond_extension(gig_id: u64, accept: bool) -> Result<Gig, GigError> {
+    let mut request = latest(gig_id)
+        .filter(|request| request.status == ExtensionStatus::Pending)
+        .ok_or_else(|| GigError::not_found("Pending extension request"))?;
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        // Ensure only the employer or a delegate can answer.
+        if !delegates::may_act(&gig, caller(), Permission::Post) {
+            return Err(GigError::unauthorized("Only the employer can answer this request"));
+        }
+        // The gig may have expired or been reassigned since the request was made.
+        if gig.status != GigStatus::Assigned || gig.assigned_to != Some(request.worker) {
+            return Err(GigError::invalid_state("Assigned", gig.status));
+        }
+        request.status = if accept { ExtensionStatus::Accepted } else { ExtensionStatus::Declined };
+        request.responded_at = Some(time());
+        if accept {
+            gig.deadline = request.new_deadline;
+            gig.updated_at = request.responded_at;
+            insert_gig(&mut storage, &mut gig);
+        }
+        EXTENSIONS.with(|extensions| {
+            extensions.borrow_mut().insert((gig_id, request.number), request)
+        });
+        Ok(gig)
+    })
+}
+
+/// Retrieve every extension request made on a gig, oldest first. Gigs the caller may not
+/// view have no requests.
+#[ic_cdk::query]
+pub fn get_extensions(gig_id: u64) -> Vec<ExtensionRequest> {
+    let viewable = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .is_some_and(|gig| visibility::can_view(&gig));
+    if !viewable {
+        return Vec::new();
+    }
+    EXTENSIONS.with(|extensions| {
+        extensions
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u32::MAX))
+            .map(|(_, request)| request)
+            .collect()
+    })
+}
@@ -0,0 +1,63 @@
+use candid::Nat;
+use ic_cdk::api::time;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{Cell, Memory as _};
+use std::cell::RefCell;
+
+use crate::{get_memory, Memory, GIG_STORAGE, LAST_UPGRADE_MEMORY_ID};
+
+/// Highest memory ID the memory manager hands out.
+const MAX_MEMORY_ID: u8 = 254;
+
+/// Stable memory pages, of 64 KiB each, used by one virtual memory region.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct MemoryRegion {
+    pub memory_id: u8,
+    pub pages: u64,
+}
+
+/// Operational snapshot of the canister for monitoring.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct Health {
+    pub cycles_balance: Nat,
+    pub stable_memory_pages: u64,       // Pages of the whole stable memory, regions included.
+    pub regions: Vec<MemoryRegion>,     // Regions that have grown, by memory ID.
+    pub gig_count: u64,
+    pub last_upgraded_at: Option<u64>,  // `None` until the canister is first upgraded.
+}
+
+thread_local! {
+    /// Time of the latest upgrade; zero before the first one.
+    static LAST_UPGRADE: RefCell<Cell<u64, Memory>> = RefCell::new(
+        Cell::init(get_memory(LAST_UPGRADE_MEMORY_ID), 0)
+            .expect("Cannot create the last upgrade cell")
+    );
+}
+
+/// Remember that the canister was upgraded just now.
+pub fn record_upgrade() {
+    LAST_UPGRADE
+        .with(|cell| cell.borrow_mut().set(time()))
+        .expect("Cannot record the upgrade time");
+}
+
+/// Retrieve the cycles balance, stable memory use per region, gig count and time of the
+/// last upgrade, so operators can alert before the canister runs out of cycles or memory.
+#[ic_cdk::query]
+pub fn health() -> Health {
+    let regions = (0..=MAX_MEMORY_ID)
+        .map(|memory_id| MemoryRegion {
+            memory_id,
+            pages: get_memory(MemoryId::new(memory_id)).size(),
+        })
+        .filter(|region| region.pages > 0)
+        .collect();
+    let last_upgraded_at = LAST_UPGRADE.with(|cell| *cell.borrow().get());
+    Health {
+        cycles_balance: Nat::from(ic_cdk::api::canister_balance128()),
+        stable_memory_pages: ic_cdk::api::stable::stable64_size(),
+        regions,
+        gig_count: GIG_STORAGE.with(|storage| storage.borrow().len()),
+        last_upgraded_at: (last_upgraded_at > 0).then_some(last_upgraded_at),
+    }
+}
@@ -0,0 +1,92 @@
+use serde::Serialize;
+
+use crate::indexes;
+use crate::{get_all_gigs, get_gig, GigStatus};
+
+/// Raw HTTP request forwarded by the HTTP gateway.
+#[derive(candid::CandidType, Deserialize)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Raw HTTP response returned to the HTTP gateway.
+#[derive(candid::CandidType, Serialize)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// JSON body of error responses.
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+/// Build a JSON response.
+fn json(status_code: u16, body: &impl Serialize) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+        body: serde_json::to_vec(body).expect("Cannot serialise the response body"),
+    }
+}
+
+/// Build a JSON error response.
+fn error(status_code: u16, message: &str) -> HttpResponse {
+    json(status_code, &ErrorBody { error: message })
+}
+
+/// Parse a status name from a query string, ignoring case and underscores.
+fn parse_status(name: &str) -> Option<GigStatus> {
+    let status = match name.to_lowercase().replace('_', "").as_str() {
+        "open" => GigStatus::Open,
+        "pendingacceptance" => GigStatus::PendingAcceptance,
+        "assigned" => GigStatus::Assigned,
+        "submitted" => GigStatus::Submitted,
+        "approved" => GigStatus::Approved,
+        "disputed" => GigStatus::Disputed,
+        "expired" => GigStatus::Expired,
+        "cancelled" => GigStatus::Cancelled,
+        "archived" => GigStatus::Archived,
+        "pendingdeletion" => GigStatus::PendingDeletion,
+        _ => return None,
+    };
+    Some(status)
+}
+
+/// Serve the read-only JSON API: `GET /gigs`, `GET /gigs?status=<status>` and
+/// `GET /gigs/<id>`.
+#[ic_cdk::query]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    if request.method != "GET" {
+        return error(405, "Only GET is supported");
+    }
+    let (path, query) = request.url.split_once('?').unwrap_or((&request.url, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    match segments.as_slice() {
+        ["gigs"] => {
+            let status = query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == "status")
+                .map(|(_, value)| value);
+            match status {
+                Some(name) => match parse_status(name) {
+                    Some(status) => json(200, &indexes::get_gigs_by_status(status)),
+                    None => error(400, "Unknown status"),
+                },
+                None => json(200, &get_all_gigs(None)),
+            }
+        }
+        ["gigs", id] => match id.parse::<u64>().ok().and_then(get_gig) {
+            Some(gig) => json(200, &gig),
+            None => error(404, "Gig not found"),
+        },
+        _ => error(404, "Not found"),
+    }
+}
@@ -0,0 +1,52 @@
+use std::cell::RefCell;
+use std::thread::LocalKey;
+
+use crate::error::GigError;
+use crate::sharding;
+use crate::{IdCell, ID_COUNTER};
+
+/// Error for a counter that cannot hand out another ID.
+fn exhausted(resource: &str) -> GigError {
+    GigError::IdExhausted { resource: resource.to_string() }
+}
+
+/// Take the next ID from `counter` and advance it, or fail without changing anything if
+/// the counter has run out or cannot be written. Hooks that run inside `insert_gig` have
+/// no error path; those that can skip their work, such as minting a certificate, note the
+/// skip in the audit log instead of trapping.
+pub fn next(counter: &'static LocalKey<RefCell<IdCell>>, resource: &str) -> Result<u64, GigError> {
+    counter.with(|counter| {
+        let id = *counter.borrow().get();
+        let next_id = id.checked_add(1).ok_or_else(|| exhausted(resource))?;
+        counter.borrow_mut().set(next_id).map_err(|_| exhausted(resource))?;
+        Ok(id)
+    })
+}
+
+/// ID the next gig posted here will get.
+pub fn peek_gig_id() -> u64 {
+    ID_COUNTER.with(|counter| *counter.borrow().get())
+}
+
+/// Take the next gig ID. IDs past the end of a sealed shard range belong to the next
+/// shard and are never handed out here.
+pub fn next_gig_id() -> Result<u64, GigError> {
+    if let Some(end_id) = sharding::end_id() {
+        if peek_gig_id() >= end_id {
+            return Err(exhausted("Gig"));
+        }
+    }
+    next(&ID_COUNTER, "Gig")
+}
+
+/// Start this canister's gig IDs at `first_id`, reserving every lower ID for the index
+/// and earlier shards. Only a canister that has not handed out a gig ID can be moved.
+pub fn reserve_gig_range(first_id: u64) -> Result<(), GigError> {
+    if peek_gig_id() != 0 {
+        return Err(GigError::AlreadyExists { resource: "Gig ID range".to_string() });
+    }
+    ID_COUNTER
+        .with(|counter| counter.borrow_mut().set(first_id))
+        .map(|_| ())
+        .map_err(|_| exhausted("Gig"))
+}
@@ -0,0 +1,86 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use ic_stable_structures::StableBTreeMap;
+use sha2::{Digest, Sha256};
+use std::{cell::RefCell, collections::BTreeSet};
+
+use crate::error::GigError;
+use crate::{get_memory, Gig, Memory, StorablePrincipal, GIG_STORAGE, IDEMPOTENCY_KEYS_MEMORY_ID};
+
+/// Longest idempotency key a client can send, in bytes.
+const MAX_KEY_LEN: usize = 64;
+/// Time a key keeps returning the gig it created: one day.
+const KEY_TTL_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// SHA-256 of a client's idempotency key, so keys of any length have a fixed-size entry.
+pub type KeyHash = [u8; 32];
+
+type KeyMap = StableBTreeMap<(StorablePrincipal, KeyHash), (u64, u64), Memory>;
+
+thread_local! {
+    /// `(gig_id, created_at)` of the gig each key created, keyed by `(employer, key hash)`.
+    static KEYS: RefCell<KeyMap> =
+        RefCell::new(StableBTreeMap::init(get_memory(IDEMPOTENCY_KEYS_MEMORY_ID)));
+
+    /// Keys of posts still awaiting their escrow deposit.
+    static IN_FLIGHT: RefCell<BTreeSet<(Principal, KeyHash)>> =
+        const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Check a client's idempotency key and hash it.
+pub fn hash(key: Option<&String>) -> Result<Option<KeyHash>, GigError> {
+    let Some(key) = key else {
+        return Ok(None);
+    };
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(GigError::validation("idempotency_key", "Must be between 1 and 64 bytes"));
+    }
+    Ok(Some(Sha256::digest(key.as_bytes()).into()))
+}
+
+/// The gig `employer` already created with this key within the last day, if any. Fails
+/// while another post with the key is still awaiting its deposit.
+pub fn replay(employer: Principal, key: KeyHash) -> Result<Option<Gig>, GigError> {
+    if IN_FLIGHT.with(|in_flight| in_flight.borrow().contains(&(employer, key))) {
+        return Err(GigError::invalid_state("No post in progress with this key", "In progress"));
+    }
+    let record = KEYS.with(|keys| keys.borrow().get(&(StorablePrincipal(employer), key)));
+    Ok(record
+        .filter(|(_, created_at)| time().saturating_sub(*created_at) < KEY_TTL_NANOS)
+        .and_then(|(gig_id, _)| GIG_STORAGE.with(|storage| storage.borrow().get(&gig_id))))
+}
+
+/// Claim a key for a post about to await its deposit. Fails if another call claimed or
+/// used the key since `replay` was checked.
+pub fn claim(employer: Principal, key: KeyHash) -> Result<(), GigError> {
+    if replay(employer, key)?.is_some() {
+        return Err(GigError::AlreadyExists { resource: "Gig for this idempotency key".into() });
+    }
+    IN_FLIGHT.with(|in_flight| in_flight.borrow_mut().insert((employer, key)));
+    Ok(())
+}
+
+/// Release a claimed key, recording the gig it created if the post went through.
+pub fn release(employer: Principal, key: KeyHash, gig_id: Option<u64>) {
+    IN_FLIGHT.with(|in_flight| in_flight.borrow_mut().remove(&(employer, key)));
+    if let Some(gig_id) = gig_id {
+        let entry = (StorablePrincipal(employer), key);
+        KEYS.with(|keys| keys.borrow_mut().insert(entry, (gig_id, time())));
+    }
+}
+
+/// Forget keys older than a day.
+pub fn prune() {
+    let now = time();
+    KEYS.with(|keys| {
+        let mut keys = keys.borrow_mut();
+        let expired: Vec<(StorablePrincipal, KeyHash)> = keys
+            .iter()
+            .filter(|(_, (_, created_at))| now.saturating_sub(*created_at) >= KEY_TTL_NANOS)
+            .map(|(entry, _)| entry)
+            .collect();
+        for entry in expired {
+            keys.remove(&entry);
+        }
+    });
+}
@@ -0,0 +1,490 @@
+use candid::Principal;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::thread::LocalKey;
+
+use crate::search;
+use crate::tags::{self, Tag};
+use crate::visibility::{self, Visibility};
+use crate::ledger;
+use crate::{
+    get_memory, Gig, GigStatus, GigSummary, Memory, StorablePrincipal, BUDGET_INDEX_MEMORY_ID,
+    DEADLINE_BUCKET_INDEX_MEMORY_ID, EMPLOYER_INDEX_MEMORY_ID, GIG_STORAGE, MAX_PAGE_SIZE,
+    ORG_INDEX_MEMORY_ID, SKILL_INDEX_MEMORY_ID, SORT_BUDGET_MEMORY_ID,
+    SORT_CREATED_MEMORY_ID, SORT_DEADLINE_MEMORY_ID, SORT_UPDATED_MEMORY_ID,
+    STATUS_INDEX_MEMORY_ID, TAG_INDEX_MEMORY_ID, TITLE_INDEX_MEMORY_ID, UNLISTED_INDEX_MEMORY_ID,
+    WORKER_INDEX_MEMORY_ID, WORD_INDEX_MEMORY_ID,
+};
+
+/// Index of gig IDs grouped by principal.
+type PrincipalIndex = StableBTreeMap<(StorablePrincipal, u64), (), Memory>;
+/// Index of gig IDs keyed by `((budget ledger, budget amount), gig_id)`.
+type BudgetIndex = StableBTreeMap<((StorablePrincipal, u64), u64), (), Memory>;
+/// Index of gig IDs keyed by `(sort key, gig_id)`.
+type SortIndex = StableBTreeMap<(u64, u64), (), Memory>;
+/// A sort index paired with the function computing a gig's sort key.
+type SortIndexKey = (&'static LocalKey<RefCell<SortIndex>>, fn(&Gig) -> u64);
+
+/// Nanoseconds in a deadline bucket: one day.
+const DEADLINE_BUCKET_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Bytes of a normalised title kept in the title index; longer titles are cut.
+const TITLE_KEY_LEN: usize = 64;
+
+/// Start of a normalised title usable as a stable map key.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TitleKey(pub String);
+
+impl TitleKey {
+    /// Trimmed, lower-cased and cut to `TITLE_KEY_LEN` bytes on a character boundary.
+    pub fn new(text: &str) -> Self {
+        let mut key = text.trim().to_lowercase();
+        let mut end = key.len().min(TITLE_KEY_LEN);
+        while !key.is_char_boundary(end) {
+            end -= 1;
+        }
+        key.truncate(end);
+        TitleKey(key)
+    }
+}
+
+impl Storable for TitleKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        TitleKey(String::from_utf8(bytes.into_owned()).unwrap())
+    }
+}
+
+impl BoundedStorable for TitleKey {
+    const MAX_SIZE: u32 = TITLE_KEY_LEN as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Orders the board can be listed in.
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+pub enum SortBy {
+    CreatedAtDesc, // Newest first.
+    DeadlineAsc,   // Soonest deadline first.
+    BudgetDesc,    // Largest budget amount first, whatever the token.
+    UpdatedAt,     // Most recently changed first; unchanged gigs count from posting.
+}
+
+thread_local! {
+    /// Gig IDs keyed by `(status, gig_id)`.
+    static STATUS_INDEX: RefCell<StableBTreeMap<(u8, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(STATUS_INDEX_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(employer, gig_id)`.
+    static EMPLOYER_INDEX: RefCell<PrincipalIndex> =
+        RefCell::new(StableBTreeMap::init(get_memory(EMPLOYER_INDEX_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(assigned worker, gig_id)`.
+    static WORKER_INDEX: RefCell<PrincipalIndex> =
+        RefCell::new(StableBTreeMap::init(get_memory(WORKER_INDEX_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(org_id, gig_id)`.
+    static ORG_INDEX: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(ORG_INDEX_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(tag, gig_id)`.
+    static TAG_INDEX: RefCell<StableBTreeMap<(Tag, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(TAG_INDEX_MEMORY_ID)));
+
+    /// Inverted index of title, description and tag words, keyed by `(term, gig_id)`, holding
+    /// the term's weight in the gig.
+    static WORD_INDEX: RefCell<StableBTreeMap<(Tag, u64), u32, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(WORD_INDEX_MEMORY_ID)));
+
+    /// Gig IDs ordered by budget within each token.
+    static BUDGET_INDEX: RefCell<BudgetIndex> =
+        RefCell::new(StableBTreeMap::init(get_memory(BUDGET_INDEX_MEMORY_ID)));
+
+    /// IDs of gigs kept off the public board by their visibility.
+    static UNLISTED_INDEX: RefCell<StableBTreeMap<u64, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(UNLISTED_INDEX_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(u64::MAX - created_at, gig_id)`, newest first.
+    static SORT_CREATED: RefCell<SortIndex> =
+        RefCell::new(StableBTreeMap::init(get_memory(SORT_CREATED_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(deadline, gig_id)`, soonest first.
+    static SORT_DEADLINE: RefCell<SortIndex> =
+        RefCell::new(StableBTreeMap::init(get_memory(SORT_DEADLINE_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(u64::MAX - budget amount, gig_id)`, largest first.
+    static SORT_BUDGET: RefCell<SortIndex> =
+        RefCell::new(StableBTreeMap::init(get_memory(SORT_BUDGET_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(u64::MAX - last update, gig_id)`, most recent first.
+    static SORT_UPDATED: RefCell<SortIndex> =
+        RefCell::new(StableBTreeMap::init(get_memory(SORT_UPDATED_MEMORY_ID)));
+
+    /// IDs of gigs that can still miss their deadline, keyed by `(deadline day, gig_id)`.
+    static DEADLINE_BUCKET_INDEX: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(DEADLINE_BUCKET_INDEX_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(required skill, gig_id)`.
+    static SKILL_INDEX: RefCell<StableBTreeMap<(Tag, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(SKILL_INDEX_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(normalised title, gig_id)`, in title order.
+    static TITLE_INDEX: RefCell<StableBTreeMap<(TitleKey, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(TITLE_INDEX_MEMORY_ID)));
+}
+
+/// Stable index key of a status. Values must never be reused once assigned.
+pub fn status_key(status: &GigStatus) -> u8 {
+    match status {
+        GigStatus::Open => 0,
+        GigStatus::Assigned => 1,
+        GigStatus::Submitted => 2,
+        GigStatus::Approved => 3,
+        GigStatus::Disputed => 4,
+        GigStatus::Expired => 5,
+        GigStatus::Cancelled => 6,
+        GigStatus::PendingAcceptance => 7,
+        GigStatus::Archived => 8,
+        GigStatus::PendingDeletion => 9,
+    }
+}
+
+/// Whether a gig's deadline still matters: it has not been delivered or closed.
+fn can_miss_deadline(gig: &Gig) -> bool {
+    matches!(gig.status, GigStatus::Open | GigStatus::PendingAcceptance | GigStatus::Assigned)
+}
+
+/// Move a gig's index entries from its `previous` to its `current` version.
+/// Pass `None` for `previous` on insert and for `current` on removal.
+pub fn reindex(previous: Option<&Gig>, current: Option<&Gig>) {
+    reindex_terms(previous, current);
+    for (index, key) in SORT_INDEXES {
+        if let Some(gig) = previous {
+            index.with(|index| index.borrow_mut().remove(&(key(gig), gig.id)));
+        }
+        if let Some(gig) = current {
+            index.with(|index| index.borrow_mut().insert((key(gig), gig.id), ()));
+        }
+    }
+    if let Some(gig) = previous {
+        STATUS_INDEX.with(|index| index.borrow_mut().remove(&(status_key(&gig.status), gig.id)));
+        EMPLOYER_INDEX
+            .with(|index| index.borrow_mut().remove(&(StorablePrincipal(gig.employer), gig.id)));
+        if let Some(worker) = gig.assigned_to {
+            WORKER_INDEX
+                .with(|index| index.borrow_mut().remove(&(StorablePrincipal(worker), gig.id)));
+        }
+        if let Some(org_id) = gig.org_id {
+            ORG_INDEX.with(|index| index.borrow_mut().remove(&(org_id, gig.id)));
+        }
+        for tag in gig.tags.iter().flatten() {
+            TAG_INDEX.with(|index| index.borrow_mut().remove(&(Tag(tag.clone()), gig.id)));
+        }
+        for skill in gig.required_skills.iter().flatten() {
+            SKILL_INDEX.with(|index| index.borrow_mut().remove(&(Tag(skill.clone()), gig.id)));
+        }
+        if let Some(budget) = &gig.budget {
+            let key = ((StorablePrincipal(budget.ledger), budget.amount), gig.id);
+            BUDGET_INDEX.with(|index| index.borrow_mut().remove(&key));
+        }
+        UNLISTED_INDEX.with(|index| index.borrow_mut().remove(&gig.id));
+        TITLE_INDEX.with(|index| index.borrow_mut().remove(&(TitleKey::new(&gig.title), gig.id)));
+        let key = (gig.deadline / DEADLINE_BUCKET_NANOS, gig.id);
+        DEADLINE_BUCKET_INDEX.with(|index| index.borrow_mut().remove(&key));
+    }
+    if let Some(gig) = current {
+        STATUS_INDEX.with(|index| index.borrow_mut().insert((status_key(&gig.status), gig.id), ()));
+        EMPLOYER_INDEX
+            .with(|index| index.borrow_mut().insert((StorablePrincipal(gig.employer), gig.id), ()));
+        if let Some(worker) = gig.assigned_to {
+            WORKER_INDEX
+                .with(|index| index.borrow_mut().insert((StorablePrincipal(worker), gig.id), ()));
+        }
+        if let Some(org_id) = gig.org_id {
+            ORG_INDEX.with(|index| index.borrow_mut().insert((org_id, gig.id), ()));
+        }
+        for tag in gig.tags.iter().flatten() {
+            TAG_INDEX.with(|index| index.borrow_mut().insert((Tag(tag.clone()), gig.id), ()));
+        }
+        for skill in gig.required_skills.iter().flatten() {
+            SKILL_INDEX.with(|index| index.borrow_mut().insert((Tag(skill.clone()), gig.id), ()));
+        }
+        if let Some(budget) = &gig.budget {
+            let key = ((StorablePrincipal(budget.ledger), budget.amount), gig.id);
+            BUDGET_INDEX.with(|index| index.borrow_mut().insert(key, ()));
+        }
+        if gig.visibility() != Visibility::Public {
+            UNLISTED_INDEX.with(|index| index.borrow_mut().insert(gig.id, ()));
+        }
+        TITLE_INDEX
+            .with(|index| index.borrow_mut().insert((TitleKey::new(&gig.title), gig.id), ()));
+        if can_miss_deadline(gig) {
+            let key = (gig.deadline / DEADLINE_BUCKET_NANOS, gig.id);
+            DEADLINE_BUCKET_INDEX.with(|index| index.borrow_mut().insert(key, ()));
+        }
+    }
+}
+
+/// Sort indexes paired with the key each orders gigs by. Descending orders store the
+/// complement of the value, since the maps only iterate in ascending key order.
+const SORT_INDEXES: [SortIndexKey; 4] = [
+    (&SORT_CREATED, |gig| u64::MAX - gig.created_at),
+    (&SORT_DEADLINE, |gig| gig.deadline),
+    (&SORT_BUDGET, |gig| u64::MAX - gig.budget.as_ref().map_or(0, |budget| budget.amount)),
+    (&SORT_UPDATED, |gig| u64::MAX - gig.updated_at.unwrap_or(gig.created_at)),
+];
+
+/// Walk gig IDs in the given order, or by ID when `sort_by` is `None`, until `visit`
+/// returns `false`.
+pub fn visit_sorted(sort_by: Option<SortBy>, mut visit: impl FnMut(u64) -> bool) {
+    let index = match sort_by {
+        None => {
+            return GIG_STORAGE.with(|storage| {
+                for (id, _) in storage.borrow().iter() {
+                    if !visit(id) {
+                        break;
+                    }
+                }
+            });
+        }
+        Some(SortBy::CreatedAtDesc) => &SORT_CREATED,
+        Some(SortBy::DeadlineAsc) => &SORT_DEADLINE,
+        Some(SortBy::BudgetDesc) => &SORT_BUDGET,
+        Some(SortBy::UpdatedAt) => &SORT_UPDATED,
+    };
+    index.with(|index| {
+        for ((_, id), _) in index.borrow().iter() {
+            if !visit(id) {
+                break;
+            }
+        }
+    });
+}
+
+/// Update the word index with only the terms and weights that changed, since most writes
+/// leave the title, description and tags untouched.
+fn reindex_terms(previous: Option<&Gig>, current: Option<&Gig>) {
+    let Some(id) = current.or(previous).map(|gig| gig.id) else {
+        return;
+    };
+    let old = previous.map(search::term_weights).unwrap_or_default();
+    let new = current.map(search::term_weights).unwrap_or_default();
+    WORD_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        for term in old.keys().filter(|term| !new.contains_key(*term)) {
+            index.remove(&(Tag(term.clone()), id));
+        }
+        for (term, weight) in new.iter().filter(|(term, weight)| old.get(*term) != Some(weight)) {
+            index.insert((Tag(term.clone()), id), *weight);
+        }
+    });
+}
+
+/// IDs of all gigs containing a search term with the term's weight in each, in ascending
+/// ID order.
+pub fn ids_with_term(term: &str) -> Vec<(u64, u32)> {
+    let term = term.to_string();
+    WORD_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((Tag(term.clone()), 0)..=(Tag(term), u64::MAX))
+            .map(|((_, id), weight)| (id, weight))
+            .collect()
+    })
+}
+
+/// Walk the IDs of gigs whose normalised title starts with `prefix`, in title order, until
+/// `visit` returns `false`.
+pub fn visit_title_prefix(prefix: &TitleKey, mut visit: impl FnMut(&TitleKey, u64) -> bool) {
+    TITLE_INDEX.with(|index| {
+        for ((title, id), _) in index.borrow().range((prefix.clone(), 0)..) {
+            if !title.0.starts_with(&prefix.0) || !visit(&title, id) {
+                break;
+            }
+        }
+    });
+}
+
+/// IDs of all gigs requiring a skill, in ascending order.
+pub fn ids_with_skill(skill: &str) -> Vec<u64> {
+    let skill = skill.to_string();
+    SKILL_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((Tag(skill.clone()), 0)..=(Tag(skill), u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    })
+}
+
+/// IDs of open, pending and assigned gigs due by the end of the day containing `time`.
+/// Callers compare exact deadlines, since gigs due later that day are included.
+pub fn ids_due_by(time: u64) -> Vec<u64> {
+    let bucket = time / DEADLINE_BUCKET_NANOS;
+    DEADLINE_BUCKET_INDEX.with(|index| {
+        index
+            .borrow()
+            .range(..=(bucket, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    })
+}
+
+/// Whether a gig is kept off the public board by its visibility.
+pub fn is_unlisted(gig_id: u64) -> bool {
+    UNLISTED_INDEX.with(|index| index.borrow().contains_key(&gig_id))
+}
+
+/// Number of gigs kept off the public board by their visibility.
+pub fn unlisted_count() -> u64 {
+    UNLISTED_INDEX.with(|index| index.borrow().len())
+}
+
+/// Weight of a search term in a gig, or `None` if the gig does not contain it.
+pub fn term_weight(term: &str, gig_id: u64) -> Option<u32> {
+    WORD_INDEX.with(|index| index.borrow().get(&(Tag(term.to_string()), gig_id)))
+}
+
+/// Resolve indexed gig IDs to summaries of the gigs the caller may list.
+fn load(ids: Vec<u64>) -> Vec<GigSummary> {
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        ids.into_iter()
+            .filter_map(|id| storage.get(&id))
+            .filter(visibility::can_list)
+            .map(|gig| GigSummary::from(&gig))
+            .collect()
+    })
+}
+
+/// Gig IDs under `principal` in a principal-keyed index.
+fn ids_for(index: &'static LocalKey<RefCell<PrincipalIndex>>, principal: Principal) -> Vec<u64> {
+    let key = StorablePrincipal(principal);
+    index.with(|index| {
+        index
+            .borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    })
+}
+
+/// IDs of all gigs with the given status.
+pub fn ids_with_status(status: &GigStatus) -> Vec<u64> {
+    let key = status_key(status);
+    STATUS_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    })
+}
+
+/// Number of gigs posted by `employer` that are still open or awaiting a worker's
+/// acceptance.
+pub fn open_gig_count(employer: Principal) -> u64 {
+    let ids = ids_for(&EMPLOYER_INDEX, employer);
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        ids.into_iter()
+            .filter_map(|id| storage.get(&id))
+            .filter(|gig| matches!(gig.status, GigStatus::Open | GigStatus::PendingAcceptance))
+            .count() as u64
+    })
+}
+
+/// Retrieve all gigs with the given status.
+#[ic_cdk::query]
+pub fn get_gigs_by_status(status: GigStatus) -> Vec<GigSummary> {
+    load(ids_with_status(&status))
+}
+
+/// Retrieve all gigs posted by an employer.
+#[ic_cdk::query]
+pub fn get_gigs_by_employer(employer: Principal) -> Vec<GigSummary> {
+    load(ids_for(&EMPLOYER_INDEX, employer))
+}
+
+/// Retrieve all gigs posted under an organization.
+#[ic_cdk::query]
+pub fn get_gigs_by_org(org_id: u64) -> Vec<GigSummary> {
+    let ids = ORG_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((org_id, 0)..=(org_id, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    load(ids)
+}
+
+/// Retrieve all gigs assigned to a worker.
+#[ic_cdk::query]
+pub fn get_gigs_assigned_to(worker: Principal) -> Vec<GigSummary> {
+    load(ids_for(&WORKER_INDEX, worker))
+}
+
+/// Retrieve all gigs carrying a tag. Matching ignores case and surrounding whitespace.
+#[ic_cdk::query]
+pub fn get_gigs_by_tag(tag: String) -> Vec<GigSummary> {
+    let tag = tags::normalize(&tag);
+    let ids = TAG_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((Tag(tag.clone()), 0)..=(Tag(tag), u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    load(ids)
+}
+
+/// Retrieve open gigs whose budget in `token` (ICP by default) lies within `min..=max`,
+/// cheapest first.
+#[ic_cdk::query]
+pub fn get_open_gigs_by_budget_range(
+    min: u64,
+    max: u64,
+    token: Option<Principal>,
+) -> Vec<GigSummary> {
+    if min > max {
+        return Vec::new();
+    }
+    let ledger = StorablePrincipal(token.unwrap_or_else(ledger::icp_ledger));
+    let ids = BUDGET_INDEX.with(|index| {
+        index
+            .borrow()
+            .range(((ledger, min), 0)..=((ledger, max), u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    load(ids)
+        .into_iter()
+        .filter(|gig| gig.status == GigStatus::Open)
+        .collect()
+}
+
+/// Retrieve gigs whose deadline lies within `from_ts..=to_ts`, soonest first, at most
+/// `MAX_PAGE_SIZE`. Walks the deadline index from `from_ts`, so gigs outside the window are
+/// never loaded.
+#[ic_cdk::query]
+pub fn get_gigs_with_deadline_between(from_ts: u64, to_ts: u64) -> Vec<GigSummary> {
+    if from_ts > to_ts {
+        return Vec::new();
+    }
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        SORT_DEADLINE.with(|index| {
+            index
+                .borrow()
+                .range((from_ts, 0)..=(to_ts, u64::MAX))
+                .filter_map(|((_, id), _)| storage.get(&id))
+                .filter(visibility::can_list)
+                .take(MAX_PAGE_SIZE as usize)
+                .map(|gig| GigSummary::from(&gig))
+                .collect()
+        })
+    })
+}
@@ -0,0 +1,188 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::delegates::{self, Permission};
+use crate::dependencies;
+use crate::error::GigError;
+use crate::moderation::{self, not_banned};
+use crate::profiles;
+use crate::roles;
+use crate::state_machine;
+use crate::{
+    get_memory, insert_gig, Gig, GigStatus, Memory, StorablePrincipal, GIG_STORAGE,
+    INVITATIONS_MEMORY_ID,
+};
+
+/// Longest note accepted on an invitation, in bytes.
+const MAX_NOTE_LEN: usize = 500;
+
+/// An employer's private offer of a gig to a specific worker.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Invitation {
+    pub gig_id: u64,
+    pub worker: Principal,
+    pub employer: Principal,
+    pub note: String,                   // Personal message from the employer.
+    pub status: InvitationStatus,
+    pub invited_at: u64,
+    pub responded_at: Option<u64>,
+}
+
+/// Enum representing possible statuses of an invitation.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum InvitationStatus {
+    Pending,    // Waiting for the worker's answer.
+    Accepted,   // The worker took the gig.
+    Declined,   // The worker turned the gig down.
+}
+
+impl Storable for Invitation {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Invitation {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Invitations keyed by `(worker, gig_id)` so a worker's invitations are contiguous.
+    static INVITATIONS: RefCell<StableBTreeMap<(StorablePrincipal, u64), Invitation, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(INVITATIONS_MEMORY_ID)));
+}
+
+/// Look up the invitation of `worker` to a gig.
+fn get_invitation(gig_id: u64, worker: Principal) -> Option<Invitation> {
+    INVITATIONS.with(|invitations| invitations.borrow().get(&(StorablePrincipal(worker), gig_id)))
+}
+
+/// Whether `worker` holds an invitation to a gig, whatever its status.
+pub fn is_invited(gig_id: u64, worker: Principal) -> bool {
+    get_invitation(gig_id, worker).is_some()
+}
+
+/// Invite a registered worker to an open gig with a personal note. Employer or their
+/// delegates only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn invite_worker(gig_id: u64, worker: Principal, note: String) -> Result<Invitation, GigError> {
+    if note.len() > MAX_NOTE_LEN {
+        return Err(GigError::validation("note", "Must be at most 500 bytes"));
+    }
+    if !profiles::is_registered(worker) {
+        return Err(GigError::validation("worker", "Worker has not registered a profile"));
+    }
+    if moderation::is_banned(worker) {
+        return Err(GigError::validation("worker", "Worker is banned"));
+    }
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer or a delegate can invite workers.
+    if !delegates::may_act(&gig, caller(), Permission::Assign) {
+        return Err(GigError::unauthorized("Only the employer can invite workers to this gig"));
+    }
+    let employer = gig.employer;
+    if worker == employer {
+        return Err(GigError::validation("worker", "Employers cannot invite themselves"));
+    }
+    if gig.status != GigStatus::Open {
+        return Err(GigError::invalid_state("Open", gig.status));
+    }
+    if is_invited(gig_id, worker) {
+        return Err(GigError::AlreadyExists { resource: "Invitation".to_string() });
+    }
+
+    let invitation = Invitation {
+        gig_id,
+        worker,
+        employer,
+        note,
+        status: InvitationStatus::Pending,
+        invited_at: time(),
+        responded_at: None,
+    };
+    INVITATIONS.with(|invitations| {
+        invitations
+            .borrow_mut()
+            .insert((StorablePrincipal(worker), gig_id), invitation.clone())
+    });
+    Ok(invitation)
+}
+
+/// Accept an invitation, which assigns the gig to the caller. Invited worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn accept_invitation(gig_id: u64) -> Result<Gig, GigError> {
+    let worker = caller();
+    moderation::ensure_not_banned(worker)?;
+    let mut invitation = pending_invitation(gig_id, worker)?;
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        // The gig may have been staffed or withdrawn since the invitation was sent.
+        if gig.status != GigStatus::Open {
+            return Err(GigError::invalid_state("Open", gig.status));
+        }
+        dependencies::ensure_met(&storage, &gig)?;
+        state_machine::transition(&mut gig, GigStatus::Assigned)?;
+        gig.assigned_to = Some(worker);
+        gig.updated_at = Some(time());
+        insert_gig(&mut storage, &mut gig);
+
+        invitation.status = InvitationStatus::Accepted;
+        invitation.responded_at = gig.updated_at;
+        save(invitation);
+        Ok(gig)
+    })
+}
+
+/// Decline an invitation. Invited worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn decline_invitation(gig_id: u64) -> Result<Invitation, GigError> {
+    let mut invitation = pending_invitation(gig_id, caller())?;
+    invitation.status = InvitationStatus::Declined;
+    invitation.responded_at = Some(time());
+    save(invitation.clone());
+    Ok(invitation)
+}
+
+/// Look up the caller's invitation to a gig and ensure it is still awaiting an answer.
+fn pending_invitation(gig_id: u64, worker: Principal) -> Result<Invitation, GigError> {
+    let invitation =
+        get_invitation(gig_id, worker).ok_or_else(|| GigError::not_found("Invitation"))?;
+    if invitation.status != InvitationStatus::Pending {
+        return Err(GigError::invalid_state("Pending", invitation.status));
+    }
+    Ok(invitation)
+}
+
+/// Store an invitation under its worker and gig.
+fn save(invitation: Invitation) {
+    let key = (StorablePrincipal(invitation.worker), invitation.gig_id);
+    INVITATIONS.with(|invitations| invitations.borrow_mut().insert(key, invitation));
+}
+
+/// Retrieve every invitation sent to a worker. Invitations are private, so only the
+/// worker and moderators can list them.
+#[ic_cdk::query]
+pub fn list_invitations(worker: Principal) -> Result<Vec<Invitation>, GigError> {
+    if caller() != worker {
+        roles::ensure_moderator()?;
+    }
+    let key = StorablePrincipal(worker);
+    Ok(INVITATIONS.with(|invitations| {
+        invitations
+            .borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|(_, invitation)| invitation)
+            .collect()
+    }))
+}
@@ -0,0 +1,274 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell, collections::BTreeMap};
+
+use crate::ledger;
+use crate::profiles;
+use crate::state_machine;
+use crate::teams;
+use crate::{
+    get_memory, Gig, GigStatus, Memory, StorablePrincipal, COMPLETIONS_MEMORY_ID,
+    MAX_PAGE_SIZE, WORKER_STATS_MEMORY_ID,
+};
+
+/// Nanoseconds in a day.
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Completed gigs needed for the `TenGigs` badge.
+const TEN_GIGS: u64 = 10;
+/// Completed gigs needed before a spotless record earns the `ZeroDisputes` badge.
+const ZERO_DISPUTES_MIN_GIGS: u64 = 5;
+
+/// Window a leaderboard covers, ending now.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub enum LeaderboardPeriod {
+    Week,
+    Month,      // The last 30 days.
+    AllTime,
+}
+
+/// What a leaderboard ranks workers by.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub enum LeaderboardMetric {
+    CompletedGigs,
+    OnTimeRate,     // Share of completed gigs submitted by the deadline.
+    Earnings,
+}
+
+/// Achievement awarded automatically and shown on the worker's profile.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum Badge {
+    FirstGig,       // Completed a first gig.
+    TenGigs,        // Completed ten gigs.
+    ZeroDisputes,   // Completed five gigs without ever being in a dispute; lost on a dispute.
+}
+
+/// A worker's running totals.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+pub struct WorkerStats {
+    pub completed_gigs: u64,
+    pub on_time_gigs: u64,
+    pub earnings: u64,          // ICP paid for completed gigs, in e8s; other tokens are left out.
+    pub disputes: u64,          // Disputes over gigs the worker was doing.
+}
+
+/// One worker's share of a completed gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct WorkerCompletion {
+    worker: Principal,
+    on_time: bool,
+    earned: u64,
+}
+
+/// The workers who completed a gig, stored under its completion time.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Completion {
+    workers: Vec<WorkerCompletion>,
+}
+
+/// A ranked worker on a leaderboard.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub rank: u32,
+    pub worker: Principal,
+    pub completed_gigs: u64,
+    pub on_time_percent: u8,
+    pub earnings: u64,
+}
+
+impl WorkerStats {
+    /// Share of completed gigs submitted on time, as a percentage.
+    fn on_time_percent(&self) -> u8 {
+        match self.completed_gigs {
+            0 => 0,
+            completed => (self.on_time_gigs * 100 / completed) as u8,
+        }
+    }
+
+    /// Badges these totals earn.
+    fn badges(&self) -> Vec<Badge> {
+        let mut badges = Vec::new();
+        if self.completed_gigs >= 1 {
+            badges.push(Badge::FirstGig);
+        }
+        if self.completed_gigs >= TEN_GIGS {
+            badges.push(Badge::TenGigs);
+        }
+        if self.completed_gigs >= ZERO_DISPUTES_MIN_GIGS && self.disputes == 0 {
+            badges.push(Badge::ZeroDisputes);
+        }
+        badges
+    }
+}
+
+impl Storable for WorkerStats {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for WorkerStats {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for Completion {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Completion {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Completed gigs keyed by `(completed_at, gig_id)`, for windowed leaderboards.
+    static COMPLETIONS: RefCell<StableBTreeMap<(u64, u64), Completion, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(COMPLETIONS_MEMORY_ID)));
+
+    /// All-time totals keyed by worker.
+    static WORKER_STATS: RefCell<StableBTreeMap<StorablePrincipal, WorkerStats, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(WORKER_STATS_MEMORY_ID)));
+}
+
+/// Apply `f` to a worker's totals and refresh the badges on their profile.
+fn update_stats(worker: Principal, f: impl FnOnce(&mut WorkerStats)) {
+    let stats = WORKER_STATS.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let key = StorablePrincipal(worker);
+        let mut stats = storage.get(&key).unwrap_or_default();
+        f(&mut stats);
+        storage.insert(key, stats.clone());
+        stats
+    });
+    profiles::set_badges(worker, stats.badges());
+}
+
+/// Count a write of a gig towards its workers' totals: completions when it is approved
+/// and disputes when one is raised.
+pub fn record(previous: Option<&Gig>, gig: &Gig) {
+    let was_disputed = previous.is_some_and(|previous| previous.status == GigStatus::Disputed);
+    if gig.status == GigStatus::Disputed && !was_disputed {
+        for worker in teams::workers(gig) {
+            update_stats(worker, |stats| stats.disputes += 1);
+        }
+    }
+    if !state_machine::is_completion(previous, gig) {
+        return;
+    }
+
+    // Team members are paid and timed by their own share.
+    let icp_amount = gig
+        .payment
+        .as_ref()
+        .filter(|payment| payment.ledger == ledger::icp_ledger())
+        .map_or(0, |payment| payment.amount);
+    let team = teams::team(gig.id);
+    let workers: Vec<WorkerCompletion> = if team.is_empty() {
+        gig.assigned_to
+            .map(|worker| WorkerCompletion {
+                worker,
+                on_time: gig.submitted_at.is_some_and(|at| at <= gig.deadline),
+                earned: icp_amount,
+            })
+            .into_iter()
+            .collect()
+    } else {
+        team.iter()
+            .map(|share| WorkerCompletion {
+                worker: share.worker,
+                on_time: share.submitted_at.is_some_and(|at| at <= gig.deadline),
+                earned: icp_amount * share.share_percent as u64 / 100,
+            })
+            .collect()
+    };
+    for completion in &workers {
+        update_stats(completion.worker, |stats| {
+            stats.completed_gigs += 1;
+            stats.on_time_gigs += completion.on_time as u64;
+            stats.earnings += completion.earned;
+        });
+    }
+    COMPLETIONS.with(|completions| {
+        completions.borrow_mut().insert((time(), gig.id), Completion { workers })
+    });
+}
+
+/// Retrieve a worker's all-time totals.
+#[ic_cdk::query]
+pub fn get_worker_stats(worker: Principal) -> WorkerStats {
+    WORKER_STATS
+        .with(|storage| storage.borrow().get(&StorablePrincipal(worker)))
+        .unwrap_or_default()
+}
+
+/// Badges a worker has earned so far.
+pub fn badges_of(worker: Principal) -> Vec<Badge> {
+    get_worker_stats(worker).badges()
+}
+
+/// Retrieve the top workers over a period by a metric, at most `MAX_PAGE_SIZE`. Ties are
+/// broken by completed gigs. Windowed periods only count gigs completed in the window.
+#[ic_cdk::query]
+pub fn get_leaderboard(
+    period: LeaderboardPeriod,
+    metric: LeaderboardMetric,
+) -> Vec<LeaderboardEntry> {
+    let days = match period {
+        LeaderboardPeriod::Week => 7,
+        LeaderboardPeriod::Month => 30,
+        LeaderboardPeriod::AllTime => 0,
+    };
+    let totals: Vec<(Principal, WorkerStats)> = if days == 0 {
+        WORKER_STATS.with(|storage| {
+            storage.borrow().iter().map(|(key, stats)| (key.0, stats)).collect()
+        })
+    } else {
+        let since = time().saturating_sub(days * DAY_NANOS);
+        let mut by_worker: BTreeMap<Principal, WorkerStats> = BTreeMap::new();
+        COMPLETIONS.with(|completions| {
+            for (_, completion) in completions.borrow().range((since, 0)..) {
+                for done in completion.workers {
+                    let stats = by_worker.entry(done.worker).or_default();
+                    stats.completed_gigs += 1;
+                    stats.on_time_gigs += done.on_time as u64;
+                    stats.earnings += done.earned;
+                }
+            }
+        });
+        by_worker.into_iter().collect()
+    };
+
+    let mut ranked: Vec<(Principal, WorkerStats)> =
+        totals.into_iter().filter(|(_, stats)| stats.completed_gigs > 0).collect();
+    ranked.sort_by(|(_, a), (_, b)| {
+        let by_metric = match metric {
+            LeaderboardMetric::CompletedGigs => b.completed_gigs.cmp(&a.completed_gigs),
+            LeaderboardMetric::OnTimeRate => b.on_time_percent().cmp(&a.on_time_percent()),
+            LeaderboardMetric::Earnings => b.earnings.cmp(&a.earnings),
+        };
+        by_metric.then(b.completed_gigs.cmp(&a.completed_gigs))
+    });
+    ranked
+        .into_iter()
+        .take(MAX_PAGE_SIZE as usize)
+        .enumerate()
+        .map(|(i, (worker, stats))| LeaderboardEntry {
+            rank: i as u32 + 1,
+            worker,
+            completed_gigs: stats.completed_gigs,
+            on_time_percent: stats.on_time_percent(),
+            earnings: stats.earnings,
+        })
+        .collect()
+}
@@ -0,0 +1,154 @@
+use candid::{CandidType, Nat, Principal};
+use ic_cdk::api::call::RejectionCode;
+use ic_cdk::api::time;
+
+use crate::error::GigError;
+
+/// Principal of the ICP ledger canister.
+pub const ICP_LEDGER_CANISTER_ID: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
+/// Fee charged by the ICP ledger for every transfer, in e8s.
+pub const ICP_TRANSFER_FEE: u64 = 10_000;
+/// Principal of the ckBTC ledger canister.
+pub const CKBTC_LEDGER_CANISTER_ID: &str = "mxzaz-hqaaa-aaaar-qaada-cai";
+/// Fee charged by the ckBTC ledger for every transfer, in satoshis.
+pub const CKBTC_TRANSFER_FEE: u64 = 10;
+/// Attempts made at a payout before a transient failure is reported.
+const MAX_TRANSFER_ATTEMPTS: u32 = 3;
+
+/// ICRC-1 account: an owner principal plus an optional 32-byte subaccount.
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+pub struct Account {
+    pub owner: Principal,
+    pub subaccount: Option<Vec<u8>>,
+}
+
+impl From<Principal> for Account {
+    fn from(owner: Principal) -> Self {
+        Account {
+            owner,
+            subaccount: None,
+        }
+    }
+}
+
+/// Arguments of `icrc1_transfer`.
+#[derive(CandidType, Serialize, Deserialize)]
+pub struct TransferArg {
+    pub from_subaccount: Option<Vec<u8>>,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+/// Errors returned by `icrc1_transfer`.
+#[derive(CandidType, Deserialize, Debug)]
+pub enum TransferError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+/// Arguments of `icrc2_transfer_from`.
+#[derive(CandidType, Serialize, Deserialize)]
+pub struct TransferFromArgs {
+    pub spender_subaccount: Option<Vec<u8>>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+/// Errors returned by `icrc2_transfer_from`.
+#[derive(CandidType, Deserialize, Debug)]
+pub enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+/// Resolve the ICP ledger principal.
+pub fn icp_ledger() -> Principal {
+    Principal::from_text(ICP_LEDGER_CANISTER_ID).expect("Invalid ICP ledger canister ID")
+}
+
+/// Resolve the ckBTC ledger principal.
+pub fn ckbtc_ledger() -> Principal {
+    Principal::from_text(CKBTC_LEDGER_CANISTER_ID).expect("Invalid ckBTC ledger canister ID")
+}
+
+/// Pull `amount` from `from` into the canister's default account using a prior ICRC-2 approval.
+/// Returns the ledger block index of the transfer.
+pub async fn transfer_from(
+    ledger: Principal,
+    from: Principal,
+    amount: u64,
+) -> Result<Nat, GigError> {
+    let args = TransferFromArgs {
+        spender_subaccount: None,
+        from: from.into(),
+        to: ic_cdk::id().into(),
+        amount: Nat::from(amount),
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+    let (result,): (Result<Nat, TransferFromError>,) =
+        ic_cdk::call(ledger, "icrc2_transfer_from", (args,))
+            .await
+            .map_err(|(code, msg)| {
+                GigError::ledger(format!("Ledger call failed: {:?} {}", code, msg))
+            })?;
+    result.map_err(|e| GigError::ledger(format!("Ledger transfer_from failed: {:?}", e)))
+}
+
+/// Send `amount` from the canister's default account to `to`. The ledger fee is charged on top.
+/// Transient failures are retried as the same ledger transaction, so a retry of a transfer
+/// that did go through is deduplicated rather than paid twice.
+/// Returns the ledger block index of the transfer.
+pub async fn transfer(ledger: Principal, to: Principal, amount: u64) -> Result<Nat, GigError> {
+    let args = TransferArg {
+        from_subaccount: None,
+        to: to.into(),
+        amount: Nat::from(amount),
+        fee: None,
+        memo: None,
+        created_at_time: Some(time()),
+    };
+    let mut attempt = 1;
+    loop {
+        let result: Result<(Result<Nat, TransferError>,), _> =
+            ic_cdk::call(ledger, "icrc1_transfer", (&args,)).await;
+        let retry = attempt < MAX_TRANSFER_ATTEMPTS;
+        match result {
+            Ok((Ok(block),)) => return Ok(block),
+            // An earlier attempt reached the ledger after all.
+            Ok((Err(TransferError::Duplicate { duplicate_of }),)) if attempt > 1 => {
+                return Ok(duplicate_of)
+            }
+            Ok((Err(TransferError::TemporarilyUnavailable),)) if retry => {}
+            Err((RejectionCode::SysTransient, _)) if retry => {}
+            Ok((Err(e),)) => {
+                return Err(GigError::ledger(format!("Ledger transfer failed: {:?}", e)))
+            }
+            Err((code, msg)) => {
+                return Err(GigError::ledger(format!("Ledger call failed: {:?} {}", code, msg)))
+            }
+        }
+        attempt += 1;
+    }
+}
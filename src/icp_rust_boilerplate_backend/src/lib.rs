@@ -1,12 +1,139 @@
 #[macro_use]
 extern crate serde;
-use candid::{Decode, Encode};
+use candid::{Encode, Nat, Principal};
+use ic_cdk::api::management_canister::http_request::{
+    HttpResponse as OutcallResponse, TransformArgs,
+};
 use ic_cdk::api::time;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{BoundedStorable, Cell, DefaultMemoryImpl, StableBTreeMap, Storable};
 use std::{borrow::Cow, cell::RefCell};
 use ic_cdk::caller;
 
+mod analytics;
+mod appeals;
+mod arbitration;
+mod attachments;
+mod audit;
+mod backup;
+mod bids;
+mod bookmarks;
+mod certificates;
+mod certified;
+mod changes;
+mod clauses;
+mod config;
+mod content_filter;
+mod contracts;
+mod cycles;
+mod deadlines;
+mod delegates;
+mod deliverables;
+mod dependencies;
+mod disputes;
+mod error;
+mod escrow;
+mod events;
+mod extensions;
+mod health;
+mod http;
+mod id;
+mod idempotency;
+mod indexes;
+mod invitations;
+mod leaderboard;
+mod ledger;
+mod log;
+mod market;
+mod messages;
+mod migrations;
+mod milestones;
+mod moderation;
+mod multisig;
+mod notifications;
+mod orgs;
+mod profiles;
+mod rate_limit;
+mod rates;
+mod referrals;
+mod reminders;
+mod reputation;
+mod reviews;
+mod revisions;
+mod roles;
+mod saved_searches;
+mod search;
+mod sharding;
+mod skills;
+mod state_machine;
+mod stats;
+mod subtasks;
+mod tags;
+mod teams;
+mod templates;
+mod timesheets;
+mod tokens;
+mod treasury;
+mod trending;
+mod validation;
+mod visibility;
+mod webhooks;
+
+use analytics::GigAnalytics;
+use appeals::Appeal;
+use arbitration::ArbitratorStake;
+use attachments::Attachment;
+use audit::{AuditEntry, AuditPage};
+use backup::{BackupChunk, RestoreProgress};
+use certificates::{Certificate, Value};
+use bids::{Bid, Offer};
+use certified::{CertifiedGig, CertifiedGigs};
+use changes::{ChangeProposal, ScopeChange};
+use clauses::DeliveryTerms;
+use config::{Config, ConfigPatch};
+use content_filter::{BannedTerm, FilterAction, FlaggedGig};
+use contracts::{Amendment, Contract, ContractTerms};
+use delegates::{Delegation, Permission};
+use deliverables::{Artifact, DeliveryProof};
+use disputes::{Dispute, DisputeRecord, DisputeSplit, Evidence, Ruling};
+use error::GigError;
+use escrow::{Escrow, PendingRefund};
+use events::EventPage;
+use extensions::ExtensionRequest;
+use health::Health;
+use http::{HttpRequest, HttpResponse};
+use indexes::SortBy;
+use invitations::Invitation;
+use leaderboard::{LeaderboardEntry, LeaderboardMetric, LeaderboardPeriod, WorkerStats};
+use ledger::Account;
+use log::{LogEntry, LogLevel};
+use market::MarketRate;
+use messages::Message;
+use milestones::Milestone;
+use moderation::{not_banned, Ban, HiddenGig};
+use multisig::{ApprovalPolicy, ApprovalSignature, Release};
+use notifications::NotificationPage;
+use orgs::Organization;
+use profiles::{Profile, ProfilePayload};
+use rates::UsdRate;
+use referrals::Referral;
+use reputation::{Rating, ReputationSummary};
+use reviews::{Review, ReviewDirection, ReviewPage};
+use revisions::Revision;
+use roles::{Role, RoleAssignment};
+use saved_searches::{SavedSearch, SearchFilter};
+use search::Suggestion;
+use sharding::{Shard, ShardStatus};
+use stats::GigStats;
+use subtasks::{Subtask, SubtaskList};
+use tags::Category;
+use teams::{TeamMember, TeamShare};
+use templates::{GigTemplate, Recurrence, TemplateOverrides, TemplatePayload};
+use timesheets::{GigType, TimeEntry};
+use tokens::{Payment, Token};
+use treasury::FeeBalance;
+use visibility::Visibility;
+use webhooks::WebhookInfo;
 
 /// Type alias for virtual memory.
 pub type Memory = VirtualMemory<DefaultMemoryImpl>;
@@ -14,26 +141,118 @@ pub type Memory = VirtualMemory<DefaultMemoryImpl>;
 pub type IdCell = Cell<u64, Memory>;
 
 /// Structure representing a gig/task.
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
 pub struct Gig {
     pub id: u64,                        
     pub title: String,                  
     pub description: String,            
-    pub employer: String,                
+    pub employer: Principal,             
     pub deadline: u64,                   
-    pub assigned_to: Option<String>,     
+    pub assigned_to: Option<Principal>,  
     pub status: GigStatus,              
     pub created_at: u64,                 
     pub updated_at: Option<u64>,         
+    pub submission_note: Option<String>, // Worker's note on the submitted work.
+    pub submitted_at: Option<u64>,       
+    pub milestones: Option<Vec<Milestone>>, // Incremental deliverables, if any.
+    pub payment: Option<Payment>,        // Token and amount held in escrow, if funded.
+    pub category: Option<Category>,      
+    pub tags: Option<Vec<String>>,       // Normalised (trimmed, lower-case) tags.
+    pub budget: Option<Payment>,         // Advertised compensation; unset on older gigs.
+    pub archived_from: Option<GigStatus>, // Status to restore an archived or deleted gig to.
+    pub auto_approve_days: Option<u32>,  // Idle days after submission before auto-approval.
+    pub attachments: Option<Vec<u64>>,   // Files attached to the brief.
+    pub submission_attachments: Option<Vec<u64>>, // Deliverables attached to the submission.
+    pub version: Option<u64>,            // Bumped on every write; `None` on never-rewritten gigs.
+    pub proposed_at: Option<u64>,        // When the current worker was proposed.
+    pub visibility: Option<Visibility>,  // Who can find the gig; `None` is public.
+    pub required_skills: Option<Vec<String>>, // Skills from the curated list the work needs.
+    pub gig_type: Option<GigType>,       // How the worker is paid; `None` is a fixed price.
+    pub delivery_terms: Option<DeliveryTerms>, // Early bonus and late penalty, if any.
+    pub depends_on: Option<Vec<u64>>,    // Gigs that must be approved before assignment.
+    pub org_id: Option<u64>,             // Organization the gig was posted under, if any.
+    pub dispute_split: Option<DisputeSplit>, // How a split dispute ruling divided the escrow.
+    pub reminder_hours: Option<Vec<u32>>, // Hours before the deadline to remind; `None` is 48, 24.
+    pub usd_budget: Option<u64>,         // USD price in cents the budget was derived from.
+    pub auto_approve_after_secs: Option<u64>, // Overrides `auto_approve_days` when set.
+    pub purge_at: Option<u64>,           // When a gig pending deletion is removed for good.
+    pub approvals: Option<Vec<ApprovalSignature>>, // Signatures on high-value escrow releases.
+}
+
+impl Gig {
+    /// Revision of the gig, which clients echo back to detect concurrent edits.
+    pub fn version(&self) -> u64 {
+        self.version.unwrap_or(0)
+    }
+
+    /// Who can find the gig.
+    pub fn visibility(&self) -> Visibility {
+        self.visibility.clone().unwrap_or_default()
+    }
+
+    /// How the worker is paid.
+    pub fn gig_type(&self) -> GigType {
+        self.gig_type.clone().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+impl Gig {
+    /// An open gig posted by the anonymous principal with every optional field unset, for
+    /// tests to fill in.
+    pub fn for_test(id: u64) -> Gig {
+        Gig {
+            id,
+            title: String::new(),
+            description: String::new(),
+            employer: Principal::anonymous(),
+            deadline: 0,
+            assigned_to: None,
+            status: GigStatus::Open,
+            created_at: 0,
+            updated_at: None,
+            submission_note: None,
+            submitted_at: None,
+            milestones: None,
+            payment: None,
+            category: None,
+            tags: None,
+            budget: None,
+            archived_from: None,
+            auto_approve_days: None,
+            attachments: None,
+            submission_attachments: None,
+            version: None,
+            proposed_at: None,
+            visibility: None,
+            required_skills: None,
+            gig_type: None,
+            delivery_terms: None,
+            depends_on: None,
+            org_id: None,
+            dispute_split: None,
+            reminder_hours: None,
+            usd_budget: None,
+            auto_approve_after_secs: None,
+            purge_at: None,
+            approvals: None,
+        }
+    }
 }
 
 /// Enum representing possible statuses of a gig.
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
 pub enum GigStatus {
-    Open,       // Gig is open and not yet assigned.
-    Assigned,   // Gig has been assigned to a worker.
-    Approved,  // Gig has been completed by the worker.
-    Disputed,   // There is a dispute over the gig.
+    Open,              // Gig is open and not yet assigned.
+    PendingAcceptance, // The employer proposed a worker who has not accepted yet.
+    Assigned,          // Gig has been assigned to a worker.
+    Submitted,         // The worker has submitted the work for approval.
+    Approved,          // The employer has approved the submitted work.
+    Disputed,          // There is a dispute over the gig.
+    Expired,           // The deadline passed before the work was submitted.
+    Cancelled,         // The employer withdrew the gig.
+    Archived,          // Deleted before the grace period existed; migrated to `PendingDeletion`.
+    PendingDeletion,   // The employer deleted the gig; it is purged after a grace period.
 }
 
 /// Default implementation for `GigStatus` sets the initial status to `Open`.
@@ -49,24 +268,357 @@ pub struct GigPayload {
     pub title: String,        // Title of the gig.
     pub description: String,  // Description of the gig.
     pub deadline: u64,        // Deadline for gig completion.
+    pub budget: u64,                // Compensation offered, in the token's base units.
+    pub escrow_amount: Option<u64>, // Amount to lock on posting; ignored by `update_gig`.
+    pub token: Option<Principal>,   // Ledger of the budget and escrow token; defaults to ICP.
+    pub category: Option<Category>, // Area of work the gig belongs to.
+    pub tags: Option<Vec<String>>,  // Free-form keywords, at most 10.
+    pub auto_approve_days: Option<u32>, // Days before a submission approves itself; has a default.
+    pub attachments: Option<Vec<u64>>,  // Finalised attachments uploaded by the employer.
+    pub visibility: Option<Visibility>, // Who can find the gig; defaults to public.
+    pub required_skills: Option<Vec<String>>, // From `list_skills`, at most 10.
+    pub gig_type: Option<GigType>,  // Fixed price by default; hourly gigs derive the budget.
+    pub delivery_terms: Option<DeliveryTerms>, // Bonus and penalty clauses on the payout.
+    pub depends_on: Option<Vec<u64>>, // Own gigs to approve before this one is assigned.
+    pub org_id: Option<u64>,        // Organization to post under; ignored by `update_gig`.
+    pub reminder_hours: Option<Vec<u32>>, // Deadline reminders; defaults to 48h and 24h before.
+    pub usd_budget: Option<u64>,    // Price in US cents, converted into `budget` on posting.
+    pub idempotency_key: Option<String>, // Replays within a day return the first gig.
+    pub auto_approve_after_secs: Option<u64>, // Finer auto-approval window; one hour to 90 days.
 }
 
+/// The fields of a gig needed to list it, without the long-form text.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct GigSummary {
+    pub id: u64,
+    pub title: String,
+    pub status: GigStatus,
+    pub budget: Option<Payment>,
+    pub deadline: u64,
+    pub employer: Principal,
+    pub employer_verified: bool,      // An admin has verified the employer.
+    pub progress_percent: Option<u8>, // Share of sub-tasks done; `None` without sub-tasks.
+    pub view_count: u64,
+    pub bid_count: u64,
+}
+
+impl From<&Gig> for GigSummary {
+    fn from(gig: &Gig) -> Self {
+        let (view_count, bid_count) = analytics::counts(gig.id);
+        GigSummary {
+            id: gig.id,
+            title: gig.title.clone(),
+            status: gig.status.clone(),
+            budget: gig.budget.clone(),
+            deadline: gig.deadline,
+            employer: gig.employer,
+            employer_verified: profiles::is_verified(gig.employer),
+            progress_percent: subtasks::progress(gig.id),
+            view_count,
+            bid_count,
+        }
+    }
+}
+
+/// A page of gigs along with the total number of gigs stored.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct GigPage {
+    pub gigs: Vec<GigSummary>,
+    pub total: u64,
+}
+
+/// Upper bound on the number of gigs returned by a single page.
+const MAX_PAGE_SIZE: u64 = 100;
+/// Most gigs `post_gigs_batch` accepts in one call.
+const MAX_BATCH_SIZE: usize = 20;
+/// Longest auto-approval window an employer can choose.
+const MAX_AUTO_APPROVE_DAYS: u32 = 90;
+/// Shortest auto-approval window in seconds: one hour, several timer sweeps.
+const MIN_AUTO_APPROVE_SECS: u64 = 60 * 60;
+/// Most required skills a gig can declare.
+const MAX_REQUIRED_SKILLS: usize = 10;
+
 /// Implement traits for storing `Gig` in stable memory.
 impl Storable for Gig {
-    fn to_bytes(&self) -> Cow<[u8]> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        // Older layouts are upgraded on read.
+        migrations::decode_gig(bytes.as_ref())
     }
 }
 
 impl BoundedStorable for Gig {
-    const MAX_SIZE: u32 = 2048;       // Maximum size for storing a gig.
+    const MAX_SIZE: u32 = 8192;       // Maximum size for storing a gig.
     const IS_FIXED_SIZE: bool = false; // Indicates that size is not fixed.
 }
 
+/// Principal wrapper usable as a stable map key.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StorablePrincipal(pub Principal);
+
+impl Storable for StorablePrincipal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_slice())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        StorablePrincipal(Principal::from_slice(bytes.as_ref()))
+    }
+}
+
+/// The empty (management canister) principal sorts first, so it bounds range scans.
+impl Default for StorablePrincipal {
+    fn default() -> Self {
+        StorablePrincipal(Principal::management_canister())
+    }
+}
+
+impl BoundedStorable for StorablePrincipal {
+    const MAX_SIZE: u32 = 29;          // Principals are at most 29 bytes long.
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Stable memory region holding escrow records.
+const ESCROW_MEMORY_ID: MemoryId = MemoryId::new(2);
+/// Stable memory region holding bids.
+const BIDS_MEMORY_ID: MemoryId = MemoryId::new(3);
+/// Stable memory region holding the bid ID counter.
+const BID_ID_COUNTER_MEMORY_ID: MemoryId = MemoryId::new(4);
+/// Stable memory region holding the arbitrator registry.
+const ARBITRATORS_MEMORY_ID: MemoryId = MemoryId::new(5);
+/// Stable memory region holding disputes.
+const DISPUTES_MEMORY_ID: MemoryId = MemoryId::new(6);
+/// Stable memory region holding dispute evidence.
+const EVIDENCE_MEMORY_ID: MemoryId = MemoryId::new(7);
+/// Stable memory region holding user profiles.
+const PROFILES_MEMORY_ID: MemoryId = MemoryId::new(8);
+/// Stable memory region holding ratings.
+const RATINGS_MEMORY_ID: MemoryId = MemoryId::new(9);
+/// Stable memory region holding aggregated reputation.
+const REPUTATION_MEMORY_ID: MemoryId = MemoryId::new(10);
+/// Stable memory region holding the gig-by-status index.
+const STATUS_INDEX_MEMORY_ID: MemoryId = MemoryId::new(11);
+/// Stable memory region holding the gig-by-employer index.
+const EMPLOYER_INDEX_MEMORY_ID: MemoryId = MemoryId::new(12);
+/// Stable memory region holding the gig-by-worker index.
+const WORKER_INDEX_MEMORY_ID: MemoryId = MemoryId::new(13);
+/// Stable memory region holding the schema version tag.
+const SCHEMA_VERSION_MEMORY_ID: MemoryId = MemoryId::new(14);
+/// Stable memory region holding the accepted token list.
+const TOKENS_MEMORY_ID: MemoryId = MemoryId::new(15);
+/// Stable memory region holding the gig-by-tag index.
+const TAG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(16);
+/// Stable memory region that held the unweighted keyword search index; emptied by the
+/// schema version 12 migration.
+const LEGACY_WORD_INDEX_MEMORY_ID: MemoryId = MemoryId::new(17);
+/// Stable memory region holding granted roles.
+const ROLES_MEMORY_ID: MemoryId = MemoryId::new(18);
+/// Stable memory region holding banned principals.
+const BANS_MEMORY_ID: MemoryId = MemoryId::new(19);
+/// Stable memory region holding gigs hidden by moderators.
+const HIDDEN_GIGS_MEMORY_ID: MemoryId = MemoryId::new(20);
+/// Stable memory region holding the audit log's entry index.
+const AUDIT_LOG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(21);
+/// Stable memory region holding the audit log's entries.
+const AUDIT_LOG_DATA_MEMORY_ID: MemoryId = MemoryId::new(22);
+/// Stable memory region holding the audit-entries-by-gig index.
+const AUDIT_INDEX_MEMORY_ID: MemoryId = MemoryId::new(23);
+/// Stable memory region holding the event feed.
+const EVENTS_MEMORY_ID: MemoryId = MemoryId::new(24);
+/// Stable memory region holding the event sequence counter.
+const EVENT_SEQ_MEMORY_ID: MemoryId = MemoryId::new(25);
+/// Stable memory region holding the gig-by-budget index.
+const BUDGET_INDEX_MEMORY_ID: MemoryId = MemoryId::new(26);
+/// Stable memory region holding attachment metadata.
+const ATTACHMENTS_MEMORY_ID: MemoryId = MemoryId::new(27);
+/// Stable memory region holding attachment content.
+const ATTACHMENT_CHUNKS_MEMORY_ID: MemoryId = MemoryId::new(28);
+/// Stable memory region holding the attachment ID counter.
+const ATTACHMENT_ID_COUNTER_MEMORY_ID: MemoryId = MemoryId::new(29);
+/// Stable memory region holding the message log's entry index.
+const MESSAGE_LOG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(30);
+/// Stable memory region holding the message log's entries.
+const MESSAGE_LOG_DATA_MEMORY_ID: MemoryId = MemoryId::new(31);
+/// Stable memory region holding the messages-by-gig index.
+const MESSAGES_INDEX_MEMORY_ID: MemoryId = MemoryId::new(32);
+/// Stable memory region that held the platform fee before it moved into the config; only
+/// read by the schema version 8 migration.
+const FEE_BPS_MEMORY_ID: MemoryId = MemoryId::new(33);
+/// Stable memory region holding accrued platform fees.
+const TREASURY_MEMORY_ID: MemoryId = MemoryId::new(34);
+/// Stable memory region holding per-caller rate limit buckets.
+const RATE_LIMITS_MEMORY_ID: MemoryId = MemoryId::new(35);
+/// Stable memory region holding the canister settings.
+const CONFIG_MEMORY_ID: MemoryId = MemoryId::new(36);
+/// Stable memory region holding the assignment acceptance window.
+const ACCEPTANCE_WINDOW_MEMORY_ID: MemoryId = MemoryId::new(37);
+/// Stable memory region holding gig counts per status.
+const STATUS_COUNTS_MEMORY_ID: MemoryId = MemoryId::new(38);
+/// Stable memory region holding hourly posting counts.
+const POSTED_PER_HOUR_MEMORY_ID: MemoryId = MemoryId::new(39);
+/// Stable memory region holding running statistics totals.
+const STATS_TOTALS_MEMORY_ID: MemoryId = MemoryId::new(40);
+/// Stable memory region holding revision requests.
+const REVISIONS_MEMORY_ID: MemoryId = MemoryId::new(41);
+/// Stable memory region holding invitations.
+const INVITATIONS_MEMORY_ID: MemoryId = MemoryId::new(42);
+/// Stable memory region holding the unlisted gig index.
+const UNLISTED_INDEX_MEMORY_ID: MemoryId = MemoryId::new(43);
+/// Stable memory region holding the curated skill list.
+const SKILLS_MEMORY_ID: MemoryId = MemoryId::new(44);
+/// Stable memory region holding the required skill index.
+const SKILL_INDEX_MEMORY_ID: MemoryId = MemoryId::new(45);
+/// Stable memory region holding the worker skill index.
+const WORKER_SKILL_INDEX_MEMORY_ID: MemoryId = MemoryId::new(46);
+/// Stable memory region holding written reviews.
+const REVIEWS_MEMORY_ID: MemoryId = MemoryId::new(47);
+/// Stable memory region holding the reviewee index.
+const REVIEWEE_INDEX_MEMORY_ID: MemoryId = MemoryId::new(48);
+/// Stable memory region holding gig templates.
+const TEMPLATES_MEMORY_ID: MemoryId = MemoryId::new(49);
+/// Stable memory region holding the template ID counter.
+const TEMPLATE_ID_COUNTER_MEMORY_ID: MemoryId = MemoryId::new(50);
+/// Stable memory region holding the template owner index.
+const TEMPLATE_OWNER_INDEX_MEMORY_ID: MemoryId = MemoryId::new(51);
+/// Stable memory region holding the recurring template schedule.
+const RECURRENCE_SCHEDULE_MEMORY_ID: MemoryId = MemoryId::new(52);
+/// Stable memory region holding the newest-first sort index.
+const SORT_CREATED_MEMORY_ID: MemoryId = MemoryId::new(53);
+/// Stable memory region holding the deadline sort index.
+const SORT_DEADLINE_MEMORY_ID: MemoryId = MemoryId::new(54);
+/// Stable memory region holding the largest-budget-first sort index.
+const SORT_BUDGET_MEMORY_ID: MemoryId = MemoryId::new(55);
+/// Stable memory region holding the recently-updated sort index.
+const SORT_UPDATED_MEMORY_ID: MemoryId = MemoryId::new(56);
+/// Stable memory region holding the deadline bucket index.
+const DEADLINE_BUCKET_INDEX_MEMORY_ID: MemoryId = MemoryId::new(57);
+/// Stable memory region holding counter-offers on bids.
+const BID_OFFERS_MEMORY_ID: MemoryId = MemoryId::new(58);
+/// Stable memory region holding refunds waiting to be retried.
+const REFUND_QUEUE_MEMORY_ID: MemoryId = MemoryId::new(59);
+/// Stable memory region holding time entries of hourly gigs.
+const TIME_ENTRIES_MEMORY_ID: MemoryId = MemoryId::new(60);
+/// Stable memory region holding deadline extension requests.
+const EXTENSIONS_MEMORY_ID: MemoryId = MemoryId::new(61);
+/// Stable memory region holding post-assignment change proposals.
+const CHANGES_MEMORY_ID: MemoryId = MemoryId::new(62);
+/// Stable memory region holding the shares of team gigs.
+const TEAM_SHARES_MEMORY_ID: MemoryId = MemoryId::new(63);
+/// Stable memory region holding gig sub-tasks.
+const SUBTASKS_MEMORY_ID: MemoryId = MemoryId::new(64);
+/// Stable memory region holding organizations.
+const ORGS_MEMORY_ID: MemoryId = MemoryId::new(65);
+/// Stable memory region holding the organization ID counter.
+const ORG_ID_COUNTER_MEMORY_ID: MemoryId = MemoryId::new(66);
+/// Stable memory region holding the gig-by-organization index.
+const ORG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(67);
+/// Stable memory region holding gig bookmarks.
+const BOOKMARKS_MEMORY_ID: MemoryId = MemoryId::new(68);
+/// Stable memory region holding saved searches.
+const SAVED_SEARCHES_MEMORY_ID: MemoryId = MemoryId::new(69);
+/// Stable memory region holding the saved search ID counter.
+const SAVED_SEARCH_ID_COUNTER_MEMORY_ID: MemoryId = MemoryId::new(70);
+/// Stable memory region holding the saved-search-by-owner index.
+const SAVED_SEARCH_OWNER_INDEX_MEMORY_ID: MemoryId = MemoryId::new(71);
+/// Stable memory region holding arbitrator stakes.
+const ARBITRATOR_STAKES_MEMORY_ID: MemoryId = MemoryId::new(72);
+/// Stable memory region holding dispute appeals.
+const APPEALS_MEMORY_ID: MemoryId = MemoryId::new(73);
+/// Stable memory region holding completion certificates.
+const CERTIFICATES_MEMORY_ID: MemoryId = MemoryId::new(74);
+/// Stable memory region holding the certificate token ID counter.
+const CERTIFICATE_ID_COUNTER_MEMORY_ID: MemoryId = MemoryId::new(75);
+/// Stable memory region holding the certificate-by-owner index.
+const CERTIFICATE_OWNER_INDEX_MEMORY_ID: MemoryId = MemoryId::new(76);
+/// Stable memory region holding the certificate-by-gig index.
+const CERTIFICATE_GIG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(77);
+/// Stable memory region holding completed gigs for windowed leaderboards.
+const COMPLETIONS_MEMORY_ID: MemoryId = MemoryId::new(78);
+/// Stable memory region holding all-time worker totals.
+const WORKER_STATS_MEMORY_ID: MemoryId = MemoryId::new(79);
+/// Stable memory region holding the deadline reminders already sent.
+const REMINDERS_SENT_MEMORY_ID: MemoryId = MemoryId::new(80);
+/// Stable memory region holding per-principal notification inboxes.
+const NOTIFICATIONS_MEMORY_ID: MemoryId = MemoryId::new(81);
+/// Stable memory region holding webhooks.
+const WEBHOOKS_MEMORY_ID: MemoryId = MemoryId::new(82);
+/// Stable memory region holding the webhook ID counter.
+const WEBHOOK_ID_COUNTER_MEMORY_ID: MemoryId = MemoryId::new(83);
+/// Stable memory region holding the webhook-by-owner index.
+const WEBHOOK_OWNER_INDEX_MEMORY_ID: MemoryId = MemoryId::new(84);
+/// Stable memory region holding pending webhook deliveries.
+const WEBHOOK_DELIVERIES_MEMORY_ID: MemoryId = MemoryId::new(85);
+/// Stable memory region holding the webhook delivery ID counter.
+const WEBHOOK_DELIVERY_ID_COUNTER_MEMORY_ID: MemoryId = MemoryId::new(86);
+/// Stable memory region holding the latest USD rate of each token.
+const USD_RATES_MEMORY_ID: MemoryId = MemoryId::new(87);
+/// Stable memory region holding this canister's place in the shard layout.
+const SHARD_STATE_MEMORY_ID: MemoryId = MemoryId::new(88);
+/// Stable memory region holding the shards spawned by the index.
+const SHARDS_MEMORY_ID: MemoryId = MemoryId::new(89);
+/// Stable memory region holding the module installed into new shards.
+const SHARD_WASM_MEMORY_ID: MemoryId = MemoryId::new(90);
+/// Stable memory region holding the time of the latest upgrade.
+const LAST_UPGRADE_MEMORY_ID: MemoryId = MemoryId::new(91);
+/// Stable memory region that held gigs while they were bounded to 2048 bytes; emptied by
+/// the schema version 9 migration.
+const LEGACY_GIGS_MEMORY_ID: MemoryId = MemoryId::new(1);
+/// Stable memory region holding every gig.
+const GIGS_MEMORY_ID: MemoryId = MemoryId::new(92);
+/// Stable memory region holding the gigs created under each idempotency key.
+const IDEMPOTENCY_KEYS_MEMORY_ID: MemoryId = MemoryId::new(93);
+/// Stable memory region holding the content filter's denylist.
+const BANNED_TERMS_MEMORY_ID: MemoryId = MemoryId::new(94);
+/// Stable memory region holding gigs flagged by the content filter.
+const FLAGGED_GIGS_MEMORY_ID: MemoryId = MemoryId::new(95);
+/// Stable memory region holding the deliverable hashes committed with submissions.
+const DELIVERY_PROOFS_MEMORY_ID: MemoryId = MemoryId::new(96);
+/// Stable memory region holding the contracts drafted from accepted bids.
+const CONTRACTS_MEMORY_ID: MemoryId = MemoryId::new(97);
+/// Stable memory region holding the amendment chains of signed contracts.
+const CONTRACT_AMENDMENTS_MEMORY_ID: MemoryId = MemoryId::new(98);
+/// Stable memory region holding the number of gigs posted by each employer.
+const EMPLOYER_GIG_COUNTS_MEMORY_ID: MemoryId = MemoryId::new(99);
+/// Stable memory region holding the number of open gigs carrying each tag.
+const OPEN_TAG_COUNTS_MEMORY_ID: MemoryId = MemoryId::new(100);
+/// Stable memory region holding the decayed activity score of each gig.
+const ACTIVITY_SCORES_MEMORY_ID: MemoryId = MemoryId::new(101);
+/// Stable memory region holding gig IDs ordered by activity score.
+const TRENDING_INDEX_MEMORY_ID: MemoryId = MemoryId::new(102);
+/// Stable memory region holding the principals that have viewed each gig.
+const GIG_VIEWERS_MEMORY_ID: MemoryId = MemoryId::new(103);
+/// Stable memory region holding the keyword search index with term weights.
+const WORD_INDEX_MEMORY_ID: MemoryId = MemoryId::new(104);
+/// Stable memory region holding gig IDs ordered by normalised title.
+const TITLE_INDEX_MEMORY_ID: MemoryId = MemoryId::new(105);
+/// Stable memory region holding the view and bid counters of each gig.
+const GIG_ANALYTICS_MEMORY_ID: MemoryId = MemoryId::new(106);
+/// Stable memory region holding recently approved budgets by required skill.
+const SKILL_MARKET_SAMPLES_MEMORY_ID: MemoryId = MemoryId::new(107);
+/// Stable memory region holding recently approved budgets by category.
+const CATEGORY_MARKET_SAMPLES_MEMORY_ID: MemoryId = MemoryId::new(108);
+/// Stable memory region holding recently approved budgets by approval time.
+const MARKET_SAMPLE_TIMES_MEMORY_ID: MemoryId = MemoryId::new(109);
+/// Stable memory region holding the owner of each referral code.
+const REFERRAL_CODES_MEMORY_ID: MemoryId = MemoryId::new(110);
+/// Stable memory region holding referrals by referee.
+const REFERRALS_MEMORY_ID: MemoryId = MemoryId::new(111);
+/// Stable memory region holding the referee index of each referrer.
+const REFERRER_INDEX_MEMORY_ID: MemoryId = MemoryId::new(112);
+/// Stable memory region holding unclaimed referral rewards.
+const REFERRAL_REWARDS_MEMORY_ID: MemoryId = MemoryId::new(113);
+/// Stable memory region holding delegations by employer.
+const DELEGATIONS_MEMORY_ID: MemoryId = MemoryId::new(114);
+/// Stable memory region holding the employers each delegate acts for.
+const DELEGATE_INDEX_MEMORY_ID: MemoryId = MemoryId::new(115);
+/// Stable memory region holding the most recent log entries.
+const LOGS_MEMORY_ID: MemoryId = MemoryId::new(116);
+/// Stable memory region holding when the canister first took an update call.
+const FIRST_USED_MEMORY_ID: MemoryId = MemoryId::new(117);
+
 // Thread-local storage for state management.
 thread_local! {
     /// Memory manager for stable memory operations.
@@ -83,154 +635,849 @@ thread_local! {
     /// Storage for all gigs using a stable BTreeMap.
     static GIG_STORAGE: RefCell<StableBTreeMap<u64, Gig, Memory>> =
         RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1)))
+            MEMORY_MANAGER.with(|m| m.borrow().get(GIGS_MEMORY_ID))
     ));
 }
 
-/// Post a new gig.
-#[ic_cdk::update]
-pub fn post_gig(payload: GigPayload) -> Gig {
-    // Generate a unique ID for the new gig.
-    let id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("Cannot increment ID counter");
+/// Hand out a virtual memory region from the shared memory manager.
+fn get_memory(id: MemoryId) -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(id))
+}
+
+/// Resolve the auto-approval window of a gig payload.
+fn auto_approve_days(days: Option<u32>) -> Result<u32, GigError> {
+    let days = days.unwrap_or_else(|| config::get().auto_approve_days);
+    if !(1..=MAX_AUTO_APPROVE_DAYS).contains(&days) {
+        return Err(GigError::validation("auto_approve_days", "Must be between 1 and 90 days"));
+    }
+    Ok(days)
+}
+
+/// Check the auto-approval window in seconds of a gig payload.
+fn auto_approve_after_secs(secs: Option<u64>) -> Result<Option<u64>, GigError> {
+    let max = MAX_AUTO_APPROVE_DAYS as u64 * 24 * 60 * 60;
+    if secs.is_some_and(|secs| !(MIN_AUTO_APPROVE_SECS..=max).contains(&secs)) {
+        return Err(GigError::validation(
+            "auto_approve_after_secs",
+            "Must be between one hour and 90 days",
+        ));
+    }
+    Ok(secs)
+}
+
+/// Ensure the gig has not been written since the caller read `expected` as its version.
+fn ensure_version(gig: &Gig, expected: u64) -> Result<(), GigError> {
+    if gig.version() != expected {
+        return Err(GigError::Conflict { expected, actual: gig.version() });
+    }
+    Ok(())
+}
+
+/// Ensure the caller is a controller of the canister.
+fn ensure_controller() -> Result<(), GigError> {
+    if !ic_cdk::api::is_controller(&caller()) {
+        return Err(GigError::unauthorized("Only a canister controller can perform this action"));
+    }
+    Ok(())
+}
+
+/// Post a new gig, optionally locking ICP or another accepted token in escrow for the worker.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn post_gig(payload: GigPayload) -> Result<Gig, GigError> {
+    post_gig_as(caller(), payload).await
+}
+
+/// Post a new gig on behalf of `employer`, who must have approved any escrow transfer.
+async fn post_gig_as(employer: Principal, mut payload: GigPayload) -> Result<Gig, GigError> {
+    // Anonymous callers cannot be held to a gig.
+    if employer == Principal::anonymous() {
+        return Err(GigError::unauthorized("Anonymous principals cannot post gigs"));
+    }
+    // A retried post returns the gig the first attempt created.
+    let idempotency_key = idempotency::hash(payload.idempotency_key.as_ref())?;
+    if let Some(key) = idempotency_key {
+        if let Some(gig) = idempotency::replay(employer, key)? {
+            return Ok(gig);
+        }
+    }
+    sharding::ensure_accepts_new_gigs()?;
+    moderation::ensure_not_banned(employer)?;
+    rate_limit::check(employer, rate_limit::Action::PostGig)?;
+    if let Some(org_id) = payload.org_id {
+        orgs::ensure_can_post(org_id, employer)?;
+    }
+    validation::ensure_open_gig_quota(employer)?;
+    timesheets::apply_gig_type(&mut payload)?;
+    // Gigs priced in USD derive their budget from the current rate; funded gigs record the
+    // rate when one is available.
+    let ledger = payload.token.unwrap_or_else(ledger::icp_ledger);
+    let usd_rate = match payload.usd_budget {
+        Some(usd_cents) => {
+            if payload.gig_type.clone().unwrap_or_default() != GigType::Fixed {
+                return Err(GigError::validation("usd_budget", "Only fixed-price gigs"));
+            }
+            let rate = rates::current(ledger).await?;
+            payload.budget = rates::to_tokens(usd_cents, &rate)?;
+            Some(rate)
+        }
+        None if payload.escrow_amount.is_some() => rates::current(ledger).await.ok(),
+        None => None,
+    };
+    validation::validate_gig_payload(&payload)?;
+    validation::validate_deadline(payload.deadline)?;
+    validation::ensure_budget_allowed(employer, payload.budget)?;
+    let flagged_terms = content_filter::screen(&payload.title, &payload.description)?;
+    if let Some(terms) = &payload.delivery_terms {
+        clauses::validate(terms)?;
+    }
+    let auto_approve_days = auto_approve_days(payload.auto_approve_days)?;
+    let auto_approve_after_secs = auto_approve_after_secs(payload.auto_approve_after_secs)?;
+    let tags = payload.tags.map(tags::normalize_tags).transpose()?;
+    let required_skills = payload
+        .required_skills
+        .map(|skills| skills::normalize_skills(skills, MAX_REQUIRED_SKILLS))
+        .transpose()?;
+    if let Some(ids) = &payload.attachments {
+        attachments::ensure_attachable(ids)?;
+    }
+    let depends_on = GIG_STORAGE.with(|storage| {
+        payload
+            .depends_on
+            .map(|ids| dependencies::validate(&storage.borrow(), None, employer, ids))
+            .transpose()
+    })?;
+    let reminder_hours = payload.reminder_hours.map(reminders::validate).transpose()?;
+    let token = tokens::accepted(ledger)?;
+
+    // Take the ID before awaiting the deposit, so a shard sealed meanwhile cannot hand the
+    // same ID to its successor. A failed deposit leaves a gap in the IDs.
+    let id = id::next_gig_id()?;
 
     // Create a new gig object.
-    let gig = Gig {
+    let mut gig = Gig {
         id,
         title: payload.title,
         description: payload.description,
-        employer: caller().to_string(),
+        employer,
         deadline: payload.deadline,
         assigned_to: None,
         status: GigStatus::Open,
         created_at: time(),
         updated_at: None,
+        submission_note: None,
+        submitted_at: None,
+        milestones: None,
+        payment: payload.escrow_amount.map(|amount| Payment { ledger: token.ledger, amount }),
+        category: payload.category,
+        tags,
+        budget: Some(Payment {
+            ledger: token.ledger,
+            amount: payload.budget,
+        }),
+        archived_from: None,
+        auto_approve_days: Some(auto_approve_days),
+        attachments: payload.attachments,
+        submission_attachments: None,
+        version: Some(0),
+        proposed_at: None,
+        visibility: Some(payload.visibility.unwrap_or_default()),
+        required_skills,
+        gig_type: Some(payload.gig_type.unwrap_or_default()),
+        delivery_terms: payload.delivery_terms,
+        depends_on,
+        org_id: payload.org_id,
+        dispute_split: None,
+        reminder_hours,
+        usd_budget: payload.usd_budget,
+        auto_approve_after_secs,
+        purge_at: None,
+        approvals: None,
+    };
+    validation::ensure_storable(&gig)?;
+    multisig::ensure_approvers(&gig, payload.escrow_amount)?;
+    if let Some(key) = idempotency_key {
+        idempotency::claim(employer, key)?;
+    }
+
+    // Pull the escrow from the employer before publishing the gig.
+    let deposit = match payload.escrow_amount {
+        Some(amount) => match escrow::deposit(&token, employer, amount).await {
+            Ok(block) => Some((amount, block)),
+            Err(error) => {
+                if let Some(key) = idempotency_key {
+                    idempotency::release(employer, key, None);
+                }
+                return Err(error);
+            }
+        },
+        None => None,
     };
 
     // Insert the gig into storage.
-    do_insert_gig(&gig);
-    gig
+    do_insert_gig(&mut gig);
+    content_filter::record(id, flagged_terms);
+    if let Some(key) = idempotency_key {
+        idempotency::release(employer, key, Some(id));
+    }
+    if let Some((amount, block)) = deposit {
+        escrow::record(id, employer, &token, amount, block, usd_rate);
+    }
+    Ok(gig)
 }
 
-/// Assign a gig to a worker.
-#[ic_cdk::update]
-pub fn assign_gig(id: u64, worker: String) -> Result<Gig, String> {
+/// Post several gigs in one call. Each payload is posted independently, so a rejected
+/// payload does not affect the others; results are returned in the order given.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn post_gigs_batch(payloads: Vec<GigPayload>) -> Vec<Result<Gig, GigError>> {
+    if payloads.len() > MAX_BATCH_SIZE {
+        let error = GigError::validation("payloads", "At most 20 gigs can be posted at once");
+        return payloads.iter().map(|_| Err(error.clone())).collect();
+    }
+    let mut results = Vec::with_capacity(payloads.len());
+    for payload in payloads {
+        results.push(post_gig(payload).await);
+    }
+    results
+}
+
+/// Post a fresh `Open` gig owned by the caller, copying the title, description, required
+/// skills and budget of gig `id`. The new deadline is as far from now as the original's
+/// was from its posting. Callers can clone their own gigs and public ones.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn clone_gig(id: u64) -> Result<Gig, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer can copy a gig that is not on the public board.
+    let public = gig.visibility() == Visibility::Public && visibility::is_listed(id);
+    if !public && !orgs::acts_for_employer(&gig, caller(), Permission::Post) {
+        return Err(GigError::unauthorized("Only public gigs or your own can be cloned"));
+    }
+    let duration = gig.deadline.saturating_sub(gig.created_at);
+    let payload = GigPayload {
+        title: gig.title,
+        description: gig.description,
+        deadline: time().saturating_add(duration),
+        budget: gig.budget.as_ref().map_or(0, |budget| budget.amount),
+        token: gig.budget.map(|budget| budget.ledger),
+        required_skills: gig.required_skills,
+        ..Default::default()
+    };
+    post_gig_as(caller(), payload).await
+}
+
+/// Propose a worker for a gig. The gig becomes `Assigned` once the worker accepts, and
+/// returns to `Open` if they do not within the acceptance window.
+/// `expected_version` must match the gig's current version.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn assign_gig(id: u64, worker: Principal, expected_version: u64) -> Result<Gig, GigError> {
+    // Reject workers that do not identify a real principal.
+    if worker == Principal::anonymous() {
+        return Err(GigError::validation(
+            "worker",
+            "Gigs cannot be assigned to the anonymous principal",
+        ));
+    }
+    // Only registered workers can be assigned.
+    if !profiles::is_registered(worker) {
+        return Err(GigError::validation("worker", "Worker has not registered a profile"));
+    }
+    if moderation::is_banned(worker) {
+        return Err(GigError::validation("worker", "Worker is banned"));
+    }
     GIG_STORAGE.with(|storage| {
         let mut storage = storage.borrow_mut();
         match storage.get(&id) {
             Some(mut gig) => {
-                // Ensure only the employer can assign the gig.
-                if gig.employer != caller().to_string() {
-                    return Err("Only the employer can assign this gig".to_string());
-                }
-                // Ensure the gig is open before assignment.
-                if gig.status != GigStatus::Open {
-                    return Err("Gig is not open for assignment".to_string());
+                // Ensure only the employer, their organization or a delegate can assign it.
+                if !orgs::acts_for_employer(&gig, caller(), Permission::Assign) {
+                    return Err(GigError::unauthorized("Only the employer can assign this gig"));
                 }
+                ensure_version(&gig, expected_version)?;
+                dependencies::ensure_met(&storage, &gig)?;
+                // Ensure the gig can take a proposed worker.
+                state_machine::transition(&mut gig, GigStatus::PendingAcceptance)?;
                 // Update gig details.
                 gig.assigned_to = Some(worker);
-                gig.status = GigStatus::Assigned;
-                gig.updated_at = Some(time());
-                storage.insert(gig.id, gig.clone());
+                gig.proposed_at = Some(time());
+                gig.updated_at = gig.proposed_at;
+                insert_gig(&mut storage, &mut gig);
                 Ok(gig)
             }
-            None => Err("Gig not found".to_string()),
+            None => Err(GigError::not_found("Gig")),
         }
     })
 }
 
-/// Approve a gig completion.
-#[ic_cdk::update]
-pub fn approve_gig(id: u64) -> Result<Gig, String> {
+/// Accept a proposed assignment. Proposed worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn accept_assignment(id: u64) -> Result<Gig, GigError> {
+    respond_to_assignment(id, true)
+}
+
+/// Decline a proposed assignment, returning the gig to `Open`. Proposed worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn decline_assignment(id: u64) -> Result<Gig, GigError> {
+    respond_to_assignment(id, false)
+}
+
+/// Settle a pending assignment with the proposed worker's answer.
+fn respond_to_assignment(id: u64, accept: bool) -> Result<Gig, GigError> {
+    if accept {
+        moderation::ensure_not_banned(caller())?;
+    }
     GIG_STORAGE.with(|storage| {
         let mut storage = storage.borrow_mut();
         match storage.get(&id) {
             Some(mut gig) => {
-                // Ensure only the employer can approve the gig.
-                if gig.employer != caller().to_string() {
-                    return Err("Only the employer can approve this gig".to_string());
+                // Ensure only the proposed worker can respond.
+                if gig.assigned_to != Some(caller()) {
+                    return Err(GigError::unauthorized("Only the proposed worker can respond"));
+                }
+                if gig.status != GigStatus::PendingAcceptance {
+                    return Err(GigError::invalid_state("PendingAcceptance", gig.status));
+                }
+                if accept {
+                    state_machine::transition(&mut gig, GigStatus::Assigned)?;
+                } else {
+                    state_machine::transition(&mut gig, GigStatus::Open)?;
+                    gig.assigned_to = None;
                 }
-                // Update gig status to approved.
-                gig.status = GigStatus::Approved;
                 gig.updated_at = Some(time());
-                storage.insert(gig.id, gig.clone());
+                insert_gig(&mut storage, &mut gig);
+                Ok(gig)
+            }
+            None => Err(GigError::not_found("Gig")),
+        }
+    })
+}
+
+/// Submit the work for an assigned gig so the employer can approve it. A SHA-256
+/// `deliverable_hash` commits to the delivered artifact, which the worker can reveal later
+/// with `reveal_deliverable`.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn submit_work(
+    gig_id: u64,
+    submission_note: String,
+    attachments: Option<Vec<u64>>,
+    deliverable_hash: Option<Vec<u8>>,
+) -> Result<Gig, GigError> {
+    if let Some(ids) = &attachments {
+        attachments::ensure_attachable(ids)?;
+    }
+    if let Some(hash) = &deliverable_hash {
+        deliverables::validate_hash(hash)?;
+    }
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        match storage.get(&gig_id) {
+            Some(mut gig) => {
+                // Ensure only the assigned worker can submit.
+                if gig.assigned_to != Some(caller()) {
+                    return Err(GigError::unauthorized("Only the assigned worker can submit work"));
+                }
+                // Ensure the gig is currently being worked on.
+                state_machine::transition(&mut gig, GigStatus::Submitted)?;
+                // Record the submission.
+                gig.submission_note = Some(submission_note);
+                gig.submission_attachments = attachments;
+                gig.submitted_at = Some(time());
+                gig.updated_at = gig.submitted_at;
+                insert_gig(&mut storage, &mut gig);
+                if let Some(hash) = deliverable_hash {
+                    deliverables::commit(gig_id, caller(), hash);
+                }
                 Ok(gig)
             }
-            None => Err("Gig not found".to_string()),
+            None => Err(GigError::not_found("Gig")),
         }
     })
 }
 
-/// Update a gig.
-#[ic_cdk::update]
-pub fn update_gig(id: u64, payload: GigPayload) -> Result<Gig, String> {
+/// Approve a gig completion, releasing any escrowed funds to the worker. Gigs with a budget
+/// above `multisig_threshold` stay `Submitted` until enough approvers have called this, and
+/// are never auto-approved.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn approve_gig(id: u64) -> Result<Gig, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer, their organization or a delegate can approve the gig.
+    if !orgs::acts_for_employer(&gig, caller(), Permission::Approve) {
+        return Err(GigError::unauthorized("Only the employer can approve this gig"));
+    }
+    if gig.status != GigStatus::Submitted {
+        return Err(GigError::invalid_state("Submitted", gig.status));
+    }
+    // High-value gigs wait for enough approvers to sign before any escrow moves.
+    if !multisig::sign(id, caller(), Release::Completion)? {
+        return GIG_STORAGE
+            .with(|storage| storage.borrow().get(&id))
+            .ok_or_else(|| GigError::not_found("Gig"));
+    }
+    approve_submission(id).await
+}
+
+/// Approve submitted work and release its escrow to the worker. Hourly gigs pay the hours
+/// the worker logged and that were not rejected, gigs with delivery terms add their bonus
+/// or take off their penalty, and whatever escrow is left over goes back to the employer.
+/// Callers are responsible for checking that the approval is authorised.
+async fn approve_submission(id: u64) -> Result<Gig, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Only submitted work can be approved; disputed work is approved by a ruling.
+    if gig.status != GigStatus::Submitted {
+        return Err(GigError::invalid_state("Submitted", gig.status));
+    }
+    state_machine::ensure_transition(&gig.status, &GigStatus::Approved)?;
+    // Team gigs pay each worker their share.
+    if teams::is_team_gig(id) {
+        return teams::approve_all(id).await;
+    }
+
+    // Pay the worker before the approval is recorded.
+    if let Some(escrow) = escrow::get_escrow(id).filter(|_| escrow::is_locked(id)) {
+        let worker = gig
+            .assigned_to
+            .ok_or_else(|| GigError::not_found("Assigned worker"))?;
+        let base = match gig.gig_type() {
+            // Plain fixed-price gigs pay out the whole escrow.
+            GigType::Fixed if gig.delivery_terms.is_none() => None,
+            GigType::Fixed => Some(gig.budget.as_ref().map_or(0, |budget| budget.amount)),
+            GigType::Hourly { rate, .. } => {
+                Some(rate.saturating_mul(timesheets::payable_hours(id, worker) as u64))
+            }
+        };
+        match base {
+            None => escrow::release(id, worker).await?,
+            Some(base) => {
+                // Milestones may already have paid part of what is owed.
+                let owed = clauses::adjust(&gig, base)
+                    .saturating_sub(escrow.released_amount.unwrap_or(0))
+                    .min(escrow.remaining());
+                escrow::release_partial(id, worker, owed).await?;
+            }
+        }
+    }
+
+    let gig = GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&id).ok_or_else(|| GigError::not_found("Gig"))?;
+        // The gig may have been disputed or sent back while the payout was awaited; its
+        // new state wins over this approval.
+        if gig.status != GigStatus::Submitted {
+            return Err(GigError::invalid_state("Submitted", gig.status));
+        }
+        // Update gig status to approved.
+        state_machine::transition(&mut gig, GigStatus::Approved)?;
+        gig.updated_at = Some(time());
+        insert_gig(&mut storage, &mut gig);
+        if let Some(worker) = gig.assigned_to {
+            reputation::record_completion(gig.employer, worker);
+        }
+        Ok::<_, GigError>(gig)
+    })?;
+    if let GigType::Hourly { .. } = gig.gig_type() {
+        timesheets::approve_pending(id);
+    }
+    // Whatever the payout did not use goes back to the employer.
+    if escrow::is_locked(id) {
+        escrow::refund_or_queue(id).await;
+    }
+    Ok(gig)
+}
+
+/// Update a gig. `expected_version` must match the gig's current version.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn update_gig(
+    id: u64,
+    mut payload: GigPayload,
+    expected_version: u64,
+) -> Result<Gig, GigError> {
     GIG_STORAGE.with(|storage| {
         let mut storage = storage.borrow_mut();
         match storage.get(&id) {
             Some(mut gig) => {
-                // Ensure only the employer can update the gig.
-                if gig.employer != caller().to_string() {
-                    return Err("Only the employer can update this gig".to_string());
+                // Ensure only the employer or a delegate can update the gig.
+                if !delegates::may_act(&gig, caller(), Permission::Post) {
+                    return Err(GigError::unauthorized("Only the employer can update this gig"));
+                }
+                ensure_version(&gig, expected_version)?;
+                // Prevent updates to approved, archived or deleted gigs.
+                if matches!(
+                    gig.status,
+                    GigStatus::Approved | GigStatus::Archived | GigStatus::PendingDeletion
+                ) {
+                    return Err(GigError::invalid_state(
+                        "any status except Approved, Archived or PendingDeletion",
+                        gig.status,
+                    ));
+                }
+                timesheets::apply_gig_type(&mut payload)?;
+                let gig_type = payload.gig_type.clone().unwrap_or_default();
+                timesheets::ensure_type_change(&gig, &gig_type)?;
+                clauses::ensure_change(&gig, &payload.delivery_terms)?;
+                if let Some(terms) = &payload.delivery_terms {
+                    clauses::validate(terms)?;
                 }
-                // Prevent updates to approved gigs.
-                if gig.status == GigStatus::Approved {
-                    return Err("Approved gigs cannot be updated".to_string());
+                validation::validate_gig_payload(&payload)?;
+                let flagged_terms =
+                    content_filter::screen(&payload.title, &payload.description)?;
+                let depends_on = payload
+                    .depends_on
+                    .take()
+                    .map(|ids| dependencies::validate(&storage, Some(id), gig.employer, ids))
+                    .transpose()?;
+                dependencies::ensure_change(&gig, depends_on.as_deref().unwrap_or_default())?;
+                if payload.deadline != gig.deadline {
+                    validation::validate_deadline(payload.deadline)?;
+                }
+                if gig.budget.as_ref().map(|budget| budget.amount) != Some(payload.budget) {
+                    validation::ensure_budget_allowed(gig.employer, payload.budget)?;
+                }
+                let auto_approve_days = auto_approve_days(payload.auto_approve_days)?;
+                let auto_approve_after_secs =
+                    auto_approve_after_secs(payload.auto_approve_after_secs)?;
+                // The window the worker submitted under cannot be stretched.
+                if gig.status == GigStatus::Submitted
+                    && (gig.auto_approve_days != Some(auto_approve_days)
+                        || gig.auto_approve_after_secs != auto_approve_after_secs)
+                {
+                    return Err(GigError::validation(
+                        "auto_approve_days",
+                        "Cannot change once work is submitted",
+                    ));
+                }
+                if let Some(ids) = &payload.attachments {
+                    attachments::ensure_attachable(ids)?;
+                }
+                let ledger = payload.token.unwrap_or_else(ledger::icp_ledger);
+                changes::ensure_scope_kept(&gig, &payload, ledger)?;
+                // Switching currency needs an accepted token and must not orphan the escrow.
+                if gig.budget.as_ref().map(|budget| budget.ledger) != Some(ledger) {
+                    tokens::accepted(ledger)?;
+                    if gig.payment.as_ref().is_some_and(|payment| payment.ledger != ledger) {
+                        return Err(GigError::validation(
+                            "token",
+                            "Must match the token held in escrow",
+                        ));
+                    }
                 }
                 // Update gig details.
                 gig.title = payload.title;
                 gig.description = payload.description;
                 gig.deadline = payload.deadline;
+                gig.category = payload.category;
+                gig.tags = payload.tags.map(tags::normalize_tags).transpose()?;
+                // A budget edited in tokens no longer follows the USD price.
+                if gig.budget.as_ref().map(|budget| budget.amount) != Some(payload.budget) {
+                    gig.usd_budget = None;
+                }
+                gig.budget = Some(Payment { ledger, amount: payload.budget });
+                multisig::ensure_approvers(&gig, None)?;
+                gig.auto_approve_days = Some(auto_approve_days);
+                gig.auto_approve_after_secs = auto_approve_after_secs;
+                gig.attachments = payload.attachments;
+                gig.visibility = Some(payload.visibility.unwrap_or_default());
+                gig.required_skills = payload
+                    .required_skills
+                    .map(|skills| skills::normalize_skills(skills, MAX_REQUIRED_SKILLS))
+                    .transpose()?;
+                gig.gig_type = Some(gig_type);
+                gig.delivery_terms = payload.delivery_terms;
+                gig.depends_on = depends_on;
+                gig.reminder_hours =
+                    payload.reminder_hours.map(reminders::validate).transpose()?;
+                gig.updated_at = Some(time());
+                validation::ensure_storable(&gig)?;
+                // Pushing the deadline of an expired gig into the future reopens it.
+                if gig.status == GigStatus::Expired && gig.deadline > time() {
+                    state_machine::transition(&mut gig, GigStatus::Open)?;
+                    gig.assigned_to = None;
+                    teams::disband(id);
+                }
+                insert_gig(&mut storage, &mut gig);
+                content_filter::record(id, flagged_terms);
+                Ok(gig)
+            }
+            None => Err(GigError::not_found("Gig")),
+        }
+    })
+}
+
+/// Cancel a gig that has not been delivered yet, refunding any escrow. Employer or their
+/// delegates only.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn cancel_gig(id: u64) -> Result<Gig, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer or a delegate can cancel the gig.
+    if !delegates::may_act(&gig, caller(), Permission::Post) {
+        return Err(GigError::unauthorized("Only the employer can cancel this gig"));
+    }
+    // Delivered or disputed work has to be approved or ruled on instead.
+    state_machine::ensure_transition(&gig.status, &GigStatus::Cancelled)?;
+
+    let gig = GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&id).ok_or_else(|| GigError::not_found("Gig"))?;
+        state_machine::transition(&mut gig, GigStatus::Cancelled)?;
+        gig.updated_at = Some(time());
+        insert_gig(&mut storage, &mut gig);
+        Ok::<_, GigError>(gig)
+    })?;
+    // Return the funds once the cancellation is recorded; failed refunds are retried.
+    escrow::refund_or_queue(id).await;
+    Ok(gig)
+}
+
+/// Return an assigned gig to `Open`. The assigned worker can step back at any time;
+/// the employer can only unassign once the deadline has passed.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn unassign_gig(id: u64) -> Result<Gig, GigError> {
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        match storage.get(&id) {
+            Some(mut gig) => {
+                let caller = caller();
+                let is_worker = gig.assigned_to == Some(caller);
+                let is_overdue_employer =
+                    delegates::may_act(&gig, caller, Permission::Assign) && gig.deadline < time();
+                if !is_worker && !is_overdue_employer {
+                    return Err(GigError::unauthorized(
+                        "Only the worker, or the employer after the deadline, can unassign",
+                    ));
+                }
+                if gig.status != GigStatus::Assigned {
+                    return Err(GigError::invalid_state("Assigned", gig.status));
+                }
+                // Return the gig to the board.
+                state_machine::transition(&mut gig, GigStatus::Open)?;
+                gig.assigned_to = None;
                 gig.updated_at = Some(time());
-                storage.insert(gig.id, gig.clone());
+                insert_gig(&mut storage, &mut gig);
+                teams::disband(id);
                 Ok(gig)
             }
-            None => Err("Gig not found".to_string()),
+            None => Err(GigError::not_found("Gig")),
         }
     })
 }
 
-/// Delete a gig.
-#[ic_cdk::update]
-pub fn delete_gig(id: u64) -> Result<String, String> {
+/// Delete a gig. The gig is kept as `PendingDeletion` for the configured grace period,
+/// during which `undo_delete` restores it, and is then purged by the timer.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn delete_gig(id: u64) -> Result<String, GigError> {
     GIG_STORAGE.with(|storage| {
         let mut storage = storage.borrow_mut();
         match storage.get(&id) {
-            Some(gig) => {
-                // Ensure only the employer can delete the gig.
-                if gig.employer != caller().to_string() {
-                    return Err("Only the employer can delete this gig".to_string());
-                }
-                // Remove gig from storage.
-                storage.remove(&id);
-                Ok("Gig deleted successfully".to_string())
+            Some(mut gig) => {
+                // Ensure only the employer or a delegate can delete the gig.
+                if !delegates::may_act(&gig, caller(), Permission::Post) {
+                    return Err(GigError::unauthorized("Only the employer can delete this gig"));
+                }
+                // Deleting would strand the escrowed funds.
+                if escrow::is_locked(id) {
+                    return Err(GigError::EscrowLocked);
+                }
+                // Schedule the purge, remembering where to restore the gig to.
+                let previous = gig.status.clone();
+                state_machine::transition(&mut gig, GigStatus::PendingDeletion)?;
+                gig.archived_from = Some(previous);
+                gig.updated_at = Some(time());
+                let grace_nanos = config::deletion_grace_secs().saturating_mul(1_000_000_000);
+                gig.purge_at = Some(time().saturating_add(grace_nanos));
+                insert_gig(&mut storage, &mut gig);
+                Ok("Gig scheduled for deletion".to_string())
             }
-            None => Err("Gig not found".to_string()),
+            None => Err(GigError::not_found("Gig")),
         }
     })
 }
 
-/// Retrieve all gigs.
+/// Cancel the deletion of a gig still in its grace period, restoring the status it had.
+/// Employer only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn undo_delete(id: u64) -> Result<Gig, GigError> {
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&id).ok_or_else(|| GigError::not_found("Gig"))?;
+        // Ensure only the employer or a delegate can undo the deletion.
+        if !delegates::may_act(&gig, caller(), Permission::Post) {
+            return Err(GigError::unauthorized("Only the employer can undo this deletion"));
+        }
+        if gig.status != GigStatus::PendingDeletion {
+            return Err(GigError::invalid_state("PendingDeletion", gig.status));
+        }
+        let previous = gig.archived_from.take().unwrap_or_default();
+        state_machine::transition(&mut gig, previous)?;
+        gig.purge_at = None;
+        gig.updated_at = Some(time());
+        insert_gig(&mut storage, &mut gig);
+        Ok(gig)
+    })
+}
+
+/// Permanently remove a deleted gig before its grace period ends. Admin only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn purge_gig(id: u64) -> Result<(), GigError> {
+    roles::ensure_admin()?;
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let gig = storage.get(&id).ok_or_else(|| GigError::not_found("Gig"))?;
+        if gig.status != GigStatus::PendingDeletion {
+            return Err(GigError::invalid_state("PendingDeletion", gig.status));
+        }
+        remove_gig(&mut storage, id);
+        Ok(())
+    })
+}
+
+/// Remove every gig whose deletion grace period has ended.
+fn purge_due_deletions() {
+    let now = time();
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        for id in indexes::ids_with_status(&GigStatus::PendingDeletion) {
+            if storage.get(&id).is_some_and(|gig| gig.purge_at.is_some_and(|at| at <= now)) {
+                remove_gig(&mut storage, id);
+            }
+        }
+    });
+}
+
+/// Retrieve a summary of every gig on the public board: public gigs not hidden by
+/// moderators. Gigs are ordered by `sort_by`, or by ID if it is not given.
 #[ic_cdk::query]
-pub fn get_all_gigs() -> Vec<Gig> {
-    GIG_STORAGE.with(|storage| storage.borrow().iter().map(|(_, gig)| gig).collect())
+pub fn get_all_gigs(sort_by: Option<SortBy>) -> Vec<GigSummary> {
+    listed_page(sort_by, 0, u64::MAX)
 }
 
-/// Retrieve a specific gig by ID.
+/// Retrieve a page of the public board ordered by `sort_by`, or by ID if it is not given.
+/// `limit` is capped at `MAX_PAGE_SIZE`.
+#[ic_cdk::query]
+pub fn get_gigs_paginated(offset: u64, limit: u64, sort_by: Option<SortBy>) -> GigPage {
+    let gigs = listed_page(sort_by, offset, limit.min(MAX_PAGE_SIZE));
+    let total = GIG_STORAGE.with(|storage| storage.borrow().len());
+    GigPage { gigs, total: visibility::listed_count(total) }
+}
+
+/// Summaries of listed gigs in the given order, skipping `offset` and taking `limit`.
+fn listed_page(sort_by: Option<SortBy>, offset: u64, limit: u64) -> Vec<GigSummary> {
+    let mut gigs = Vec::new();
+    let mut skipped = 0;
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        indexes::visit_sorted(sort_by, |id| {
+            if (gigs.len() as u64) >= limit {
+                return false;
+            }
+            if !visibility::is_listed(id) {
+                return true;
+            }
+            if skipped < offset {
+                skipped += 1;
+                return true;
+            }
+            if let Some(gig) = storage.get(&id) {
+                gigs.push(GigSummary::from(&gig));
+            }
+            true
+        });
+    });
+    gigs
+}
+
+/// Retrieve a specific gig by ID. Hidden and invite-only gigs are only returned to their
+/// parties, invited workers and moderators.
 #[ic_cdk::query]
 pub fn get_gig(id: u64) -> Option<Gig> {
-    GIG_STORAGE.with(|storage| storage.borrow().get(&id))
+    GIG_STORAGE
+        .with(|storage| storage.borrow().get(&id))
+        .filter(visibility::can_view)
+}
+
+/// Retrieve several gigs by ID, in the order given. Missing gigs, and hidden gigs the
+/// caller may not see, are `None`. At most `MAX_PAGE_SIZE` IDs are looked up.
+#[ic_cdk::query]
+pub fn get_gigs_by_ids(ids: Vec<u64>) -> Vec<Option<Gig>> {
+    ids.into_iter().take(MAX_PAGE_SIZE as usize).map(get_gig).collect()
+}
+
+/// Tag fresh stable memory with the current schema and start the background timers.
+#[ic_cdk::init]
+fn init() {
+    migrations::stamp_current_version();
+    tokens::seed_default();
+    certified::rebuild();
+    deadlines::start_timer();
+}
+
+/// All state lives in stable structures, so nothing needs serialising; the version tag
+/// records the layout this build wrote for the next build's migrations. After a restore
+/// the stable memory is the source's, version tag included, and is left untouched.
+#[ic_cdk::pre_upgrade]
+fn pre_upgrade() {
+    if backup::is_restoring() {
+        return;
+    }
+    migrations::stamp_current_version();
+}
+
+/// Migrate stable data written by an older build, remember when the upgrade happened and
+/// restart the background timers.
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    migrations::run_migrations();
+    health::record_upgrade();
+    certified::rebuild();
+    deadlines::start_timer();
 }
 
 /// Helper function to insert a gig into storage.
-fn do_insert_gig(gig: &Gig) {
+fn do_insert_gig(gig: &mut Gig) {
     GIG_STORAGE.with(|storage| {
-        storage.borrow_mut().insert(gig.id, gig.clone());
+        insert_gig(&mut storage.borrow_mut(), gig);
     });
 }
 
+/// Insert a gig into the borrowed gig map, keeping the secondary indexes, the audit log,
+/// the event feed, the certified tree, the statistics, the leaderboards and the market
+/// rates in sync, minting completion certificates and crediting referral bonuses. Every
+/// write to `GIG_STORAGE` must go through this or `remove_gig`. The gig's version is bumped
+/// in place.
+fn insert_gig(storage: &mut StableBTreeMap<u64, Gig, Memory>, gig: &mut Gig) {
+    let stored = storage.get(&gig.id);
+    multisig::expire(stored.as_ref(), gig);
+    gig.version = Some(stored.map_or(0, |previous| previous.version() + 1));
+    let previous = storage.insert(gig.id, gig.clone());
+    indexes::reindex(previous.as_ref(), Some(gig));
+    audit::record(previous.as_ref(), Some(gig));
+    events::emit(previous.as_ref(), Some(gig));
+    certified::update(previous.as_ref(), Some(gig));
+    stats::record(previous.as_ref(), Some(gig));
+    certificates::issue(previous.as_ref(), gig);
+    leaderboard::record(previous.as_ref(), gig);
+    market::record(previous.as_ref(), gig);
+    referrals::record(previous.as_ref(), gig);
+}
+
+/// Remove a gig from the borrowed gig map along with its index entries.
+fn remove_gig(storage: &mut StableBTreeMap<u64, Gig, Memory>, id: u64) -> Option<Gig> {
+    let previous = storage.remove(&id);
+    indexes::reindex(previous.as_ref(), None);
+    audit::record(previous.as_ref(), None);
+    events::emit(previous.as_ref(), None);
+    certified::update(previous.as_ref(), None);
+    stats::record(previous.as_ref(), None);
+    moderation::forget_gig(id);
+    content_filter::forget_gig(id);
+    trending::forget_gig(id);
+    analytics::forget_gig(id);
+    previous
+}
+
 // Export candid interface.
 ic_cdk::export_candid!();
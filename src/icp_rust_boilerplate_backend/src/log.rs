@@ -0,0 +1,110 @@
+use candid::{Decode, Encode};
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::{ensure_controller, get_memory, Memory, LOGS_MEMORY_ID};
+
+/// Most entries kept; each new entry past this drops the oldest.
+const CAPACITY: u64 = 10_000;
+/// Longest message kept, in bytes; longer messages are cut short.
+const MAX_MESSAGE_LEN: usize = 500;
+/// Most entries `get_logs` returns in one call.
+const MAX_LOGS_RETURNED: usize = 1_000;
+
+/// Severity of a log entry, least severe first.
+#[derive(
+    candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug,
+)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// A message the canister logged while handling a call or a timer.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub seq: u64,           // Position in the log; keeps counting as old entries are dropped.
+    pub timestamp: u64,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+impl Storable for LogEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for LogEntry {
+    const MAX_SIZE: u32 = 640;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// The most recent `CAPACITY` log entries keyed by sequence number.
+    static LOGS: RefCell<StableBTreeMap<u64, LogEntry, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(LOGS_MEMORY_ID)));
+}
+
+/// Append an entry to the log, dropping the oldest once it is full.
+fn write(level: LogLevel, message: &str) {
+    let mut end = message.len().min(MAX_MESSAGE_LEN);
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+    LOGS.with(|logs| {
+        let mut logs = logs.borrow_mut();
+        let seq = logs.last_key_value().map_or(0, |(seq, _)| seq + 1);
+        let entry =
+            LogEntry { seq, timestamp: time(), level, message: message[..end].to_string() };
+        logs.insert(seq, entry);
+        if seq >= CAPACITY {
+            logs.remove(&(seq - CAPACITY));
+        }
+    });
+}
+
+/// Log detail that only matters while chasing a problem.
+pub fn debug(message: &str) {
+    write(LogLevel::Debug, message);
+}
+
+/// Log a notable event in the canister's normal operation.
+pub fn info(message: &str) {
+    write(LogLevel::Info, message);
+}
+
+/// Log a failure the canister recovers from, such as one retried by the next sweep.
+pub fn warn(message: &str) {
+    write(LogLevel::Warn, message);
+}
+
+/// Log a failure that needs a controller's attention.
+pub fn error(message: &str) {
+    write(LogLevel::Error, message);
+}
+
+/// Retrieve the entries logged at or after `since` (nanoseconds since the epoch) at
+/// `level` or above, oldest first, at most 1000 at a time. To read on, pass the timestamp
+/// of the last entry returned; entries sharing it come back again and can be told apart
+/// by `seq`. Controller only.
+#[ic_cdk::query]
+pub fn get_logs(since: u64, level: LogLevel) -> Result<Vec<LogEntry>, GigError> {
+    ensure_controller()?;
+    Ok(LOGS.with(|logs| {
+        logs.borrow()
+            .iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| entry.timestamp >= since && entry.level >= level)
+            .take(MAX_LOGS_RETURNED)
+            .collect()
+    }))
+}
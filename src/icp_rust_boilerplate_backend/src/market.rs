@@ -0,0 +1,212 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::state_machine;
+use crate::tags::{self, Category, Tag};
+use crate::{
+    get_memory, Gig, GigStatus, Memory, StorablePrincipal, CATEGORY_MARKET_SAMPLES_MEMORY_ID,
+    GIG_STORAGE, MARKET_SAMPLE_TIMES_MEMORY_ID, SKILL_MARKET_SAMPLES_MEMORY_ID,
+};
+
+/// Age after which an approved gig no longer counts towards market rates: 180 days.
+const MARKET_WINDOW_NANOS: u64 = 180 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Budget statistics of recently approved gigs paid in one token.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct MarketRate {
+    pub ledger: Principal,
+    pub count: u64,
+    pub mean: u64,          // In the token's base units, rounded down.
+    pub median: u64,        // The mean of the middle two budgets when `count` is even.
+}
+
+/// An approved gig's budget, and what it was counted under, kept so it can be uncounted
+/// once it leaves the window.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Sample {
+    ledger: Principal,
+    amount: u64,
+    skills: Vec<String>,
+    category: Option<Category>,
+}
+
+impl Storable for Sample {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Sample {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Budgets keyed by `((market, ledger), amount), gig_id)`, cheapest first within each
+/// market and token.
+type SampleIndex<K> = StableBTreeMap<(((K, StorablePrincipal), u64), u64), (), Memory>;
+
+thread_local! {
+    /// Budgets of recently approved gigs under each required skill.
+    static SKILL_SAMPLES: RefCell<SampleIndex<Tag>> =
+        RefCell::new(StableBTreeMap::init(get_memory(SKILL_MARKET_SAMPLES_MEMORY_ID)));
+
+    /// Budgets of recently approved gigs under each category, keyed by category key.
+    static CATEGORY_SAMPLES: RefCell<SampleIndex<u8>> =
+        RefCell::new(StableBTreeMap::init(get_memory(CATEGORY_MARKET_SAMPLES_MEMORY_ID)));
+
+    /// Counted budgets keyed by `(approved_at, gig_id)`, oldest first, for pruning.
+    static SAMPLE_TIMES: RefCell<StableBTreeMap<(u64, u64), Sample, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(MARKET_SAMPLE_TIMES_MEMORY_ID)));
+}
+
+/// Count or uncount a sample in the skill and category indexes.
+fn apply(gig_id: u64, sample: &Sample, insert: bool) {
+    let ledger = StorablePrincipal(sample.ledger);
+    SKILL_SAMPLES.with(|index| {
+        let mut index = index.borrow_mut();
+        for skill in &sample.skills {
+            let key = (((Tag(skill.clone()), ledger), sample.amount), gig_id);
+            if insert {
+                index.insert(key, ());
+            } else {
+                index.remove(&key);
+            }
+        }
+    });
+    if let Some(category) = &sample.category {
+        let key = (((tags::category_key(category), ledger), sample.amount), gig_id);
+        CATEGORY_SAMPLES.with(|index| {
+            if insert {
+                index.borrow_mut().insert(key, ());
+            } else {
+                index.borrow_mut().remove(&key);
+            }
+        });
+    }
+}
+
+/// Count the budget of a gig approved at `approved_at`, unless it is already out of the
+/// window. Gigs without a budget are not counted.
+fn count(gig: &Gig, approved_at: u64) {
+    let Some(budget) = &gig.budget else {
+        return;
+    };
+    if approved_at.saturating_add(MARKET_WINDOW_NANOS) <= time() {
+        return;
+    }
+    let sample = Sample {
+        ledger: budget.ledger,
+        amount: budget.amount,
+        skills: gig
+            .required_skills
+            .iter()
+            .flatten()
+            .map(|skill| tags::normalize(skill))
+            .filter(|skill| skill.len() <= Tag::MAX_SIZE as usize)
+            .collect(),
+        category: gig.category.clone(),
+    };
+    apply(gig.id, &sample, true);
+    SAMPLE_TIMES.with(|times| times.borrow_mut().insert((approved_at, gig.id), sample));
+}
+
+/// Count the budget of a gig that was just approved towards its skills' and category's
+/// market rates.
+pub fn record(previous: Option<&Gig>, gig: &Gig) {
+    if state_machine::is_completion(previous, gig) {
+        count(gig, time());
+    }
+}
+
+/// Stop counting budgets approved more than `MARKET_WINDOW_NANOS` ago. Runs from the
+/// periodic sweep.
+pub fn prune() {
+    let cutoff = time().saturating_sub(MARKET_WINDOW_NANOS);
+    let expired: Vec<((u64, u64), Sample)> =
+        SAMPLE_TIMES.with(|times| times.borrow().range(..(cutoff, 0)).collect());
+    for ((approved_at, gig_id), sample) in expired {
+        apply(gig_id, &sample, false);
+        SAMPLE_TIMES.with(|times| times.borrow_mut().remove(&(approved_at, gig_id)));
+    }
+}
+
+/// Count the approved gigs still in the window, for canisters upgraded from before market
+/// rates were kept. Approval times are taken from the gigs' last update.
+pub fn backfill() {
+    GIG_STORAGE.with(|storage| {
+        for (_, gig) in storage.borrow().iter() {
+            if gig.status == GigStatus::Approved && gig.dispute_split.is_none() {
+                count(&gig, gig.updated_at.unwrap_or(gig.created_at));
+            }
+        }
+    });
+}
+
+/// Statistics of `(ledger, amount)` pairs sorted by ledger and then amount, one per ledger.
+fn summarize(amounts: impl Iterator<Item = (Principal, u64)>) -> Vec<MarketRate> {
+    let mut groups: Vec<(Principal, Vec<u64>)> = Vec::new();
+    for (ledger, amount) in amounts {
+        match groups.last_mut() {
+            Some((last, group)) if *last == ledger => group.push(amount),
+            _ => groups.push((ledger, vec![amount])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(ledger, amounts)| {
+            let count = amounts.len();
+            let sum: u128 = amounts.iter().map(|amount| *amount as u128).sum();
+            let middle = count / 2;
+            let median = if count % 2 == 0 {
+                ((amounts[middle - 1] as u128 + amounts[middle] as u128) / 2) as u64
+            } else {
+                amounts[middle]
+            };
+            MarketRate { ledger, count: count as u64, mean: (sum / count as u128) as u64, median }
+        })
+        .collect()
+}
+
+/// Lowest principal, to start a range scan over every ledger.
+fn min_ledger() -> StorablePrincipal {
+    StorablePrincipal(Principal::from_slice(&[]))
+}
+
+/// Retrieve the mean and median budget of gigs requiring a skill that were approved in the
+/// last 180 days, one entry per token they were paid in.
+#[ic_cdk::query]
+pub fn get_market_rates(skill: String) -> Vec<MarketRate> {
+    let skill = Tag(tags::normalize(&skill));
+    if skill.0.len() > Tag::MAX_SIZE as usize {
+        return Vec::new();
+    }
+    SKILL_SAMPLES.with(|index| {
+        let index = index.borrow();
+        let amounts = index
+            .range((((skill.clone(), min_ledger()), 0), 0)..)
+            .take_while(|((((key, _), _), _), _)| *key == skill)
+            .map(|((((_, ledger), amount), _), _)| (ledger.0, amount));
+        summarize(amounts)
+    })
+}
+
+/// Retrieve the mean and median budget of gigs in a category that were approved in the
+/// last 180 days, one entry per token they were paid in.
+#[ic_cdk::query]
+pub fn get_category_market_rates(category: Category) -> Vec<MarketRate> {
+    let category = tags::category_key(&category);
+    CATEGORY_SAMPLES.with(|index| {
+        let index = index.borrow();
+        let amounts = index
+            .range((((category, min_ledger()), 0), 0)..)
+            .take_while(|((((key, _), _), _), _)| *key == category)
+            .map(|((((_, ledger), amount), _), _)| (ledger.0, amount));
+        summarize(amounts)
+    })
+}
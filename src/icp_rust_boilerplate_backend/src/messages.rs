@@ -0,0 +1,115 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{StableBTreeMap, StableLog, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::disputes;
+use crate::error::GigError;
+use crate::moderation::{self, not_banned};
+use crate::rate_limit;
+use crate::teams;
+use crate::{
+    get_memory, Gig, Memory, GIG_STORAGE, MAX_PAGE_SIZE, MESSAGES_INDEX_MEMORY_ID,
+    MESSAGE_LOG_DATA_MEMORY_ID, MESSAGE_LOG_INDEX_MEMORY_ID,
+};
+
+/// Longest message body accepted, in bytes.
+const MAX_MESSAGE_LEN: usize = 2000;
+
+/// A message in a gig's thread.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub seq: u64,           // Position in the message log; increases across all threads.
+    pub gig_id: u64,
+    pub sender: Principal,
+    pub body: String,
+    pub sent_at: u64,
+}
+
+impl Storable for Message {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+thread_local! {
+    /// Append-only log of every message.
+    static MESSAGE_LOG: RefCell<StableLog<Message, Memory, Memory>> = RefCell::new(
+        StableLog::init(
+            get_memory(MESSAGE_LOG_INDEX_MEMORY_ID),
+            get_memory(MESSAGE_LOG_DATA_MEMORY_ID),
+        )
+        .expect("Cannot create the message log")
+    );
+
+    /// Log positions keyed by `(gig_id, seq)` so a gig's thread is contiguous.
+    static MESSAGES_INDEX: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(MESSAGES_INDEX_MEMORY_ID)));
+}
+
+/// Load a gig and ensure the caller takes part in its thread: the employer, the assigned
+/// worker or team members, or an arbitrator.
+fn participating_gig(gig_id: u64) -> Result<Gig, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    let caller = caller();
+    let is_party = gig.employer == caller || teams::workers(&gig).contains(&caller);
+    if !is_party && !disputes::is_arbitrator(caller) {
+        return Err(GigError::unauthorized(
+            "Only the employer, the assigned worker or an arbitrator can use this thread",
+        ));
+    }
+    Ok(gig)
+}
+
+/// Post a message to a gig's thread.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn send_message(gig_id: u64, body: String) -> Result<Message, GigError> {
+    let sender = caller();
+    moderation::ensure_not_banned(sender)?;
+    participating_gig(gig_id)?;
+    rate_limit::check(sender, rate_limit::Action::SendMessage)?;
+    if body.trim().is_empty() || body.len() > MAX_MESSAGE_LEN {
+        return Err(GigError::validation("body", "Message must be between 1 and 2000 bytes"));
+    }
+
+    MESSAGE_LOG.with(|log| {
+        let log = log.borrow();
+        let message = Message {
+            seq: log.len(),
+            gig_id,
+            sender,
+            body,
+            sent_at: time(),
+        };
+        let seq = log.append(&message).expect("Cannot append to the message log");
+        MESSAGES_INDEX.with(|index| index.borrow_mut().insert((gig_id, seq), ()));
+        Ok(message)
+    })
+}
+
+/// Retrieve a gig's messages, oldest first, starting after the message with sequence
+/// number `after`. `limit` is capped at `MAX_PAGE_SIZE`. Thread participants only.
+#[ic_cdk::query]
+pub fn get_messages(gig_id: u64, after: Option<u64>, limit: u64) -> Result<Vec<Message>, GigError> {
+    participating_gig(gig_id)?;
+    let start = after.map_or(0, |seq| seq.saturating_add(1));
+    let seqs: Vec<u64> = MESSAGES_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((gig_id, start)..=(gig_id, u64::MAX))
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|((_, seq), _)| seq)
+            .collect()
+    });
+    Ok(MESSAGE_LOG.with(|log| {
+        let log = log.borrow();
+        seqs.into_iter().filter_map(|seq| log.get(seq)).collect()
+    }))
+}
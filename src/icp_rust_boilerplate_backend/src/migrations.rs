@@ -0,0 +1,261 @@
+use candid::{Decode, Principal};
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, Cell, Memory as _, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::backup;
+use crate::config;
+use crate::disputes;
+use crate::indexes;
+use crate::log;
+use crate::market;
+use crate::profiles;
+use crate::stats;
+use crate::tags::Tag;
+use crate::tokens;
+use crate::{
+    get_memory, insert_gig, Gig, GigStatus, Memory, FEE_BPS_MEMORY_ID, GIG_STORAGE,
+    LEGACY_GIGS_MEMORY_ID, LEGACY_WORD_INDEX_MEMORY_ID, SCHEMA_VERSION_MEMORY_ID,
+};
+
+/// Version of the stable data layout written by this build. Bump it together with a new
+/// entry in `MIGRATIONS` whenever stored data needs converting.
+pub const CURRENT_SCHEMA_VERSION: u64 = 18;
+
+/// Migration steps as `(from_version, step)`. Each step upgrades data from `from_version`
+/// to `from_version + 1`.
+const MIGRATIONS: &[(u64, fn())] = &[
+    (0, rewrite_gigs),
+    (1, seed_tokens),
+    (2, index_all_gigs),
+    (3, seed_tokens),
+    (4, stats::rebuild),
+    (5, profiles::index_skills),
+    (6, index_all_gigs),
+    (7, index_all_gigs),
+    (8, move_fee_into_config),
+    (9, move_legacy_gigs),
+    (10, stats::rebuild),
+    (11, move_word_index),
+    (12, index_all_gigs),
+    (13, market::backfill),
+    (14, profiles::assign_referral_codes),
+    (15, disputes::freeze_disputed_escrows),
+    (16, backup::mark_in_use),
+    (17, schedule_archived_deletions),
+];
+
+thread_local! {
+    /// Version of the layout the stable data was last migrated to. Canisters deployed
+    /// before versioning existed read as version 0.
+    static SCHEMA_VERSION: RefCell<Cell<u64, Memory>> = RefCell::new(
+        Cell::init(get_memory(SCHEMA_VERSION_MEMORY_ID), 0)
+            .expect("Cannot create the schema version cell")
+    );
+}
+
+/// Stored schema version.
+pub fn schema_version() -> u64 {
+    SCHEMA_VERSION.with(|version| *version.borrow().get())
+}
+
+fn set_schema_version(version: u64) {
+    SCHEMA_VERSION
+        .with(|cell| cell.borrow_mut().set(version))
+        .expect("Cannot write the schema version");
+}
+
+/// Tag the stable data as being on the layout written by this build.
+pub fn stamp_current_version() {
+    set_schema_version(CURRENT_SCHEMA_VERSION);
+}
+
+/// Run every migration between the stored version and `CURRENT_SCHEMA_VERSION`.
+pub fn run_migrations() {
+    let stored = schema_version();
+    if stored > CURRENT_SCHEMA_VERSION {
+        ic_cdk::trap(&format!(
+            "Stable data is at schema version {} but this build only understands up to {}",
+            stored, CURRENT_SCHEMA_VERSION
+        ));
+    }
+    // Every step reads `GIG_STORAGE`, so gigs still in the 2048-byte map move first.
+    if stored < 10 {
+        move_legacy_gigs();
+    }
+    for (from, step) in MIGRATIONS {
+        if *from >= stored && *from < CURRENT_SCHEMA_VERSION {
+            step();
+            set_schema_version(from + 1);
+            log::info(&format!("Migrated stable data to schema version {}", from + 1));
+        }
+    }
+}
+
+/// Decode a stored gig, falling back to older encodings.
+pub fn decode_gig(bytes: &[u8]) -> Gig {
+    Decode!(bytes, Gig).unwrap_or_else(|_| Decode!(bytes, GigV0).unwrap().into())
+}
+
+/// v0 -> v1: rewrite every gig in the current encoding and rebuild the secondary indexes.
+fn rewrite_gigs() {
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let gigs: Vec<Gig> = storage.iter().map(|(_, gig)| gig).collect();
+        for mut gig in gigs {
+            insert_gig(&mut storage, &mut gig);
+        }
+    });
+}
+
+/// v17 -> v18: give gigs archived before deletions had a grace period one, so
+/// `undo_delete` brings them back and the timer purges them like any other deleted gig.
+fn schedule_archived_deletions() {
+    let grace_nanos = config::deletion_grace_secs().saturating_mul(1_000_000_000);
+    let purge_at = time().saturating_add(grace_nanos);
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let archived: Vec<Gig> = storage
+            .iter()
+            .map(|(_, gig)| gig)
+            .filter(|gig| gig.status == GigStatus::Archived)
+            .collect();
+        for mut gig in archived {
+            gig.status = GigStatus::PendingDeletion;
+            gig.purge_at = Some(purge_at);
+            insert_gig(&mut storage, &mut gig);
+        }
+    });
+}
+
+/// v1 -> v2: accept ICP in the newly introduced token list.
+/// v3 -> v4: accept ckBTC alongside it.
+fn seed_tokens() {
+    tokens::seed_default();
+}
+
+/// v2 -> v3, v6 -> v7, v7 -> v8 and v12 -> v13: build the keyword search index, and later
+/// the sort, deadline bucket and title indexes, for gigs posted before they existed.
+/// Rebuilding the other indexes is harmless.
+fn index_all_gigs() {
+    GIG_STORAGE.with(|storage| {
+        for (_, gig) in storage.borrow().iter() {
+            indexes::reindex(None, Some(&gig));
+        }
+    });
+}
+
+/// Stable encoding of `Gig` from before principals were stored as `Principal`.
+#[derive(Deserialize, candid::CandidType)]
+struct GigV0 {
+    id: u64,
+    title: String,
+    description: String,
+    employer: String,
+    deadline: u64,
+    assigned_to: Option<String>,
+    status: GigStatus,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl From<GigV0> for Gig {
+    fn from(legacy: GigV0) -> Self {
+        Gig {
+            id: legacy.id,
+            title: legacy.title,
+            description: legacy.description,
+            // Employers were always recorded from `caller()`, so the text is a valid principal.
+            employer: Principal::from_text(&legacy.employer).unwrap_or(Principal::anonymous()),
+            deadline: legacy.deadline,
+            // Free-form worker strings that never named a principal cannot be migrated.
+            assigned_to: legacy
+                .assigned_to
+                .and_then(|worker| Principal::from_text(worker).ok()),
+            status: legacy.status,
+            created_at: legacy.created_at,
+            updated_at: legacy.updated_at,
+            submission_note: None,
+            submitted_at: None,
+            milestones: None,
+            payment: None,
+            category: None,
+            tags: None,
+            budget: None,
+            archived_from: None,
+            auto_approve_days: None,
+            attachments: None,
+            submission_attachments: None,
+            version: None,
+            proposed_at: None,
+            visibility: None,
+            required_skills: None,
+            gig_type: None,
+            delivery_terms: None,
+            depends_on: None,
+            org_id: None,
+            dispute_split: None,
+            reminder_hours: None,
+            usd_budget: None,
+            auto_approve_after_secs: None,
+            purge_at: None,
+            approvals: None,
+        }
+    }
+}
+
+/// v8 -> v9: move the platform fee from its own cell into the config.
+fn move_fee_into_config() {
+    let fee_bps = Cell::<u16, Memory>::init(get_memory(FEE_BPS_MEMORY_ID), 0)
+        .map(|cell| *cell.get())
+        .unwrap_or(0);
+    let mut config = config::get();
+    config.fee_bps = fee_bps;
+    config::set(config);
+}
+
+/// A gig as stored in the map bounded to 2048 bytes, kept encoded.
+struct LegacyGig(Vec<u8>);
+
+impl Storable for LegacyGig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        LegacyGig(bytes.into_owned())
+    }
+}
+
+impl BoundedStorable for LegacyGig {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// v9 -> v10: copy every gig from the 2048-byte map into the larger one, re-encoded in
+/// the current layout, and empty the old map. Index entries are keyed by gig ID and stay
+/// valid. Does nothing once the old map is empty, or on canisters that never had it.
+fn move_legacy_gigs() {
+    let memory = get_memory(LEGACY_GIGS_MEMORY_ID);
+    if memory.size() == 0 {
+        return;
+    }
+    let legacy: StableBTreeMap<u64, LegacyGig, Memory> = StableBTreeMap::init(memory);
+    if legacy.is_empty() {
+        return;
+    }
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        for (id, gig) in legacy.iter() {
+            storage.insert(id, decode_gig(&gig.0));
+        }
+    });
+    StableBTreeMap::<u64, LegacyGig, Memory>::new(get_memory(LEGACY_GIGS_MEMORY_ID));
+}
+
+/// v11 -> v12: build the weighted keyword index, which now also covers tags, and empty the
+/// unweighted one it replaces.
+fn move_word_index() {
+    StableBTreeMap::<(Tag, u64), (), Memory>::new(get_memory(LEGACY_WORD_INDEX_MEMORY_ID));
+    index_all_gigs();
+}
@@ -0,0 +1,162 @@
+use ic_cdk::api::time;
+use ic_cdk::caller;
+
+use crate::delegates::{self, Permission};
+use crate::error::GigError;
+use crate::escrow;
+use crate::moderation::not_banned;
+use crate::multisig::{self, Release};
+use crate::orgs;
+use crate::teams;
+use crate::{do_insert_gig, insert_gig, Gig, GigStatus, GIG_STORAGE};
+
+/// Maximum number of milestones on a gig.
+const MAX_MILESTONES: usize = 5;
+/// Maximum length of a milestone title.
+const MAX_MILESTONE_TITLE_LEN: usize = 64;
+
+/// An incrementally delivered and paid part of a gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Milestone {
+    pub title: String,
+    pub amount: u64,                // Escrow share paid out on approval.
+    pub status: MilestoneStatus,
+    pub submitted_at: Option<u64>,
+    pub approved_at: Option<u64>,
+}
+
+/// Enum representing possible statuses of a milestone.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum MilestoneStatus {
+    Pending,    // Not yet delivered.
+    Submitted,  // Delivered by the worker, awaiting approval.
+    Approved,   // Approved by the employer and paid out.
+}
+
+/// Fetch a gig and check the caller is its employer or a delegate allowed to post.
+fn employer_gig(gig_id: u64) -> Result<Gig, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    if !delegates::may_act(&gig, caller(), Permission::Post) {
+        return Err(GigError::unauthorized("Only the employer can manage milestones"));
+    }
+    Ok(gig)
+}
+
+/// Look up a milestone by its position on the gig.
+fn milestone_mut(gig: &mut Gig, index: u32) -> Result<&mut Milestone, GigError> {
+    gig.milestones
+        .as_mut()
+        .and_then(|milestones| milestones.get_mut(index as usize))
+        .ok_or_else(|| GigError::not_found("Milestone"))
+}
+
+/// Add a milestone to an open or assigned gig. Employer or their delegates only.
+///
+/// On funded gigs the milestone amounts together must fit within the escrow.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn add_milestone(gig_id: u64, title: String, amount: u64) -> Result<Gig, GigError> {
+    let mut gig = employer_gig(gig_id)?;
+    if gig.status != GigStatus::Open && gig.status != GigStatus::Assigned {
+        return Err(GigError::invalid_state("Open or Assigned", gig.status));
+    }
+    // Team shares split the whole payout, leaving nothing for milestones to release.
+    if teams::is_team_gig(gig_id) {
+        return Err(GigError::validation("gig_id", "Team gigs cannot have milestones"));
+    }
+    if title.trim().is_empty() || title.len() > MAX_MILESTONE_TITLE_LEN {
+        return Err(GigError::validation("title", "Must be between 1 and 64 bytes"));
+    }
+    let milestones = gig.milestones.get_or_insert_with(Vec::new);
+    if milestones.len() >= MAX_MILESTONES {
+        return Err(GigError::validation("milestones", "A gig can have at most 5 milestones"));
+    }
+    if let Some(escrow) = escrow::get_escrow(gig_id) {
+        if amount <= escrow.fee() {
+            return Err(GigError::validation("amount", "Must exceed the ledger fee"));
+        }
+        let allocated: u64 = milestones.iter().map(|m| m.amount).sum();
+        if allocated + amount > escrow.amount {
+            return Err(GigError::validation("amount", "Milestones exceed the escrowed amount"));
+        }
+    }
+
+    milestones.push(Milestone {
+        title,
+        amount,
+        status: MilestoneStatus::Pending,
+        submitted_at: None,
+        approved_at: None,
+    });
+    gig.updated_at = Some(time());
+    do_insert_gig(&mut gig);
+    Ok(gig)
+}
+
+/// Mark a milestone as delivered. Assigned worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn submit_milestone(gig_id: u64, index: u32) -> Result<Gig, GigError> {
+    let mut gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    if gig.assigned_to != Some(caller()) {
+        return Err(GigError::unauthorized("Only the assigned worker can submit milestones"));
+    }
+    if gig.status != GigStatus::Assigned {
+        return Err(GigError::invalid_state("Assigned", gig.status));
+    }
+    let milestone = milestone_mut(&mut gig, index)?;
+    if milestone.status != MilestoneStatus::Pending {
+        return Err(GigError::invalid_state("Pending", &milestone.status));
+    }
+    milestone.status = MilestoneStatus::Submitted;
+    milestone.submitted_at = Some(time());
+    gig.updated_at = Some(time());
+    do_insert_gig(&mut gig);
+    Ok(gig)
+}
+
+/// Approve a delivered milestone, paying its amount out of escrow. Milestones of gigs with
+/// a budget above `multisig_threshold` are paid once enough approvers have called this.
+/// Employer, organization manager or delegate only.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn approve_milestone(gig_id: u64, index: u32) -> Result<Gig, GigError> {
+    let mut gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer, their organization or a delegate can approve milestones.
+    if !orgs::acts_for_employer(&gig, caller(), Permission::Approve) {
+        return Err(GigError::unauthorized("Only the employer can approve milestones"));
+    }
+    if gig.status != GigStatus::Assigned && gig.status != GigStatus::Submitted {
+        return Err(GigError::invalid_state("Assigned or Submitted", gig.status));
+    }
+    let worker = gig.assigned_to.ok_or_else(|| GigError::not_found("Assigned worker"))?;
+    let milestone = milestone_mut(&mut gig, index)?;
+    if milestone.status != MilestoneStatus::Submitted {
+        return Err(GigError::invalid_state("Submitted", &milestone.status));
+    }
+    let amount = milestone.amount;
+    if !multisig::sign(gig_id, caller(), Release::Milestone { index })? {
+        return GIG_STORAGE
+            .with(|storage| storage.borrow().get(&gig_id))
+            .ok_or_else(|| GigError::not_found("Gig"));
+    }
+
+    // Pay the worker before the approval is recorded.
+    if escrow::is_locked(gig_id) {
+        escrow::release_partial(gig_id, worker, amount).await?;
+    }
+
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        let milestone = milestone_mut(&mut gig, index)?;
+        milestone.status = MilestoneStatus::Approved;
+        milestone.approved_at = Some(time());
+        gig.updated_at = Some(time());
+        insert_gig(&mut storage, &mut gig);
+        Ok(gig)
+    })
+}
@@ -0,0 +1,207 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::backup;
+use crate::disputes::{self, Ruling};
+use crate::error::GigError;
+use crate::roles;
+use crate::teams;
+use crate::{
+    get_memory, Gig, Memory, StorablePrincipal, BANS_MEMORY_ID, GIG_STORAGE,
+    HIDDEN_GIGS_MEMORY_ID,
+};
+
+/// Maximum length of a moderation reason.
+const MAX_REASON_LEN: usize = 500;
+
+/// A principal barred from using the platform.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    pub principal: Principal,
+    pub reason: String,
+    pub banned_by: Principal,
+    pub banned_at: u64,
+}
+
+/// A gig removed from public listings by a moderator.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct HiddenGig {
+    pub gig_id: u64,
+    pub reason: String,
+    pub hidden_by: Principal,
+    pub hidden_at: u64,
+}
+
+impl Storable for Ban {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Ban {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for HiddenGig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for HiddenGig {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Bans keyed by principal.
+    static BANS: RefCell<StableBTreeMap<StorablePrincipal, Ban, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(BANS_MEMORY_ID)));
+
+    /// Hidden gigs keyed by gig ID.
+    static HIDDEN_GIGS: RefCell<StableBTreeMap<u64, HiddenGig, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(HIDDEN_GIGS_MEMORY_ID)));
+}
+
+/// Check a moderation reason.
+fn validate_reason(reason: &str) -> Result<(), GigError> {
+    if reason.trim().is_empty() || reason.len() > MAX_REASON_LEN {
+        return Err(GigError::validation("reason", "Reason must be between 1 and 500 bytes"));
+    }
+    Ok(())
+}
+
+/// Whether the principal is banned.
+pub fn is_banned(principal: Principal) -> bool {
+    BANS.with(|bans| bans.borrow().contains_key(&StorablePrincipal(principal)))
+}
+
+/// Ensure the principal is not banned.
+pub fn ensure_not_banned(principal: Principal) -> Result<(), GigError> {
+    if is_banned(principal) {
+        return Err(GigError::unauthorized("Principal is banned"));
+    }
+    Ok(())
+}
+
+/// Guard run before every update call: calls during a restore and banned callers are
+/// rejected before the call does anything. Calls let through mark the canister as in use.
+pub fn not_banned() -> Result<(), String> {
+    backup::ensure_not_restoring()?;
+    ensure_not_banned(caller()).map_err(|_| "Principal is banned".to_string())?;
+    backup::mark_in_use();
+    Ok(())
+}
+
+/// Whether a moderator has hidden the gig.
+pub fn is_hidden(gig_id: u64) -> bool {
+    HIDDEN_GIGS.with(|hidden| hidden.borrow().contains_key(&gig_id))
+}
+
+/// Drop the hidden marker of a deleted gig.
+pub fn forget_gig(gig_id: u64) {
+    HIDDEN_GIGS.with(|hidden| hidden.borrow_mut().remove(&gig_id));
+}
+
+/// IDs of all hidden gigs.
+pub fn hidden_ids() -> Vec<u64> {
+    HIDDEN_GIGS.with(|hidden| hidden.borrow().iter().map(|(id, _)| id).collect())
+}
+
+/// Bar a principal from posting, bidding and taking assignments. Moderator only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn ban_principal(principal: Principal, reason: String) -> Result<Ban, GigError> {
+    roles::ensure_moderator()?;
+    validate_reason(&reason)?;
+    // Staff cannot be banned; revoke the role first.
+    if ic_cdk::api::is_controller(&principal) || roles::get_role(principal).is_some() {
+        return Err(GigError::validation("principal", "Controllers and staff cannot be banned"));
+    }
+    let ban = Ban {
+        principal,
+        reason,
+        banned_by: caller(),
+        banned_at: time(),
+    };
+    BANS.with(|bans| bans.borrow_mut().insert(StorablePrincipal(principal), ban.clone()));
+    Ok(ban)
+}
+
+/// Lift a ban. Moderator only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn unban_principal(principal: Principal) -> Result<(), GigError> {
+    roles::ensure_moderator()?;
+    BANS
+        .with(|bans| bans.borrow_mut().remove(&StorablePrincipal(principal)))
+        .map(|_| ())
+        .ok_or_else(|| GigError::not_found("Ban"))
+}
+
+/// Retrieve every active ban.
+#[ic_cdk::query]
+pub fn list_bans() -> Vec<Ban> {
+    BANS.with(|bans| bans.borrow().iter().map(|(_, ban)| ban).collect())
+}
+
+/// Remove an abusive gig from public listings. Its parties can still see it. Moderator only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn hide_gig(gig_id: u64, reason: String) -> Result<HiddenGig, GigError> {
+    roles::ensure_moderator()?;
+    validate_reason(&reason)?;
+    if !GIG_STORAGE.with(|storage| storage.borrow().contains_key(&gig_id)) {
+        return Err(GigError::not_found("Gig"));
+    }
+    let hidden = HiddenGig {
+        gig_id,
+        reason,
+        hidden_by: caller(),
+        hidden_at: time(),
+    };
+    HIDDEN_GIGS.with(|gigs| gigs.borrow_mut().insert(gig_id, hidden.clone()));
+    Ok(hidden)
+}
+
+/// Restore a hidden gig to public listings. Moderator only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn unhide_gig(gig_id: u64) -> Result<(), GigError> {
+    roles::ensure_moderator()?;
+    HIDDEN_GIGS
+        .with(|gigs| gigs.borrow_mut().remove(&gig_id))
+        .map(|_| ())
+        .ok_or_else(|| GigError::not_found("Hidden gig"))
+}
+
+/// Retrieve every hidden gig along with why it was hidden. Moderator only.
+#[ic_cdk::query]
+pub fn list_hidden_gigs() -> Result<Vec<HiddenGig>, GigError> {
+    roles::ensure_moderator()?;
+    Ok(HIDDEN_GIGS.with(|gigs| gigs.borrow().iter().map(|(_, hidden)| hidden).collect()))
+}
+
+/// Resolve a stuck dispute without an arbitrator. Moderator only, and never a party to
+/// the gig.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn force_resolve_dispute(gig_id: u64, ruling: Ruling) -> Result<Gig, GigError> {
+    roles::ensure_moderator()?;
+    let moderator = caller();
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure moderators cannot rule on disputes they are a party to.
+    if gig.employer == moderator || teams::workers(&gig).contains(&moderator) {
+        return Err(GigError::unauthorized("Moderators cannot resolve their own disputes"));
+    }
+    disputes::settle(gig_id, ruling, moderator).await
+}
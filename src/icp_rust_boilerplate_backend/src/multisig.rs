@@ -0,0 +1,198 @@
+use candid::Principal;
+use ic_cdk::api::time;
+
+use crate::config;
+use crate::delegates::{self, Permission};
+use crate::error::GigError;
+use crate::orgs;
+use crate::validation;
+use crate::visibility;
+use crate::{insert_gig, Gig, GigStatus, GIG_STORAGE};
+
+/// An escrow release an approval signature consents to.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum Release {
+    Completion,                         // Approving the gig's submitted work.
+    Milestone { index: u32 },           // Approving one delivered milestone.
+    TeamShare { worker: Principal },    // Approving one team member's share.
+}
+
+/// An approver's consent to a release of a high-value gig's escrow.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct ApprovalSignature {
+    pub approver: Principal,
+    pub release: Release,
+    pub signed_at: u64,
+}
+
+/// Who can approve a gig's escrow releases, and how many of them must sign each one.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct ApprovalPolicy {
+    pub required: u32,              // 1 unless the budget is above `multisig_threshold`.
+    pub approvers: Vec<Principal>,
+}
+
+/// Principals who can approve a gig: its employer, the owner and managers of its
+/// organization, and the employer's delegates allowed to approve.
+fn approvers(gig: &Gig) -> Vec<Principal> {
+    let mut approvers = vec![gig.employer];
+    let others = gig
+        .org_id
+        .map(orgs::managers)
+        .unwrap_or_default()
+        .into_iter()
+        .chain(delegates::holding(gig.employer, Permission::Approve));
+    for approver in others {
+        if !approvers.contains(&approver) {
+            approvers.push(approver);
+        }
+    }
+    approvers
+}
+
+/// Signatures each escrow release of a gig worth `value` needs: `multisig_approvals` above
+/// the threshold, otherwise one.
+fn required_for(value: Option<u64>) -> u32 {
+    let high_value = config::multisig_threshold()
+        .zip(value)
+        .is_some_and(|(threshold, value)| value > threshold);
+    if high_value {
+        config::multisig_approvals()
+    } else {
+        1
+    }
+}
+
+/// Largest of a gig's budget and escrow.
+fn value(gig: &Gig) -> Option<u64> {
+    gig.budget.iter().chain(&gig.payment).map(|payment| payment.amount).max()
+}
+
+/// Approval policy of a gig.
+fn policy(gig: &Gig) -> ApprovalPolicy {
+    ApprovalPolicy { required: required_for(value(gig)), approvers: approvers(gig) }
+}
+
+/// Whether escrow releases of a gig need more than one signature.
+pub fn needs_signatures(gig: &Gig) -> bool {
+    required_for(value(gig)) > 1
+}
+
+/// Ensure a gig, or the escrow of `escrow_amount` about to be locked for it, has enough
+/// approvers to ever collect the signatures its policy requires.
+pub fn ensure_approvers(gig: &Gig, escrow_amount: Option<u64>) -> Result<(), GigError> {
+    let required = required_for(value(gig).max(escrow_amount));
+    if required as usize > approvers(gig).len() {
+        return Err(GigError::validation(
+            "budget",
+            &format!(
+                "Budgets above the multisig threshold need at least {} approvers: name \
+                 organization managers or delegates allowed to approve first",
+                required
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Drop the signatures a write to a gig makes stale, before it is stored: all of them once
+/// another worker is assigned, those on the completion once the gig leaves `Submitted` or
+/// is submitted again, those on team shares once the gig stops being in progress, and a
+/// milestone's once its delivery changes. Team shares are submitted only once, so their
+/// signatures survive the gig moving from `Assigned` to `Submitted` with the last share.
+pub fn expire(previous: Option<&Gig>, gig: &mut Gig) {
+    let Some(previous) = previous else {
+        return;
+    };
+    if gig.approvals.is_none() {
+        return;
+    }
+    let reassigned = previous.assigned_to != gig.assigned_to;
+    let resubmitted = (previous.status == GigStatus::Submitted
+        && gig.status != GigStatus::Submitted)
+        || previous.submitted_at != gig.submitted_at;
+    let in_progress = |status: &GigStatus| {
+        *status == GigStatus::Assigned || *status == GigStatus::Submitted
+    };
+    let stopped = in_progress(&previous.status) && !in_progress(&gig.status);
+    let delivery = |gig: &Gig, index: u32| {
+        gig.milestones
+            .as_ref()
+            .and_then(|milestones| milestones.get(index as usize))
+            .map(|milestone| (milestone.status.clone(), milestone.submitted_at))
+    };
+    let redelivered: Vec<u32> = gig
+        .approvals
+        .iter()
+        .flatten()
+        .filter_map(|signature| match signature.release {
+            Release::Milestone { index } => Some(index),
+            _ => None,
+        })
+        .filter(|index| delivery(previous, *index) != delivery(gig, *index))
+        .collect();
+    let signatures = gig.approvals.get_or_insert_with(Vec::new);
+    signatures.retain(|signature| {
+        !reassigned
+            && match &signature.release {
+                Release::Completion => !resubmitted,
+                Release::TeamShare { .. } => !stopped,
+                Release::Milestone { index } => !redelivered.contains(index),
+            }
+    });
+    if signatures.is_empty() {
+        gig.approvals = None;
+    }
+}
+
+/// Record `approver`'s signature on a release of a gig's escrow, and return whether the
+/// release now has enough signatures to go ahead. Callers must have checked that
+/// `approver` can approve the gig. Gigs that need a single approval store no signatures.
+/// Such gigs are not auto-approved; a worker left waiting raises a dispute instead, whose
+/// ruling moves the escrow without signatures because an arbitrator, not the employer's
+/// side, decides it.
+pub fn sign(gig_id: u64, approver: Principal, release: Release) -> Result<bool, GigError> {
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        let policy = policy(&gig);
+        if policy.required <= 1 {
+            return Ok(true);
+        }
+        let signatures = gig.approvals.get_or_insert_with(Vec::new);
+        let signed = signatures
+            .iter()
+            .any(|signature| signature.approver == approver && signature.release == release);
+        if !signed {
+            signatures.push(ApprovalSignature {
+                approver,
+                release: release.clone(),
+                signed_at: time(),
+            });
+        }
+        // Signatures of principals who have since lost the right to approve do not count.
+        let count = signatures
+            .iter()
+            .filter(|signature| {
+                signature.release == release && policy.approvers.contains(&signature.approver)
+            })
+            .count() as u32;
+        if !signed {
+            gig.updated_at = Some(time());
+            validation::ensure_storable(&gig)?;
+            insert_gig(&mut storage, &mut gig);
+        }
+        Ok(count >= policy.required)
+    })
+}
+
+/// Retrieve who can approve a gig's escrow releases and how many signatures each needs.
+/// Signatures collected so far are listed in the gig's `approvals`.
+#[ic_cdk::query]
+pub fn get_approval_policy(gig_id: u64) -> Result<ApprovalPolicy, GigError> {
+    GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .filter(visibility::can_view)
+        .map(|gig| policy(&gig))
+        .ok_or_else(|| GigError::not_found("Gig"))
+}
@@ -0,0 +1,118 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::events::Event;
+use crate::moderation::not_banned;
+use crate::{get_memory, Memory, StorablePrincipal, NOTIFICATIONS_MEMORY_ID};
+
+/// Notifications kept per principal before the oldest are dropped.
+const MAX_NOTIFICATIONS: usize = 500;
+/// Notifications returned per page.
+const NOTIFICATION_PAGE_SIZE: usize = 50;
+/// Most notifications `mark_read` accepts in one call.
+const MAX_MARK_READ: usize = 100;
+
+/// A feed event delivered to a principal it affects.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub event: Event,       // Its `seq` identifies the notification.
+    pub read: bool,
+}
+
+/// A page of the caller's notifications.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct NotificationPage {
+    pub notifications: Vec<Notification>,
+    pub unread: u64,        // Unread notifications in the whole inbox.
+}
+
+impl Storable for Notification {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Notification {
+    const MAX_SIZE: u32 = 320;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Notifications keyed by `(recipient, event seq)`.
+    static NOTIFICATIONS: RefCell<StableBTreeMap<(StorablePrincipal, u64), Notification, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(NOTIFICATIONS_MEMORY_ID)));
+}
+
+/// Notifications of `recipient`, oldest first.
+fn inbox(recipient: Principal) -> Vec<Notification> {
+    let key = StorablePrincipal(recipient);
+    NOTIFICATIONS.with(|notifications| {
+        notifications
+            .borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|(_, notification)| notification)
+            .collect()
+    })
+}
+
+/// Deliver an event to each recipient's inbox, dropping their oldest notification when
+/// the inbox is full.
+pub fn deliver(event: &Event, recipients: &[Principal]) {
+    NOTIFICATIONS.with(|notifications| {
+        let mut notifications = notifications.borrow_mut();
+        for recipient in recipients {
+            let key = StorablePrincipal(*recipient);
+            let held = notifications.range((key, 0)..=(key, u64::MAX)).count();
+            if held >= MAX_NOTIFICATIONS {
+                let oldest = notifications.range((key, 0)..=(key, u64::MAX)).next();
+                if let Some((oldest, _)) = oldest {
+                    notifications.remove(&oldest);
+                }
+            }
+            let notification = Notification { event: event.clone(), read: false };
+            notifications.insert((key, event.seq), notification);
+        }
+    });
+}
+
+/// Retrieve a page of the caller's notifications, newest first. `page` counts from zero.
+#[ic_cdk::query]
+pub fn get_notifications(unread_only: bool, page: u64) -> NotificationPage {
+    let inbox = inbox(caller());
+    let unread = inbox.iter().filter(|notification| !notification.read).count() as u64;
+    let notifications = inbox
+        .into_iter()
+        .rev()
+        .filter(|notification| !unread_only || !notification.read)
+        .skip(page as usize * NOTIFICATION_PAGE_SIZE)
+        .take(NOTIFICATION_PAGE_SIZE)
+        .collect();
+    NotificationPage { notifications, unread }
+}
+
+/// Mark the caller's notifications with the given event sequence numbers as read. Unknown
+/// IDs are ignored. Returns how many notifications changed.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn mark_read(ids: Vec<u64>) -> u64 {
+    let key = StorablePrincipal(caller());
+    NOTIFICATIONS.with(|notifications| {
+        let mut notifications = notifications.borrow_mut();
+        let mut marked = 0;
+        for seq in ids.into_iter().take(MAX_MARK_READ) {
+            if let Some(mut notification) = notifications.get(&(key, seq)) {
+                if !notification.read {
+                    notification.read = true;
+                    notifications.insert((key, seq), notification);
+                    marked += 1;
+                }
+            }
+        }
+        marked
+    })
+}
@@ -0,0 +1,162 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::delegates::{self, Permission};
+use crate::error::GigError;
+use crate::id;
+use crate::moderation::{self, not_banned};
+use crate::{get_memory, Gig, IdCell, Memory, ORGS_MEMORY_ID, ORG_ID_COUNTER_MEMORY_ID};
+
+/// Longest organization name, in bytes.
+const MAX_NAME_LEN: usize = 100;
+/// Most managers an organization can have.
+const MAX_MANAGERS: usize = 20;
+
+/// A company or group whose owner and managers post and run gigs together.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Organization {
+    pub id: u64,
+    pub name: String,
+    pub owner: Principal,
+    pub managers: Vec<Principal>,   // Can post, assign and approve the organization's gigs.
+    pub created_at: u64,
+    pub updated_at: Option<u64>,
+}
+
+impl Organization {
+    /// Whether `principal` can act on the organization's gigs.
+    fn is_manager(&self, principal: Principal) -> bool {
+        self.owner == principal || self.managers.contains(&principal)
+    }
+}
+
+impl Storable for Organization {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Organization {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    static ORG_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(get_memory(ORG_ID_COUNTER_MEMORY_ID), 0)
+            .expect("Cannot create an organization counter")
+    );
+
+    /// Organizations keyed by organization ID.
+    static ORGS: RefCell<StableBTreeMap<u64, Organization, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(ORGS_MEMORY_ID)));
+}
+
+/// Ensure `principal` can post gigs on behalf of an organization.
+pub fn ensure_can_post(org_id: u64, principal: Principal) -> Result<(), GigError> {
+    let org = ORGS
+        .with(|orgs| orgs.borrow().get(&org_id))
+        .ok_or_else(|| GigError::validation("org_id", "Organization not found"))?;
+    if !org.is_manager(principal) {
+        return Err(GigError::unauthorized("Only the organization's managers can post for it"));
+    }
+    Ok(())
+}
+
+/// Owner and managers of an organization; empty if it does not exist.
+pub fn managers(org_id: u64) -> Vec<Principal> {
+    ORGS.with(|orgs| orgs.borrow().get(&org_id))
+        .map(|org| std::iter::once(org.owner).chain(org.managers).collect())
+        .unwrap_or_default()
+}
+
+/// Whether `principal` can do what `permission` covers on a gig: its employer, an owner or
+/// manager of the organization it was posted under, or a delegate holding the permission.
+pub fn acts_for_employer(gig: &Gig, principal: Principal, permission: Permission) -> bool {
+    if delegates::may_act(gig, principal, permission) {
+        return true;
+    }
+    gig.org_id
+        .and_then(|id| ORGS.with(|orgs| orgs.borrow().get(&id)))
+        .is_some_and(|org| org.is_manager(principal))
+}
+
+/// Fetch an organization and check the caller owns it.
+fn owned_org(org_id: u64) -> Result<Organization, GigError> {
+    let org = ORGS
+        .with(|orgs| orgs.borrow().get(&org_id))
+        .ok_or_else(|| GigError::not_found("Organization"))?;
+    if org.owner != caller() {
+        return Err(GigError::unauthorized("Only the owner can manage this organization"));
+    }
+    Ok(org)
+}
+
+/// Create an organization owned by the caller.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn create_organization(name: String) -> Result<Organization, GigError> {
+    let owner = caller();
+    if owner == Principal::anonymous() {
+        return Err(GigError::unauthorized("Anonymous principals cannot own organizations"));
+    }
+    moderation::ensure_not_banned(owner)?;
+    if name.trim().is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(GigError::validation("name", "Must be between 1 and 100 bytes"));
+    }
+    let id = id::next(&ORG_ID_COUNTER, "Organization")?;
+    let org = Organization {
+        id,
+        name: name.trim().to_string(),
+        owner,
+        managers: Vec::new(),
+        created_at: time(),
+        updated_at: None,
+    };
+    ORGS.with(|orgs| orgs.borrow_mut().insert(id, org.clone()));
+    Ok(org)
+}
+
+/// Let `manager` post, assign and approve the organization's gigs. Owner only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn add_org_manager(org_id: u64, manager: Principal) -> Result<Organization, GigError> {
+    let mut org = owned_org(org_id)?;
+    if manager == Principal::anonymous() || manager == org.owner {
+        return Err(GigError::validation("manager", "Must be another real principal"));
+    }
+    if org.managers.contains(&manager) {
+        return Err(GigError::AlreadyExists { resource: "Manager".to_string() });
+    }
+    if org.managers.len() >= MAX_MANAGERS {
+        return Err(GigError::validation("manager", "An organization can have at most 20 managers"));
+    }
+    org.managers.push(manager);
+    org.updated_at = Some(time());
+    ORGS.with(|orgs| orgs.borrow_mut().insert(org_id, org.clone()));
+    Ok(org)
+}
+
+/// Revoke a manager's rights over the organization's gigs. Owner only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn remove_org_manager(org_id: u64, manager: Principal) -> Result<Organization, GigError> {
+    let mut org = owned_org(org_id)?;
+    if !org.managers.contains(&manager) {
+        return Err(GigError::not_found("Manager"));
+    }
+    org.managers.retain(|existing| *existing != manager);
+    org.updated_at = Some(time());
+    ORGS.with(|orgs| orgs.borrow_mut().insert(org_id, org.clone()));
+    Ok(org)
+}
+
+/// Retrieve an organization by ID.
+#[ic_cdk::query]
+pub fn get_organization(org_id: u64) -> Option<Organization> {
+    ORGS.with(|orgs| orgs.borrow().get(&org_id))
+}
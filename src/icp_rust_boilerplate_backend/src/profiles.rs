@@ -0,0 +1,217 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::leaderboard::{self, Badge};
+use crate::moderation::{self, not_banned};
+use crate::referrals;
+use crate::roles;
+use crate::skills;
+use crate::{get_memory, Memory, StorablePrincipal, PROFILES_MEMORY_ID};
+
+/// Maximum length of a display name.
+const MAX_DISPLAY_NAME_LEN: usize = 100;
+/// Maximum length of a bio.
+const MAX_BIO_LEN: usize = 1000;
+/// Maximum number of skills on a profile.
+const MAX_SKILLS: usize = 20;
+/// Maximum length of the contact info.
+const MAX_CONTACT_LEN: usize = 200;
+
+/// Public profile of a worker or employer.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub principal: Principal,
+    pub display_name: String,
+    pub bio: String,
+    pub skills: Vec<String>,        // Normalised names from the curated skill list.
+    pub contact: String,            // Free-form contact info (email, handle, ...).
+    pub created_at: u64,
+    pub updated_at: Option<u64>,
+    pub badges: Option<Vec<Badge>>, // Awarded automatically as the principal completes work.
+    pub verification: Option<Verification>, // Set by an admin who checked the principal's identity.
+    pub referral_code: Option<String>,      // Others pass it to `register_with_referral`.
+}
+
+/// An admin's attestation that a principal's identity was checked.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Verification {
+    pub verified_by: Principal,
+    pub verified_at: u64,
+}
+
+/// Structure for registering or updating a profile.
+#[derive(candid::CandidType, Serialize, Deserialize, Default)]
+pub struct ProfilePayload {
+    pub display_name: String,
+    pub bio: String,
+    pub skills: Vec<String>,
+    pub contact: String,
+}
+
+impl Storable for Profile {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Profile {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Profiles keyed by owner principal.
+    static PROFILE_STORAGE: RefCell<StableBTreeMap<StorablePrincipal, Profile, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(PROFILES_MEMORY_ID)));
+}
+
+/// Check the payload against the profile limits.
+fn validate(payload: &ProfilePayload) -> Result<(), GigError> {
+    if payload.display_name.trim().is_empty() || payload.display_name.len() > MAX_DISPLAY_NAME_LEN {
+        return Err(GigError::validation("display_name", "Must be between 1 and 100 bytes"));
+    }
+    if payload.bio.len() > MAX_BIO_LEN {
+        return Err(GigError::validation("bio", "Must be at most 1000 bytes"));
+    }
+    if payload.contact.len() > MAX_CONTACT_LEN {
+        return Err(GigError::validation("contact", "Must be at most 200 bytes"));
+    }
+    Ok(())
+}
+
+/// Whether the principal has registered a profile.
+pub fn is_registered(principal: Principal) -> bool {
+    PROFILE_STORAGE.with(|storage| storage.borrow().contains_key(&StorablePrincipal(principal)))
+}
+
+/// Register a profile for the caller.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn register_profile(payload: ProfilePayload) -> Result<Profile, GigError> {
+    let principal = caller();
+    if principal == Principal::anonymous() {
+        return Err(GigError::unauthorized("Anonymous principals cannot register a profile"));
+    }
+    moderation::ensure_not_banned(principal)?;
+    validate(&payload)?;
+    let skills = skills::normalize_skills(payload.skills, MAX_SKILLS)?;
+    if is_registered(principal) {
+        return Err(GigError::AlreadyExists { resource: "Profile".to_string() });
+    }
+
+    let profile = Profile {
+        principal,
+        display_name: payload.display_name,
+        bio: payload.bio,
+        skills,
+        contact: payload.contact,
+        created_at: time(),
+        updated_at: None,
+        badges: Some(leaderboard::badges_of(principal)),
+        verification: None,
+        referral_code: Some(referrals::assign_code(principal)),
+    };
+    skills::reindex_profile(None, Some(&profile));
+    PROFILE_STORAGE
+        .with(|storage| storage.borrow_mut().insert(StorablePrincipal(principal), profile.clone()));
+    Ok(profile)
+}
+
+/// Update the caller's profile.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn update_profile(payload: ProfilePayload) -> Result<Profile, GigError> {
+    moderation::ensure_not_banned(caller())?;
+    validate(&payload)?;
+    let skills = skills::normalize_skills(payload.skills, MAX_SKILLS)?;
+    let key = StorablePrincipal(caller());
+    PROFILE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let previous = storage.get(&key).ok_or_else(|| GigError::not_found("Profile"))?;
+        let mut profile = previous.clone();
+        profile.display_name = payload.display_name;
+        profile.bio = payload.bio;
+        profile.skills = skills;
+        profile.contact = payload.contact;
+        profile.updated_at = Some(time());
+        skills::reindex_profile(Some(&previous), Some(&profile));
+        storage.insert(key, profile.clone());
+        Ok(profile)
+    })
+}
+
+/// Replace the badges shown on a principal's profile. Principals without a profile get
+/// theirs when they register.
+pub fn set_badges(principal: Principal, badges: Vec<Badge>) {
+    PROFILE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let key = StorablePrincipal(principal);
+        if let Some(mut profile) = storage.get(&key) {
+            if profile.badges.as_ref() != Some(&badges) {
+                profile.badges = Some(badges);
+                storage.insert(key, profile);
+            }
+        }
+    });
+}
+
+/// Whether an admin has verified the principal.
+pub fn is_verified(principal: Principal) -> bool {
+    get_profile(principal).is_some_and(|profile| profile.verification.is_some())
+}
+
+/// Mark a principal with a profile as verified, or withdraw the verification. Admin only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn set_verified(principal: Principal, verified: bool) -> Result<Profile, GigError> {
+    // Ensure only an admin can vouch for a principal's identity
+    roles::ensure_admin()?;
+    let key = StorablePrincipal(principal);
+    PROFILE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut profile = storage.get(&key).ok_or_else(|| GigError::not_found("Profile"))?;
+        profile.verification =
+            verified.then(|| Verification { verified_by: caller(), verified_at: time() });
+        profile.updated_at = Some(time());
+        storage.insert(key, profile.clone());
+        Ok(profile)
+    })
+}
+
+/// Retrieve the profile of a principal.
+#[ic_cdk::query]
+pub fn get_profile(principal: Principal) -> Option<Profile> {
+    PROFILE_STORAGE.with(|storage| storage.borrow().get(&StorablePrincipal(principal)))
+}
+
+/// Index the skills of every stored profile, for canisters upgraded from before skill
+/// matching existed.
+pub fn index_skills() {
+    PROFILE_STORAGE.with(|storage| {
+        for (_, profile) in storage.borrow().iter() {
+            skills::reindex_profile(None, Some(&profile));
+        }
+    });
+}
+
+/// Give every stored profile its referral code, for canisters upgraded from before
+/// referrals existed.
+pub fn assign_referral_codes() {
+    PROFILE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let missing: Vec<Profile> = storage
+            .iter()
+            .map(|(_, profile)| profile)
+            .filter(|profile| profile.referral_code.is_none())
+            .collect();
+        for mut profile in missing {
+            profile.referral_code = Some(referrals::assign_code(profile.principal));
+            storage.insert(StorablePrincipal(profile.principal), profile);
+        }
+    });
+}
@@ -0,0 +1,126 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::config;
+use crate::error::GigError;
+use crate::{get_memory, Memory, StorablePrincipal, RATE_LIMITS_MEMORY_ID};
+
+/// Nanoseconds in a second.
+const SECOND_NANOS: u64 = 1_000_000_000;
+
+/// Calls that draw from a caller's token bucket.
+#[derive(Clone, Copy)]
+pub enum Action {
+    PostGig,
+    ApplyToGig,
+    SendMessage,
+    RecordView,
+}
+
+impl Action {
+    /// All rate-limited actions, in key order.
+    const ALL: [Action; 4] =
+        [Action::PostGig, Action::ApplyToGig, Action::SendMessage, Action::RecordView];
+
+    /// Burst size and the nanoseconds it takes to earn back one call, as configured.
+    fn limits(self) -> (u64, u64) {
+        let config = config::get();
+        let limit = match self {
+            Action::PostGig => config.post_gig_limit,
+            Action::ApplyToGig => config.apply_limit,
+            Action::SendMessage => config.message_limit,
+            Action::RecordView => config::view_limit(),
+        };
+        (limit.burst, limit.refill_secs * SECOND_NANOS)
+    }
+}
+
+/// Calls a principal has left for an action, as of `updated_at`.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Bucket {
+    tokens: u64,
+    updated_at: u64,
+}
+
+impl Bucket {
+    /// Credit the calls earned since `updated_at`, keeping the partial interval.
+    fn refill(&mut self, action: Action, now: u64) {
+        let (capacity, interval) = action.limits();
+        let earned = now.saturating_sub(self.updated_at) / interval;
+        if self.tokens + earned >= capacity {
+            self.tokens = capacity;
+            self.updated_at = now;
+        } else {
+            self.tokens += earned;
+            self.updated_at += earned * interval;
+        }
+    }
+}
+
+impl Storable for Bucket {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Bucket {
+    const MAX_SIZE: u32 = 64;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Token buckets keyed by `(principal, action)`. Principals without a bucket have a
+    /// full one.
+    static BUCKETS: RefCell<StableBTreeMap<(StorablePrincipal, u8), Bucket, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(RATE_LIMITS_MEMORY_ID)));
+}
+
+/// Spend one call of `action` from the principal's bucket, or fail if it is empty.
+/// Controllers are not limited.
+pub fn check(principal: Principal, action: Action) -> Result<(), GigError> {
+    if ic_cdk::api::is_controller(&principal) {
+        return Ok(());
+    }
+    let now = time();
+    let (capacity, interval) = action.limits();
+    let key = (StorablePrincipal(principal), action as u8);
+    BUCKETS.with(|buckets| {
+        let mut buckets = buckets.borrow_mut();
+        let mut bucket = buckets.get(&key).unwrap_or(Bucket { tokens: capacity, updated_at: now });
+        bucket.refill(action, now);
+        if bucket.tokens == 0 {
+            let retry_after = (bucket.updated_at + interval - now).div_ceil(SECOND_NANOS);
+            return Err(GigError::RateLimited { retry_after_secs: retry_after });
+        }
+        bucket.tokens -= 1;
+        buckets.insert(key, bucket);
+        Ok(())
+    })
+}
+
+/// Drop buckets that have refilled completely, since they carry no information.
+pub fn prune() {
+    let now = time();
+    BUCKETS.with(|buckets| {
+        let mut buckets = buckets.borrow_mut();
+        let full: Vec<(StorablePrincipal, u8)> = buckets
+            .iter()
+            .filter(|((_, action), bucket)| {
+                let mut bucket = bucket.clone();
+                let action = Action::ALL[*action as usize];
+                bucket.refill(action, now);
+                bucket.tokens == action.limits().0
+            })
+            .map(|(key, _)| key)
+            .collect();
+        for key in full {
+            buckets.remove(&key);
+        }
+    });
+}
@@ -0,0 +1,237 @@
+use candid::{CandidType, Decode, Encode, Principal};
+use ic_cdk::api::call::call_with_payment128;
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::ledger;
+use crate::moderation::not_banned;
+use crate::{ensure_controller, get_memory, Memory, StorablePrincipal, USD_RATES_MEMORY_ID};
+
+/// Principal of the exchange rate canister.
+const XRC_CANISTER_ID: &str = "uf6dk-hyaaa-aaaaq-qaaaq-cai";
+/// Cycles the exchange rate canister charges per request.
+const XRC_CYCLES: u128 = 1_000_000_000;
+/// Age after which a cached rate is fetched again: five minutes.
+const RATE_MAX_AGE_NANOS: u64 = 5 * 60 * 1_000_000_000;
+/// Decimals of the base units of every token priced in USD; ICP and ckBTC both use 8.
+const TOKEN_DECIMALS: u32 = 8;
+
+/// A token's USD price as reported by the exchange rate canister.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct UsdRate {
+    pub symbol: String,     // Asset priced, e.g. "BTC" for ckBTC.
+    pub rate: u64,          // USD per whole token, scaled by `10^decimals`.
+    pub decimals: u32,
+    pub timestamp: u64,     // Time the rate applies to, in seconds since the epoch.
+    pub fetched_at: u64,
+}
+
+/// Asset class understood by the exchange rate canister.
+#[derive(CandidType, Deserialize)]
+enum AssetClass {
+    Cryptocurrency,
+    FiatCurrency,
+}
+
+/// Asset understood by the exchange rate canister.
+#[derive(CandidType, Deserialize)]
+struct Asset {
+    symbol: String,
+    class: AssetClass,
+}
+
+/// Arguments of `get_exchange_rate`.
+#[derive(CandidType)]
+struct GetExchangeRateRequest {
+    base_asset: Asset,
+    quote_asset: Asset,
+    timestamp: Option<u64>,
+}
+
+/// Metadata of an exchange rate; only the fields used are decoded.
+#[derive(CandidType, Deserialize)]
+struct ExchangeRateMetadata {
+    decimals: u32,
+}
+
+/// Rate returned by `get_exchange_rate`; only the fields used are decoded.
+#[derive(CandidType, Deserialize)]
+struct ExchangeRate {
+    timestamp: u64,
+    rate: u64,
+    metadata: ExchangeRateMetadata,
+}
+
+/// Errors returned by `get_exchange_rate`.
+#[derive(CandidType, Deserialize, Debug)]
+enum ExchangeRateError {
+    AnonymousPrincipalNotAllowed,
+    Pending,
+    CryptoBaseAssetNotFound,
+    CryptoQuoteAssetNotFound,
+    StablecoinRateNotFound,
+    StablecoinRateTooFewRates,
+    StablecoinRateZeroRate,
+    ForexInvalidTimestamp,
+    ForexBaseAssetNotFound,
+    ForexQuoteAssetNotFound,
+    ForexAssetsNotFound,
+    RateLimited,
+    NotEnoughCycles,
+    FailedToAcceptCycles,
+    InconsistentRatesReceived,
+    Other { code: u32, description: String },
+}
+
+impl Storable for UsdRate {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for UsdRate {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Latest USD rate of each token, keyed by ledger.
+    static USD_RATES: RefCell<StableBTreeMap<StorablePrincipal, UsdRate, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(USD_RATES_MEMORY_ID)));
+}
+
+/// Exchange rate canister symbol of a token, if it can be priced in USD.
+fn symbol_of(ledger: Principal) -> Option<&'static str> {
+    if ledger == ledger::icp_ledger() {
+        Some("ICP")
+    } else if ledger == ledger::ckbtc_ledger() {
+        Some("BTC")
+    } else {
+        None
+    }
+}
+
+/// Error for a rate that could not be obtained.
+fn unavailable(message: String) -> GigError {
+    GigError::ExchangeRateUnavailable { message }
+}
+
+/// Ask the exchange rate canister for the current USD price of a token and cache it.
+async fn fetch(ledger: Principal) -> Result<UsdRate, GigError> {
+    let symbol = symbol_of(ledger)
+        .ok_or_else(|| GigError::validation("token", "Token cannot be priced in USD"))?;
+    let request = GetExchangeRateRequest {
+        base_asset: Asset { symbol: symbol.to_string(), class: AssetClass::Cryptocurrency },
+        quote_asset: Asset { symbol: "USD".to_string(), class: AssetClass::FiatCurrency },
+        timestamp: None,
+    };
+    let xrc = Principal::from_text(XRC_CANISTER_ID).expect("Invalid exchange rate canister ID");
+    let (result,): (Result<ExchangeRate, ExchangeRateError>,) =
+        call_with_payment128(xrc, "get_exchange_rate", (request,), XRC_CYCLES)
+            .await
+            .map_err(|(code, message)| unavailable(format!("{:?}: {}", code, message)))?;
+    let exchange_rate = result.map_err(|error| unavailable(format!("{:?}", error)))?;
+    if exchange_rate.rate == 0 {
+        return Err(unavailable("The exchange rate canister returned a zero rate".to_string()));
+    }
+    let rate = UsdRate {
+        symbol: symbol.to_string(),
+        rate: exchange_rate.rate,
+        decimals: exchange_rate.metadata.decimals,
+        timestamp: exchange_rate.timestamp,
+        fetched_at: time(),
+    };
+    USD_RATES.with(|rates| rates.borrow_mut().insert(StorablePrincipal(ledger), rate.clone()));
+    Ok(rate)
+}
+
+/// USD rate of a token, fetched again when the cached one is older than five minutes.
+pub async fn current(ledger: Principal) -> Result<UsdRate, GigError> {
+    let cached = USD_RATES.with(|rates| rates.borrow().get(&StorablePrincipal(ledger)));
+    match cached {
+        Some(rate) if time().saturating_sub(rate.fetched_at) < RATE_MAX_AGE_NANOS => Ok(rate),
+        _ => fetch(ledger).await,
+    }
+}
+
+/// Token base units worth `usd_cents` at `rate`, rounded down.
+pub fn to_tokens(usd_cents: u64, rate: &UsdRate) -> Result<u64, GigError> {
+    if rate.rate == 0 {
+        return Err(GigError::validation("usd_budget", "The token has no USD price"));
+    }
+    let too_large = || GigError::validation("usd_budget", "Budget is too large");
+    let amount = 10u128
+        .checked_pow(TOKEN_DECIMALS + rate.decimals)
+        .and_then(|scale| (usd_cents as u128).checked_mul(scale))
+        .ok_or_else(too_large)?
+        / (rate.rate as u128 * 100);
+    u64::try_from(amount).map_err(|_| too_large())
+}
+
+/// Retrieve the latest cached USD rate of a token.
+#[ic_cdk::query]
+pub fn get_usd_rate(ledger: Principal) -> Option<UsdRate> {
+    USD_RATES.with(|rates| rates.borrow().get(&StorablePrincipal(ledger)))
+}
+
+/// Fetch a token's USD rate from the exchange rate canister now. Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn refresh_usd_rate(ledger: Principal) -> Result<UsdRate, GigError> {
+    ensure_controller()?;
+    fetch(ledger).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(rate: u64, decimals: u32) -> UsdRate {
+        UsdRate { symbol: "ICP".to_string(), rate, decimals, timestamp: 0, fetched_at: 0 }
+    }
+
+    #[test]
+    fn converts_at_the_quoted_price() {
+        // $10.00 at $5.00 a token is two tokens.
+        assert_eq!(to_tokens(1_000, &rate(5_000_000_000, 9)), Ok(200_000_000));
+        // $1.00 at $2.00 a token is half a token.
+        assert_eq!(to_tokens(100, &rate(2, 0)), Ok(50_000_000));
+    }
+
+    #[test]
+    fn zero_cents_is_zero_tokens() {
+        assert_eq!(to_tokens(0, &rate(5_000_000_000, 9)), Ok(0));
+    }
+
+    #[test]
+    fn zero_rate_is_rejected() {
+        assert!(to_tokens(1_000, &rate(0, 9)).is_err());
+    }
+
+    #[test]
+    fn rounds_down_to_whole_base_units() {
+        // One cent at $3.00 a token is 333_333.33 base units.
+        assert_eq!(to_tokens(1, &rate(3, 0)), Ok(333_333));
+        // One cent of a token priced above $1,000,000 is less than a base unit.
+        assert_eq!(to_tokens(1, &rate(1_000_000_000_000_000, 0)), Ok(0));
+    }
+
+    #[test]
+    fn amounts_beyond_u64_are_rejected() {
+        assert!(to_tokens(u64::MAX, &rate(1, 0)).is_err());
+        assert!(to_tokens(u64::MAX, &rate(1, 9)).is_err());
+        // Scales that overflow u128 are rejected rather than wrapping.
+        assert!(to_tokens(1, &rate(1, 40)).is_err());
+        assert!(to_tokens(u64::MAX, &rate(u64::MAX, 30)).is_err());
+    }
+
+    #[test]
+    fn large_rates_do_not_overflow_the_divisor() {
+        assert_eq!(to_tokens(u64::MAX, &rate(u64::MAX, 0)), Ok(1_000_000));
+    }
+}
@@ -0,0 +1,223 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use sha2::{Digest, Sha256};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::config;
+use crate::error::GigError;
+use crate::escrow::{self, EscrowStatus};
+use crate::ledger;
+use crate::moderation::not_banned;
+use crate::profiles::{self, Profile, ProfilePayload};
+use crate::state_machine;
+use crate::tags::Tag;
+use crate::teams;
+use crate::treasury;
+use crate::{
+    get_memory, Gig, Memory, StorablePrincipal, REFERRALS_MEMORY_ID, REFERRAL_CODES_MEMORY_ID,
+    REFERRAL_REWARDS_MEMORY_ID, REFERRER_INDEX_MEMORY_ID,
+};
+
+/// Hex characters in a referral code.
+const CODE_LEN: usize = 12;
+
+/// A principal who registered with someone else's referral code.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Referral {
+    pub referrer: Principal,
+    pub referee: Principal,
+    pub registered_at: u64,
+    pub rewarded_at: Option<u64>,   // When the referee completed their first qualifying gig.
+    pub reward: u64,                // ICP credited to the referrer, in e8s.
+}
+
+impl Storable for Referral {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Referral {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Owner of each referral code.
+    static CODES: RefCell<StableBTreeMap<Tag, StorablePrincipal, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(REFERRAL_CODES_MEMORY_ID)));
+
+    /// Referrals keyed by referee; a principal is referred at most once.
+    static REFERRALS: RefCell<StableBTreeMap<StorablePrincipal, Referral, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(REFERRALS_MEMORY_ID)));
+
+    /// Referees keyed by `(referrer, referee)`.
+    static REFERRER_INDEX:
+        RefCell<StableBTreeMap<(StorablePrincipal, StorablePrincipal), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(REFERRER_INDEX_MEMORY_ID)));
+
+    /// ICP owed to each referrer and not yet claimed, in e8s.
+    static REWARDS: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(REFERRAL_REWARDS_MEMORY_ID)));
+}
+
+/// Referral code of a principal: the start of the hex SHA-256 of its bytes, so it never
+/// changes and can be derived again for profiles registered before codes existed.
+pub fn code_for(principal: Principal) -> String {
+    let hash = Sha256::digest(principal.as_slice());
+    hash[..CODE_LEN / 2].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Make a principal's referral code resolve to it, and return the code.
+pub fn assign_code(principal: Principal) -> String {
+    let code = code_for(principal);
+    CODES.with(|codes| codes.borrow_mut().insert(Tag(code.clone()), StorablePrincipal(principal)));
+    code
+}
+
+/// Register a profile for the caller, crediting the owner of `code` with a bonus once the
+/// caller completes their first qualifying gig: one paying them at least
+/// `referral_min_payout` of ICP escrow, posted by neither of them.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn register_with_referral(payload: ProfilePayload, code: String) -> Result<Profile, GigError> {
+    let code = code.trim().to_lowercase();
+    let referrer = (code.len() == CODE_LEN)
+        .then(|| CODES.with(|codes| codes.borrow().get(&Tag(code))))
+        .flatten()
+        .map(|owner| owner.0)
+        .ok_or_else(|| GigError::not_found("Referral code"))?;
+    let profile = profiles::register_profile(payload)?;
+    let referee = profile.principal;
+    let referral =
+        Referral { referrer, referee, registered_at: time(), rewarded_at: None, reward: 0 };
+    REFERRALS.with(|referrals| referrals.borrow_mut().insert(StorablePrincipal(referee), referral));
+    REFERRER_INDEX.with(|index| {
+        index.borrow_mut().insert((StorablePrincipal(referrer), StorablePrincipal(referee)), ())
+    });
+    Ok(profile)
+}
+
+/// ICP escrow of a gig paid out to `worker` by the time it is approved, in e8s, after the
+/// platform fee: everything released for a solo gig and their share of it for a team gig.
+/// Leftovers still locked for a refund to the employer are not counted, and other tokens
+/// count as nothing.
+fn paid_to(gig: &Gig, worker: Principal) -> u64 {
+    // Escrow refunded before the gig was reopened and approved went to the employer.
+    let Some(escrow) = escrow::get_escrow(gig.id)
+        .filter(|escrow| escrow.status != EscrowStatus::Refunded)
+        .filter(|escrow| escrow.ledger() == ledger::icp_ledger())
+    else {
+        return 0;
+    };
+    // Refunds to the employer only follow the approval, so everything released so far went
+    // to the workers.
+    let released =
+        escrow.released_amount.unwrap_or(0).saturating_sub(escrow.platform_fee.unwrap_or(0));
+    match teams::team(gig.id).iter().find(|share| share.worker == worker) {
+        Some(share) => released * share.share_percent as u64 / 100,
+        None => released,
+    }
+}
+
+/// Credit the referrers of the workers who completed a gig that was just approved, for
+/// each worker's first qualifying gig. Gigs paying the worker less than
+/// `referral_min_payout` of ICP escrow, or posted by the referrer or the worker, do not
+/// qualify, so a referrer cannot earn bonuses by hiring their own referees.
+pub fn record(previous: Option<&Gig>, gig: &Gig) {
+    if !state_machine::is_completion(previous, gig) {
+        return;
+    }
+    for worker in teams::workers(gig) {
+        let key = StorablePrincipal(worker);
+        let Some(mut referral) = REFERRALS.with(|referrals| referrals.borrow().get(&key)) else {
+            continue;
+        };
+        if referral.rewarded_at.is_some()
+            || gig.employer == referral.referrer
+            || gig.employer == worker
+            || paid_to(gig, worker) < config::referral_min_payout()
+        {
+            continue;
+        }
+        let bonus = config::referral_bonus();
+        referral.rewarded_at = Some(time());
+        referral.reward = bonus;
+        REFERRALS.with(|referrals| referrals.borrow_mut().insert(key, referral.clone()));
+        if bonus > 0 {
+            REWARDS.with(|rewards| {
+                let mut rewards = rewards.borrow_mut();
+                let key = StorablePrincipal(referral.referrer);
+                let owed = rewards.get(&key).unwrap_or(0);
+                rewards.insert(key, owed.saturating_add(bonus));
+            });
+        }
+    }
+}
+
+/// Retrieve the caller's referral code. Principals need a profile to have one.
+#[ic_cdk::query]
+pub fn get_referral_code() -> Result<String, GigError> {
+    profiles::get_profile(caller())
+        .and_then(|profile| profile.referral_code)
+        .ok_or_else(|| GigError::not_found("Profile"))
+}
+
+/// Retrieve the principals the caller referred, in principal order.
+#[ic_cdk::query]
+pub fn get_referrals() -> Vec<Referral> {
+    let referrer = StorablePrincipal(caller());
+    let min = StorablePrincipal(Principal::from_slice(&[]));
+    let referees: Vec<StorablePrincipal> = REFERRER_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((referrer, min)..)
+            .take_while(|((key, _), _)| *key == referrer)
+            .map(|((_, referee), _)| referee)
+            .collect()
+    });
+    REFERRALS.with(|referrals| {
+        let referrals = referrals.borrow();
+        referees.iter().filter_map(|referee| referrals.get(referee)).collect()
+    })
+}
+
+/// ICP a referrer has earned and not yet claimed, in e8s.
+#[ic_cdk::query]
+pub fn get_referral_rewards(referrer: Principal) -> u64 {
+    REWARDS.with(|rewards| rewards.borrow().get(&StorablePrincipal(referrer)).unwrap_or(0))
+}
+
+/// Pay the caller's unclaimed referral rewards out of the treasury's ICP, minus the ledger
+/// fee, and return the amount claimed. Fails while the treasury cannot cover them.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn claim_referral_rewards() -> Result<u64, GigError> {
+    let referrer = caller();
+    let key = StorablePrincipal(referrer);
+    let owed = get_referral_rewards(referrer);
+    if owed <= ledger::ICP_TRANSFER_FEE {
+        return Err(GigError::validation("rewards", "Nothing to claim above the ledger fee"));
+    }
+    // Take the rewards and the treasury's share out before awaiting so concurrent calls
+    // cannot claim twice.
+    if !treasury::take(ledger::icp_ledger(), owed) {
+        return Err(GigError::validation("rewards", "The treasury cannot cover the rewards yet"));
+    }
+    REWARDS.with(|rewards| rewards.borrow_mut().remove(&key));
+    let amount = owed - ledger::ICP_TRANSFER_FEE;
+    if let Err(error) = ledger::transfer(ledger::icp_ledger(), referrer, amount).await {
+        treasury::accrue(ledger::icp_ledger(), ledger::ICP_TRANSFER_FEE, owed);
+        REWARDS.with(|rewards| {
+            let mut rewards = rewards.borrow_mut();
+            let pending = rewards.get(&key).unwrap_or(0);
+            rewards.insert(key, pending.saturating_add(owed));
+        });
+        return Err(error);
+    }
+    Ok(owed)
+}
@@ -0,0 +1,81 @@
+use ic_cdk::api::time;
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+
+use crate::error::GigError;
+use crate::events::{self, EventKind};
+use crate::indexes;
+use crate::{get_memory, GigStatus, Memory, GIG_STORAGE, REMINDERS_SENT_MEMORY_ID};
+
+/// Nanoseconds in an hour.
+const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+/// Reminders sent for gigs that do not choose their own: 48 and 24 hours before the deadline.
+const DEFAULT_REMINDER_HOURS: [u32; 2] = [48, 24];
+/// Most reminders a gig can schedule.
+const MAX_REMINDERS: usize = 5;
+/// Earliest a reminder can be scheduled: thirty days before the deadline.
+const MAX_REMINDER_HOURS: u32 = 30 * 24;
+
+thread_local! {
+    /// Deadline each reminder was sent for, keyed by `(gig_id, hours_before)`. A moved
+    /// deadline no longer matches, so its reminders are sent again.
+    static REMINDERS_SENT: RefCell<StableBTreeMap<(u64, u32), u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(REMINDERS_SENT_MEMORY_ID)));
+}
+
+/// Check the reminder schedule of a gig payload and return it deduplicated, latest first.
+/// An empty schedule turns reminders off.
+pub fn validate(mut hours: Vec<u32>) -> Result<Vec<u32>, GigError> {
+    hours.sort_unstable_by(|a, b| b.cmp(a));
+    hours.dedup();
+    if hours.len() > MAX_REMINDERS {
+        return Err(GigError::validation("reminder_hours", "At most 5 reminders are allowed"));
+    }
+    if hours.iter().any(|h| *h == 0 || *h > MAX_REMINDER_HOURS) {
+        return Err(GigError::validation("reminder_hours", "Must be between 1 and 720 hours"));
+    }
+    Ok(hours)
+}
+
+/// Push a reminder into the event feed for every assigned gig that entered one of its
+/// reminder windows. When several windows opened since the last sweep, for instance on a
+/// gig assigned close to its deadline, only the nearest one is sent.
+pub fn send_due() {
+    let now = time();
+    let assigned = indexes::ids_with_status(&GigStatus::Assigned);
+    let gigs: Vec<_> = GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        assigned.iter().filter_map(|id| storage.get(id)).collect()
+    });
+    for gig in gigs {
+        let Some(worker) = gig.assigned_to else {
+            continue;
+        };
+        if now >= gig.deadline {
+            continue;
+        }
+        let hours = gig.reminder_hours.clone().unwrap_or_else(|| DEFAULT_REMINDER_HOURS.to_vec());
+        let due: Vec<u32> = REMINDERS_SENT.with(|sent| {
+            let sent = sent.borrow();
+            hours
+                .into_iter()
+                .filter(|h| now >= gig.deadline.saturating_sub(*h as u64 * HOUR_NANOS))
+                .filter(|h| sent.get(&(gig.id, *h)) != Some(gig.deadline))
+                .collect()
+        });
+        let Some(nearest) = due.iter().min().copied() else {
+            continue;
+        };
+        events::push(
+            gig.id,
+            EventKind::DeadlineReminder { hours_before: nearest, employer: gig.employer, worker },
+            &[gig.employer, worker],
+        );
+        REMINDERS_SENT.with(|sent| {
+            let mut sent = sent.borrow_mut();
+            for h in due {
+                sent.insert((gig.id, h), gig.deadline);
+            }
+        });
+    }
+}
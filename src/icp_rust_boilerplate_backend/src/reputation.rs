@@ -0,0 +1,179 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::moderation::not_banned;
+use crate::{
+    get_memory, GigStatus, Memory, StorablePrincipal, GIG_STORAGE, RATINGS_MEMORY_ID,
+    REPUTATION_MEMORY_ID,
+};
+
+/// Maximum length of a rating comment.
+const MAX_COMMENT_LEN: usize = 500;
+
+/// A rating one party of an approved gig left for the other.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Rating {
+    pub gig_id: u64,
+    pub rater: Principal,
+    pub ratee: Principal,
+    pub score: u8,          // 1 to 5.
+    pub comment: String,
+    pub created_at: u64,
+}
+
+/// Aggregated reputation of a principal.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+pub struct Reputation {
+    pub total_score: u64,       // Sum of all received scores.
+    pub rating_count: u64,
+    pub completed_gigs: u64,    // Approved gigs the principal took part in.
+}
+
+/// Reputation summary returned by `get_reputation`.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct ReputationSummary {
+    pub principal: Principal,
+    pub average_score: f64,
+    pub rating_count: u64,
+    pub completed_gigs: u64,
+}
+
+impl Storable for Rating {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Rating {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for Reputation {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Reputation {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Ratings keyed by `(gig_id, rater)`, so each party can rate a gig once.
+    static RATING_STORAGE: RefCell<StableBTreeMap<(u64, StorablePrincipal), Rating, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(RATINGS_MEMORY_ID)));
+
+    /// Aggregated reputation keyed by principal.
+    static REPUTATION_STORAGE: RefCell<StableBTreeMap<StorablePrincipal, Reputation, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(REPUTATION_MEMORY_ID)));
+}
+
+/// Apply `f` to the stored reputation of `principal`.
+fn update_reputation(principal: Principal, f: impl FnOnce(&mut Reputation)) {
+    REPUTATION_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let key = StorablePrincipal(principal);
+        let mut reputation = storage.get(&key).unwrap_or_default();
+        f(&mut reputation);
+        storage.insert(key, reputation);
+    });
+}
+
+/// Count an approved gig towards the completed-gig totals of both parties.
+pub fn record_completion(employer: Principal, worker: Principal) {
+    update_reputation(employer, |r| r.completed_gigs += 1);
+    update_reputation(worker, |r| r.completed_gigs += 1);
+}
+
+/// Rate the other party of an approved gig. Each party can rate once.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn rate_counterparty(gig_id: u64, score: u8, comment: String) -> Result<Rating, GigError> {
+    let rater = caller();
+    if !(1..=5).contains(&score) {
+        return Err(GigError::validation("score", "Score must be between 1 and 5"));
+    }
+    if comment.len() > MAX_COMMENT_LEN {
+        return Err(GigError::validation("comment", "Comment must be at most 500 bytes"));
+    }
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ratings are only meaningful once the work has been approved.
+    if gig.status != GigStatus::Approved {
+        return Err(GigError::invalid_state("Approved", gig.status));
+    }
+    let worker = gig.assigned_to.ok_or_else(|| GigError::not_found("Assigned worker"))?;
+    let ratee = if rater == gig.employer {
+        worker
+    } else if rater == worker {
+        gig.employer
+    } else {
+        return Err(GigError::unauthorized("Only the employer or worker can rate this gig"));
+    };
+
+    let key = (gig_id, StorablePrincipal(rater));
+    if RATING_STORAGE.with(|storage| storage.borrow().contains_key(&key)) {
+        return Err(GigError::AlreadyExists { resource: "Rating".to_string() });
+    }
+    let rating = Rating {
+        gig_id,
+        rater,
+        ratee,
+        score,
+        comment,
+        created_at: time(),
+    };
+    RATING_STORAGE.with(|storage| storage.borrow_mut().insert(key, rating.clone()));
+    update_reputation(ratee, |r| {
+        r.total_score += score as u64;
+        r.rating_count += 1;
+    });
+    Ok(rating)
+}
+
+/// Retrieve the aggregated reputation of a principal.
+#[ic_cdk::query]
+pub fn get_reputation(principal: Principal) -> ReputationSummary {
+    let reputation = REPUTATION_STORAGE
+        .with(|storage| storage.borrow().get(&StorablePrincipal(principal)))
+        .unwrap_or_default();
+    let average_score = if reputation.rating_count == 0 {
+        0.0
+    } else {
+        reputation.total_score as f64 / reputation.rating_count as f64
+    };
+    ReputationSummary {
+        principal,
+        average_score,
+        rating_count: reputation.rating_count,
+        completed_gigs: reputation.completed_gigs,
+    }
+}
+
+/// Retrieve the ratings left on a gig.
+#[ic_cdk::query]
+pub fn get_gig_ratings(gig_id: u64) -> Vec<Rating> {
+    let start = (gig_id, StorablePrincipal::default());
+    RATING_STORAGE.with(|storage| {
+        storage
+            .borrow()
+            .range(start..)
+            .take_while(|((id, _), _)| *id == gig_id)
+            .map(|(_, rating)| rating)
+            .collect()
+    })
+}
@@ -0,0 +1,192 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::moderation::{self, not_banned};
+use crate::{
+    get_memory, GigStatus, Memory, StorablePrincipal, GIG_STORAGE, REVIEWEE_INDEX_MEMORY_ID,
+    REVIEWS_MEMORY_ID,
+};
+
+/// Longest review text accepted, in bytes.
+const MAX_REVIEW_LEN: usize = 2000;
+/// Longest response to a review accepted, in bytes.
+const MAX_RESPONSE_LEN: usize = 1000;
+/// Number of reviews returned per page by `get_reviews_for`.
+const REVIEWS_PAGE_SIZE: u64 = 20;
+
+/// Which party of a gig wrote a review about the other.
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+pub enum ReviewDirection {
+    EmployerToWorker, // The employer reviewing the worker's delivery.
+    WorkerToEmployer, // The worker reviewing the employer's conduct.
+}
+
+impl ReviewDirection {
+    /// Stable storage key of a direction. Values must never be reused once assigned.
+    fn key(self) -> u8 {
+        match self {
+            ReviewDirection::EmployerToWorker => 0,
+            ReviewDirection::WorkerToEmployer => 1,
+        }
+    }
+}
+
+/// Written feedback one party of an approved gig left about the other.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Review {
+    pub gig_id: u64,
+    pub direction: ReviewDirection,
+    pub reviewer: Principal,
+    pub reviewee: Principal,
+    pub text: String,
+    pub created_at: u64,
+    pub response: Option<String>,   // The reviewee's public reply, if any.
+    pub responded_at: Option<u64>,
+}
+
+/// A page of reviews returned by `get_reviews_for`.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct ReviewPage {
+    pub reviews: Vec<Review>,
+    pub total: u64, // Reviews received by the principal across all pages.
+}
+
+impl Storable for Review {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Review {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Reviews keyed by `(gig_id, direction key)`, so each party can review a gig once.
+    static REVIEWS: RefCell<StableBTreeMap<(u64, u8), Review, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(REVIEWS_MEMORY_ID)));
+
+    /// Direction keys of reviews keyed by `(reviewee, gig_id)`.
+    static REVIEWEE_INDEX: RefCell<StableBTreeMap<(StorablePrincipal, u64), u8, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(REVIEWEE_INDEX_MEMORY_ID)));
+}
+
+/// Review the other party of an approved gig. Each party can review a gig once.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn submit_review(gig_id: u64, text: String) -> Result<Review, GigError> {
+    let reviewer = caller();
+    moderation::ensure_not_banned(reviewer)?;
+    if text.trim().is_empty() || text.len() > MAX_REVIEW_LEN {
+        return Err(GigError::validation("text", "Must be between 1 and 2000 bytes"));
+    }
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Reviews, like ratings, are only meaningful once the work has been approved.
+    if gig.status != GigStatus::Approved {
+        return Err(GigError::invalid_state("Approved", gig.status));
+    }
+    let worker = gig.assigned_to.ok_or_else(|| GigError::not_found("Assigned worker"))?;
+    let (direction, reviewee) = if reviewer == gig.employer {
+        (ReviewDirection::EmployerToWorker, worker)
+    } else if reviewer == worker {
+        (ReviewDirection::WorkerToEmployer, gig.employer)
+    } else {
+        return Err(GigError::unauthorized("Only the employer or worker can review this gig"));
+    };
+
+    let key = (gig_id, direction.key());
+    if REVIEWS.with(|reviews| reviews.borrow().contains_key(&key)) {
+        return Err(GigError::AlreadyExists { resource: "Review".to_string() });
+    }
+    let review = Review {
+        gig_id,
+        direction,
+        reviewer,
+        reviewee,
+        text,
+        created_at: time(),
+        response: None,
+        responded_at: None,
+    };
+    REVIEWS.with(|reviews| reviews.borrow_mut().insert(key, review.clone()));
+    REVIEWEE_INDEX.with(|index| {
+        index.borrow_mut().insert((StorablePrincipal(reviewee), gig_id), direction.key())
+    });
+    Ok(review)
+}
+
+/// Publicly reply once to a review written about the caller.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn respond_to_review(
+    gig_id: u64,
+    direction: ReviewDirection,
+    response: String,
+) -> Result<Review, GigError> {
+    moderation::ensure_not_banned(caller())?;
+    if response.trim().is_empty() || response.len() > MAX_RESPONSE_LEN {
+        return Err(GigError::validation("response", "Must be between 1 and 1000 bytes"));
+    }
+    let key = (gig_id, direction.key());
+    REVIEWS.with(|reviews| {
+        let mut reviews = reviews.borrow_mut();
+        let mut review = reviews.get(&key).ok_or_else(|| GigError::not_found("Review"))?;
+        // Ensure only the reviewee can respond.
+        if review.reviewee != caller() {
+            return Err(GigError::unauthorized("Only the reviewee can respond to this review"));
+        }
+        if review.response.is_some() {
+            return Err(GigError::AlreadyExists { resource: "Response".to_string() });
+        }
+        review.response = Some(response);
+        review.responded_at = Some(time());
+        reviews.insert(key, review.clone());
+        Ok(review)
+    })
+}
+
+/// Retrieve the reviews written about a principal, newest gig first, `REVIEWS_PAGE_SIZE`
+/// per page. `page` counts from zero.
+#[ic_cdk::query]
+pub fn get_reviews_for(principal: Principal, page: u64) -> ReviewPage {
+    let key = StorablePrincipal(principal);
+    let entries: Vec<(u64, u8)> = REVIEWEE_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|((_, gig_id), direction)| (gig_id, direction))
+            .collect()
+    });
+    let reviews = REVIEWS.with(|reviews| {
+        let reviews = reviews.borrow();
+        entries
+            .iter()
+            .rev()
+            .skip(page.saturating_mul(REVIEWS_PAGE_SIZE) as usize)
+            .take(REVIEWS_PAGE_SIZE as usize)
+            .filter_map(|key| reviews.get(key))
+            .collect()
+    });
+    ReviewPage { reviews, total: entries.len() as u64 }
+}
+
+/// Retrieve the reviews left on a gig.
+#[ic_cdk::query]
+pub fn get_gig_reviews(gig_id: u64) -> Vec<Review> {
+    REVIEWS.with(|reviews| {
+        reviews
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u8::MAX))
+            .map(|(_, review)| review)
+            .collect()
+    })
+}
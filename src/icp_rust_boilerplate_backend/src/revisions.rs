@@ -0,0 +1,112 @@
+use candid::{Decode, Encode};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::contracts;
+use crate::delegates::{self, Permission};
+use crate::error::GigError;
+use crate::moderation::not_banned;
+use crate::state_machine;
+use crate::visibility;
+use crate::{get_memory, insert_gig, Gig, GigStatus, Memory, GIG_STORAGE, REVISIONS_MEMORY_ID};
+
+/// Most revisions an employer can request on a gig.
+pub const MAX_REVISIONS: u32 = 3;
+/// Longest revision feedback accepted, in bytes.
+const MAX_FEEDBACK_LEN: usize = 1000;
+
+/// An employer's request to rework a submission.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    pub gig_id: u64,
+    pub number: u32,                              // 1 for the first revision of the gig.
+    pub feedback: String,
+    pub submission_note: Option<String>,          // The submission that was sent back.
+    pub submission_attachments: Option<Vec<u64>>,
+    pub requested_at: u64,
+}
+
+impl Storable for Revision {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Revision {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Revisions keyed by `(gig_id, number)` so a gig's revisions are contiguous.
+    static REVISIONS: RefCell<StableBTreeMap<(u64, u32), Revision, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(REVISIONS_MEMORY_ID)));
+}
+
+/// Send submitted work back to the worker with feedback, returning the gig to `Assigned`.
+/// At most three revisions can be requested per gig unless its contract says otherwise.
+/// Employer or their delegates only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn request_revision(gig_id: u64, feedback: String) -> Result<Gig, GigError> {
+    if feedback.trim().is_empty() || feedback.len() > MAX_FEEDBACK_LEN {
+        return Err(GigError::validation("feedback", "Must be between 1 and 1000 bytes"));
+    }
+    let number = REVISIONS.with(|revisions| {
+        revisions.borrow().range((gig_id, 0)..=(gig_id, u32::MAX)).count() as u32 + 1
+    });
+    if number > contracts::max_revisions(gig_id) {
+        return Err(GigError::validation("gig_id", "No revisions left for this gig"));
+    }
+
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        // Ensure only the employer or a delegate can request a revision.
+        if !delegates::may_act(&gig, caller(), Permission::Approve) {
+            return Err(GigError::unauthorized("Only the employer can request a revision"));
+        }
+        if gig.status != GigStatus::Submitted {
+            return Err(GigError::invalid_state("Submitted", gig.status));
+        }
+        state_machine::transition(&mut gig, GigStatus::Assigned)?;
+
+        let revision = Revision {
+            gig_id,
+            number,
+            feedback,
+            submission_note: gig.submission_note.take(),
+            submission_attachments: gig.submission_attachments.take(),
+            requested_at: time(),
+        };
+        gig.submitted_at = None;
+        gig.updated_at = Some(revision.requested_at);
+        REVISIONS.with(|revisions| revisions.borrow_mut().insert((gig_id, number), revision));
+        insert_gig(&mut storage, &mut gig);
+        Ok(gig)
+    })
+}
+
+/// Retrieve every revision requested on a gig, oldest first. Gigs the caller may not view
+/// have no revisions.
+#[ic_cdk::query]
+pub fn get_revisions(gig_id: u64) -> Vec<Revision> {
+    let viewable = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .is_some_and(|gig| visibility::can_view(&gig));
+    if !viewable {
+        return Vec::new();
+    }
+    REVISIONS.with(|revisions| {
+        revisions
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u32::MAX))
+            .map(|(_, revision)| revision)
+            .collect()
+    })
+}
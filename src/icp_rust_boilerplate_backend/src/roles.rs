@@ -0,0 +1,127 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::moderation::not_banned;
+use crate::{get_memory, Memory, StorablePrincipal, ROLES_MEMORY_ID};
+
+/// Platform role granted to a principal.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum Role {
+    Admin,      // Manages moderators and has every moderator permission.
+    Moderator,  // Hides gigs, bans principals and force-resolves disputes.
+}
+
+/// A principal together with its role.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct RoleAssignment {
+    pub principal: Principal,
+    pub role: Role,
+}
+
+impl Storable for Role {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Role {
+    const MAX_SIZE: u32 = 32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Roles keyed by principal.
+    static ROLES: RefCell<StableBTreeMap<StorablePrincipal, Role, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(ROLES_MEMORY_ID)));
+}
+
+/// Ensure the caller may grant or revoke `role`. Controllers manage every role; admins
+/// manage moderators.
+fn ensure_can_manage(role: &Role) -> Result<(), GigError> {
+    let caller = caller();
+    if ic_cdk::api::is_controller(&caller) {
+        return Ok(());
+    }
+    if *role == Role::Moderator && get_role(caller) == Some(Role::Admin) {
+        return Ok(());
+    }
+    Err(GigError::unauthorized("Only a controller or admin can manage this role"))
+}
+
+/// Ensure the caller can moderate: a controller, an admin or a moderator.
+pub fn ensure_moderator() -> Result<(), GigError> {
+    let caller = caller();
+    if ic_cdk::api::is_controller(&caller) || get_role(caller).is_some() {
+        return Ok(());
+    }
+    Err(GigError::unauthorized("Only a moderator can perform this action"))
+}
+
+/// Ensure the caller is a controller or an admin.
+pub fn ensure_admin() -> Result<(), GigError> {
+    let caller = caller();
+    if ic_cdk::api::is_controller(&caller) || get_role(caller) == Some(Role::Admin) {
+        return Ok(());
+    }
+    Err(GigError::unauthorized("Only an admin can perform this action"))
+}
+
+/// Grant a role, replacing any role the principal already has.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn grant_role(principal: Principal, role: Role) -> Result<(), GigError> {
+    ensure_can_manage(&role)?;
+    if let Some(current) = get_role(principal) {
+        ensure_can_manage(&current)?;
+    }
+    if principal == Principal::anonymous() {
+        return Err(GigError::validation("principal", "Roles cannot be granted to anonymous"));
+    }
+    ROLES.with(|roles| roles.borrow_mut().insert(StorablePrincipal(principal), role));
+    Ok(())
+}
+
+/// Revoke the role of a principal.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn revoke_role(principal: Principal) -> Result<(), GigError> {
+    let role = get_role(principal).ok_or_else(|| GigError::not_found("Role"))?;
+    ensure_can_manage(&role)?;
+    ROLES.with(|roles| roles.borrow_mut().remove(&StorablePrincipal(principal)));
+    Ok(())
+}
+
+/// Retrieve the role of a principal, if any.
+#[ic_cdk::query]
+pub fn get_role(principal: Principal) -> Option<Role> {
+    ROLES.with(|roles| roles.borrow().get(&StorablePrincipal(principal)))
+}
+
+/// Principals holding the admin role.
+pub fn admins() -> Vec<Principal> {
+    ROLES.with(|roles| {
+        roles
+            .borrow()
+            .iter()
+            .filter(|(_, role)| *role == Role::Admin)
+            .map(|(principal, _)| principal.0)
+            .collect()
+    })
+}
+
+/// Retrieve every principal holding a role.
+#[ic_cdk::query]
+pub fn list_roles() -> Vec<RoleAssignment> {
+    ROLES.with(|roles| {
+        roles
+            .borrow()
+            .iter()
+            .map(|(principal, role)| RoleAssignment { principal: principal.0, role })
+            .collect()
+    })
+}
@@ -0,0 +1,199 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::id;
+use crate::indexes::{self, SortBy};
+use crate::moderation::not_banned;
+use crate::search;
+use crate::tags::{self, Category};
+use crate::visibility;
+use crate::{
+    get_memory, Gig, GigStatus, GigSummary, IdCell, Memory, StorablePrincipal, GIG_STORAGE,
+    MAX_PAGE_SIZE, SAVED_SEARCHES_MEMORY_ID, SAVED_SEARCH_ID_COUNTER_MEMORY_ID,
+    SAVED_SEARCH_OWNER_INDEX_MEMORY_ID,
+};
+
+/// Most searches a principal can save.
+const MAX_SAVED_SEARCHES: usize = 20;
+/// Longest saved search name, in bytes.
+const MAX_NAME_LEN: usize = 100;
+/// Longest keyword query, in bytes.
+const MAX_KEYWORDS_LEN: usize = 200;
+
+/// Criteria an open gig must meet to match a saved search. Unset criteria match anything.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct SearchFilter {
+    pub keywords: String,           // Every word must appear in the title or description.
+    pub tags: Vec<String>,          // The gig must carry at least one of these tags.
+    pub category: Option<Category>,
+    pub min_budget: Option<u64>,
+    pub max_budget: Option<u64>,
+}
+
+/// A search filter a principal subscribed to.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub id: u64,
+    pub owner: Principal,
+    pub name: String,
+    pub filter: SearchFilter,
+    pub created_at: u64,
+}
+
+impl Storable for SavedSearch {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for SavedSearch {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    static SAVED_SEARCH_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(get_memory(SAVED_SEARCH_ID_COUNTER_MEMORY_ID), 0)
+            .expect("Cannot create a saved search counter")
+    );
+
+    /// Saved searches keyed by saved search ID.
+    static SAVED_SEARCHES: RefCell<StableBTreeMap<u64, SavedSearch, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(SAVED_SEARCHES_MEMORY_ID)));
+
+    /// Saved search IDs keyed by `(owner, search_id)`.
+    static OWNER_INDEX: RefCell<StableBTreeMap<(StorablePrincipal, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(SAVED_SEARCH_OWNER_INDEX_MEMORY_ID)));
+}
+
+/// IDs of the searches saved by `owner`.
+fn ids_owned_by(owner: Principal) -> Vec<u64> {
+    let key = StorablePrincipal(owner);
+    OWNER_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    })
+}
+
+/// Whether a gig meets every criterion of a filter. `terms` are the filter's keywords.
+fn matches(gig: &Gig, filter: &SearchFilter, terms: &[String]) -> bool {
+    if !filter.tags.is_empty()
+        && !gig.tags.iter().flatten().any(|tag| filter.tags.contains(tag))
+    {
+        return false;
+    }
+    if filter.category.is_some() && gig.category != filter.category {
+        return false;
+    }
+    let budget = gig.budget.as_ref().map_or(0, |budget| budget.amount);
+    if filter.min_budget.is_some_and(|min| budget < min)
+        || filter.max_budget.is_some_and(|max| budget > max)
+    {
+        return false;
+    }
+    if terms.is_empty() {
+        return true;
+    }
+    let gig_terms = search::gig_terms(gig);
+    terms.iter().all(|term| gig_terms.contains(term))
+}
+
+/// Save a search filter so new matching gigs can be polled with `get_new_matches`.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn save_search(name: String, mut filter: SearchFilter) -> Result<SavedSearch, GigError> {
+    let owner = caller();
+    if owner == Principal::anonymous() {
+        return Err(GigError::unauthorized("Anonymous principals cannot save searches"));
+    }
+    if name.trim().is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(GigError::validation("name", "Must be between 1 and 100 bytes"));
+    }
+    if filter.keywords.len() > MAX_KEYWORDS_LEN {
+        return Err(GigError::validation("keywords", "Must be at most 200 bytes"));
+    }
+    filter.tags = tags::normalize_tags(filter.tags)?;
+    if let (Some(min), Some(max)) = (filter.min_budget, filter.max_budget) {
+        if min > max {
+            return Err(GigError::validation("min_budget", "Must not exceed max_budget"));
+        }
+    }
+    if ids_owned_by(owner).len() >= MAX_SAVED_SEARCHES {
+        return Err(GigError::validation("name", "At most 20 searches can be saved"));
+    }
+
+    let id = id::next(&SAVED_SEARCH_ID_COUNTER, "Saved search")?;
+    let search = SavedSearch { id, owner, name, filter, created_at: time() };
+    SAVED_SEARCHES.with(|searches| searches.borrow_mut().insert(id, search.clone()));
+    OWNER_INDEX.with(|index| index.borrow_mut().insert((StorablePrincipal(owner), id), ()));
+    Ok(search)
+}
+
+/// Fetch a saved search and check the caller owns it.
+fn owned_search(id: u64) -> Result<SavedSearch, GigError> {
+    let search = SAVED_SEARCHES
+        .with(|searches| searches.borrow().get(&id))
+        .ok_or_else(|| GigError::not_found("Saved search"))?;
+    if search.owner != caller() {
+        return Err(GigError::unauthorized("Only the owner can use this saved search"));
+    }
+    Ok(search)
+}
+
+/// Delete a saved search. Owner only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn delete_saved_search(id: u64) -> Result<(), GigError> {
+    let search = owned_search(id)?;
+    SAVED_SEARCHES.with(|searches| searches.borrow_mut().remove(&id));
+    OWNER_INDEX.with(|index| index.borrow_mut().remove(&(StorablePrincipal(search.owner), id)));
+    Ok(())
+}
+
+/// Retrieve the searches saved by the caller.
+#[ic_cdk::query]
+pub fn list_saved_searches() -> Vec<SavedSearch> {
+    let ids = ids_owned_by(caller());
+    SAVED_SEARCHES.with(|searches| {
+        let searches = searches.borrow();
+        ids.iter().filter_map(|id| searches.get(id)).collect()
+    })
+}
+
+/// Retrieve open gigs posted after `since` that match a saved search, newest first, at
+/// most `MAX_PAGE_SIZE`. Walks the newest-first index, so only gigs newer than `since`
+/// are looked at. Owner only.
+#[ic_cdk::query]
+pub fn get_new_matches(subscription_id: u64, since: u64) -> Result<Vec<GigSummary>, GigError> {
+    let search = owned_search(subscription_id)?;
+    let terms: Vec<String> = search::terms(&search.filter.keywords).into_iter().collect();
+    let mut found = Vec::new();
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        indexes::visit_sorted(Some(SortBy::CreatedAtDesc), |id| {
+            let Some(gig) = storage.get(&id) else {
+                return true;
+            };
+            if gig.created_at <= since {
+                return false;
+            }
+            if gig.status == GigStatus::Open
+                && visibility::can_list(&gig)
+                && matches(&gig, &search.filter, &terms)
+            {
+                found.push(GigSummary::from(&gig));
+            }
+            (found.len() as u64) < MAX_PAGE_SIZE
+        });
+    });
+    Ok(found)
+}
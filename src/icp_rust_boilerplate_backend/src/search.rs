@@ -0,0 +1,138 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::indexes::{self, TitleKey};
+use crate::skills;
+use crate::visibility;
+use crate::{Gig, GigSummary, GIG_STORAGE, MAX_PAGE_SIZE};
+
+/// Shortest word that is indexed.
+const MIN_TERM_LEN: usize = 2;
+/// Longest word that is indexed; longer words are skipped.
+const MAX_TERM_LEN: usize = 32;
+/// Weight of each occurrence of a term in a gig's title.
+const TITLE_WEIGHT: u32 = 3;
+/// Weight of each occurrence of a term in a gig's tags.
+const TAG_WEIGHT: u32 = 2;
+/// Weight of each occurrence of a term in a gig's description.
+const DESCRIPTION_WEIGHT: u32 = 1;
+
+/// What an autocomplete suggestion completes to.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub enum SuggestionKind {
+    Skill,
+    Title { gig_id: u64 }, // The first listed gig with this title.
+}
+
+/// A completion offered for a typed prefix.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub text: String,          // The skill name, or the gig title as posted.
+    pub kind: SuggestionKind,
+}
+
+/// Split text into lower-case search terms, repeats included.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| (MIN_TERM_LEN..=MAX_TERM_LEN).contains(&word.len()))
+}
+
+/// Split text into distinct lower-case search terms.
+pub fn terms(text: &str) -> BTreeSet<String> {
+    words(text).collect()
+}
+
+/// Search terms of a gig's title and description.
+pub fn gig_terms(gig: &Gig) -> BTreeSet<String> {
+    let mut found = terms(&gig.title);
+    found.extend(terms(&gig.description));
+    found
+}
+
+/// Weight of every term of a gig's title, description and tags: the number of times the
+/// term occurs in each, times that field's weight.
+pub fn term_weights(gig: &Gig) -> BTreeMap<String, u32> {
+    let mut weights = BTreeMap::new();
+    let mut add = |text: &str, weight: u32| {
+        for word in words(text) {
+            let total: &mut u32 = weights.entry(word).or_default();
+            *total = total.saturating_add(weight);
+        }
+    };
+    add(&gig.title, TITLE_WEIGHT);
+    add(&gig.description, DESCRIPTION_WEIGHT);
+    for tag in gig.tags.iter().flatten() {
+        add(tag, TAG_WEIGHT);
+    }
+    weights
+}
+
+/// Find gigs whose title, description or tags contain every word of `query`, most
+/// relevant first. A gig's relevance is the summed weight of the query's terms in it, so
+/// repeated words and words in the title or tags rank higher; ties go to the older gig.
+#[ic_cdk::query]
+pub fn search_gigs(query: String, limit: u64) -> Vec<GigSummary> {
+    let terms: Vec<String> = terms(&query).into_iter().collect();
+    let Some((first, rest)) = terms.split_first() else {
+        return Vec::new();
+    };
+    let limit = limit.min(MAX_PAGE_SIZE) as usize;
+
+    let mut ranked: Vec<(u64, u64)> = indexes::ids_with_term(first)
+        .into_iter()
+        .filter_map(|(id, weight)| {
+            rest.iter()
+                .try_fold(weight as u64, |score, term| {
+                    indexes::term_weight(term, id).map(|weight| score + weight as u64)
+                })
+                .map(|score| (id, score))
+        })
+        .collect();
+    ranked.sort_by(|(a_id, a_score), (b_id, b_score)| b_score.cmp(a_score).then(a_id.cmp(b_id)));
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        ranked
+            .into_iter()
+            .filter_map(|(id, _)| storage.get(&id))
+            .filter(visibility::can_list)
+            .take(limit)
+            .map(|gig| GigSummary::from(&gig))
+            .collect()
+    })
+}
+
+/// Suggest skill names and gig titles starting with `prefix`, ignoring case and surrounding
+/// whitespace: skills first, then titles of gigs the caller may list, each in alphabetical
+/// order, at most `limit` (capped at `MAX_PAGE_SIZE`). Gigs sharing a title are suggested
+/// once. Both lists are read from ordered indexes starting at the prefix.
+#[ic_cdk::query]
+pub fn autocomplete(prefix: String, limit: u64) -> Vec<Suggestion> {
+    let limit = limit.min(MAX_PAGE_SIZE) as usize;
+    let prefix = TitleKey::new(&prefix);
+    if prefix.0.is_empty() || limit == 0 {
+        return Vec::new();
+    }
+    let mut found: Vec<Suggestion> = skills::skills_with_prefix(&prefix.0, limit)
+        .into_iter()
+        .map(|skill| Suggestion { text: skill, kind: SuggestionKind::Skill })
+        .collect();
+    let mut last_title: Option<TitleKey> = None;
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        indexes::visit_title_prefix(&prefix, |title, id| {
+            if found.len() >= limit {
+                return false;
+            }
+            if last_title.as_ref() == Some(title) {
+                return true;
+            }
+            if let Some(gig) = storage.get(&id).filter(visibility::can_list) {
+                last_title = Some(title.clone());
+                let kind = SuggestionKind::Title { gig_id: id };
+                found.push(Suggestion { text: gig.title, kind });
+            }
+            true
+        });
+    });
+    found
+}
@@ -0,0 +1,423 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::management_canister::main::{
+    create_canister, install_code, CanisterInstallMode, CanisterSettings, CreateCanisterArgument,
+    InstallCodeArgument,
+};
+use ic_cdk::api::call::RejectionCode;
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, Cell, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::id;
+use crate::indexes::SortBy;
+use crate::log;
+use crate::moderation::not_banned;
+use crate::{
+    ensure_controller, get_gig, get_gigs_paginated, get_memory, Gig, GigPage, Memory,
+    MAX_PAGE_SIZE, SHARDS_MEMORY_ID, SHARD_STATE_MEMORY_ID, SHARD_WASM_MEMORY_ID,
+};
+
+/// Most gigs a canister takes before new gigs move to a fresh shard.
+const MAX_GIGS_PER_CANISTER: u64 = 250_000;
+/// Stable memory a canister may use before new gigs move to a fresh shard: 32 GiB.
+const MAX_STABLE_MEMORY_BYTES: u64 = 32 * 1024 * 1024 * 1024;
+/// Size of a stable memory page.
+const WASM_PAGE_BYTES: u64 = 64 * 1024;
+/// Cycles a new shard canister is created with.
+const SHARD_CYCLES: u128 = 2_000_000_000_000;
+
+/// A shard canister spawned by the index, holding the gigs from `first_id` up to the
+/// next shard's `first_id`.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Shard {
+    pub canister_id: Principal,
+    pub first_id: u64,
+    pub created_at: u64,
+}
+
+/// Where this canister sits in the shard layout.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ShardState {
+    index: Option<Principal>,   // The index that spawned this shard; `None` on the index.
+    first_id: u64,              // First gig ID this canister holds.
+    end_id: Option<u64>,        // First gig ID past this canister's range, once sealed.
+}
+
+/// Capacity report of a canister, used by the index to decide when to spawn a shard.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct ShardStatus {
+    pub first_id: u64,
+    pub end_id: Option<u64>,        // Set once the canister no longer takes new gigs.
+    pub gig_count: u64,             // Gigs posted to this canister since it was created.
+    pub stable_memory_bytes: u64,
+    pub full: bool,
+}
+
+/// Module installed into new shards, uploaded by a controller.
+#[derive(Default)]
+struct ShardWasm(Vec<u8>);
+
+impl Storable for Shard {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Shard {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for ShardState {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl Storable for ShardWasm {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        ShardWasm(bytes.into_owned())
+    }
+}
+
+thread_local! {
+    /// This canister's place in the shard layout.
+    static STATE: RefCell<Cell<ShardState, Memory>> = RefCell::new(
+        Cell::init(get_memory(SHARD_STATE_MEMORY_ID), ShardState::default())
+            .expect("Cannot create the shard state")
+    );
+
+    /// Shards spawned by the index, keyed by the first gig ID they hold.
+    static SHARDS: RefCell<StableBTreeMap<u64, Shard, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(SHARDS_MEMORY_ID)));
+
+    /// Module installed into new shards.
+    static SHARD_WASM: RefCell<Cell<ShardWasm, Memory>> = RefCell::new(
+        Cell::init(get_memory(SHARD_WASM_MEMORY_ID), ShardWasm::default())
+            .expect("Cannot create the shard module")
+    );
+
+    /// Whether a shard is being spawned, so overlapping sweeps do not spawn two.
+    static SPAWNING: RefCell<bool> = const { RefCell::new(false) };
+}
+
+fn state() -> ShardState {
+    STATE.with(|state| state.borrow().get().clone())
+}
+
+/// The shard taking new gigs, if the index has spawned any.
+fn active_shard() -> Option<Shard> {
+    SHARDS.with(|shards| shards.borrow().iter().last().map(|(_, shard)| shard))
+}
+
+/// Capacity report of this canister.
+fn status() -> ShardStatus {
+    let state = state();
+    let next_id = id::peek_gig_id();
+    let gig_count = next_id.saturating_sub(state.first_id);
+    let stable_memory_bytes = ic_cdk::api::stable::stable64_size() * WASM_PAGE_BYTES;
+    ShardStatus {
+        first_id: state.first_id,
+        end_id: state.end_id,
+        gig_count,
+        stable_memory_bytes,
+        full: gig_count >= MAX_GIGS_PER_CANISTER || stable_memory_bytes >= MAX_STABLE_MEMORY_BYTES,
+    }
+}
+
+/// Stop taking new gigs here and return the first ID past this canister's range.
+fn seal() -> u64 {
+    STATE.with(|cell| {
+        let mut state = cell.borrow().get().clone();
+        let end_id = state
+            .end_id
+            .unwrap_or_else(id::peek_gig_id);
+        state.end_id = Some(end_id);
+        cell.borrow_mut().set(state).expect("Cannot update the shard state");
+        end_id
+    })
+}
+
+/// First gig ID past this canister's range, once it is sealed.
+pub fn end_id() -> Option<u64> {
+    state().end_id
+}
+
+/// Ensure new gigs may be posted to this canister. Once the index has spawned a shard,
+/// new gigs go to the newest shard; a sealed shard sends callers back to the index.
+pub fn ensure_accepts_new_gigs() -> Result<(), GigError> {
+    let state = state();
+    if state.index.is_none() {
+        if let Some(shard) = active_shard() {
+            return Err(GigError::Sharded { canister_id: shard.canister_id });
+        }
+    }
+    match (state.end_id, state.index) {
+        (None, _) => Ok(()),
+        (Some(_), Some(index)) => Err(GigError::Sharded { canister_id: index }),
+        (Some(_), None) => Err(GigError::invalid_state("Accepting new gigs", "Sealed")),
+    }
+}
+
+/// Canister holding a gig, routed by ID range. Shards route IDs outside their own range
+/// to the index.
+#[ic_cdk::query]
+pub fn locate_gig(id: u64) -> Principal {
+    let state = state();
+    if let Some(index) = state.index {
+        let in_range = id >= state.first_id && state.end_id.is_none_or(|end_id| id < end_id);
+        return if in_range { ic_cdk::id() } else { index };
+    }
+    SHARDS.with(|shards| {
+        shards
+            .borrow()
+            .iter()
+            .take_while(|(first_id, _)| *first_id <= id)
+            .last()
+            .map_or(ic_cdk::id(), |(_, shard)| shard.canister_id)
+    })
+}
+
+/// Retrieve the shards spawned by the index, by the first gig ID they hold.
+#[ic_cdk::query]
+pub fn list_shards() -> Vec<Shard> {
+    SHARDS.with(|shards| shards.borrow().iter().map(|(_, shard)| shard).collect())
+}
+
+/// Retrieve this canister's capacity report.
+#[ic_cdk::query]
+pub fn get_shard_status() -> ShardStatus {
+    status()
+}
+
+/// Upload the module installed into new shards, normally a build of this canister.
+/// Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn set_shard_wasm(wasm: Vec<u8>) -> Result<(), GigError> {
+    ensure_controller()?;
+    if wasm.is_empty() {
+        return Err(GigError::validation("wasm", "Must not be empty"));
+    }
+    SHARD_WASM
+        .with(|module| module.borrow_mut().set(ShardWasm(wasm)))
+        .expect("Cannot store the shard module");
+    Ok(())
+}
+
+/// Turn a freshly installed canister into a shard holding gigs from `first_id` onwards.
+/// Called by the index, which controls the shards it spawns.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn configure_shard(first_id: u64) -> Result<(), GigError> {
+    ensure_controller()?;
+    if state().index.is_some() {
+        return Err(GigError::AlreadyExists { resource: "Shard configuration".to_string() });
+    }
+    id::reserve_gig_range(first_id)?;
+    let state = ShardState { index: Some(caller()), first_id, end_id: None };
+    STATE
+        .with(|cell| cell.borrow_mut().set(state))
+        .expect("Cannot update the shard state");
+    Ok(())
+}
+
+/// Stop taking new gigs and return the first gig ID past this shard's range, so the
+/// index can hand the next range to a new shard. Index only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn seal_shard() -> Result<u64, GigError> {
+    // Ensure only the index that spawned this shard can seal it.
+    if state().index != Some(caller()) {
+        return Err(GigError::unauthorized("Only the index can seal a shard"));
+    }
+    Ok(seal())
+}
+
+/// Spawn a new shard once the canister taking new gigs is full. Runs on the index only,
+/// and only after a controller has uploaded the shard module.
+pub fn check_capacity() {
+    if state().index.is_some() || SPAWNING.with(|spawning| *spawning.borrow()) {
+        return;
+    }
+    if SHARD_WASM.with(|module| module.borrow().get().0.is_empty()) {
+        return;
+    }
+    let active = active_shard();
+    if active.is_none() && !status().full {
+        return;
+    }
+    SPAWNING.with(|spawning| *spawning.borrow_mut() = true);
+    ic_cdk::spawn(async move {
+        if let Err(error) = grow(active).await {
+            log::error(&format!("Cannot spawn a shard: {}", error));
+        }
+        SPAWNING.with(|spawning| *spawning.borrow_mut() = false);
+    });
+}
+
+/// Describe a failed inter-canister call.
+fn call_error((code, message): (RejectionCode, String)) -> String {
+    format!("{:?}: {}", code, message)
+}
+
+/// Seal the canister taking new gigs if it is full, and spawn a shard for the next range.
+async fn grow(active: Option<Shard>) -> Result<(), String> {
+    let first_id = match active {
+        None => seal(),
+        Some(shard) => {
+            let (status,): (ShardStatus,) = ic_cdk::call(shard.canister_id, "get_shard_status", ())
+                .await
+                .map_err(call_error)?;
+            if !status.full && status.end_id.is_none() {
+                return Ok(());
+            }
+            let (result,): (Result<u64, GigError>,) =
+                ic_cdk::call(shard.canister_id, "seal_shard", ()).await.map_err(call_error)?;
+            result.map_err(|error| format!("{:?}", error))?
+        }
+    };
+    spawn_shard(first_id).await
+}
+
+/// Create, install and configure a shard holding gigs from `first_id` onwards.
+async fn spawn_shard(first_id: u64) -> Result<(), String> {
+    let settings = CanisterSettings {
+        controllers: Some(vec![ic_cdk::id()]),
+        compute_allocation: None,
+        memory_allocation: None,
+        freezing_threshold: None,
+    };
+    let (record,) =
+        create_canister(CreateCanisterArgument { settings: Some(settings) }, SHARD_CYCLES)
+            .await
+            .map_err(call_error)?;
+    let canister_id = record.canister_id;
+    let wasm_module = SHARD_WASM.with(|module| module.borrow().get().0.clone());
+    install_code(InstallCodeArgument {
+        mode: CanisterInstallMode::Install,
+        canister_id,
+        wasm_module,
+        arg: Encode!().unwrap(),
+    })
+    .await
+    .map_err(call_error)?;
+    let (result,): (Result<(), GigError>,) =
+        ic_cdk::call(canister_id, "configure_shard", (first_id,))
+            .await
+            .map_err(call_error)?;
+    result.map_err(|error| format!("{:?}", error))?;
+    let shard = Shard { canister_id, first_id, created_at: time() };
+    SHARDS.with(|shards| shards.borrow_mut().insert(first_id, shard));
+    Ok(())
+}
+
+/// Call a query method of a shard from a composite query.
+async fn query_shard<A, R>(canister_id: Principal, method: &str, args: A) -> Result<R, GigError>
+where
+    A: candid::utils::ArgumentEncoder,
+    R: for<'a> candid::utils::ArgumentDecoder<'a>,
+{
+    ic_cdk::call(canister_id, method, args)
+        .await
+        .map_err(|error| GigError::ShardUnavailable { canister_id, message: call_error(error) })
+}
+
+/// Retrieve a gig from whichever canister holds it. Shards see the index as the caller,
+/// so gigs held by a shard are only returned when they are visible to everyone.
+#[ic_cdk::query(composite = true)]
+pub async fn get_gig_sharded(id: u64) -> Result<Option<Gig>, GigError> {
+    let canister_id = locate_gig(id);
+    if canister_id == ic_cdk::id() {
+        return Ok(get_gig(id));
+    }
+    let (gig,) = query_shard(canister_id, "get_gig", (id,)).await?;
+    Ok(gig)
+}
+
+/// Retrieve several gigs by ID from whichever canisters hold them, in the order given,
+/// with one call per shard. At most `MAX_PAGE_SIZE` IDs are looked up.
+#[ic_cdk::query(composite = true)]
+pub async fn get_gigs_by_ids_sharded(ids: Vec<u64>) -> Result<Vec<Option<Gig>>, GigError> {
+    let ids: Vec<u64> = ids.into_iter().take(MAX_PAGE_SIZE as usize).collect();
+    let mut gigs: Vec<Option<Gig>> = vec![None; ids.len()];
+    let mut by_canister: Vec<(Principal, Vec<usize>)> = Vec::new();
+    for (position, id) in ids.iter().enumerate() {
+        let canister_id = locate_gig(*id);
+        match by_canister.iter_mut().find(|(canister, _)| *canister == canister_id) {
+            Some((_, positions)) => positions.push(position),
+            None => by_canister.push((canister_id, vec![position])),
+        }
+    }
+    for (canister_id, positions) in by_canister {
+        let wanted: Vec<u64> = positions.iter().map(|position| ids[*position]).collect();
+        let found = if canister_id == ic_cdk::id() {
+            wanted.into_iter().map(get_gig).collect()
+        } else {
+            let (found,): (Vec<Option<Gig>>,) =
+                query_shard(canister_id, "get_gigs_by_ids", (wanted,)).await?;
+            found
+        };
+        for (position, gig) in positions.into_iter().zip(found) {
+            gigs[position] = gig;
+        }
+    }
+    Ok(gigs)
+}
+
+/// Retrieve a page of the public board across the index and every shard, as if it were
+/// one canister. Shards hold consecutive ID ranges, so only ID order and `CreatedAtDesc`
+/// can be stitched together; `limit` is capped at `MAX_PAGE_SIZE`.
+#[ic_cdk::query(composite = true)]
+pub async fn get_gigs_paginated_sharded(
+    offset: u64,
+    limit: u64,
+    sort_by: Option<SortBy>,
+) -> Result<GigPage, GigError> {
+    let newest_first = match sort_by {
+        None => false,
+        Some(SortBy::CreatedAtDesc) => true,
+        Some(_) => {
+            return Err(GigError::validation("sort_by", "Only ID order and CreatedAtDesc"));
+        }
+    };
+    let mut canisters = vec![ic_cdk::id()];
+    canisters.extend(list_shards().into_iter().map(|shard| shard.canister_id));
+    if newest_first {
+        canisters.reverse();
+    }
+    // Each canister reports its own total, so the window can be walked one canister at a
+    // time: skip whole canisters until the offset falls inside one, then fill the page.
+    let mut limit = limit.min(MAX_PAGE_SIZE);
+    let mut offset = offset;
+    let mut page = GigPage { gigs: Vec::new(), total: 0 };
+    for canister_id in canisters {
+        let shard_page = if canister_id == ic_cdk::id() {
+            get_gigs_paginated(offset, limit, sort_by)
+        } else {
+            let (shard_page,): (GigPage,) = query_shard(
+                canister_id,
+                "get_gigs_paginated",
+                (offset, limit, sort_by),
+            )
+            .await?;
+            shard_page
+        };
+        offset = offset.saturating_sub(shard_page.total);
+        limit -= (shard_page.gigs.len() as u64).min(limit);
+        page.total += shard_page.total;
+        page.gigs.extend(shard_page.gigs);
+    }
+    Ok(page)
+}
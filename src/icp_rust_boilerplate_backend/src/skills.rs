@@ -0,0 +1,181 @@
+use candid::Principal;
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use crate::error::GigError;
+use crate::indexes;
+use crate::moderation::{self, not_banned};
+use crate::profiles::{self, Profile};
+use crate::tags::{self, Tag};
+use crate::visibility;
+use crate::{
+    ensure_controller, get_memory, GigStatus, GigSummary, Memory, StorablePrincipal,
+    GIG_STORAGE, MAX_PAGE_SIZE, SKILLS_MEMORY_ID, WORKER_SKILL_INDEX_MEMORY_ID,
+};
+
+/// Longest skill name, in bytes.
+const MAX_SKILL_LEN: usize = 32;
+
+thread_local! {
+    /// Curated skill taxonomy that gigs and profiles pick from.
+    static SKILLS: RefCell<StableBTreeMap<Tag, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(SKILLS_MEMORY_ID)));
+
+    /// Principals keyed by `(skill, principal)` for every profile listing the skill.
+    static WORKER_SKILL_INDEX: RefCell<StableBTreeMap<(Tag, StorablePrincipal), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(WORKER_SKILL_INDEX_MEMORY_ID)));
+}
+
+/// Normalise a list of skills and ensure each is in the taxonomy, dropping duplicates.
+pub fn normalize_skills(skills: Vec<String>, max: usize) -> Result<Vec<String>, GigError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(skills.len());
+    for skill in skills {
+        let skill = tags::normalize(&skill);
+        let known = skill.len() <= MAX_SKILL_LEN
+            && SKILLS.with(|skills| skills.borrow().contains_key(&Tag(skill.clone())));
+        if !known {
+            return Err(GigError::validation("skills", &format!("Unknown skill: {}", skill)));
+        }
+        if !normalized.contains(&skill) {
+            normalized.push(skill);
+        }
+    }
+    if normalized.len() > max {
+        return Err(GigError::validation("skills", &format!("At most {} skills", max)));
+    }
+    Ok(normalized)
+}
+
+/// Move a profile's skill index entries from its `previous` to its `current` version.
+/// Skills too long to be in the taxonomy, left from before it existed, are not indexed.
+pub fn reindex_profile(previous: Option<&Profile>, current: Option<&Profile>) {
+    WORKER_SKILL_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        if let Some(profile) = previous {
+            for skill in &profile.skills {
+                let skill = tags::normalize(skill);
+                index.remove(&(Tag(skill), StorablePrincipal(profile.principal)));
+            }
+        }
+        if let Some(profile) = current {
+            for skill in &profile.skills {
+                let skill = tags::normalize(skill);
+                if skill.len() <= MAX_SKILL_LEN {
+                    index.insert((Tag(skill), StorablePrincipal(profile.principal)), ());
+                }
+            }
+        }
+    });
+}
+
+/// Rank the keys `lookup` finds under any of `skills` by how many of them they share.
+fn tally<K: Ord>(skills: &[String], lookup: impl Fn(&str) -> Vec<K>) -> Vec<K> {
+    let mut counts: BTreeMap<K, usize> = BTreeMap::new();
+    for skill in skills {
+        for key in lookup(&tags::normalize(skill)) {
+            *counts.entry(key).or_default() += 1;
+        }
+    }
+    let mut ranked: Vec<(K, usize)> = counts.into_iter().collect();
+    // Most shared skills first; the sort is stable, so ties keep key order.
+    ranked.sort_by_key(|(_, shared)| Reverse(*shared));
+    ranked.into_iter().map(|(key, _)| key).collect()
+}
+
+/// Add a skill to the taxonomy. Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn add_skill(skill: String) -> Result<String, GigError> {
+    ensure_controller()?;
+    let skill = tags::normalize(&skill);
+    if skill.is_empty() || skill.len() > MAX_SKILL_LEN {
+        return Err(GigError::validation("skill", "Must be between 1 and 32 bytes"));
+    }
+    SKILLS.with(|skills| skills.borrow_mut().insert(Tag(skill.clone()), ()));
+    Ok(skill)
+}
+
+/// Remove a skill from the taxonomy. Gigs and profiles that already list it keep it.
+/// Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn remove_skill(skill: String) -> Result<(), GigError> {
+    ensure_controller()?;
+    SKILLS
+        .with(|skills| skills.borrow_mut().remove(&Tag(tags::normalize(&skill))))
+        .ok_or_else(|| GigError::not_found("Skill"))
+}
+
+/// List the skill taxonomy in alphabetical order.
+#[ic_cdk::query]
+pub fn list_skills() -> Vec<String> {
+    SKILLS.with(|skills| skills.borrow().iter().map(|(skill, _)| skill.0).collect())
+}
+
+/// Skills of the taxonomy starting with an already normalised `prefix`, in alphabetical
+/// order, at most `limit`.
+pub fn skills_with_prefix(prefix: &str, limit: usize) -> Vec<String> {
+    if prefix.len() > MAX_SKILL_LEN {
+        return Vec::new();
+    }
+    SKILLS.with(|skills| {
+        skills
+            .borrow()
+            .range(Tag(prefix.to_string())..)
+            .map(|(skill, _)| skill.0)
+            .take_while(|skill| skill.starts_with(prefix))
+            .take(limit)
+            .collect()
+    })
+}
+
+/// Find open gigs requiring any of a worker's skills, those sharing the most skills first.
+/// At most `MAX_PAGE_SIZE` gigs are returned.
+#[ic_cdk::query]
+pub fn match_gigs_for_worker(worker: Principal) -> Vec<GigSummary> {
+    let Some(profile) = profiles::get_profile(worker) else {
+        return Vec::new();
+    };
+    let ids = tally(&profile.skills, indexes::ids_with_skill);
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        ids.into_iter()
+            .filter_map(|id| storage.get(&id))
+            .filter(|gig| gig.status == GigStatus::Open && gig.employer != worker)
+            .filter(visibility::can_list)
+            .take(MAX_PAGE_SIZE as usize)
+            .map(|gig| GigSummary::from(&gig))
+            .collect()
+    })
+}
+
+/// Find workers with any of a gig's required skills, those sharing the most skills first.
+/// Banned principals and the employer are left out. At most `MAX_PAGE_SIZE` profiles are
+/// returned.
+#[ic_cdk::query]
+pub fn match_workers_for_gig(gig_id: u64) -> Vec<Profile> {
+    let Some(gig) = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .filter(visibility::can_view)
+    else {
+        return Vec::new();
+    };
+    let workers = tally(gig.required_skills.as_deref().unwrap_or_default(), |skill| {
+        let skill = Tag(skill.to_string());
+        // The default principal is empty, so it sorts before every other.
+        WORKER_SKILL_INDEX.with(|index| {
+            index
+                .borrow()
+                .range((skill.clone(), StorablePrincipal::default())..)
+                .take_while(|((found, _), _)| *found == skill)
+                .map(|((_, principal), _)| principal.0)
+                .collect()
+        })
+    });
+    workers
+        .into_iter()
+        .filter(|worker| *worker != gig.employer && !moderation::is_banned(*worker))
+        .filter_map(profiles::get_profile)
+        .take(MAX_PAGE_SIZE as usize)
+        .collect()
+}
@@ -0,0 +1,188 @@
+use crate::error::GigError;
+use crate::{Gig, GigStatus};
+
+use GigStatus::*;
+
+/// Every status change a gig may go through, as `(from, to)`. Any status write that is
+/// not listed here is rejected.
+const TRANSITIONS: &[(GigStatus, GigStatus)] = &[
+    (Open, PendingAcceptance),      // The employer proposes a worker.
+    (Open, Assigned),               // The employer accepts a bid.
+    (Open, Expired),
+    (Open, Cancelled),
+    (Open, PendingDeletion),
+    (PendingAcceptance, Assigned),  // The worker accepts.
+    (PendingAcceptance, Open),      // The worker declines.
+    (PendingAcceptance, Expired),
+    (PendingAcceptance, Cancelled),
+    (Assigned, Submitted),
+    (Assigned, Open),               // The worker steps back or is unassigned.
+    (Assigned, Disputed),
+    (Assigned, Expired),
+    (Assigned, Cancelled),
+    (Submitted, Approved),
+    (Submitted, Assigned),          // The employer requests a revision.
+    (Submitted, Disputed),
+    (Disputed, Approved),           // Ruling for the worker.
+    (Disputed, Open),               // Ruling for the employer.
+    (Expired, Open),                // The deadline is pushed back.
+    (Expired, Cancelled),
+    (Expired, PendingDeletion),
+    (Approved, PendingDeletion),
+    (Cancelled, PendingDeletion),
+    (PendingDeletion, Open),        // Deletion undone during the grace period.
+    (PendingDeletion, Expired),
+    (PendingDeletion, Approved),
+    (PendingDeletion, Cancelled),
+];
+
+/// Whether a gig may move from `from` to `to`.
+pub fn can_transition(from: &GigStatus, to: &GigStatus) -> bool {
+    TRANSITIONS.iter().any(|(f, t)| f == from && t == to)
+}
+
+/// Ensure a gig may move from `from` to `to`. The error lists the statuses `to` can be
+/// reached from.
+pub fn ensure_transition(from: &GigStatus, to: &GigStatus) -> Result<(), GigError> {
+    if can_transition(from, to) {
+        return Ok(());
+    }
+    let sources: Vec<String> = TRANSITIONS
+        .iter()
+        .filter(|(_, t)| t == to)
+        .map(|(f, _)| format!("{:?}", f))
+        .collect();
+    let expected = match sources.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => "none".to_string(),
+    };
+    Err(GigError::invalid_state(&expected, from))
+}
+
+/// Whether a write completed a gig: it became `Approved` other than by being restored from
+/// the archive or a deletion, and not through a split dispute ruling.
+pub fn is_completion(previous: Option<&Gig>, gig: &Gig) -> bool {
+    let was_done = previous.is_some_and(|previous| {
+        matches!(previous.status, Approved | Archived | PendingDeletion)
+    });
+    gig.status == Approved && !was_done && gig.dispute_split.is_none()
+}
+
+/// Move a gig to `to` if the transition is allowed.
+pub fn transition(gig: &mut Gig, to: GigStatus) -> Result<(), GigError> {
+    ensure_transition(&gig.status, &to)?;
+    gig.status = to;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disputes::DisputeSplit;
+
+    const ALL: [GigStatus; 10] = [
+        Open,
+        PendingAcceptance,
+        Assigned,
+        Submitted,
+        Approved,
+        Disputed,
+        Expired,
+        Cancelled,
+        Archived,
+        PendingDeletion,
+    ];
+
+    #[test]
+    fn listed_transitions_are_allowed() {
+        for (from, to) in TRANSITIONS {
+            assert!(can_transition(from, to), "{:?} -> {:?}", from, to);
+            assert_eq!(ensure_transition(from, to), Ok(()));
+        }
+    }
+
+    #[test]
+    fn unlisted_transitions_are_rejected() {
+        for from in &ALL {
+            for to in &ALL {
+                let listed = TRANSITIONS.iter().any(|(f, t)| f == from && t == to);
+                assert_eq!(can_transition(from, to), listed, "{:?} -> {:?}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn no_status_moves_to_itself() {
+        for status in &ALL {
+            assert!(!can_transition(status, status), "{:?}", status);
+        }
+    }
+
+    #[test]
+    fn work_cannot_skip_review() {
+        assert!(!can_transition(&Open, &Submitted));
+        assert!(!can_transition(&Open, &Approved));
+        assert!(!can_transition(&Assigned, &Approved));
+        assert!(!can_transition(&Approved, &Open));
+        assert!(!can_transition(&Disputed, &Submitted));
+        assert!(!can_transition(&Open, &Disputed));
+    }
+
+    #[test]
+    fn rejection_lists_every_source_status() {
+        assert_eq!(
+            ensure_transition(&Open, &Submitted),
+            Err(GigError::invalid_state("Assigned", Open))
+        );
+        assert_eq!(
+            ensure_transition(&Open, &Disputed),
+            Err(GigError::invalid_state("Assigned or Submitted", Open))
+        );
+        assert_eq!(
+            ensure_transition(&Approved, &Assigned),
+            Err(GigError::invalid_state("Open, PendingAcceptance or Submitted", Approved))
+        );
+    }
+
+    /// A gig in `status`.
+    fn gig(status: GigStatus) -> Gig {
+        Gig { status, ..Gig::for_test(0) }
+    }
+
+    #[test]
+    fn approving_submitted_work_is_a_completion() {
+        assert!(is_completion(Some(&gig(Submitted)), &gig(Approved)));
+        assert!(is_completion(Some(&gig(Disputed)), &gig(Approved)));
+        assert!(is_completion(None, &gig(Approved)));
+    }
+
+    #[test]
+    fn other_writes_are_not_completions() {
+        assert!(!is_completion(Some(&gig(Approved)), &gig(Approved)));
+        assert!(!is_completion(Some(&gig(Submitted)), &gig(Assigned)));
+        assert!(!is_completion(None, &gig(Open)));
+    }
+
+    #[test]
+    fn restoring_an_approved_gig_is_not_a_completion() {
+        assert!(!is_completion(Some(&gig(Archived)), &gig(Approved)));
+        assert!(!is_completion(Some(&gig(PendingDeletion)), &gig(Approved)));
+    }
+
+    #[test]
+    fn split_rulings_are_not_completions() {
+        let split = DisputeSplit { worker_percent: 50, worker_amount: 5, employer_amount: 5 };
+        let approved = Gig { dispute_split: Some(split), ..gig(Approved) };
+        assert!(!is_completion(Some(&gig(Disputed)), &approved));
+    }
+
+    #[test]
+    fn transition_moves_only_along_allowed_edges() {
+        let mut moved = gig(Open);
+        assert_eq!(transition(&mut moved, Assigned), Ok(()));
+        assert_eq!(moved.status, Assigned);
+        assert!(transition(&mut moved, Approved).is_err());
+        assert_eq!(moved.status, Assigned);
+    }
+}
@@ -0,0 +1,245 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+
+use crate::indexes;
+use crate::tags::{self, Tag};
+use crate::{
+    get_memory, Gig, GigStatus, Memory, StorablePrincipal, EMPLOYER_GIG_COUNTS_MEMORY_ID,
+    GIG_STORAGE, OPEN_TAG_COUNTS_MEMORY_ID, POSTED_PER_HOUR_MEMORY_ID, STATS_TOTALS_MEMORY_ID,
+    STATUS_COUNTS_MEMORY_ID,
+};
+
+/// Nanoseconds in an hour, the width of a posting bucket.
+const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+/// Hours of posting history kept.
+const POSTING_HISTORY_HOURS: u64 = 7 * 24;
+/// Key in `TOTALS` of the summed nanoseconds from posting to assignment.
+const TIME_TO_ASSIGN_SUM: u8 = 0;
+/// Key in `TOTALS` of the number of assignments summed.
+const TIME_TO_ASSIGN_COUNT: u8 = 1;
+
+/// Every gig status, in the order statistics report them.
+const STATUSES: [GigStatus; 10] = [
+    GigStatus::Open,
+    GigStatus::PendingAcceptance,
+    GigStatus::Assigned,
+    GigStatus::Submitted,
+    GigStatus::Approved,
+    GigStatus::Disputed,
+    GigStatus::Expired,
+    GigStatus::Cancelled,
+    GigStatus::Archived,
+    GigStatus::PendingDeletion,
+];
+
+/// Board-wide statistics.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct GigStats {
+    pub total_gigs: u64,
+    pub by_status: Vec<(GigStatus, u64)>,
+    pub posted_last_24h: u64,
+    pub posted_last_7d: u64,
+    pub average_time_to_assign_secs: Option<u64>, // `None` until a gig has been assigned.
+    pub stable_memory_bytes: u64,
+}
+
+thread_local! {
+    /// Number of gigs per status, keyed by status index key.
+    static STATUS_COUNTS: RefCell<StableBTreeMap<u8, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(STATUS_COUNTS_MEMORY_ID)));
+
+    /// Gigs posted per hour, keyed by hours since the epoch. Only the last week is kept.
+    static POSTED_PER_HOUR: RefCell<StableBTreeMap<u64, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(POSTED_PER_HOUR_MEMORY_ID)));
+
+    /// Running totals such as the time-to-assign sum, keyed by the `TIME_TO_ASSIGN_*` keys.
+    static TOTALS: RefCell<StableBTreeMap<u8, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(STATS_TOTALS_MEMORY_ID)));
+
+    /// Number of stored gigs per employer, whatever their status.
+    static EMPLOYER_COUNTS: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(EMPLOYER_GIG_COUNTS_MEMORY_ID)));
+
+    /// Number of open gigs per tag.
+    static OPEN_TAG_COUNTS: RefCell<StableBTreeMap<Tag, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(OPEN_TAG_COUNTS_MEMORY_ID)));
+}
+
+/// Add `delta` to the counter under `key`, saturating at zero.
+fn bump<K: Ord + Clone + ic_stable_structures::BoundedStorable>(
+    map: &mut StableBTreeMap<K, u64, Memory>,
+    key: K,
+    delta: i64,
+) {
+    let count = map.get(&key).unwrap_or(0).saturating_add_signed(delta);
+    map.insert(key, count);
+}
+
+/// Count a gig posted at `created_at` and forget buckets older than a week.
+fn count_posting(created_at: u64) {
+    let hour = created_at / HOUR_NANOS;
+    let oldest = (time() / HOUR_NANOS).saturating_sub(POSTING_HISTORY_HOURS);
+    if hour < oldest {
+        return;
+    }
+    POSTED_PER_HOUR.with(|buckets| {
+        let mut buckets = buckets.borrow_mut();
+        bump(&mut buckets, hour, 1);
+        let expired: Vec<u64> = buckets.range(..oldest).map(|(hour, _)| hour).collect();
+        for hour in expired {
+            buckets.remove(&hour);
+        }
+    });
+}
+
+/// Tags a gig counts towards in `OPEN_TAG_COUNTS`: its own while it is open, none otherwise.
+fn open_tags(gig: Option<&Gig>) -> &[String] {
+    match gig {
+        Some(gig) if gig.status == GigStatus::Open => gig.tags.as_deref().unwrap_or_default(),
+        _ => &[],
+    }
+}
+
+/// Move a gig between the per-employer and per-tag counters.
+fn record_counts(previous: Option<&Gig>, current: Option<&Gig>) {
+    EMPLOYER_COUNTS.with(|counts| {
+        let mut counts = counts.borrow_mut();
+        match (previous, current) {
+            (None, Some(gig)) => bump(&mut counts, StorablePrincipal(gig.employer), 1),
+            (Some(gig), None) => bump(&mut counts, StorablePrincipal(gig.employer), -1),
+            _ => {}
+        }
+    });
+    let (old, new) = (open_tags(previous), open_tags(current));
+    if old == new {
+        return;
+    }
+    OPEN_TAG_COUNTS.with(|counts| {
+        let mut counts = counts.borrow_mut();
+        for tag in old.iter().filter(|tag| !new.contains(tag)) {
+            bump(&mut counts, Tag(tag.clone()), -1);
+        }
+        for tag in new.iter().filter(|tag| !old.contains(tag)) {
+            bump(&mut counts, Tag(tag.clone()), 1);
+        }
+    });
+}
+
+/// Update the counters for a gig moving from its `previous` to its `current` version.
+/// Pass `None` for `previous` on insert and for `current` on removal.
+pub fn record(previous: Option<&Gig>, current: Option<&Gig>) {
+    record_counts(previous, current);
+    if previous.map(|gig| &gig.status) == current.map(|gig| &gig.status) {
+        return;
+    }
+    STATUS_COUNTS.with(|counts| {
+        let mut counts = counts.borrow_mut();
+        if let Some(gig) = previous {
+            bump(&mut counts, indexes::status_key(&gig.status), -1);
+        }
+        if let Some(gig) = current {
+            bump(&mut counts, indexes::status_key(&gig.status), 1);
+        }
+    });
+    match (previous, current) {
+        (None, Some(gig)) => count_posting(gig.created_at),
+        // Only first assignments count; revisions also return gigs to `Assigned`.
+        (Some(old), Some(gig))
+            if gig.status == GigStatus::Assigned && old.status != GigStatus::Submitted =>
+        {
+            TOTALS.with(|totals| {
+                let mut totals = totals.borrow_mut();
+                let elapsed = time().saturating_sub(gig.created_at);
+                bump(&mut totals, TIME_TO_ASSIGN_SUM, elapsed.min(i64::MAX as u64) as i64);
+                bump(&mut totals, TIME_TO_ASSIGN_COUNT, 1);
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Empty a counter map.
+fn clear<K: Ord + Clone + ic_stable_structures::BoundedStorable>(
+    map: &mut StableBTreeMap<K, u64, Memory>,
+) {
+    let keys: Vec<K> = map.iter().map(|(key, _)| key).collect();
+    for key in keys {
+        map.remove(&key);
+    }
+}
+
+/// Recount the status, employer, tag and posting counters from stored gigs, for
+/// canisters upgraded from before they were kept. Times to assign cannot be recovered.
+pub fn rebuild() {
+    STATUS_COUNTS.with(|counts| clear(&mut counts.borrow_mut()));
+    EMPLOYER_COUNTS.with(|counts| clear(&mut counts.borrow_mut()));
+    OPEN_TAG_COUNTS.with(|counts| clear(&mut counts.borrow_mut()));
+    POSTED_PER_HOUR.with(|buckets| {
+        let mut buckets = buckets.borrow_mut();
+        let hours: Vec<u64> = buckets.iter().map(|(hour, _)| hour).collect();
+        for hour in hours {
+            buckets.remove(&hour);
+        }
+    });
+    GIG_STORAGE.with(|storage| {
+        for (_, gig) in storage.borrow().iter() {
+            STATUS_COUNTS.with(|counts| {
+                bump(&mut counts.borrow_mut(), indexes::status_key(&gig.status), 1)
+            });
+            record_counts(None, Some(&gig));
+            count_posting(gig.created_at);
+        }
+    });
+}
+
+/// Retrieve board-wide statistics. Everything is read from maintained counters.
+#[ic_cdk::query]
+pub fn get_stats() -> GigStats {
+    let now_hour = time() / HOUR_NANOS;
+    let posted_since = |hours: u64| -> u64 {
+        let from = now_hour.saturating_sub(hours - 1);
+        POSTED_PER_HOUR.with(|buckets| buckets.borrow().range(from..).map(|(_, n)| n).sum())
+    };
+    let (sum, count) = TOTALS.with(|totals| {
+        let totals = totals.borrow();
+        (
+            totals.get(&TIME_TO_ASSIGN_SUM).unwrap_or(0),
+            totals.get(&TIME_TO_ASSIGN_COUNT).unwrap_or(0),
+        )
+    });
+    GigStats {
+        total_gigs: GIG_STORAGE.with(|storage| storage.borrow().len()),
+        by_status: count_gigs_by_status(),
+        posted_last_24h: posted_since(24),
+        posted_last_7d: posted_since(POSTING_HISTORY_HOURS),
+        average_time_to_assign_secs: (count > 0).then(|| sum / count / 1_000_000_000),
+        stable_memory_bytes: ic_cdk::api::stable::stable64_size() * 65536,
+    }
+}
+
+/// Number of gigs in each status, read from the maintained counters.
+#[ic_cdk::query]
+pub fn count_gigs_by_status() -> Vec<(GigStatus, u64)> {
+    STATUS_COUNTS.with(|counts| {
+        let counts = counts.borrow();
+        STATUSES
+            .iter()
+            .map(|status| (status.clone(), counts.get(&indexes::status_key(status)).unwrap_or(0)))
+            .collect()
+    })
+}
+
+/// Number of gigs an employer has posted that are still stored, whatever their status.
+#[ic_cdk::query]
+pub fn count_gigs_for_employer(employer: Principal) -> u64 {
+    EMPLOYER_COUNTS.with(|counts| counts.borrow().get(&StorablePrincipal(employer)).unwrap_or(0))
+}
+
+/// Number of open gigs carrying a tag. Matching ignores case and surrounding whitespace.
+#[ic_cdk::query]
+pub fn count_open_gigs_by_tag(tag: String) -> u64 {
+    let tag = Tag(tags::normalize(&tag));
+    OPEN_TAG_COUNTS.with(|counts| counts.borrow().get(&tag).unwrap_or(0))
+}
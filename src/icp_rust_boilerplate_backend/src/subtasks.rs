@@ -0,0 +1,183 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::delegates::{self, Permission};
+use crate::error::GigError;
+use crate::moderation::not_banned;
+use crate::teams;
+use crate::visibility;
+use crate::{get_memory, Gig, GigStatus, Memory, GIG_STORAGE, SUBTASKS_MEMORY_ID};
+
+/// Most sub-tasks a gig can have.
+const MAX_SUBTASKS: usize = 50;
+/// Longest sub-task title accepted, in bytes.
+const MAX_SUBTASK_TITLE_LEN: usize = 100;
+
+/// A unit of work tracked within a gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Subtask {
+    pub gig_id: u64,
+    pub id: u32,                        // 1 for the first sub-task on the gig.
+    pub title: String,
+    pub status: SubtaskStatus,
+    pub assignee: Option<Principal>,    // Worker responsible for it; `None` is anyone assigned.
+    pub created_at: u64,
+    pub completed_at: Option<u64>,
+}
+
+/// Enum representing the progress of a sub-task.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum SubtaskStatus {
+    Todo, // Not done yet.
+    Done, // Marked complete.
+}
+
+/// The sub-tasks of a gig and how far along they are.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct SubtaskList {
+    pub subtasks: Vec<Subtask>,
+    pub progress_percent: Option<u8>, // Share of sub-tasks done; `None` without sub-tasks.
+}
+
+impl Storable for Subtask {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Subtask {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Sub-tasks keyed by `(gig_id, subtask_id)` so a gig's list is contiguous.
+    static SUBTASKS: RefCell<StableBTreeMap<(u64, u32), Subtask, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(SUBTASKS_MEMORY_ID)));
+}
+
+/// Sub-tasks of a gig, oldest first.
+fn subtasks(gig_id: u64) -> Vec<Subtask> {
+    SUBTASKS.with(|subtasks| {
+        subtasks
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u32::MAX))
+            .map(|(_, subtask)| subtask)
+            .collect()
+    })
+}
+
+/// Share of a gig's sub-tasks that are done, rounded down, or `None` if it has none.
+pub fn progress(gig_id: u64) -> Option<u8> {
+    let subtasks = subtasks(gig_id);
+    if subtasks.is_empty() {
+        return None;
+    }
+    let done = subtasks.iter().filter(|subtask| subtask.status == SubtaskStatus::Done).count();
+    Some((done * 100 / subtasks.len()) as u8)
+}
+
+/// Whether `principal` is working on a gig, alone or as part of its team.
+fn is_worker(gig: &Gig, principal: Principal) -> bool {
+    gig.assigned_to == Some(principal) || teams::is_member(gig.id, principal)
+}
+
+/// Add a sub-task to a gig that has not been delivered yet, optionally naming the worker
+/// responsible for it. Employer or their delegates only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn add_subtask(
+    gig_id: u64,
+    title: String,
+    assignee: Option<Principal>,
+) -> Result<Subtask, GigError> {
+    if title.trim().is_empty() || title.len() > MAX_SUBTASK_TITLE_LEN {
+        return Err(GigError::validation("title", "Must be between 1 and 100 bytes"));
+    }
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer or a delegate can plan the work.
+    if !delegates::may_act(&gig, caller(), Permission::Post) {
+        return Err(GigError::unauthorized("Only the employer can add sub-tasks"));
+    }
+    if !matches!(
+        gig.status,
+        GigStatus::Open | GigStatus::PendingAcceptance | GigStatus::Assigned
+    ) {
+        return Err(GigError::invalid_state("Open, PendingAcceptance or Assigned", gig.status));
+    }
+    if assignee.is_some_and(|assignee| !is_worker(&gig, assignee)) {
+        return Err(GigError::validation("assignee", "Must be working on the gig"));
+    }
+    let existing = subtasks(gig_id);
+    if existing.len() >= MAX_SUBTASKS {
+        return Err(GigError::validation("subtasks", "A gig can have at most 50 sub-tasks"));
+    }
+
+    let subtask = Subtask {
+        gig_id,
+        id: existing.last().map_or(1, |subtask| subtask.id + 1),
+        title,
+        status: SubtaskStatus::Todo,
+        assignee,
+        created_at: time(),
+        completed_at: None,
+    };
+    SUBTASKS.with(|subtasks| {
+        subtasks.borrow_mut().insert((gig_id, subtask.id), subtask.clone())
+    });
+    Ok(subtask)
+}
+
+/// Mark a sub-task of an assigned gig as done. The sub-task's assignee, or any worker on
+/// the gig if it has none, and the employer can complete it.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn complete_subtask(gig_id: u64, subtask_id: u32) -> Result<Subtask, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    if gig.status != GigStatus::Assigned {
+        return Err(GigError::invalid_state("Assigned", gig.status));
+    }
+    SUBTASKS.with(|subtasks| {
+        let mut subtasks = subtasks.borrow_mut();
+        let mut subtask = subtasks
+            .get(&(gig_id, subtask_id))
+            .ok_or_else(|| GigError::not_found("Sub-task"))?;
+        // Ensure only the employer or the responsible worker can complete it.
+        let allowed = match subtask.assignee {
+            Some(assignee) => assignee == caller(),
+            None => is_worker(&gig, caller()),
+        };
+        if !allowed && !delegates::may_act(&gig, caller(), Permission::Post) {
+            return Err(GigError::unauthorized("Only the assignee can complete this sub-task"));
+        }
+        if subtask.status == SubtaskStatus::Done {
+            return Err(GigError::invalid_state("Todo", subtask.status));
+        }
+        subtask.status = SubtaskStatus::Done;
+        subtask.completed_at = Some(time());
+        subtasks.insert((gig_id, subtask_id), subtask.clone());
+        Ok(subtask)
+    })
+}
+
+/// Retrieve the sub-tasks of a gig and its progress. Gigs the caller may not view have
+/// no sub-tasks.
+#[ic_cdk::query]
+pub fn get_subtasks(gig_id: u64) -> SubtaskList {
+    let viewable = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .is_some_and(|gig| visibility::can_view(&gig));
+    if !viewable {
+        return SubtaskList { subtasks: Vec::new(), progress_percent: None };
+    }
+    SubtaskList { subtasks: subtasks(gig_id), progress_percent: progress(gig_id) }
+}
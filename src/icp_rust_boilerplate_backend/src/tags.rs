@@ -0,0 +1,150 @@
+use ic_stable_structures::{BoundedStorable, Storable};
+use std::borrow::Cow;
+
+use crate::error::GigError;
+
+/// Maximum number of tags on a gig.
+const MAX_TAGS: usize = 10;
+/// Maximum length of a single tag.
+const MAX_TAG_LEN: usize = 32;
+
+/// Broad area of work a gig belongs to.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum Category {
+    Development,
+    Design,
+    Writing,
+    Translation,
+    Marketing,
+    Data,
+    Support,
+    Other,
+}
+
+/// Every category, in display order.
+const CATEGORIES: [Category; 8] = [
+    Category::Development,
+    Category::Design,
+    Category::Writing,
+    Category::Translation,
+    Category::Marketing,
+    Category::Data,
+    Category::Support,
+    Category::Other,
+];
+
+/// Normalised tag or search term usable as a stable map key.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Tag(pub String);
+
+impl Storable for Tag {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Tag(String::from_utf8(bytes.into_owned()).unwrap())
+    }
+}
+
+impl BoundedStorable for Tag {
+    const MAX_SIZE: u32 = MAX_TAG_LEN as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Stable index key of a category. Values must never be reused once assigned.
+pub fn category_key(category: &Category) -> u8 {
+    match category {
+        Category::Development => 0,
+        Category::Design => 1,
+        Category::Writing => 2,
+        Category::Translation => 3,
+        Category::Marketing => 4,
+        Category::Data => 5,
+        Category::Support => 6,
+        Category::Other => 7,
+    }
+}
+
+/// Normalise a tag for storage and lookups: trimmed and lower-cased.
+pub fn normalize(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// Normalise and validate the tags of a gig payload, dropping duplicates.
+pub fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, GigError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = normalize(&tag);
+        if tag.is_empty() || tag.len() > MAX_TAG_LEN {
+            return Err(GigError::validation("tags", "Each tag must be between 1 and 32 bytes"));
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err(GigError::validation("tags", "A gig can have at most 10 tags"));
+    }
+    Ok(normalized)
+}
+
+/// List the categories gigs can be filed under.
+#[ic_cdk::query]
+pub fn get_categories() -> Vec<Category> {
+    CATEGORIES.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn no_tags_is_fine() {
+        assert_eq!(normalize_tags(Vec::new()), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn tags_are_trimmed_and_lowercased() {
+        assert_eq!(normalize_tags(tags(&["  Rust ", "WASM"])), Ok(tags(&["rust", "wasm"])));
+    }
+
+    #[test]
+    fn duplicates_after_normalising_are_dropped() {
+        assert_eq!(normalize_tags(tags(&["rust", " RUST", "Rust "])), Ok(tags(&["rust"])));
+    }
+
+    #[test]
+    fn empty_tags_are_rejected() {
+        assert!(normalize_tags(tags(&[""])).is_err());
+        assert!(normalize_tags(tags(&["   "])).is_err());
+    }
+
+    #[test]
+    fn tag_length_is_counted_in_bytes_after_trimming() {
+        let longest = "a".repeat(MAX_TAG_LEN);
+        assert!(normalize_tags(vec![format!(" {} ", longest)]).is_ok());
+        assert!(normalize_tags(vec![format!("{}a", longest)]).is_err());
+        // Sixteen two-byte characters fit; seventeen do not.
+        assert!(normalize_tags(vec!["é".repeat(16)]).is_ok());
+        assert!(normalize_tags(vec!["é".repeat(17)]).is_err());
+    }
+
+    #[test]
+    fn tag_count_is_checked_after_dropping_duplicates() {
+        let unique: Vec<String> = (0..MAX_TAGS).map(|index| format!("tag{}", index)).collect();
+        assert_eq!(normalize_tags(unique.clone()).map(|tags| tags.len()), Ok(MAX_TAGS));
+
+        let mut repeated = unique.clone();
+        repeated.push("TAG0".to_string());
+        assert_eq!(normalize_tags(repeated).map(|tags| tags.len()), Ok(MAX_TAGS));
+
+        let mut too_many = unique;
+        too_many.push("one more".to_string());
+        assert!(normalize_tags(too_many).is_err());
+    }
+}
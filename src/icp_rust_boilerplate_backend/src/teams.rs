@@ -0,0 +1,441 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::delegates::Permission;
+use crate::dependencies;
+use crate::error::GigError;
+use crate::escrow;
+use crate::moderation::{self, not_banned};
+use crate::multisig::{self, Release};
+use crate::orgs;
+use crate::profiles;
+use crate::reputation;
+use crate::state_machine;
+use crate::timesheets::GigType;
+use crate::visibility;
+use crate::{
+    get_memory, insert_gig, Gig, GigStatus, Memory, StorablePrincipal, GIG_STORAGE,
+    TEAM_SHARES_MEMORY_ID,
+};
+
+/// Smallest team a gig can be assigned to; smaller teams use `assign_gig`.
+const MIN_TEAM_SIZE: usize = 2;
+/// Largest team a gig can be assigned to.
+const MAX_TEAM_SIZE: usize = 10;
+/// Longest note accepted on a share submission, in bytes.
+const MAX_NOTE_LEN: usize = 1000;
+
+/// A worker proposed for a team gig and their share of the payout.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct TeamMember {
+    pub worker: Principal,
+    pub share_percent: u8, // Shares of a team add up to 100.
+}
+
+/// One worker's part of a team gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct TeamShare {
+    pub gig_id: u64,
+    pub worker: Principal,
+    pub share_percent: u8,
+    pub status: ShareStatus,
+    pub submission_note: Option<String>,
+    pub submitted_at: Option<u64>,
+    pub approved_at: Option<u64>,
+}
+
+/// Enum representing the progress of one worker's share of a team gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum ShareStatus {
+    Proposed,   // Waiting for the worker to accept.
+    Accepted,   // The worker is working on their share.
+    Submitted,  // The worker submitted their share for approval.
+    Approved,   // The employer approved the share and it was paid.
+}
+
+impl Storable for TeamShare {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for TeamShare {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Team shares keyed by `(gig_id, worker)` so a gig's team is contiguous.
+    static TEAM_SHARES: RefCell<StableBTreeMap<(u64, StorablePrincipal), TeamShare, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(TEAM_SHARES_MEMORY_ID)));
+}
+
+/// Shares of a gig's team, keyed as stored.
+fn team_entries(gig_id: u64) -> Vec<((u64, StorablePrincipal), TeamShare)> {
+    TEAM_SHARES.with(|shares| {
+        shares
+            .borrow()
+            .range((gig_id, StorablePrincipal::default())..)
+            .take_while(|((id, _), _)| *id == gig_id)
+            .collect()
+    })
+}
+
+/// Shares of a gig's team.
+pub fn team(gig_id: u64) -> Vec<TeamShare> {
+    team_entries(gig_id).into_iter().map(|(_, share)| share).collect()
+}
+
+/// Share of `worker` in a gig's team, if any.
+fn share_of(gig_id: u64, worker: Principal) -> Option<TeamShare> {
+    TEAM_SHARES.with(|shares| shares.borrow().get(&(gig_id, StorablePrincipal(worker))))
+}
+
+/// Store a share of a gig's team.
+fn save(share: &TeamShare) {
+    TEAM_SHARES.with(|shares| {
+        shares.borrow_mut().insert((share.gig_id, StorablePrincipal(share.worker)), share.clone())
+    });
+}
+
+/// Whether a gig is assigned to a team rather than a single worker.
+pub fn is_team_gig(gig_id: u64) -> bool {
+    TEAM_SHARES.with(|shares| {
+        shares
+            .borrow()
+            .range((gig_id, StorablePrincipal::default())..)
+            .next()
+            .is_some_and(|((id, _), _)| id == gig_id)
+    })
+}
+
+/// Workers doing a gig: its team, or else the assigned worker.
+pub fn workers(gig: &Gig) -> Vec<Principal> {
+    let team = team(gig.id);
+    if team.is_empty() {
+        return gig.assigned_to.into_iter().collect();
+    }
+    team.into_iter().map(|share| share.worker).collect()
+}
+
+/// Whether `principal` is on the team of a gig.
+pub fn is_member(gig_id: u64, principal: Principal) -> bool {
+    share_of(gig_id, principal).is_some()
+}
+
+/// Drop the team of a gig that went back to the board.
+pub fn disband(gig_id: u64) {
+    let entries = team_entries(gig_id);
+    TEAM_SHARES.with(|shares| {
+        let mut shares = shares.borrow_mut();
+        for (key, _) in entries {
+            shares.remove(&key);
+        }
+    });
+}
+
+/// Check a proposed team: between 2 and 10 distinct, registered workers whose shares add
+/// up to 100.
+fn validate_team(gig: &Gig, members: &[TeamMember]) -> Result<(), GigError> {
+    if !(MIN_TEAM_SIZE..=MAX_TEAM_SIZE).contains(&members.len()) {
+        return Err(GigError::validation("members", "Teams must have 2 to 10 workers"));
+    }
+    let mut seen: Vec<Principal> = Vec::with_capacity(members.len());
+    for member in members {
+        if member.worker == Principal::anonymous() || member.worker == gig.employer {
+            return Err(GigError::validation("members", "Workers must be other real principals"));
+        }
+        if seen.contains(&member.worker) {
+            return Err(GigError::validation("members", "Each worker can only appear once"));
+        }
+        if !profiles::is_registered(member.worker) {
+            return Err(GigError::validation("members", "Worker has not registered a profile"));
+        }
+        if moderation::is_banned(member.worker) {
+            return Err(GigError::validation("members", "Worker is banned"));
+        }
+        if member.share_percent == 0 {
+            return Err(GigError::validation("members", "Shares must be positive"));
+        }
+        seen.push(member.worker);
+    }
+    if members.iter().map(|member| member.share_percent as u32).sum::<u32>() != 100 {
+        return Err(GigError::validation("members", "Shares must add up to 100"));
+    }
+    Ok(())
+}
+
+/// Propose a team of workers for a gig, each with a percentage of the payout. The gig
+/// becomes `Assigned` once every worker accepts, and returns to `Open` if any declines or
+/// the acceptance window passes. `expected_version` must match the gig's current version.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn assign_team(
+    gig_id: u64,
+    members: Vec<TeamMember>,
+    expected_version: u64,
+) -> Result<Gig, GigError> {
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        // Ensure only the employer, their organization or a delegate can assign the gig.
+        if !orgs::acts_for_employer(&gig, caller(), Permission::Assign) {
+            return Err(GigError::unauthorized("Only the employer can assign this gig"));
+        }
+        if gig.version() != expected_version {
+            return Err(GigError::Conflict { expected: expected_version, actual: gig.version() });
+        }
+        // Shares split a single fixed payout, so clauses that reshape it do not apply.
+        if gig.gig_type() != GigType::Fixed
+            || gig.delivery_terms.is_some()
+            || gig.milestones.as_ref().is_some_and(|milestones| !milestones.is_empty())
+        {
+            return Err(GigError::validation(
+                "gig_id",
+                "Team gigs must be fixed-price without milestones or delivery terms",
+            ));
+        }
+        validate_team(&gig, &members)?;
+        dependencies::ensure_met(&storage, &gig)?;
+        // Ensure the gig can take proposed workers.
+        state_machine::transition(&mut gig, GigStatus::PendingAcceptance)?;
+        gig.proposed_at = Some(time());
+        gig.updated_at = gig.proposed_at;
+        insert_gig(&mut storage, &mut gig);
+        disband(gig_id);
+        for member in members {
+            save(&TeamShare {
+                gig_id,
+                worker: member.worker,
+                share_percent: member.share_percent,
+                status: ShareStatus::Proposed,
+                submission_note: None,
+                submitted_at: None,
+                approved_at: None,
+            });
+        }
+        Ok(gig)
+    })
+}
+
+/// Accept a proposed share of a team gig. The gig becomes `Assigned` once the whole team
+/// has accepted. Proposed worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn accept_team_share(gig_id: u64) -> Result<TeamShare, GigError> {
+    moderation::ensure_not_banned(caller())?;
+    let mut share = proposed_share(gig_id)?;
+    share.status = ShareStatus::Accepted;
+    save(&share);
+    if team(gig_id).iter().all(|share| share.status == ShareStatus::Accepted) {
+        GIG_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+            let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+            state_machine::transition(&mut gig, GigStatus::Assigned)?;
+            gig.updated_at = Some(time());
+            insert_gig(&mut storage, &mut gig);
+            Ok::<_, GigError>(())
+        })?;
+    }
+    Ok(share)
+}
+
+/// Decline a proposed share of a team gig, disbanding the team and returning the gig to
+/// `Open`. Proposed worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn decline_team_share(gig_id: u64) -> Result<Gig, GigError> {
+    proposed_share(gig_id)?;
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        state_machine::transition(&mut gig, GigStatus::Open)?;
+        gig.updated_at = Some(time());
+        insert_gig(&mut storage, &mut gig);
+        disband(gig_id);
+        Ok(gig)
+    })
+}
+
+/// The caller's share of a team gig that is still waiting for the team to accept.
+fn proposed_share(gig_id: u64) -> Result<TeamShare, GigError> {
+    let share = share_of(gig_id, caller())
+        .ok_or_else(|| GigError::unauthorized("Only a proposed worker can respond"))?;
+    if share.status != ShareStatus::Proposed {
+        return Err(GigError::invalid_state("Proposed", share.status));
+    }
+    let status = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .map(|gig| gig.status)
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    if status != GigStatus::PendingAcceptance {
+        return Err(GigError::invalid_state("PendingAcceptance", status));
+    }
+    Ok(share)
+}
+
+/// Submit the caller's share of an assigned team gig for approval. The gig becomes
+/// `Submitted` once every share has been submitted. Team member only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn submit_team_share(gig_id: u64, submission_note: String) -> Result<TeamShare, GigError> {
+    if submission_note.len() > MAX_NOTE_LEN {
+        return Err(GigError::validation("submission_note", "Must be at most 1000 bytes"));
+    }
+    let mut share = share_of(gig_id, caller())
+        .ok_or_else(|| GigError::unauthorized("Only a team member can submit a share"))?;
+    GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        if gig.status != GigStatus::Assigned {
+            return Err(GigError::invalid_state("Assigned", gig.status));
+        }
+        if share.status != ShareStatus::Accepted {
+            return Err(GigError::invalid_state("Accepted", share.status));
+        }
+        share.status = ShareStatus::Submitted;
+        share.submission_note = Some(submission_note);
+        share.submitted_at = Some(time());
+        save(&share);
+        // The gig waits for approval once nobody is still working on it.
+        if team(gig_id).iter().all(|share| share.status != ShareStatus::Accepted) {
+            state_machine::transition(&mut gig, GigStatus::Submitted)?;
+            gig.submitted_at = share.submitted_at;
+            gig.updated_at = share.submitted_at;
+            insert_gig(&mut storage, &mut gig);
+        }
+        Ok(share)
+    })
+}
+
+/// Approve one worker's submitted share and pay them their percentage of the escrow. The
+/// gig becomes `Approved` once every share is approved, and whatever escrow is left over
+/// goes back to the employer. Shares of gigs with a budget above `multisig_threshold` are
+/// paid once enough approvers have called this, and stay `Submitted` until then. Employer,
+/// organization manager or delegate only.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn approve_team_share(gig_id: u64, worker: Principal) -> Result<TeamShare, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer, their organization or a delegate can approve shares.
+    if !orgs::acts_for_employer(&gig, caller(), Permission::Approve) {
+        return Err(GigError::unauthorized("Only the employer can approve this gig"));
+    }
+    // Shares of disputed, cancelled or closed gigs are settled by the ruling, not here.
+    if gig.status != GigStatus::Assigned && gig.status != GigStatus::Submitted {
+        return Err(GigError::invalid_state("Assigned or Submitted", gig.status));
+    }
+    if escrow::get_escrow(gig_id).is_some_and(|escrow| escrow.frozen_at.is_some()) {
+        return Err(GigError::invalid_state_text("Not frozen", "Frozen by a dispute"));
+    }
+    let share = share_of(gig_id, worker).ok_or_else(|| GigError::not_found("Team share"))?;
+    if share.status != ShareStatus::Submitted {
+        return Err(GigError::invalid_state("Submitted", share.status));
+    }
+    if !multisig::sign(gig_id, caller(), Release::TeamShare { worker })? {
+        return Ok(share);
+    }
+    approve_share(gig_id, worker).await
+}
+
+/// Approve every submitted share of a team gig, as `approve_gig` and auto-approval do.
+/// Callers are responsible for checking that the approval is authorised.
+pub async fn approve_all(gig_id: u64) -> Result<Gig, GigError> {
+    for share in team(gig_id) {
+        if share.status == ShareStatus::Submitted {
+            approve_share(gig_id, share.worker).await?;
+        }
+    }
+    GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))
+}
+
+/// Pay `owed` of a disputed team gig's escrow to the members whose shares are not approved
+/// yet, in proportion to their shares, as a ruling for the workers directs. Each member's
+/// share is approved once they are paid, so a retry after a failed transfer pays only the
+/// members still owed, out of what is then still owed.
+pub async fn release_ruled(gig_id: u64, mut owed: u64) -> Result<(), GigError> {
+    loop {
+        let unpaid: Vec<TeamShare> = team(gig_id)
+            .into_iter()
+            .filter(|share| share.status != ShareStatus::Approved)
+            .collect();
+        let Some(share) = unpaid.first() else {
+            return Ok(());
+        };
+        // The last member also takes whatever rounding left behind.
+        let amount = if unpaid.len() == 1 {
+            owed
+        } else {
+            let weight: u128 = unpaid.iter().map(|share| share.share_percent as u128).sum();
+            (owed as u128 * share.share_percent as u128 / weight) as u64
+        };
+        if amount > 0 {
+            escrow::release_ruled(gig_id, share.worker, Some(amount)).await?;
+        }
+        owed -= amount;
+        let mut share = share.clone();
+        share.status = ShareStatus::Approved;
+        share.approved_at = Some(time());
+        save(&share);
+    }
+}
+
+/// Pay and approve a submitted share, completing the gig with its last share.
+async fn approve_share(gig_id: u64, worker: Principal) -> Result<TeamShare, GigError> {
+    let share = share_of(gig_id, worker).ok_or_else(|| GigError::not_found("Team share"))?;
+    if share.status != ShareStatus::Submitted {
+        return Err(GigError::invalid_state("Submitted", share.status));
+    }
+    let is_last = team(gig_id)
+        .iter()
+        .all(|other| other.worker == worker || other.status == ShareStatus::Approved);
+
+    // Pay the worker before the approval is recorded. The last share also takes whatever
+    // rounding left behind.
+    if let Some(escrow) = escrow::get_escrow(gig_id).filter(|_| escrow::is_locked(gig_id)) {
+        let amount = if is_last {
+            escrow.remaining()
+        } else {
+            (escrow.amount as u128 * share.share_percent as u128 / 100) as u64
+        };
+        escrow::release_partial(gig_id, worker, amount.min(escrow.remaining())).await?;
+    }
+
+    let mut share = share_of(gig_id, worker).ok_or_else(|| GigError::not_found("Team share"))?;
+    share.status = ShareStatus::Approved;
+    share.approved_at = Some(time());
+    save(&share);
+    let gig = GIG_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let mut gig = storage.get(&gig_id).ok_or_else(|| GigError::not_found("Gig"))?;
+        reputation::record_completion(gig.employer, worker);
+        if team(gig_id).iter().all(|share| share.status == ShareStatus::Approved) {
+            state_machine::transition(&mut gig, GigStatus::Approved)?;
+            gig.updated_at = share.approved_at;
+            insert_gig(&mut storage, &mut gig);
+        }
+        Ok::<_, GigError>(gig)
+    })?;
+    if gig.status == GigStatus::Approved && escrow::is_locked(gig_id) {
+        escrow::refund_or_queue(gig_id).await;
+    }
+    Ok(share)
+}
+
+/// Retrieve the team of a gig. Gigs the caller may not view have no team.
+#[ic_cdk::query]
+pub fn get_team(gig_id: u64) -> Vec<TeamShare> {
+    let viewable = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .is_some_and(|gig| visibility::can_view(&gig));
+    if !viewable {
+        return Vec::new();
+    }
+    team(gig_id)
+}
@@ -0,0 +1,415 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::id;
+use crate::ledger;
+use crate::moderation::not_banned;
+use crate::skills;
+use crate::tags::{self, Category};
+use crate::tokens;
+use crate::validation;
+use crate::visibility::Visibility;
+use crate::{
+    auto_approve_days, get_memory, post_gig_as, Gig, GigPayload, IdCell, Memory,
+    StorablePrincipal, MAX_REQUIRED_SKILLS, RECURRENCE_SCHEDULE_MEMORY_ID, TEMPLATES_MEMORY_ID,
+    TEMPLATE_ID_COUNTER_MEMORY_ID, TEMPLATE_OWNER_INDEX_MEMORY_ID,
+};
+
+/// Most templates an employer can keep.
+const MAX_TEMPLATES_PER_OWNER: usize = 20;
+/// Longest template name, in bytes.
+const MAX_NAME_LEN: usize = 100;
+/// Nanoseconds in a day.
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// How often a recurring template posts a fresh gig.
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+pub enum Recurrence {
+    Weekly,  // Every seven days.
+    Monthly, // Every thirty days.
+}
+
+impl Recurrence {
+    /// Nanoseconds between two postings.
+    fn interval(self) -> u64 {
+        match self {
+            Recurrence::Weekly => 7 * DAY_NANOS,
+            Recurrence::Monthly => 30 * DAY_NANOS,
+        }
+    }
+}
+
+/// Saved defaults an employer can post new gigs from.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct GigTemplate {
+    pub id: u64,
+    pub owner: Principal,
+    pub name: String,                       // Employer's label for the template.
+    pub title: String,
+    pub description: String,
+    pub budget: u64,
+    pub token: Option<Principal>,           // Defaults to ICP.
+    pub category: Option<Category>,
+    pub tags: Option<Vec<String>>,
+    pub required_skills: Option<Vec<String>>,
+    pub auto_approve_days: Option<u32>,
+    pub visibility: Option<Visibility>,
+    pub deadline_secs: Option<u64>,         // Seconds from posting to the deadline, if set.
+    pub created_at: u64,
+    pub updated_at: Option<u64>,
+    pub recurrence: Option<Recurrence>,     // Schedule for posting gigs automatically, if any.
+    pub next_run_at: Option<u64>,           // When the next scheduled gig is posted.
+    pub last_posted_gig: Option<u64>,       // Gig most recently posted on schedule.
+}
+
+/// Structure for saving or updating a template.
+#[derive(candid::CandidType, Serialize, Deserialize, Default)]
+pub struct TemplatePayload {
+    pub name: String,
+    pub title: String,
+    pub description: String,
+    pub budget: u64,
+    pub token: Option<Principal>,
+    pub category: Option<Category>,
+    pub tags: Option<Vec<String>>,
+    pub required_skills: Option<Vec<String>>,
+    pub auto_approve_days: Option<u32>,
+    pub visibility: Option<Visibility>,
+    pub deadline_secs: Option<u64>,
+}
+
+/// Per-gig values that replace a template's defaults in `post_gig_from_template`.
+#[derive(candid::CandidType, Serialize, Deserialize, Default)]
+pub struct TemplateOverrides {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub budget: Option<u64>,
+    pub deadline: Option<u64>,              // Required unless the template sets `deadline_secs`.
+    pub escrow_amount: Option<u64>,
+    pub tags: Option<Vec<String>>,
+    pub required_skills: Option<Vec<String>>,
+    pub attachments: Option<Vec<u64>>,
+}
+
+impl Storable for GigTemplate {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for GigTemplate {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Counter to generate unique IDs for templates.
+    static TEMPLATE_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(get_memory(TEMPLATE_ID_COUNTER_MEMORY_ID), 0)
+            .expect("Cannot create a template counter")
+    );
+
+    /// Templates keyed by template ID.
+    static TEMPLATES: RefCell<StableBTreeMap<u64, GigTemplate, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(TEMPLATES_MEMORY_ID)));
+
+    /// Template IDs keyed by `(owner, template_id)`.
+    static OWNER_INDEX: RefCell<StableBTreeMap<(StorablePrincipal, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(TEMPLATE_OWNER_INDEX_MEMORY_ID)));
+
+    /// Recurring template IDs keyed by `(next_run_at, template_id)`, soonest first.
+    static SCHEDULE: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(RECURRENCE_SCHEDULE_MEMORY_ID)));
+}
+
+/// Check a template payload against the same limits as a gig and normalise its tags and
+/// skills.
+fn validate(mut payload: TemplatePayload) -> Result<TemplatePayload, GigError> {
+    if payload.name.trim().is_empty() || payload.name.len() > MAX_NAME_LEN {
+        return Err(GigError::validation("name", "Must be between 1 and 100 bytes"));
+    }
+    validation::validate_gig_payload(&GigPayload {
+        title: payload.title.clone(),
+        description: payload.description.clone(),
+        budget: payload.budget,
+        ..Default::default()
+    })?;
+    auto_approve_days(payload.auto_approve_days)?;
+    tokens::accepted(payload.token.unwrap_or_else(ledger::icp_ledger))?;
+    payload.tags = payload.tags.map(tags::normalize_tags).transpose()?;
+    payload.required_skills = payload
+        .required_skills
+        .map(|skills| skills::normalize_skills(skills, MAX_REQUIRED_SKILLS))
+        .transpose()?;
+    Ok(payload)
+}
+
+/// Look up a template and ensure the caller owns it.
+fn owned_template(template_id: u64) -> Result<GigTemplate, GigError> {
+    let template = TEMPLATES
+        .with(|templates| templates.borrow().get(&template_id))
+        .ok_or_else(|| GigError::not_found("Template"))?;
+    if template.owner != caller() {
+        return Err(GigError::unauthorized("Only the owner can use this template"));
+    }
+    Ok(template)
+}
+
+/// Save a new template for the caller. An employer can keep up to 20 templates.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn save_template(payload: TemplatePayload) -> Result<GigTemplate, GigError> {
+    let owner = caller();
+    if owner == Principal::anonymous() {
+        return Err(GigError::unauthorized("Anonymous principals cannot save templates"));
+    }
+    let payload = validate(payload)?;
+    if list_templates().len() >= MAX_TEMPLATES_PER_OWNER {
+        return Err(GigError::validation("payload", "At most 20 templates per employer"));
+    }
+
+    let id = id::next(&TEMPLATE_ID_COUNTER, "Template")?;
+    let template = GigTemplate {
+        id,
+        owner,
+        name: payload.name,
+        title: payload.title,
+        description: payload.description,
+        budget: payload.budget,
+        token: payload.token,
+        category: payload.category,
+        tags: payload.tags,
+        required_skills: payload.required_skills,
+        auto_approve_days: payload.auto_approve_days,
+        visibility: payload.visibility,
+        deadline_secs: payload.deadline_secs,
+        created_at: time(),
+        updated_at: None,
+        recurrence: None,
+        next_run_at: None,
+        last_posted_gig: None,
+    };
+    TEMPLATES.with(|templates| templates.borrow_mut().insert(id, template.clone()));
+    OWNER_INDEX.with(|index| index.borrow_mut().insert((StorablePrincipal(owner), id), ()));
+    Ok(template)
+}
+
+/// Replace the defaults of a template. Owner only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn update_template(
+    template_id: u64,
+    payload: TemplatePayload,
+) -> Result<GigTemplate, GigError> {
+    let mut template = owned_template(template_id)?;
+    let payload = validate(payload)?;
+    // Scheduled postings take their deadline from the template.
+    if template.recurrence.is_some() && payload.deadline_secs.is_none() {
+        return Err(GigError::validation("deadline_secs", "Required on recurring templates"));
+    }
+    template.name = payload.name;
+    template.title = payload.title;
+    template.description = payload.description;
+    template.budget = payload.budget;
+    template.token = payload.token;
+    template.category = payload.category;
+    template.tags = payload.tags;
+    template.required_skills = payload.required_skills;
+    template.auto_approve_days = payload.auto_approve_days;
+    template.visibility = payload.visibility;
+    template.deadline_secs = payload.deadline_secs;
+    template.updated_at = Some(time());
+    TEMPLATES.with(|templates| templates.borrow_mut().insert(template_id, template.clone()));
+    Ok(template)
+}
+
+/// Delete a template. Gigs already posted from it are unaffected. Owner only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn delete_template(template_id: u64) -> Result<(), GigError> {
+    let template = owned_template(template_id)?;
+    unschedule(&template);
+    TEMPLATES.with(|templates| templates.borrow_mut().remove(&template_id));
+    OWNER_INDEX.with(|index| {
+        index.borrow_mut().remove(&(StorablePrincipal(template.owner), template_id))
+    });
+    Ok(())
+}
+
+/// Retrieve one of the caller's templates.
+#[ic_cdk::query]
+pub fn get_template(template_id: u64) -> Result<GigTemplate, GigError> {
+    owned_template(template_id)
+}
+
+/// Retrieve the caller's templates, oldest first.
+#[ic_cdk::query]
+pub fn list_templates() -> Vec<GigTemplate> {
+    let key = StorablePrincipal(caller());
+    let ids: Vec<u64> = OWNER_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    TEMPLATES.with(|templates| {
+        let templates = templates.borrow();
+        ids.into_iter().filter_map(|id| templates.get(&id)).collect()
+    })
+}
+
+/// Build the payload of a gig posted from a template, with `overrides` replacing its
+/// defaults.
+fn instance_payload(
+    template: GigTemplate,
+    overrides: TemplateOverrides,
+) -> Result<GigPayload, GigError> {
+    let deadline = match (overrides.deadline, template.deadline_secs) {
+        (Some(deadline), _) => deadline,
+        (None, Some(secs)) => time().saturating_add(secs.saturating_mul(1_000_000_000)),
+        (None, None) => {
+            return Err(GigError::validation("deadline", "The template sets no deadline"));
+        }
+    };
+    Ok(GigPayload {
+        title: overrides.title.unwrap_or(template.title),
+        description: overrides.description.unwrap_or(template.description),
+        deadline,
+        budget: overrides.budget.unwrap_or(template.budget),
+        escrow_amount: overrides.escrow_amount,
+        token: template.token,
+        category: template.category,
+        tags: overrides.tags.or(template.tags),
+        auto_approve_days: template.auto_approve_days,
+        attachments: overrides.attachments,
+        visibility: template.visibility,
+        required_skills: overrides.required_skills.or(template.required_skills),
+        gig_type: None,
+        delivery_terms: None,
+        depends_on: None,
+        org_id: None,
+        reminder_hours: None,
+        usd_budget: None,
+        idempotency_key: None,
+        auto_approve_after_secs: None,
+    })
+}
+
+/// Post a gig from one of the caller's templates, replacing any defaults given in
+/// `overrides`. The gig goes through the same checks as `post_gig`.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn post_gig_from_template(
+    template_id: u64,
+    overrides: TemplateOverrides,
+) -> Result<Gig, GigError> {
+    let template = owned_template(template_id)?;
+    let employer = template.owner;
+    post_gig_as(employer, instance_payload(template, overrides)?).await
+}
+
+/// Drop a template from the recurrence schedule.
+fn unschedule(template: &GigTemplate) {
+    if let Some(next_run_at) = template.next_run_at {
+        SCHEDULE.with(|schedule| schedule.borrow_mut().remove(&(next_run_at, template.id)));
+    }
+}
+
+/// Store a template and keep its schedule entry in step with its next run.
+fn save_scheduled(previous: &GigTemplate, template: GigTemplate) {
+    unschedule(previous);
+    if let Some(next_run_at) = template.next_run_at {
+        SCHEDULE.with(|schedule| schedule.borrow_mut().insert((next_run_at, template.id), ()));
+    }
+    TEMPLATES.with(|templates| templates.borrow_mut().insert(template.id, template));
+}
+
+/// Post a fresh gig from a template on a schedule, starting at `first_run_at` (now by
+/// default), until the recurrence is cancelled. The template must set `deadline_secs`.
+/// Owner only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn set_recurrence(
+    template_id: u64,
+    recurrence: Recurrence,
+    first_run_at: Option<u64>,
+) -> Result<GigTemplate, GigError> {
+    let previous = owned_template(template_id)?;
+    if previous.deadline_secs.is_none() {
+        return Err(GigError::validation("deadline_secs", "Required on recurring templates"));
+    }
+    let mut template = previous.clone();
+    template.recurrence = Some(recurrence);
+    template.next_run_at = Some(first_run_at.unwrap_or_else(time).max(time()));
+    template.updated_at = Some(time());
+    save_scheduled(&previous, template.clone());
+    Ok(template)
+}
+
+/// Stop posting gigs from a template on a schedule. Gigs already posted are unaffected.
+/// Owner only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn cancel_recurrence(template_id: u64) -> Result<GigTemplate, GigError> {
+    let previous = owned_template(template_id)?;
+    if previous.recurrence.is_none() {
+        return Err(GigError::validation("template_id", "The template does not recur"));
+    }
+    let mut template = previous.clone();
+    template.recurrence = None;
+    template.next_run_at = None;
+    template.updated_at = Some(time());
+    save_scheduled(&previous, template.clone());
+    Ok(template)
+}
+
+/// Post a gig from every recurring template that is due and schedule its next run. A
+/// posting that fails, for instance because the owner is banned, is skipped rather than
+/// retried so a broken template cannot post a backlog of gigs later.
+pub fn post_due_recurrences() {
+    let now = time();
+    let due: Vec<u64> = SCHEDULE.with(|schedule| {
+        schedule
+            .borrow()
+            .range(..(now, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    for id in due {
+        let Some(previous) = TEMPLATES.with(|templates| templates.borrow().get(&id)) else {
+            continue;
+        };
+        let (Some(recurrence), Some(next_run_at)) = (previous.recurrence, previous.next_run_at)
+        else {
+            continue;
+        };
+        let mut template = previous.clone();
+        // Skip runs missed while the canister was stopped instead of posting them all.
+        let mut next = next_run_at.saturating_add(recurrence.interval());
+        while next <= now {
+            next = next.saturating_add(recurrence.interval());
+        }
+        template.next_run_at = Some(next);
+        save_scheduled(&previous, template.clone());
+
+        ic_cdk::spawn(async move {
+            let employer = template.owner;
+            let gig = match instance_payload(template, TemplateOverrides::default()) {
+                Ok(payload) => post_gig_as(employer, payload).await,
+                Err(error) => Err(error),
+            };
+            if let Ok(gig) = gig {
+                TEMPLATES.with(|templates| {
+                    let mut templates = templates.borrow_mut();
+                    if let Some(mut template) = templates.get(&id) {
+                        template.last_posted_gig = Some(gig.id);
+                        templates.insert(id, template);
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,248 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::delegates::{self, Permission};
+use crate::error::GigError;
+use crate::moderation::{self, not_banned};
+use crate::roles;
+use crate::{get_memory, Gig, GigPayload, GigStatus, Memory, GIG_STORAGE, TIME_ENTRIES_MEMORY_ID};
+
+/// Most hours a single time entry can log.
+const MAX_HOURS_PER_ENTRY: u32 = 24;
+/// Highest hour cap an hourly gig can set.
+const MAX_HOURS_CAP: u32 = 1000;
+/// Longest note accepted on a time entry, in bytes.
+const MAX_NOTE_LEN: usize = 500;
+
+/// How a gig's worker is paid.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum GigType {
+    #[default]
+    Fixed,                                 // The budget is paid in full on approval.
+    Hourly { rate: u64, max_hours: u32 },  // Approved hours are paid at `rate`, up to a cap.
+}
+
+/// Hours a worker logged against an hourly gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct TimeEntry {
+    pub gig_id: u64,
+    pub id: u32,                        // 1 for the first entry on the gig.
+    pub worker: Principal,
+    pub hours: u32,
+    pub note: String,
+    pub status: TimeEntryStatus,
+    pub logged_at: u64,
+    pub reviewed_at: Option<u64>,
+}
+
+/// Enum representing the employer's review of a time entry.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum TimeEntryStatus {
+    Pending,    // Waiting for the employer's review.
+    Approved,   // The hours will be paid.
+    Rejected,   // The hours will not be paid.
+}
+
+impl Storable for TimeEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for TimeEntry {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Time entries keyed by `(gig_id, entry_id)` so a gig's timesheet is contiguous.
+    static TIME_ENTRIES: RefCell<StableBTreeMap<(u64, u32), TimeEntry, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(TIME_ENTRIES_MEMORY_ID)));
+}
+
+/// Check the gig type of a payload and, for hourly gigs, set its budget to the most the
+/// gig can pay out: the rate times the hour cap.
+pub fn apply_gig_type(payload: &mut GigPayload) -> Result<(), GigError> {
+    if let Some(GigType::Hourly { rate, max_hours }) = payload.gig_type {
+        if rate == 0 {
+            return Err(GigError::validation("gig_type", "The hourly rate must be positive"));
+        }
+        if !(1..=MAX_HOURS_CAP).contains(&max_hours) {
+            return Err(GigError::validation("gig_type", "The hour cap must be 1 to 1000"));
+        }
+        payload.budget = rate
+            .checked_mul(max_hours as u64)
+            .ok_or_else(|| GigError::validation("gig_type", "The rate times the cap overflows"))?;
+    }
+    Ok(())
+}
+
+/// Ensure a gig can switch to `gig_type`: only open gigs can change how the worker is
+/// paid, and hours already logged by an earlier worker must not be stranded.
+pub fn ensure_type_change(gig: &Gig, gig_type: &GigType) -> Result<(), GigError> {
+    if gig.gig_type() == *gig_type {
+        return Ok(());
+    }
+    if gig.status != GigStatus::Open {
+        return Err(GigError::validation("gig_type", "Can only change while the gig is open"));
+    }
+    let logged = logged_hours(gig.id);
+    match gig_type {
+        GigType::Fixed if logged > 0 => {
+            Err(GigError::validation("gig_type", "Hours have already been logged"))
+        }
+        GigType::Hourly { max_hours, .. } if logged > *max_hours => {
+            Err(GigError::validation("gig_type", "The hour cap is below the hours logged"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Entries logged against a gig, oldest first.
+fn entries(gig_id: u64) -> Vec<TimeEntry> {
+    TIME_ENTRIES.with(|entries| {
+        entries
+            .borrow()
+            .range((gig_id, 0)..=(gig_id, u32::MAX))
+            .map(|(_, entry)| entry)
+            .collect()
+    })
+}
+
+/// Hours logged against a gig that are approved or still pending.
+fn logged_hours(gig_id: u64) -> u32 {
+    entries(gig_id)
+        .iter()
+        .filter(|entry| entry.status != TimeEntryStatus::Rejected)
+        .map(|entry| entry.hours)
+        .sum()
+}
+
+/// Hours `worker` logged against a gig that are approved or still pending: what the
+/// worker is owed once the work is approved.
+pub fn payable_hours(gig_id: u64, worker: Principal) -> u32 {
+    entries(gig_id)
+        .iter()
+        .filter(|entry| entry.worker == worker && entry.status != TimeEntryStatus::Rejected)
+        .map(|entry| entry.hours)
+        .sum()
+}
+
+/// Approve every entry still pending, since approving the work accepts its timesheet.
+pub fn approve_pending(gig_id: u64) {
+    let now = time();
+    TIME_ENTRIES.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        for mut entry in entries(gig_id) {
+            if entry.status == TimeEntryStatus::Pending {
+                entry.status = TimeEntryStatus::Approved;
+                entry.reviewed_at = Some(now);
+                storage.insert((gig_id, entry.id), entry);
+            }
+        }
+    });
+}
+
+/// Log hours worked on an assigned hourly gig. The hours logged and not rejected cannot
+/// exceed the gig's hour cap. Assigned worker only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn log_hours(gig_id: u64, hours: u32, note: String) -> Result<TimeEntry, GigError> {
+    let worker = caller();
+    moderation::ensure_not_banned(worker)?;
+    if !(1..=MAX_HOURS_PER_ENTRY).contains(&hours) {
+        return Err(GigError::validation("hours", "Must be between 1 and 24"));
+    }
+    if note.len() > MAX_NOTE_LEN {
+        return Err(GigError::validation("note", "Must be at most 500 bytes"));
+    }
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the assigned worker can log hours.
+    if gig.assigned_to != Some(worker) {
+        return Err(GigError::unauthorized("Only the assigned worker can log hours"));
+    }
+    if gig.status != GigStatus::Assigned {
+        return Err(GigError::invalid_state("Assigned", gig.status));
+    }
+    let GigType::Hourly { max_hours, .. } = gig.gig_type() else {
+        return Err(GigError::validation("gig_id", "Only hourly gigs track time"));
+    };
+    if logged_hours(gig_id) + hours > max_hours {
+        return Err(GigError::validation("hours", "Would exceed the gig's hour cap"));
+    }
+
+    let id = entries(gig_id).last().map_or(1, |entry| entry.id + 1);
+    let entry = TimeEntry {
+        gig_id,
+        id,
+        worker,
+        hours,
+        note,
+        status: TimeEntryStatus::Pending,
+        logged_at: time(),
+        reviewed_at: None,
+    };
+    TIME_ENTRIES.with(|entries| entries.borrow_mut().insert((gig_id, id), entry.clone()));
+    Ok(entry)
+}
+
+/// Approve or reject a pending time entry. Employer or their delegates only.
+fn review_hours(gig_id: u64, entry_id: u32, approve: bool) -> Result<TimeEntry, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    // Ensure only the employer or a delegate can review timesheets.
+    if !delegates::may_act(&gig, caller(), Permission::Approve) {
+        return Err(GigError::unauthorized("Only the employer can review hours"));
+    }
+    if gig.status != GigStatus::Assigned && gig.status != GigStatus::Submitted {
+        return Err(GigError::invalid_state("Assigned or Submitted", gig.status));
+    }
+    TIME_ENTRIES.with(|entries| {
+        let mut entries = entries.borrow_mut();
+        let mut entry = entries
+            .get(&(gig_id, entry_id))
+            .ok_or_else(|| GigError::not_found("Time entry"))?;
+        if entry.status != TimeEntryStatus::Pending {
+            return Err(GigError::invalid_state("Pending", entry.status));
+        }
+        entry.status = if approve { TimeEntryStatus::Approved } else { TimeEntryStatus::Rejected };
+        entry.reviewed_at = Some(time());
+        entries.insert((gig_id, entry_id), entry.clone());
+        Ok(entry)
+    })
+}
+
+/// Approve a pending time entry, so its hours are paid on approval. Employer or their
+/// delegates only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn approve_hours(gig_id: u64, entry_id: u32) -> Result<TimeEntry, GigError> {
+    review_hours(gig_id, entry_id, true)
+}
+
+/// Reject a pending time entry, so its hours are not paid. Employer or their delegates only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn reject_hours(gig_id: u64, entry_id: u32) -> Result<TimeEntry, GigError> {
+    review_hours(gig_id, entry_id, false)
+}
+
+/// Retrieve the timesheet of a gig, oldest entry first. Timesheets are private to the
+/// employer, the worker and moderators.
+#[ic_cdk::query]
+pub fn get_timesheet(gig_id: u64) -> Result<Vec<TimeEntry>, GigError> {
+    let gig = GIG_STORAGE
+        .with(|storage| storage.borrow().get(&gig_id))
+        .ok_or_else(|| GigError::not_found("Gig"))?;
+    if caller() != gig.employer && gig.assigned_to != Some(caller()) {
+        roles::ensure_moderator()?;
+    }
+    Ok(entries(gig_id))
+}
@@ -0,0 +1,113 @@
+use candid::{Decode, Encode, Principal};
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::ledger;
+use crate::moderation::not_banned;
+use crate::{ensure_controller, get_memory, Memory, StorablePrincipal, TOKENS_MEMORY_ID};
+
+/// Maximum length of a token symbol.
+const MAX_SYMBOL_LEN: usize = 16;
+
+/// An ICRC-1/ICRC-2 ledger accepted for gig escrow.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Token {
+    pub ledger: Principal,  // Ledger canister of the token.
+    pub symbol: String,     // Display symbol, e.g. "ckBTC".
+    pub fee: u64,           // Transfer fee charged by the ledger, in the token's base units.
+}
+
+/// Token and amount a gig pays out.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Payment {
+    pub ledger: Principal,  // Ledger canister of the escrowed token.
+    pub amount: u64,        // Amount escrowed, in the token's base units.
+}
+
+impl Storable for Token {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Token {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Accepted tokens keyed by ledger canister.
+    static TOKENS: RefCell<StableBTreeMap<StorablePrincipal, Token, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(TOKENS_MEMORY_ID)));
+}
+
+/// The ICP ledger, accepted out of the box.
+fn icp() -> Token {
+    Token {
+        ledger: ledger::icp_ledger(),
+        symbol: "ICP".to_string(),
+        fee: ledger::ICP_TRANSFER_FEE,
+    }
+}
+
+/// The ckBTC ledger, accepted out of the box.
+fn ckbtc() -> Token {
+    Token {
+        ledger: ledger::ckbtc_ledger(),
+        symbol: "ckBTC".to_string(),
+        fee: ledger::CKBTC_TRANSFER_FEE,
+    }
+}
+
+/// Accept ICP and ckBTC unless the token list already has entries for them.
+pub fn seed_default() {
+    TOKENS.with(|tokens| {
+        let mut tokens = tokens.borrow_mut();
+        for token in [icp(), ckbtc()] {
+            let key = StorablePrincipal(token.ledger);
+            if !tokens.contains_key(&key) {
+                tokens.insert(key, token);
+            }
+        }
+    });
+}
+
+/// Look up an accepted token by its ledger.
+pub fn accepted(ledger: Principal) -> Result<Token, GigError> {
+    TOKENS
+        .with(|tokens| tokens.borrow().get(&StorablePrincipal(ledger)))
+        .ok_or_else(|| GigError::validation("token", "Token is not accepted for escrow"))
+}
+
+/// Accept a token for escrow, or update its symbol and fee. Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn add_token(ledger: Principal, symbol: String, fee: u64) -> Result<Token, GigError> {
+    ensure_controller()?;
+    if symbol.trim().is_empty() || symbol.len() > MAX_SYMBOL_LEN {
+        return Err(GigError::validation("symbol", "Must be between 1 and 16 bytes"));
+    }
+    let token = Token { ledger, symbol, fee };
+    TOKENS.with(|tokens| tokens.borrow_mut().insert(StorablePrincipal(ledger), token.clone()));
+    Ok(token)
+}
+
+/// Stop accepting a token for new gigs. Existing escrows keep paying out. Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn remove_token(ledger: Principal) -> Result<(), GigError> {
+    ensure_controller()?;
+    TOKENS
+        .with(|tokens| tokens.borrow_mut().remove(&StorablePrincipal(ledger)))
+        .map(|_| ())
+        .ok_or_else(|| GigError::not_found("Token"))
+}
+
+/// List the tokens accepted for escrow.
+#[ic_cdk::query]
+pub fn list_tokens() -> Vec<Token> {
+    TOKENS.with(|tokens| tokens.borrow().iter().map(|(_, token)| token).collect())
+}
@@ -0,0 +1,122 @@
+use candid::{Decode, Encode, Principal};
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::config;
+use crate::error::GigError;
+use crate::ledger;
+use crate::moderation::not_banned;
+use crate::{ensure_controller, get_memory, Memory, StorablePrincipal, TREASURY_MEMORY_ID};
+
+/// Basis points in one whole.
+const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Platform fees collected in one token and not yet withdrawn.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct FeeBalance {
+    pub ledger: Principal,
+    pub amount: u64,        // Accrued fees, in the token's base units.
+    pub ledger_fee: u64,    // Fee the ledger charges on withdrawal.
+}
+
+impl Storable for FeeBalance {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for FeeBalance {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    /// Accrued platform fees keyed by token ledger.
+    static TREASURY: RefCell<StableBTreeMap<StorablePrincipal, FeeBalance, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(TREASURY_MEMORY_ID)));
+}
+
+/// Platform fee owed on a payout of `amount` at the current rate.
+pub fn fee_for(amount: u64) -> u64 {
+    let bps = config::get().fee_bps as u128;
+    (amount as u128 * bps / BPS_DENOMINATOR as u128) as u64
+}
+
+/// Add collected fees to the treasury. `ledger_fee` is refreshed on every accrual.
+pub fn accrue(ledger: Principal, ledger_fee: u64, amount: u64) {
+    if amount == 0 {
+        return;
+    }
+    TREASURY.with(|treasury| {
+        let mut treasury = treasury.borrow_mut();
+        let key = StorablePrincipal(ledger);
+        let accrued = treasury.get(&key).map_or(0, |balance| balance.amount);
+        treasury.insert(key, FeeBalance { ledger, amount: accrued + amount, ledger_fee });
+    });
+}
+
+/// Take `amount` out of the fees accrued in a token to pay it elsewhere. Returns `false`,
+/// taking nothing, if the treasury holds less.
+pub fn take(ledger: Principal, amount: u64) -> bool {
+    TREASURY.with(|treasury| {
+        let mut treasury = treasury.borrow_mut();
+        let key = StorablePrincipal(ledger);
+        match treasury.get(&key) {
+            Some(mut balance) if balance.amount >= amount => {
+                balance.amount -= amount;
+                treasury.insert(key, balance);
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Retrieve the fees accrued in every token.
+#[ic_cdk::query]
+pub fn get_treasury() -> Vec<FeeBalance> {
+    TREASURY.with(|treasury| treasury.borrow().iter().map(|(_, balance)| balance).collect())
+}
+
+/// Transfer every accrued fee balance to `to`, minus ledger fees, and return the balances
+/// that were paid out. Balances too small to cover the ledger fee, or whose transfer
+/// fails, stay in the treasury. Controller only.
+#[ic_cdk::update(guard = "not_banned")]
+pub async fn withdraw_fees(to: Principal) -> Result<Vec<FeeBalance>, GigError> {
+    ensure_controller()?;
+    // Take the balances out before awaiting so concurrent calls cannot withdraw twice.
+    let balances: Vec<FeeBalance> = TREASURY.with(|treasury| {
+        let mut treasury = treasury.borrow_mut();
+        let payable: Vec<FeeBalance> = treasury
+            .iter()
+            .map(|(_, balance)| balance)
+            .filter(|balance| balance.amount > balance.ledger_fee)
+            .collect();
+        for balance in &payable {
+            treasury.remove(&StorablePrincipal(balance.ledger));
+        }
+        payable
+    });
+
+    let mut withdrawn = Vec::new();
+    let mut failure = None;
+    for balance in balances {
+        let amount = balance.amount - balance.ledger_fee;
+        match ledger::transfer(balance.ledger, to, amount).await {
+            Ok(_) => withdrawn.push(balance),
+            Err(e) => {
+                // Return the fees to the treasury so the withdrawal can be retried.
+                accrue(balance.ledger, balance.ledger_fee, balance.amount);
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    match failure {
+        Some(e) if withdrawn.is_empty() => Err(e),
+        _ => Ok(withdrawn),
+    }
+}
@@ -0,0 +1,147 @@
+use candid::Principal;
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+
+use crate::indexes::{self, SortBy};
+use crate::visibility;
+use crate::{
+    get_memory, GigStatus, GigSummary, Memory, StorablePrincipal, ACTIVITY_SCORES_MEMORY_ID,
+    GIG_STORAGE, GIG_VIEWERS_MEMORY_ID, MAX_PAGE_SIZE, TRENDING_INDEX_MEMORY_ID,
+};
+
+/// Score a principal's first view of a gig adds.
+const VIEW_WEIGHT: u64 = 1_000;
+/// Score a bid adds; bids signal more interest than views.
+const BID_WEIGHT: u64 = 5_000;
+/// Each sweep takes `1 / DECAY_DIVISOR` off every score, roughly halving it every 11 hours
+/// at the ten-minute sweep interval.
+const DECAY_DIVISOR: u64 = 100;
+/// Scores below this are dropped rather than decayed further.
+const MIN_SCORE: u64 = 10;
+
+thread_local! {
+    /// Decayed activity score of each gig with recent views or bids, keyed by gig ID.
+    static SCORES: RefCell<StableBTreeMap<u64, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(ACTIVITY_SCORES_MEMORY_ID)));
+
+    /// Gig IDs keyed by `(u64::MAX - score, gig_id)`, most active first.
+    static TRENDING_INDEX: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(TRENDING_INDEX_MEMORY_ID)));
+
+    /// Principals that have viewed each gig, keyed by `(gig_id, viewer)`.
+    static VIEWERS: RefCell<StableBTreeMap<(u64, StorablePrincipal), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(GIG_VIEWERS_MEMORY_ID)));
+}
+
+/// Replace a gig's score, keeping the trending index in step. A zero score removes it.
+fn set_score(gig_id: u64, score: u64) {
+    let previous = SCORES.with(|scores| {
+        let mut scores = scores.borrow_mut();
+        if score == 0 {
+            scores.remove(&gig_id)
+        } else {
+            scores.insert(gig_id, score)
+        }
+    });
+    TRENDING_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        if let Some(previous) = previous {
+            index.remove(&(u64::MAX - previous, gig_id));
+        }
+        if score > 0 {
+            index.insert((u64::MAX - score, gig_id), ());
+        }
+    });
+}
+
+/// Add `weight` to a gig's activity score.
+fn bump(gig_id: u64, weight: u64) {
+    let score = SCORES.with(|scores| scores.borrow().get(&gig_id).unwrap_or(0));
+    set_score(gig_id, score.saturating_add(weight));
+}
+
+/// Count a bid placed on a gig towards its activity score.
+pub fn record_bid(gig_id: u64) {
+    bump(gig_id, BID_WEIGHT);
+}
+
+/// Count a view of a gig towards its activity score. Repeat views by the same principal
+/// count once; returns whether this was `viewer`'s first.
+pub fn record_view(gig_id: u64, viewer: Principal) -> bool {
+    let first = VIEWERS
+        .with(|viewers| viewers.borrow_mut().insert((gig_id, StorablePrincipal(viewer)), ()))
+        .is_none();
+    if first {
+        bump(gig_id, VIEW_WEIGHT);
+    }
+    first
+}
+
+/// Decay every activity score, dropping those that have faded out. Runs from the
+/// periodic sweep.
+pub fn decay() {
+    let scores: Vec<(u64, u64)> = SCORES.with(|scores| scores.borrow().iter().collect());
+    for (gig_id, score) in scores {
+        let decayed = score - score / DECAY_DIVISOR;
+        set_score(gig_id, if decayed < MIN_SCORE { 0 } else { decayed });
+    }
+}
+
+/// Drop the activity of a gig that was removed.
+pub fn forget_gig(gig_id: u64) {
+    set_score(gig_id, 0);
+    VIEWERS.with(|viewers| {
+        let mut viewers = viewers.borrow_mut();
+        let min = StorablePrincipal(Principal::from_slice(&[]));
+        let keys: Vec<(u64, StorablePrincipal)> = viewers
+            .range((gig_id, min)..)
+            .take_while(|((id, _), _)| *id == gig_id)
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            viewers.remove(&key);
+        }
+    });
+}
+
+/// Retrieve the open gigs with the most recent views and bids, most active first, at most
+/// `limit` (capped at `MAX_PAGE_SIZE`).
+#[ic_cdk::query]
+pub fn get_trending_gigs(limit: u64) -> Vec<GigSummary> {
+    let limit = limit.min(MAX_PAGE_SIZE) as usize;
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        TRENDING_INDEX.with(|index| {
+            index
+                .borrow()
+                .iter()
+                .filter_map(|((_, id), _)| storage.get(&id))
+                .filter(|gig| gig.status == GigStatus::Open && visibility::can_list(gig))
+                .take(limit)
+                .map(|gig| GigSummary::from(&gig))
+                .collect()
+        })
+    })
+}
+
+/// Retrieve the most recently changed gigs the caller may list, newest change first, at
+/// most `limit` (capped at `MAX_PAGE_SIZE`). Walks the last-update index, so only the
+/// returned gigs and any the caller may not list are loaded.
+#[ic_cdk::query]
+pub fn get_recently_updated(limit: u64) -> Vec<GigSummary> {
+    let limit = limit.min(MAX_PAGE_SIZE) as usize;
+    let mut found = Vec::new();
+    if limit == 0 {
+        return found;
+    }
+    GIG_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        indexes::visit_sorted(Some(SortBy::UpdatedAt), |id| {
+            if let Some(gig) = storage.get(&id).filter(visibility::can_list) {
+                found.push(GigSummary::from(&gig));
+            }
+            found.len() < limit
+        });
+    });
+    found
+}
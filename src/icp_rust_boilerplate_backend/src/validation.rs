@@ -0,0 +1,102 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, Storable};
+
+use crate::config;
+use crate::error::GigError;
+use crate::indexes;
+use crate::profiles;
+use crate::{Gig, GigPayload};
+
+/// Ensure `text` has no control characters other than line breaks and tabs, which are
+/// only allowed when `multiline` is set.
+fn ensure_printable(field: &str, text: &str, multiline: bool) -> Result<(), GigError> {
+    let allowed = |c: char| multiline && matches!(c, '\n' | '\r' | '\t');
+    if text.chars().any(|c| c.is_control() && !allowed(c)) {
+        return Err(GigError::validation(field, "Must not contain control characters"));
+    }
+    Ok(())
+}
+
+/// Check the budget of a gig payload.
+fn validate_budget(budget: u64) -> Result<(), GigError> {
+    let config = config::get();
+    if budget < config.min_budget || budget > config.max_budget {
+        return Err(GigError::validation(
+            "budget",
+            &format!("Must be between {} and {}", config.min_budget, config.max_budget),
+        ));
+    }
+    Ok(())
+}
+
+/// Ensure a gig's encoding fits its stable size bound, so storing it cannot trap. Limits
+/// on individual fields keep most gigs far below it; this catches the sum of them.
+pub fn ensure_storable(gig: &Gig) -> Result<(), GigError> {
+    if gig.to_bytes().len() > Gig::MAX_SIZE as usize {
+        return Err(GigError::validation(
+            "payload",
+            &format!("The gig must encode to at most {} bytes", Gig::MAX_SIZE),
+        ));
+    }
+    Ok(())
+}
+
+/// Ensure `employer` has fewer open gigs than the configured quota, which is higher for
+/// verified employers. Gigs awaiting a worker's acceptance count as open.
+pub fn ensure_open_gig_quota(employer: Principal) -> Result<(), GigError> {
+    let config = config::get();
+    let limit = if profiles::is_verified(employer) {
+        config.verified_max_open_gigs
+    } else {
+        config.max_open_gigs
+    };
+    match limit {
+        Some(limit) if indexes::open_gig_count(employer) >= limit as u64 => {
+            Err(GigError::QuotaExceeded { resource: "Open gigs".to_string(), limit })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Ensure `employer` may offer `budget`: budgets above `unverified_max_budget` are
+/// reserved for verified employers.
+pub fn ensure_budget_allowed(employer: Principal, budget: u64) -> Result<(), GigError> {
+    if let Some(max) = config::get().unverified_max_budget {
+        if budget > max && !profiles::is_verified(employer) {
+            return Err(GigError::unauthorized(&format!(
+                "Only verified employers can offer budgets above {}",
+                max
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check the deadline of a gig payload.
+pub fn validate_deadline(deadline: u64) -> Result<(), GigError> {
+    if deadline <= time() {
+        return Err(GigError::validation("deadline", "Must be in the future"));
+    }
+    Ok(())
+}
+
+/// Check the title, description and budget of a gig payload against the current limits.
+pub fn validate_gig_payload(payload: &GigPayload) -> Result<(), GigError> {
+    let config = config::get();
+    if payload.title.trim().is_empty() || payload.title.len() > config.max_title_len as usize {
+        return Err(GigError::validation(
+            "title",
+            &format!("Must be between 1 and {} bytes", config.max_title_len),
+        ));
+    }
+    ensure_printable("title", &payload.title, false)?;
+    if payload.description.len() > config.max_description_len as usize {
+        return Err(GigError::validation(
+            "description",
+            &format!("Must be at most {} bytes", config.max_description_len),
+        ));
+    }
+    ensure_printable("description", &payload.description, true)?;
+    validate_budget(payload.budget)
+}
@@ -0,0 +1,60 @@
+use ic_cdk::caller;
+
+use crate::delegates::{self, Permission};
+use crate::indexes;
+use crate::invitations;
+use crate::moderation;
+use crate::orgs;
+use crate::roles;
+use crate::teams;
+use crate::Gig;
+
+/// Who can find and open a gig.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum Visibility {
+    #[default]
+    Public,     // Listed on the board and open to bids from anyone.
+    Unlisted,   // Kept off listings and search; anyone with the ID can open it.
+    InviteOnly, // Kept off listings and search; only invited workers can open it or bid.
+}
+
+/// Whether the caller is a party to the gig or a moderator, who see every gig.
+fn is_privileged(gig: &Gig) -> bool {
+    let caller = caller();
+    orgs::acts_for_employer(gig, caller, Permission::Post)
+        || delegates::is_any_delegate(gig.employer, caller)
+        || gig.assigned_to == Some(caller)
+        || teams::is_member(gig.id, caller)
+        || roles::ensure_moderator().is_ok()
+}
+
+/// Whether the caller may retrieve a gig by ID.
+pub fn can_view(gig: &Gig) -> bool {
+    if is_privileged(gig) {
+        return true;
+    }
+    if moderation::is_hidden(gig.id) {
+        return false;
+    }
+    match gig.visibility() {
+        Visibility::Public | Visibility::Unlisted => true,
+        Visibility::InviteOnly => invitations::is_invited(gig.id, caller()),
+    }
+}
+
+/// Whether a gig appears in the caller's listing and search results.
+pub fn can_list(gig: &Gig) -> bool {
+    is_privileged(gig) || is_listed(gig.id)
+}
+
+/// Whether a gig is on the public board: public and not hidden by moderators.
+pub fn is_listed(gig_id: u64) -> bool {
+    !moderation::is_hidden(gig_id) && !indexes::is_unlisted(gig_id)
+}
+
+/// Number of gigs on the public board.
+pub fn listed_count(total: u64) -> u64 {
+    let hidden = moderation::hidden_ids();
+    let both = hidden.iter().filter(|id| indexes::is_unlisted(**id)).count() as u64;
+    total - (hidden.len() as u64 + indexes::unlisted_count() - both)
+}
@@ -0,0 +1,331 @@
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
+    HttpResponse as OutcallResponse, TransformArgs, TransformContext,
+};
+use ic_cdk::api::time;
+use ic_cdk::caller;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use sha2::{Digest, Sha256};
+use std::{borrow::Cow, cell::RefCell};
+
+use crate::error::GigError;
+use crate::id;
+use crate::events::{self, Event};
+use crate::moderation::not_banned;
+use crate::{
+    get_memory, IdCell, Memory, StorablePrincipal, WEBHOOKS_MEMORY_ID,
+    WEBHOOK_DELIVERIES_MEMORY_ID, WEBHOOK_DELIVERY_ID_COUNTER_MEMORY_ID,
+    WEBHOOK_ID_COUNTER_MEMORY_ID, WEBHOOK_OWNER_INDEX_MEMORY_ID,
+};
+
+/// Most webhooks a principal can register.
+const MAX_WEBHOOKS: usize = 5;
+/// Longest webhook URL, in bytes.
+const MAX_URL_LEN: usize = 500;
+/// Bounds on the shared secret length, in bytes.
+const SECRET_LEN_RANGE: std::ops::RangeInclusive<usize> = 16..=128;
+/// Cycles attached to each outcall. Whatever the call does not use is refunded.
+const OUTCALL_CYCLES: u128 = 2_000_000_000;
+/// Largest response body accepted from a webhook endpoint; only the status is used.
+const MAX_RESPONSE_BYTES: u64 = 2048;
+/// Wait before the first retry of a failed delivery: ten minutes. Each further failure
+/// doubles it.
+const RETRY_BASE_NANOS: u64 = 10 * 60 * 1_000_000_000;
+/// Longest wait between delivery retries: one day.
+const RETRY_MAX_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Attempts made at a delivery before it is dropped.
+const MAX_ATTEMPTS: u32 = 8;
+/// Block size of SHA-256, used for the HMAC key.
+const SHA256_BLOCK_LEN: usize = 64;
+
+/// An endpoint notified when the owner's gigs change status.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: u64,
+    pub owner: Principal,
+    pub url: String,
+    pub secret: String,         // Key of the HMAC-SHA256 payload signature; never returned.
+    pub created_at: u64,
+    pub failed_deliveries: u64, // Deliveries dropped after running out of retries.
+}
+
+/// A webhook as shown to its owner.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct WebhookInfo {
+    pub id: u64,
+    pub url: String,
+    pub created_at: u64,
+    pub failed_deliveries: u64,
+}
+
+impl From<&Webhook> for WebhookInfo {
+    fn from(webhook: &Webhook) -> Self {
+        WebhookInfo {
+            id: webhook.id,
+            url: webhook.url.clone(),
+            created_at: webhook.created_at,
+            failed_deliveries: webhook.failed_deliveries,
+        }
+    }
+}
+
+/// A signed payload waiting to be posted to a webhook.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Delivery {
+    webhook_id: u64,
+    body: String,
+    attempts: u32,
+    next_attempt_at: u64,
+    last_error: Option<String>,
+}
+
+impl Storable for Webhook {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Webhook {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for Delivery {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Delivery {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    static WEBHOOK_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(get_memory(WEBHOOK_ID_COUNTER_MEMORY_ID), 0)
+            .expect("Cannot create a webhook counter")
+    );
+
+    static DELIVERY_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(get_memory(WEBHOOK_DELIVERY_ID_COUNTER_MEMORY_ID), 0)
+            .expect("Cannot create a webhook delivery counter")
+    );
+
+    /// Webhooks keyed by webhook ID.
+    static WEBHOOKS: RefCell<StableBTreeMap<u64, Webhook, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(WEBHOOKS_MEMORY_ID)));
+
+    /// Webhook IDs keyed by `(owner, webhook_id)`.
+    static OWNER_INDEX: RefCell<StableBTreeMap<(StorablePrincipal, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(WEBHOOK_OWNER_INDEX_MEMORY_ID)));
+
+    /// Pending deliveries keyed by delivery ID.
+    static DELIVERIES: RefCell<StableBTreeMap<u64, Delivery, Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(WEBHOOK_DELIVERIES_MEMORY_ID)));
+}
+
+/// IDs of the webhooks registered by `owner`.
+fn ids_owned_by(owner: Principal) -> Vec<u64> {
+    let key = StorablePrincipal(owner);
+    OWNER_INDEX.with(|index| {
+        index
+            .borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    })
+}
+
+/// HMAC-SHA256 of `message` under `key`, hex encoded.
+fn sign(key: &[u8], message: &[u8]) -> String {
+    let mut block = [0u8; SHA256_BLOCK_LEN];
+    if key.len() > SHA256_BLOCK_LEN {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<u8>>();
+    let inner = Sha256::new().chain_update(pad(0x36)).chain_update(message).finalize();
+    let outer = Sha256::new().chain_update(pad(0x5c)).chain_update(inner).finalize();
+    outer.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Register an HTTPS endpoint to receive the caller's gig status changes as JSON. Each
+/// request carries an `X-GigSphere-Signature` header with the HMAC-SHA256 of the body
+/// under `secret`.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn register_webhook(url: String, secret: String) -> Result<WebhookInfo, GigError> {
+    let owner = caller();
+    if owner == Principal::anonymous() {
+        return Err(GigError::unauthorized("Anonymous principals cannot register webhooks"));
+    }
+    if !url.starts_with("https://") || url.len() > MAX_URL_LEN {
+        return Err(GigError::validation("url", "Must be an https:// URL of at most 500 bytes"));
+    }
+    if !SECRET_LEN_RANGE.contains(&secret.len()) {
+        return Err(GigError::validation("secret", "Must be between 16 and 128 bytes"));
+    }
+    if ids_owned_by(owner).len() >= MAX_WEBHOOKS {
+        return Err(GigError::validation("url", "At most 5 webhooks can be registered"));
+    }
+    let id = id::next(&WEBHOOK_ID_COUNTER, "Webhook")?;
+    let webhook =
+        Webhook { id, owner, url, secret, created_at: time(), failed_deliveries: 0 };
+    let info = WebhookInfo::from(&webhook);
+    WEBHOOKS.with(|webhooks| webhooks.borrow_mut().insert(id, webhook));
+    OWNER_INDEX.with(|index| index.borrow_mut().insert((StorablePrincipal(owner), id), ()));
+    Ok(info)
+}
+
+/// Delete a webhook, dropping its pending deliveries. Owner only.
+#[ic_cdk::update(guard = "not_banned")]
+pub fn delete_webhook(id: u64) -> Result<(), GigError> {
+    let webhook = WEBHOOKS
+        .with(|webhooks| webhooks.borrow().get(&id))
+        .filter(|webhook| webhook.owner == caller())
+        .ok_or_else(|| GigError::not_found("Webhook"))?;
+    WEBHOOKS.with(|webhooks| webhooks.borrow_mut().remove(&id));
+    OWNER_INDEX.with(|index| index.borrow_mut().remove(&(StorablePrincipal(webhook.owner), id)));
+    DELIVERIES.with(|deliveries| {
+        let mut deliveries = deliveries.borrow_mut();
+        let orphaned: Vec<u64> = deliveries
+            .iter()
+            .filter(|(_, delivery)| delivery.webhook_id == id)
+            .map(|(delivery_id, _)| delivery_id)
+            .collect();
+        for delivery_id in orphaned {
+            deliveries.remove(&delivery_id);
+        }
+    });
+    Ok(())
+}
+
+/// Retrieve the caller's webhooks.
+#[ic_cdk::query]
+pub fn list_webhooks() -> Vec<WebhookInfo> {
+    let ids = ids_owned_by(caller());
+    WEBHOOKS.with(|webhooks| {
+        let webhooks = webhooks.borrow();
+        ids.iter().filter_map(|id| webhooks.get(id)).map(|webhook| (&webhook).into()).collect()
+    })
+}
+
+/// Queue a status change of one of `owner`'s gigs for each of their webhooks. Deliveries
+/// go out on the next timer sweep. Fails once the delivery counter has run out, leaving
+/// the remaining webhooks unqueued.
+pub fn enqueue(event: &Event, owner: Principal) -> Result<(), GigError> {
+    if !events::is_status_change(&event.kind) {
+        return Ok(());
+    }
+    for webhook_id in ids_owned_by(owner) {
+        let id = id::next(&DELIVERY_ID_COUNTER, "Webhook delivery")?;
+        let body = serde_json::json!({ "delivery_id": id, "event": event }).to_string();
+        let delivery =
+            Delivery { webhook_id, body, attempts: 0, next_attempt_at: 0, last_error: None };
+        DELIVERIES.with(|deliveries| deliveries.borrow_mut().insert(id, delivery));
+    }
+    Ok(())
+}
+
+/// Post every delivery that is due. Failures are retried with exponential backoff and
+/// dropped after `MAX_ATTEMPTS`.
+pub fn deliver_due() {
+    let now = time();
+    let due: Vec<u64> = DELIVERIES.with(|deliveries| {
+        deliveries
+            .borrow()
+            .iter()
+            .filter(|(_, delivery)| delivery.next_attempt_at <= now)
+            .map(|(id, _)| id)
+            .collect()
+    });
+    for id in due {
+        ic_cdk::spawn(deliver(id));
+    }
+}
+
+/// Post one delivery to its webhook and record the outcome.
+async fn deliver(id: u64) {
+    // Push the next attempt out before awaiting so an overlapping sweep does not resend.
+    let Some(mut delivery) = DELIVERIES.with(|deliveries| {
+        let mut deliveries = deliveries.borrow_mut();
+        let mut delivery = deliveries.get(&id)?;
+        delivery.next_attempt_at = u64::MAX;
+        deliveries.insert(id, delivery.clone());
+        Some(delivery)
+    }) else {
+        return;
+    };
+    let Some(webhook) = WEBHOOKS.with(|webhooks| webhooks.borrow().get(&delivery.webhook_id))
+    else {
+        DELIVERIES.with(|deliveries| deliveries.borrow_mut().remove(&id));
+        return;
+    };
+
+    // Every replica sends the request, so receivers should deduplicate on the delivery ID.
+    let request = CanisterHttpRequestArgument {
+        url: webhook.url.clone(),
+        max_response_bytes: Some(MAX_RESPONSE_BYTES),
+        method: HttpMethod::POST,
+        headers: vec![
+            HttpHeader { name: "Content-Type".to_string(), value: "application/json".to_string() },
+            HttpHeader { name: "X-GigSphere-Delivery".to_string(), value: id.to_string() },
+            HttpHeader {
+                name: "X-GigSphere-Signature".to_string(),
+                value: format!(
+                    "sha256={}",
+                    sign(webhook.secret.as_bytes(), delivery.body.as_bytes())
+                ),
+            },
+        ],
+        body: Some(delivery.body.clone().into_bytes()),
+        transform: Some(TransformContext::from_name(
+            "transform_webhook_response".to_string(),
+            Vec::new(),
+        )),
+    };
+    let error = match http_request(request, OUTCALL_CYCLES).await {
+        Ok((response,)) if response.status >= 200u32 && response.status < 300u32 => None,
+        Ok((response,)) => Some(format!("HTTP status {}", response.status)),
+        Err((code, message)) => Some(format!("{:?}: {}", code, message)),
+    };
+
+    let Some(error) = error else {
+        DELIVERIES.with(|deliveries| deliveries.borrow_mut().remove(&id));
+        return;
+    };
+    delivery.attempts += 1;
+    if delivery.attempts >= MAX_ATTEMPTS {
+        DELIVERIES.with(|deliveries| deliveries.borrow_mut().remove(&id));
+        WEBHOOKS.with(|webhooks| {
+            let mut webhooks = webhooks.borrow_mut();
+            if let Some(mut webhook) = webhooks.get(&delivery.webhook_id) {
+                webhook.failed_deliveries += 1;
+                webhooks.insert(webhook.id, webhook);
+            }
+        });
+        return;
+    }
+    let backoff = RETRY_BASE_NANOS
+        .saturating_mul(1 << (delivery.attempts - 1))
+        .min(RETRY_MAX_NANOS);
+    delivery.next_attempt_at = time().saturating_add(backoff);
+    delivery.last_error = Some(error);
+    DELIVERIES.with(|deliveries| deliveries.borrow_mut().insert(id, delivery));
+}
+
+/// Strip a webhook response down to its status so every replica agrees on it.
+#[ic_cdk::query]
+pub fn transform_webhook_response(args: TransformArgs) -> OutcallResponse {
+    OutcallResponse { status: args.response.status, ..Default::default() }
+}